@@ -0,0 +1,4 @@
+fn main() {
+    // `/api/version`と起動時のログで使用するビルド情報を出力する
+    built::write_built_file().expect("Failed to acquire build-time information");
+}
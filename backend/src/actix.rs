@@ -0,0 +1,144 @@
+use std::pin::Pin;
+
+use actix_web::http::{StatusCode, header};
+use actix_web::{
+    FromRequest, HttpMessage as _, HttpRequest, HttpResponse, ResponseError, dev::Payload, web,
+};
+
+use crate::common::{RequestError, www_authenticate};
+use crate::entra_id::{BearerToken, Claims, EntraIdTokenVerifier, VerifiedToken};
+
+/// actix-webで認証済みクレームをリクエストから抽出するエクストラクタ
+///
+/// axumの`AuthClaims`と同様に、`Authorization`ヘッダのBearerトークンを検証し、クレームを認証コンテキストに変換する。
+/// アプリケーションデータに`web::Data<EntraIdTokenVerifier>`を登録して使用する。
+///
+/// リクエストのエクステンションに`VerifiedToken`が存在する場合は、トークンを再検証せずに再利用する。
+/// 検証した場合は、同じリクエストの他のエクストラクタが再利用できるように、`VerifiedToken`を挿入する。
+///
+/// ```ignore
+/// App::new()
+///     .app_data(web::Data::from(verifier))
+///     .route("/api/me", web::get().to(|auth: AuthClaims| async move { auth.claims.oid }))
+/// ```
+#[derive(Clone)]
+pub struct AuthClaims {
+    pub claims: Claims,
+    pub access_token: BearerToken,
+}
+
+impl FromRequest for AuthClaims {
+    type Error = AuthError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(request: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        if let Some(verified) = request.extensions().get::<VerifiedToken>().cloned() {
+            return Box::pin(async move { Ok(verified.into()) });
+        }
+        let request = request.clone();
+        let verifier = request
+            .app_data::<web::Data<EntraIdTokenVerifier>>()
+            .cloned();
+        let token = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(BearerToken::from_authorization);
+        Box::pin(async move {
+            let Some(verifier) = verifier else {
+                tracing::error!("EntraIdTokenVerifier is not registered as actix-web app data");
+                return Err(AuthError(RequestError {
+                    code: http::StatusCode::INTERNAL_SERVER_ERROR,
+                    message: "Token verifier is not configured".into(),
+                }));
+            };
+            let token = token.ok_or_else(|| {
+                AuthError(RequestError::unauthorized(
+                    "Authorization header with Bearer token is required",
+                ))
+            })?;
+            let context = verifier.authenticate(&token).await.map_err(|e| {
+                tracing::error!(error = %e, "Token verification failed");
+                AuthError(RequestError::from(e))
+            })?;
+            let verified = VerifiedToken {
+                context,
+                access_token: token,
+            };
+            request.extensions_mut().insert(verified.clone());
+            Ok(verified.into())
+        })
+    }
+}
+
+impl From<VerifiedToken> for AuthClaims {
+    fn from(verified: VerifiedToken) -> Self {
+        Self {
+            claims: verified.context.claims,
+            access_token: verified.access_token,
+        }
+    }
+}
+
+/// actix-webのエクストラクタが返す認証エラー
+///
+/// axumの`RequestError`と同じステータスコード、`WWW-Authenticate`ヘッダおよびJSONボディのレスポンスに変換する。
+#[derive(Debug, thiserror::Error)]
+#[error("{}", .0.message)]
+pub struct AuthError(pub RequestError);
+
+impl ResponseError for AuthError {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::from_u16(self.0.code.as_u16()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if let Some(value) = www_authenticate(self.0.code) {
+            response.insert_header((header::WWW_AUTHENTICATE, value));
+        }
+        response.json(serde_json::json!({
+            "code": self.0.code.as_u16(),
+            "error": self.0.code.canonical_reason().unwrap_or("Unknown Error"),
+            "message": self.0.message,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{App, test};
+
+    use super::*;
+    use crate::fixtures::{build_verifier, sign_token};
+
+    async fn oid(auth: AuthClaims) -> String {
+        auth.claims.oid
+    }
+
+    #[actix_web::test]
+    async fn extractor_verifies_bearer_token() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::from(build_verifier().await))
+                .route("/", web::get().to(oid)),
+        )
+        .await;
+
+        let request = test::TestRequest::get()
+            .uri("/")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", sign_token())))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(test::read_body(response).await, "user-oid");
+
+        let request = test::TestRequest::get().uri("/").to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            response.headers().get(header::WWW_AUTHENTICATE).unwrap(),
+            "Bearer"
+        );
+    }
+}
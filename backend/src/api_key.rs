@@ -0,0 +1,255 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use axum::http::{HeaderMap, HeaderName, StatusCode};
+use secrecy::{ExposeSecret as _, SecretString};
+use sha2::{Digest as _, Sha256};
+
+use crate::common::RequestError;
+use crate::config::{ApiKeyConfig, ApiKeyHash, ApiKeyRouteGroup, ApiKeysConfig};
+
+/// APIキーを送信するヘッダ
+pub const X_API_KEY: HeaderName = HeaderName::from_static("x-api-key");
+
+/// 非同期に検索したAPIキーの呼び出し元
+pub type ApiKeyFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Option<ApiKeyPrincipal>, String>> + Send + 'a>>;
+
+/// APIキーで認証した呼び出し元
+///
+/// APIキー認証に成功したリクエストのエクステンションに挿入される。
+#[derive(Debug, Clone)]
+pub struct ApiKeyPrincipal {
+    /// 呼び出し元の名前
+    pub name: String,
+    /// 呼び出し元に付与されたアプリケーション内部の権限
+    pub permissions: HashSet<String>,
+}
+
+impl fmt::Display for ApiKeyPrincipal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "api-key:{}", self.name)
+    }
+}
+
+/// APIキーのストア
+///
+/// 設定ファイル以外（例: シークレットマネージャーやデータベース）でAPIキーを管理する場合は、このトレイトを実装する。
+pub trait ApiKeyStore: Send + Sync {
+    /// APIキーに対応する呼び出し元を検索する。
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - リクエストで送信されたAPIキー
+    ///
+    /// # Returns
+    ///
+    /// * 呼び出し元、APIキーが登録されていない場合は`None`、またはエラー
+    ///
+    /// # Notes
+    ///
+    /// タイミング攻撃を防ぐため、APIキーの比較には一定時間で比較する方法を使用すること。
+    fn find<'a>(&'a self, key: &'a SecretString) -> ApiKeyFuture<'a>;
+}
+
+/// 設定ファイルに登録したAPIキーのSHA-256ハッシュで検索するストア
+pub struct ConfigApiKeyStore {
+    /// APIキーのSHA-256ハッシュと呼び出し元
+    keys: Vec<(ApiKeyHash, ApiKeyPrincipal)>,
+}
+
+impl ConfigApiKeyStore {
+    /// コンストラクタ
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - 設定ファイルに登録したAPIキー
+    pub fn new(keys: &[ApiKeyConfig]) -> Self {
+        Self {
+            keys: keys
+                .iter()
+                .map(|key| {
+                    let principal = ApiKeyPrincipal {
+                        name: key.name.clone(),
+                        permissions: key.permissions.iter().cloned().collect(),
+                    };
+                    (key.sha256.clone(), principal)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl ApiKeyStore for ConfigApiKeyStore {
+    fn find<'a>(&'a self, key: &'a SecretString) -> ApiKeyFuture<'a> {
+        Box::pin(async move {
+            let hash: [u8; 32] = Sha256::digest(key.expose_secret().as_bytes()).into();
+            // 一致したAPIキーの位置によって処理時間が変わらないように、すべてのAPIキーと比較する
+            let mut found = None;
+            for (expected, principal) in &self.keys {
+                if constant_time_eq(&expected.0, &hash) && found.is_none() {
+                    found = Some(principal.clone());
+                }
+            }
+            Ok(found)
+        })
+    }
+}
+
+/// 2つのハッシュを、内容にかかわらず一定の時間で比較する。
+fn constant_time_eq(a: &[u8; 32], b: &[u8; 32]) -> bool {
+    let diff = a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y));
+    std::hint::black_box(diff) == 0
+}
+
+/// APIキー認証
+pub struct ApiKeyAuth {
+    /// APIキーのストア
+    store: Arc<dyn ApiKeyStore>,
+    /// APIキー認証を受け付けるルートグループ
+    route_groups: HashSet<ApiKeyRouteGroup>,
+}
+
+impl ApiKeyAuth {
+    /// コンストラクタ
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - APIキーのストア
+    /// * `route_groups` - APIキー認証を受け付けるルートグループ
+    pub fn new(store: Arc<dyn ApiKeyStore>, route_groups: &[ApiKeyRouteGroup]) -> Self {
+        Self {
+            store,
+            route_groups: route_groups.iter().copied().collect(),
+        }
+    }
+
+    /// 設定ファイルに登録したAPIキーで認証するAPIキー認証を作成する。
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - APIキー認証設定
+    pub fn from_config(config: &ApiKeysConfig) -> Self {
+        Self::new(
+            Arc::new(ConfigApiKeyStore::new(&config.keys)),
+            &config.route_groups,
+        )
+    }
+
+    /// ルートグループでAPIキー認証を受け付けるかを判定する。
+    pub fn accepts(&self, route_group: ApiKeyRouteGroup) -> bool {
+        self.route_groups.contains(&route_group)
+    }
+
+    /// `X-API-Key`ヘッダのAPIキーで呼び出し元を認証する。
+    ///
+    /// # Arguments
+    ///
+    /// * `headers` - リクエストのヘッダ
+    ///
+    /// # Returns
+    ///
+    /// * 呼び出し元、`X-API-Key`ヘッダが存在しない場合は`None`、またはエラー
+    ///
+    /// # Notes
+    ///
+    /// APIキーが登録されていない場合は401エラー、ストアで検索できない場合は503エラーを返す。
+    pub async fn authenticate(
+        &self,
+        headers: &HeaderMap,
+    ) -> Result<Option<ApiKeyPrincipal>, RequestError> {
+        let Some(value) = headers.get(X_API_KEY) else {
+            return Ok(None);
+        };
+        let key = value
+            .to_str()
+            .ok()
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .ok_or_else(|| RequestError::unauthorized("Invalid API key"))?;
+        let key = SecretString::new(key.into());
+        match self.store.find(&key).await {
+            Ok(Some(principal)) => Ok(Some(principal)),
+            Ok(None) => {
+                tracing::warn!("Unknown API key");
+                Err(RequestError::unauthorized("Invalid API key"))
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to look up API key");
+                Err(RequestError {
+                    code: StatusCode::SERVICE_UNAVAILABLE,
+                    message: "Failed to look up API key".into(),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn auth() -> ApiKeyAuth {
+        let keys = [ApiKeyConfig {
+            name: "batch".into(),
+            // `secret-key`のSHA-256ハッシュ
+            sha256: ApiKeyHash::try_from(
+                "85DBE15D75EF9308C7AE0F33C7A324CC6F4BF519A2ED2F3027BD33C140A4F9AA".to_string(),
+            )
+            .unwrap(),
+            permissions: vec!["jwks:read".into()],
+        }];
+        ApiKeyAuth::new(
+            Arc::new(ConfigApiKeyStore::new(&keys)),
+            &[ApiKeyRouteGroup::Admin],
+        )
+    }
+
+    fn headers(key: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(X_API_KEY, HeaderValue::from_str(key).unwrap());
+        headers
+    }
+
+    #[tokio::test]
+    async fn api_key_is_matched_by_its_sha256_hash() {
+        let auth = auth();
+
+        let principal = auth
+            .authenticate(&headers("secret-key"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(principal.to_string(), "api-key:batch");
+        assert!(principal.permissions.contains("jwks:read"));
+
+        assert!(
+            auth.authenticate(&HeaderMap::new())
+                .await
+                .unwrap()
+                .is_none()
+        );
+        for key in [
+            "secret-keY",
+            "",
+            "85dbe15d75ef9308c7ae0f33c7a324cc6f4bf519a2ed2f3027bd33c140a4f9aa",
+        ] {
+            let err = auth.authenticate(&headers(key)).await.unwrap_err();
+            assert_eq!(err.code, StatusCode::UNAUTHORIZED);
+        }
+        assert!(auth.accepts(ApiKeyRouteGroup::Admin));
+        assert!(!auth.accepts(ApiKeyRouteGroup::Api));
+    }
+
+    #[test]
+    fn api_key_hash_must_be_64_hexadecimal_digits() {
+        for value in ["", "85dbe15d", &"g".repeat(64), &"é".repeat(32)] {
+            assert!(ApiKeyHash::try_from(value.to_string()).is_err());
+        }
+    }
+}
@@ -0,0 +1,550 @@
+use std::borrow::Borrow;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::Mutex;
+
+use crate::entra_id::Claims;
+
+/// アプリケーション内部の権限
+///
+/// Entra IDのアプリケーションロール名に依存せずに認可できるように、ルートには権限を要求させ、
+/// ロールと権限の対応は設定ファイルで管理する。
+pub trait Permission {
+    /// 権限名（例: `reports:read`）
+    const NAME: &'static str;
+}
+
+/// 権限を表す型を定義する。
+///
+/// # Arguments
+///
+/// * `$name` - 権限を表す型名
+/// * `$permission` - 権限名
+macro_rules! define_permission {
+    ($(#[$meta:meta])* $name:ident, $permission:literal) => {
+        $(#[$meta])*
+        pub struct $name;
+
+        impl Permission for $name {
+            const NAME: &'static str = $permission;
+        }
+    };
+}
+
+define_permission!(
+    /// テナントごとのJWK公開鍵キャッシュの状態を参照する権限
+    JwksRead,
+    "jwks:read"
+);
+
+define_permission!(
+    /// テナントのJWK公開鍵を強制的にリフレッシュする権限
+    JwksRefresh,
+    "jwks:refresh"
+);
+
+/// 管理者ロールに付与する権限
+const ADMIN_PERMISSIONS: &[&str] = &[JwksRead::NAME, JwksRefresh::NAME];
+
+/// Entra IDのアプリケーションロールおよびグループと、権限の対応表
+#[derive(Clone)]
+pub struct PermissionMap {
+    /// ロールをキー、ロールに付与された権限を値としたハッシュマップ（ロールの階層を展開済み）
+    role_permissions: HashMap<Role, HashSet<String>>,
+    /// グループのオブジェクトIDをキー、グループに付与された権限を値としたハッシュマップ
+    group_permissions: HashMap<String, HashSet<String>>,
+    /// ロールをキー、ロールが包含するすべての下位ロール（ロール自身を含む）を値としたハッシュマップ
+    implied_roles: HashMap<Role, HashSet<Role>>,
+}
+
+impl PermissionMap {
+    /// コンストラクタ
+    ///
+    /// # Arguments
+    ///
+    /// * `registry` - 既知のロールとスコープ
+    /// * `role_permissions` - ロールをキー、ロールに付与する権限を値としたハッシュマップ
+    /// * `group_permissions` - グループのオブジェクトIDをキー、グループに付与する権限を値としたハッシュマップ
+    /// * `role_hierarchy` - ロールをキー、ロールが包含する下位ロールを値としたハッシュマップ
+    /// * `admin_role` - 管理者ロール
+    ///
+    /// # Returns
+    ///
+    /// * 権限の対応表、または既知のロールでないロールを参照している場合はエラー
+    ///
+    /// # Notes
+    ///
+    /// 管理者ロールには、設定にかかわらず管理者APIの権限を付与する。
+    /// 上位ロールには、包含する下位ロールに付与された権限を推移的に付与する。
+    /// アプリ登録のマニフェストとのロール名の不一致を起動時に検出するため、ロール名はすべて`registry`で解決する。
+    pub fn new(
+        registry: &RoleScopeRegistry,
+        role_permissions: HashMap<String, Vec<String>>,
+        group_permissions: HashMap<String, Vec<String>>,
+        role_hierarchy: &HashMap<String, Vec<String>>,
+        admin_role: &str,
+    ) -> Result<Self, String> {
+        let mut direct_permissions = HashMap::<Role, HashSet<String>>::new();
+        for (role, permissions) in role_permissions {
+            direct_permissions
+                .entry(registry.role(&role)?)
+                .or_default()
+                .extend(permissions);
+        }
+        let group_permissions = group_permissions
+            .into_iter()
+            .map(|(group, permissions)| (group, permissions.into_iter().collect()))
+            .collect();
+        direct_permissions
+            .entry(registry.role(admin_role)?)
+            .or_default()
+            .extend(ADMIN_PERMISSIONS.iter().map(|p| p.to_string()));
+        let mut role_implications = HashMap::new();
+        for role in role_hierarchy.keys() {
+            let implied = implied_roles(role_hierarchy, role)
+                .iter()
+                .map(|implied| registry.role(implied))
+                .collect::<Result<HashSet<_>, _>>()?;
+            role_implications.insert(registry.role(role)?, implied);
+        }
+        let mut role_permissions = direct_permissions.clone();
+        for (role, implied) in &role_implications {
+            let permissions = implied
+                .iter()
+                .filter_map(|implied| direct_permissions.get(implied))
+                .flatten()
+                .cloned();
+            role_permissions
+                .entry(role.clone())
+                .or_default()
+                .extend(permissions);
+        }
+        Ok(Self {
+            role_permissions,
+            group_permissions,
+            implied_roles: role_implications,
+        })
+    }
+
+    /// クレームのロールに、指定した権限が付与されているかを判定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `claims` - 検証済みのクレーム
+    /// * `permission` - 権限名
+    ///
+    /// # Returns
+    ///
+    /// * 権限が付与されている場合は`true`
+    pub fn is_granted_by_role(&self, claims: &Claims, permission: &str) -> bool {
+        claims.roles.iter().flatten().any(|role| {
+            self.role_permissions
+                .get(role.as_str())
+                .is_some_and(|permissions| permissions.contains(permission))
+        })
+    }
+
+    /// クレームのロールが、ロールの階層を考慮して指定したロールを包含するかを判定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `claims` - 検証済みのクレーム
+    /// * `role` - ロール
+    ///
+    /// # Returns
+    ///
+    /// * 指定したロール、または指定したロールを包含する上位ロールを持つ場合は`true`
+    pub fn has_role(&self, claims: &Claims, role: &Role) -> bool {
+        claims.roles.iter().flatten().any(|granted| {
+            granted == role.name()
+                || self
+                    .implied_roles
+                    .get(granted.as_str())
+                    .is_some_and(|implied| implied.contains(role))
+        })
+    }
+
+    /// 指定した権限が付与されたグループのオブジェクトIDを返す。
+    ///
+    /// # Arguments
+    ///
+    /// * `permission` - 権限名
+    ///
+    /// # Returns
+    ///
+    /// * 権限が付与されたグループのオブジェクトID
+    pub fn groups_granting(&self, permission: &str) -> Vec<String> {
+        let mut groups: Vec<String> = self
+            .group_permissions
+            .iter()
+            .filter(|(_, permissions)| permissions.contains(permission))
+            .map(|(group, _)| group.clone())
+            .collect();
+        groups.sort();
+        groups
+    }
+}
+
+/// 既知のアプリケーションロール
+///
+/// `RoleScopeRegistry`からのみ取得でき、アプリ登録のマニフェストとコードのロール名の不一致を起動時に検出する。
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Role(Arc<str>);
+
+impl Role {
+    /// ロール名を返す。
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+
+    /// クレームがロールを持つかを判定する。
+    pub fn is_held_by(&self, claims: &Claims) -> bool {
+        claims.roles.iter().flatten().any(|role| *role == *self.0)
+    }
+}
+
+impl Borrow<str> for Role {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+/// 既知の委任スコープ
+///
+/// `RoleScopeRegistry`からのみ取得でき、アプリ登録とコードのスコープ名の不一致を起動時に検出する。
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Scope(Arc<str>);
+
+impl Scope {
+    /// スコープ名を返す。
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+
+    /// クレームにスコープが委任されているかを判定する。
+    pub fn is_granted_to(&self, claims: &Claims) -> bool {
+        claims.scopes().contains(&self.0)
+    }
+}
+
+/// 設定ファイルに登録した既知のロールとスコープ
+///
+/// ハンドラーや権限の対応表は、起動時に`role`や`scope`で型付きのハンドルを取得し、文字列のロール名やスコープ名を直接比較しない。
+pub struct RoleScopeRegistry {
+    /// ロール名をキー、ロールを値としたハッシュマップ
+    roles: HashMap<String, Role>,
+    /// 小文字に正規化したスコープ名をキー、スコープを値としたハッシュマップ
+    scopes: HashMap<String, Scope>,
+    /// 警告済みの未知のロール
+    warned_roles: std::sync::Mutex<HashSet<String>>,
+}
+
+impl RoleScopeRegistry {
+    /// コンストラクタ
+    ///
+    /// # Arguments
+    ///
+    /// * `roles` - 既知のロール
+    /// * `scopes` - 既知のスコープ
+    pub fn new(roles: &[String], scopes: &[String]) -> Self {
+        Self {
+            roles: roles
+                .iter()
+                .map(|role| (role.clone(), Role(role.as_str().into())))
+                .collect(),
+            scopes: scopes
+                .iter()
+                .map(|scope| (scope.to_ascii_lowercase(), Scope(scope.as_str().into())))
+                .collect(),
+            warned_roles: std::sync::Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// 既知のロールを返す。
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - ロール名
+    ///
+    /// # Returns
+    ///
+    /// * ロール、または既知のロールでない場合はエラー
+    ///
+    /// # Notes
+    ///
+    /// 既知のロールを登録していない場合は、すべてのロール名をロールとして返す。
+    pub fn role(&self, name: &str) -> Result<Role, String> {
+        if self.roles.is_empty() {
+            return Ok(Role(name.into()));
+        }
+        self.roles
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("Unknown role: {name}"))
+    }
+
+    /// 既知のスコープを返す。
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - スコープ名（大文字と小文字を区別しない）
+    ///
+    /// # Returns
+    ///
+    /// * スコープ、または既知のスコープでない場合はエラー
+    ///
+    /// # Notes
+    ///
+    /// 既知のスコープを登録していない場合は、すべてのスコープ名をスコープとして返す。
+    pub fn scope(&self, name: &str) -> Result<Scope, String> {
+        if self.scopes.is_empty() {
+            return Ok(Scope(name.into()));
+        }
+        self.scopes
+            .get(&name.to_ascii_lowercase())
+            .cloned()
+            .ok_or_else(|| format!("Unknown scope: {name}"))
+    }
+
+    /// クレームに含まれる未知のロールを警告する。
+    ///
+    /// # Arguments
+    ///
+    /// * `claims` - 検証済みのクレーム
+    ///
+    /// # Notes
+    ///
+    /// 既知のロールを登録していない場合は警告しない。
+    /// ログが大量に出力されないように、同じロールは一度だけ警告する。
+    pub fn warn_unknown_roles(&self, claims: &Claims) {
+        if self.roles.is_empty() {
+            return;
+        }
+        let unknown = claims
+            .roles
+            .iter()
+            .flatten()
+            .filter(|role| !self.roles.contains_key(*role));
+        let mut warned = self
+            .warned_roles
+            .lock()
+            .expect("Warned roles lock must not be poisoned");
+        for role in unknown {
+            if warned.insert(role.clone()) {
+                tracing::warn!(role = %role, oid = %claims.oid, "Token carries a role that is not in authorization.known_roles");
+            }
+        }
+    }
+}
+
+/// ロールが推移的に包含するすべての下位ロールを返す。
+///
+/// # Arguments
+///
+/// * `role_hierarchy` - ロールをキー、ロールが包含する下位ロールを値としたハッシュマップ
+/// * `role` - ロール
+///
+/// # Returns
+///
+/// * ロールが包含するすべての下位ロール（ロール自身を含む）
+///
+/// # Notes
+///
+/// ロールの階層が循環している場合でも、同じロールを二度たどらないため終了する。
+pub fn implied_roles(role_hierarchy: &HashMap<String, Vec<String>>, role: &str) -> HashSet<String> {
+    let mut implied = HashSet::from([role.to_string()]);
+    let mut pending = vec![role];
+    while let Some(role) = pending.pop() {
+        for lower in role_hierarchy.get(role).into_iter().flatten() {
+            if implied.insert(lower.clone()) {
+                pending.push(lower);
+            }
+        }
+    }
+    implied
+}
+
+/// キャッシュしたグループのメンバーシップ
+struct CachedMembership {
+    /// グループに所属しているか
+    is_member: bool,
+    /// キャッシュの有効期限
+    expires_at: Instant,
+}
+
+/// Graph APIで確認したグループのメンバーシップのキャッシュ
+///
+/// グループの超過によってトークンに`groups`クレームが含まれていない場合、リクエストごとにGraph APIを呼び出さないように、
+/// ユーザーとグループの組み合わせごとに確認結果をキャッシュする。
+pub struct GroupMembershipCache {
+    /// キャッシュの有効期間
+    ttl: Duration,
+    /// ユーザーのオブジェクトIDとグループのオブジェクトIDをキー、メンバーシップを値としたハッシュマップ
+    entries: Mutex<HashMap<(String, String), CachedMembership>>,
+}
+
+impl GroupMembershipCache {
+    /// コンストラクタ
+    ///
+    /// # Arguments
+    ///
+    /// * `ttl` - キャッシュの有効期間
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// ユーザーが指定したグループのいずれかに所属しているかを、キャッシュから判定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `oid` - ユーザーのオブジェクトID
+    /// * `group_ids` - グループのオブジェクトID
+    ///
+    /// # Returns
+    ///
+    /// * いずれかのグループに所属している場合は`Ok(true)`
+    /// * すべてのグループに所属していない場合は`Ok(false)`
+    /// * キャッシュされていないグループがある場合は、キャッシュされていないグループのオブジェクトID
+    pub async fn lookup(&self, oid: &str, group_ids: &[String]) -> Result<bool, Vec<String>> {
+        let now = Instant::now();
+        let entries = self.entries.lock().await;
+        let mut missing = Vec::new();
+        for group_id in group_ids {
+            match entries.get(&(oid.to_string(), group_id.clone())) {
+                Some(cached) if now < cached.expires_at => {
+                    if cached.is_member {
+                        return Ok(true);
+                    }
+                }
+                _ => missing.push(group_id.clone()),
+            }
+        }
+        if missing.is_empty() {
+            Ok(false)
+        } else {
+            Err(missing)
+        }
+    }
+
+    /// Graph APIで確認したグループのメンバーシップをキャッシュする。
+    ///
+    /// # Arguments
+    ///
+    /// * `oid` - ユーザーのオブジェクトID
+    /// * `checked` - 確認したグループのオブジェクトID
+    /// * `member_of` - 確認したグループのうち、ユーザーが所属するグループのオブジェクトID
+    pub async fn store(&self, oid: &str, checked: &[String], member_of: &[String]) {
+        let now = Instant::now();
+        let expires_at = now + self.ttl;
+        let mut entries = self.entries.lock().await;
+        // 有効期限が切れたエントリを削除して、キャッシュが際限なく大きくならないようにする
+        entries.retain(|_, cached| now < cached.expires_at);
+        for group_id in checked {
+            entries.insert(
+                (oid.to_string(), group_id.clone()),
+                CachedMembership {
+                    is_member: member_of.contains(group_id),
+                    expires_at,
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(roles: &[&str]) -> Claims {
+        serde_json::from_value(serde_json::json!({
+            "aud": "api://backend",
+            "iss": "https://login.microsoftonline.com/tenant/v2.0",
+            "exp": 0,
+            "oid": "user-oid",
+            "sub": "user-sub",
+            "roles": roles,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn superior_role_is_granted_permissions_of_implied_roles() {
+        let role_permissions = HashMap::from([
+            ("Reader".to_string(), vec!["reports:read".to_string()]),
+            ("Writer".to_string(), vec!["reports:write".to_string()]),
+        ]);
+        let role_hierarchy = HashMap::from([
+            ("SuperAdmin".to_string(), vec!["Admin".to_string()]),
+            ("Admin".to_string(), vec!["Writer".to_string()]),
+            ("Writer".to_string(), vec!["Reader".to_string()]),
+        ]);
+        let registry = RoleScopeRegistry::new(&[], &[]);
+        let map = PermissionMap::new(
+            &registry,
+            role_permissions,
+            HashMap::new(),
+            &role_hierarchy,
+            "Admin",
+        )
+        .unwrap();
+
+        let super_admin = claims(&["SuperAdmin"]);
+        for permission in ["reports:read", "reports:write", JwksRefresh::NAME] {
+            assert!(map.is_granted_by_role(&super_admin, permission));
+        }
+        assert!(map.has_role(&super_admin, &registry.role("Reader").unwrap()));
+
+        let writer = claims(&["Writer"]);
+        assert!(map.is_granted_by_role(&writer, "reports:read"));
+        assert!(!map.is_granted_by_role(&writer, JwksRead::NAME));
+        assert!(!map.has_role(&writer, &registry.role("Admin").unwrap()));
+    }
+
+    #[test]
+    fn registry_hands_out_only_known_roles_and_scopes() {
+        let registry = RoleScopeRegistry::new(
+            &["Admin".to_string(), "Reader".to_string()],
+            &["Files.Read".to_string()],
+        );
+
+        let reader = registry.role("Reader").unwrap();
+        assert!(reader.is_held_by(&claims(&["Reader"])));
+        assert!(!reader.is_held_by(&claims(&["reader"])));
+        assert!(registry.role("Raeder").is_err());
+        assert_eq!(registry.scope("files.read").unwrap().name(), "Files.Read");
+        assert!(registry.scope("Files.ReadWrite").is_err());
+    }
+
+    #[test]
+    fn permission_map_rejects_roles_unknown_to_registry() {
+        let registry = RoleScopeRegistry::new(&["Admin".to_string(), "Reader".to_string()], &[]);
+        let permission_map = |role_permissions: &[(&str, &str)], admin_role: &str| {
+            let role_permissions = role_permissions
+                .iter()
+                .map(|(role, permission)| (role.to_string(), vec![permission.to_string()]))
+                .collect();
+            PermissionMap::new(
+                &registry,
+                role_permissions,
+                HashMap::new(),
+                &HashMap::new(),
+                admin_role,
+            )
+        };
+
+        assert!(permission_map(&[("Reader", "reports:read")], "Admin").is_ok());
+        assert_eq!(
+            permission_map(&[("Raeder", "reports:read")], "Admin").err(),
+            Some("Unknown role: Raeder".to_string())
+        );
+        assert_eq!(
+            permission_map(&[], "Admni").err(),
+            Some("Unknown role: Admni".to_string())
+        );
+    }
+}
@@ -0,0 +1,267 @@
+use std::{collections::HashMap, future::Future, pin::Pin};
+
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{common::RequestError, entra_id::Claims, trace_context::TraceContext};
+
+/// 非同期に評価する認可ポリシーの結果
+pub type PolicyFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<PolicyDecision, PolicyError>> + Send + 'a>>;
+
+/// 認可ポリシーの評価に渡す入力
+#[derive(Debug, Serialize)]
+pub struct PolicyInput<'a> {
+    /// HTTPメソッド
+    pub method: &'a str,
+    /// リクエストのパス
+    pub path: &'a str,
+    /// 検証済みのクレーム
+    pub claims: &'a Claims,
+    /// 認証コンテキストの属性
+    pub attributes: &'a HashMap<String, String>,
+}
+
+/// 認可ポリシーの評価結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyDecision {
+    /// 許可
+    Allow,
+    /// 拒否（理由）
+    Deny(String),
+}
+
+/// 認可ポリシー関連のエラー
+#[derive(Debug, thiserror::Error)]
+pub enum PolicyError {
+    /// 外部のポリシーエンジンへのリクエストの送信に失敗
+    #[error("Failed to call policy engine: {0}")]
+    Request(reqwest::Error),
+
+    /// 外部のポリシーエンジンがエラーステータスを返した
+    #[error("Policy engine returned error status: {0}")]
+    ErrorStatus(reqwest::StatusCode),
+
+    /// 外部のポリシーエンジンのレスポンスのパースに失敗
+    #[error("Failed to parse policy engine response: {0}")]
+    ResponseParse(reqwest::Error),
+}
+
+impl From<PolicyError> for RequestError {
+    /// 認可ポリシーを評価できない場合は、リクエストを許可せずに503エラーとする。
+    fn from(err: PolicyError) -> Self {
+        RequestError {
+            code: StatusCode::SERVICE_UNAVAILABLE,
+            message: err.to_string(),
+        }
+    }
+}
+
+/// 認可ポリシー
+///
+/// トークンの検証に成功した後に評価され、組織固有の複雑な認可ルールをアプリケーションの外部に委ねられるようにする。
+pub trait AuthorizationPolicy: Send + Sync {
+    /// 認可ポリシーを評価する。
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - 認可ポリシーの評価に渡す入力
+    /// * `trace` - 外部のポリシーエンジンに伝搬するトレースコンテキスト
+    ///
+    /// # Returns
+    ///
+    /// * 認可ポリシーの評価結果、またはエラー
+    fn evaluate<'a>(
+        &'a self,
+        input: &'a PolicyInput<'a>,
+        trace: &'a TraceContext,
+    ) -> PolicyFuture<'a>;
+}
+
+/// すべてのリクエストを許可する認可ポリシー
+pub struct AllowAllPolicy;
+
+impl AuthorizationPolicy for AllowAllPolicy {
+    fn evaluate<'a>(
+        &'a self,
+        _input: &'a PolicyInput<'a>,
+        _trace: &'a TraceContext,
+    ) -> PolicyFuture<'a> {
+        Box::pin(async { Ok(PolicyDecision::Allow) })
+    }
+}
+
+/// Open Policy AgentのData APIのレスポンス
+///
+/// ポリシーが定義されていない場合、`result`は含まれない。
+#[derive(Deserialize)]
+struct OpaResponse {
+    result: Option<bool>,
+}
+
+/// Open Policy AgentのData APIで評価する認可ポリシー
+///
+/// `{"input": {"method": ..., "path": ..., "claims": {...}}}`をPOSTし、`{"result": true}`が返された場合に許可する。
+/// ポリシーが定義されていない場合（`result`が含まれない場合）は拒否する。
+pub struct OpaHttpPolicy {
+    /// HTTPクライアント
+    client: reqwest::Client,
+    /// ポリシーのData APIのURL（例: `http://localhost:8181/v1/data/backend/allow`）
+    url: Url,
+}
+
+impl OpaHttpPolicy {
+    /// コンストラクタ
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - HTTPクライアント
+    /// * `url` - ポリシーのData APIのURL
+    pub fn new(client: reqwest::Client, url: Url) -> Self {
+        Self { client, url }
+    }
+}
+
+impl AuthorizationPolicy for OpaHttpPolicy {
+    fn evaluate<'a>(
+        &'a self,
+        input: &'a PolicyInput<'a>,
+        trace: &'a TraceContext,
+    ) -> PolicyFuture<'a> {
+        Box::pin(async move {
+            let response = trace
+                .inject(self.client.post(self.url.clone()))
+                .json(&serde_json::json!({ "input": input }))
+                .send()
+                .await
+                .map_err(PolicyError::Request)?;
+            let status = response.status();
+            if status.is_client_error() || status.is_server_error() {
+                return Err(PolicyError::ErrorStatus(status));
+            }
+            let response = response
+                .json::<OpaResponse>()
+                .await
+                .map_err(PolicyError::ResponseParse)?;
+            match response.result {
+                Some(true) => Ok(PolicyDecision::Allow),
+                Some(false) => Ok(PolicyDecision::Deny("Denied by OPA policy".into())),
+                None => Ok(PolicyDecision::Deny("OPA policy is not defined".into())),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::{Router, routing::post};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    /// ポリシーのData APIのパス
+    const POLICY_PATH: &str = "/v1/data/backend/allow";
+
+    /// 指定した遅延の後に、指定したステータスとボディを返すポリシーエンジンのモックを起動する。
+    ///
+    /// # Returns
+    ///
+    /// * モックのポリシーのData APIのURL
+    async fn serve_policy_engine(status: StatusCode, body: &'static str, delay: Duration) -> Url {
+        let app = Router::new().route(
+            POLICY_PATH,
+            post(move || async move {
+                tokio::time::sleep(delay).await;
+                (status, body)
+            }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        Url::parse(&format!("http://{address}{POLICY_PATH}")).unwrap()
+    }
+
+    /// ポリシーエンジンのモックで認可ポリシーを評価する。
+    async fn evaluate(
+        status: StatusCode,
+        body: &'static str,
+        delay: Duration,
+    ) -> Result<PolicyDecision, PolicyError> {
+        let _ = crate::crypto::install_default_provider();
+        let url = serve_policy_engine(status, body, delay).await;
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+        let policy = OpaHttpPolicy::new(client, url);
+        let claims: Claims = serde_json::from_value(serde_json::json!({
+            "aud": "api://backend",
+            "iss": "https://login.microsoftonline.com/tenant/v2.0",
+            "exp": 0,
+            "oid": "user-oid",
+            "sub": "user-sub",
+        }))
+        .unwrap();
+        let attributes = HashMap::new();
+        let input = PolicyInput {
+            method: "GET",
+            path: "/api/me",
+            claims: &claims,
+            attributes: &attributes,
+        };
+        policy.evaluate(&input, &TraceContext::new_root()).await
+    }
+
+    #[tokio::test]
+    async fn true_result_allows() {
+        let decision = evaluate(StatusCode::OK, r#"{"result": true}"#, Duration::ZERO).await;
+
+        assert_eq!(decision.unwrap(), PolicyDecision::Allow);
+    }
+
+    #[tokio::test]
+    async fn false_result_denies() {
+        let decision = evaluate(StatusCode::OK, r#"{"result": false}"#, Duration::ZERO).await;
+
+        assert!(matches!(decision.unwrap(), PolicyDecision::Deny(_)));
+    }
+
+    #[tokio::test]
+    async fn undefined_result_denies() {
+        let decision = evaluate(StatusCode::OK, "{}", Duration::ZERO).await;
+
+        assert!(matches!(decision.unwrap(), PolicyDecision::Deny(_)));
+    }
+
+    #[tokio::test]
+    async fn error_status_fails_closed() {
+        let decision = evaluate(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            r#"{"result": true}"#,
+            Duration::ZERO,
+        )
+        .await;
+
+        assert!(matches!(
+            decision,
+            Err(PolicyError::ErrorStatus(StatusCode::INTERNAL_SERVER_ERROR))
+        ));
+        let error = RequestError::from(decision.unwrap_err());
+        assert_eq!(error.code, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn timeout_fails_closed() {
+        let decision = evaluate(
+            StatusCode::OK,
+            r#"{"result": true}"#,
+            Duration::from_secs(5),
+        )
+        .await;
+
+        assert!(matches!(decision, Err(PolicyError::Request(e)) if e.is_timeout()));
+    }
+}
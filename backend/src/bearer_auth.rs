@@ -0,0 +1,216 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use http::{HeaderMap, HeaderValue, Request, Response, header};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::common::{RequestError, www_authenticate};
+use crate::entra_id::{BearerToken, EntraIdTokenVerifier, VerifiedToken};
+
+/// Bearerトークンを検証し、検証済みのクレームをリクエストの拡張に付与するtowerのレイヤー
+///
+/// axumに依存しないため、hyperやtonicなど、towerの`Service`で構成したサーバーで使用できる。
+/// 後続のサービスは、リクエストの拡張から`Claims`または`VerifiedToken`を取得する。
+///
+/// リクエストの拡張に`VerifiedToken`が既に存在する場合は、トークンを再検証せずに後続のサービスを呼び出す。
+///
+/// トークンが存在しない、または検証に失敗した場合は、後続のサービスを呼び出さずに、
+/// 401または503のステータスコードと`WWW-Authenticate`ヘッダを含む、空のボディのレスポンスを返す。
+#[derive(Clone)]
+pub struct BearerAuthLayer {
+    /// Entra IDトークン検証者
+    verifier: Arc<EntraIdTokenVerifier>,
+}
+
+impl BearerAuthLayer {
+    /// コンストラクタ
+    ///
+    /// # Arguments
+    ///
+    /// * `verifier` - Entra IDトークン検証者
+    pub fn new(verifier: Arc<EntraIdTokenVerifier>) -> Self {
+        Self { verifier }
+    }
+}
+
+impl<S> Layer<S> for BearerAuthLayer {
+    type Service = BearerAuthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        BearerAuthService {
+            inner,
+            verifier: Arc::clone(&self.verifier),
+        }
+    }
+}
+
+/// Bearerトークンを検証してから、後続のサービスを呼び出すサービス
+#[derive(Clone)]
+pub struct BearerAuthService<S> {
+    /// 後続のサービス
+    inner: S,
+    /// Entra IDトークン検証者
+    verifier: Arc<EntraIdTokenVerifier>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for BearerAuthService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    ReqBody: Send + 'static,
+    ResBody: Default,
+{
+    type Response = Response<ResBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<ReqBody>) -> Self::Future {
+        // `poll_ready`で準備できたサービスを使用するため、クローンしたサービスと入れ替える
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let verifier = Arc::clone(&self.verifier);
+        Box::pin(async move {
+            if request.extensions().get::<VerifiedToken>().is_some() {
+                return inner.call(request).await;
+            }
+            let Some(token) = bearer_token(request.headers()) else {
+                return Ok(reject(RequestError::unauthorized(
+                    "Authorization header with Bearer token is required",
+                )));
+            };
+            match verifier.authenticate(&token).await {
+                Ok(context) => {
+                    let extensions = request.extensions_mut();
+                    extensions.insert(context.claims.clone());
+                    extensions.insert(VerifiedToken {
+                        context,
+                        access_token: token,
+                    });
+                    inner.call(request).await
+                }
+                Err(e) => {
+                    tracing::error!(error = %e, "Token verification failed");
+                    Ok(reject(RequestError::from(e)))
+                }
+            }
+        })
+    }
+}
+
+/// `Authorization`ヘッダからBearerトークンを取得する。
+///
+/// # Arguments
+///
+/// * `headers` - リクエストのヘッダ
+///
+/// # Returns
+///
+/// * Bearerトークン、またはヘッダが存在しないか、Bearerスキームでない場合は`None`
+pub fn bearer_token(headers: &HeaderMap) -> Option<BearerToken> {
+    BearerToken::from_authorization(headers.get(header::AUTHORIZATION)?.to_str().ok()?)
+}
+
+/// 認証に失敗したリクエストに返すレスポンスを作成する。
+///
+/// # Arguments
+///
+/// * `err` - 認証のエラー
+///
+/// # Returns
+///
+/// * エラーのステータスコードと`WWW-Authenticate`ヘッダを含む、空のボディのレスポンス
+fn reject<B: Default>(err: RequestError) -> Response<B> {
+    let mut response = Response::new(B::default());
+    *response.status_mut() = err.code;
+    if let Some(value) = www_authenticate(err.code) {
+        response
+            .headers_mut()
+            .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static(value));
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use tower::{ServiceBuilder, ServiceExt as _};
+
+    use super::*;
+    use crate::entra_id::Claims;
+    use crate::fixtures::{build_verifier, sign_token};
+
+    /// リクエストの拡張に付与されたクレームの`oid`をボディとして返すサービス
+    async fn echo_oid(request: Request<()>) -> Result<Response<String>, Infallible> {
+        let oid = request
+            .extensions()
+            .get::<Claims>()
+            .map(|claims| claims.oid.clone())
+            .unwrap_or_default();
+        Ok(Response::new(oid))
+    }
+
+    #[tokio::test]
+    async fn layer_injects_claims_of_verified_token() {
+        let service = ServiceBuilder::new()
+            .layer(BearerAuthLayer::new(build_verifier().await))
+            .service_fn(echo_oid);
+        let request = Request::builder()
+            .header(header::AUTHORIZATION, format!("Bearer {}", sign_token()))
+            .body(())
+            .unwrap();
+
+        let response = service.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), http::StatusCode::OK);
+        assert_eq!(response.body(), "user-oid");
+    }
+
+    #[tokio::test]
+    async fn layer_rejects_request_without_valid_bearer_token() {
+        let layer = BearerAuthLayer::new(build_verifier().await);
+        for authorization in [None, Some("Basic dXNlcjpwYXNz"), Some("Bearer not-a-jwt")] {
+            let service = ServiceBuilder::new()
+                .layer(layer.clone())
+                .service_fn(echo_oid);
+            let mut request = Request::builder();
+            if let Some(value) = authorization {
+                request = request.header(header::AUTHORIZATION, value);
+            }
+
+            let response = service.oneshot(request.body(()).unwrap()).await.unwrap();
+
+            assert_eq!(response.status(), http::StatusCode::UNAUTHORIZED);
+            assert_eq!(response.headers()[header::WWW_AUTHENTICATE], "Bearer");
+            assert!(response.body().is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn layer_reuses_token_verified_by_preceding_middleware() {
+        let verifier = build_verifier().await;
+        let access_token = BearerToken(sign_token().into());
+        let context = verifier.authenticate(&access_token).await.unwrap();
+        let service = ServiceBuilder::new()
+            .layer(BearerAuthLayer::new(verifier))
+            .service_fn(echo_oid);
+        // `Authorization`ヘッダがなくても、検証済みのトークンを再利用する
+        let mut request = Request::builder().body(()).unwrap();
+        request.extensions_mut().insert(context.claims.clone());
+        request.extensions_mut().insert(VerifiedToken {
+            context,
+            access_token,
+        });
+
+        let response = service.oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), http::StatusCode::OK);
+        assert_eq!(response.body(), "user-oid");
+    }
+}
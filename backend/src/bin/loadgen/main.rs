@@ -0,0 +1,374 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use axum::{Json, Router, extract::State, routing};
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use clap::Parser;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use rsa::pkcs1::EncodeRsaPrivateKey as _;
+use rsa::traits::PublicKeyParts as _;
+use rsa::{RsaPrivateKey, rand_core::OsRng};
+use serde_json::json;
+use tokio::net::TcpListener;
+use url::Url;
+
+/// 署名鍵のkid
+const SIGNING_KEY_ID: &str = "loadgen";
+
+/// 署名鍵のRSA鍵のビット数
+const SIGNING_KEY_BITS: usize = 2048;
+
+/// モックのトークンエンドポイントが発行するGraph API用のアクセストークン
+const MOCK_GRAPH_ACCESS_TOKEN: &str = "loadgen-graph-access-token";
+
+/// 負荷試験用のトークンの有効期間
+const TOKEN_LIFETIME: Duration = Duration::from_hours(1);
+
+/// バックエンドの準備が完了するまで待機する間隔
+const READINESS_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 合成したトークンでバックエンドに負荷をかけ、スループットとレイテンシを計測する
+///
+/// Entra IDのJWKsエンドポイントとトークンエンドポイント、Graph APIのモックを起動するため、
+/// 実際のテナントを使用せずにキャパシティを計画できる。
+#[derive(Debug, Parser)]
+struct Args {
+    /// 負荷をかけるバックエンドのベースURL
+    #[arg(long, default_value = "http://127.0.0.1:8000")]
+    target: Url,
+
+    /// 負荷をかけるパス
+    #[arg(long, default_value = "/api/me")]
+    path: String,
+
+    /// モックが待ち受けるアドレス
+    #[arg(long, default_value = "127.0.0.1:9100")]
+    mock_address: SocketAddr,
+
+    /// トークンのテナントID
+    #[arg(long, default_value = "11111111-2222-3333-4444-555555555555")]
+    tenant_id: String,
+
+    /// トークンの購読者（バックエンドの設定の`audience`）
+    #[arg(long, default_value = "api://loadgen")]
+    audience: String,
+
+    /// トークンを合成するユーザー数（ユーザーごとにOBOとGraph APIのキャッシュが分かれる）
+    #[arg(long, default_value_t = 1000)]
+    users: usize,
+
+    /// 同時に送信するリクエスト数
+    #[arg(long, default_value_t = 32)]
+    concurrency: usize,
+
+    /// 負荷をかける時間（秒）
+    #[arg(long, default_value_t = 30)]
+    duration: u64,
+
+    /// モックのトークンエンドポイントとGraph APIが応答するまでの遅延（ミリ秒）
+    #[arg(long, default_value_t = 0)]
+    upstream_latency: u64,
+
+    /// バックエンドの準備が完了するまで待機する最大時間（秒）
+    #[arg(long, default_value_t = 300)]
+    wait_ready: u64,
+}
+
+/// 負荷試験用のトークンに署名する鍵
+///
+/// loadgenを起動するたびに生成し、loadgenのモックのJWKsエンドポイントのみが公開鍵を提供する。
+struct SigningKey {
+    /// 署名に使用する秘密鍵
+    encoding_key: EncodingKey,
+    /// RSA公開鍵のモジュラス（Base64URL）
+    modulus: String,
+    /// RSA公開鍵の指数（Base64URL）
+    exponent: String,
+}
+
+impl SigningKey {
+    /// 署名鍵を生成する。
+    fn generate() -> anyhow::Result<Self> {
+        let private_key = RsaPrivateKey::new(&mut OsRng, SIGNING_KEY_BITS)?;
+        let der = private_key.to_pkcs1_der()?;
+        Ok(Self {
+            encoding_key: EncodingKey::from_rsa_der(der.as_bytes()),
+            modulus: URL_SAFE_NO_PAD.encode(private_key.n().to_bytes_be()),
+            exponent: URL_SAFE_NO_PAD.encode(private_key.e().to_bytes_be()),
+        })
+    }
+}
+
+/// 1つのリクエストの結果
+struct Sample {
+    /// ステータスコード（接続エラーなどでレスポンスを受信できなかった場合は`None`）
+    status: Option<u16>,
+    /// レイテンシ
+    latency: Duration,
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    backend::crypto::install_default_provider()
+        .map_err(|e| anyhow::anyhow!("Failed to install crypto provider: {e}"))?;
+    tokio::runtime::Runtime::new()?.block_on(run(args))
+}
+
+async fn run(args: Args) -> anyhow::Result<()> {
+    // Entra IDとGraph APIのモックを起動
+    let listener = TcpListener::bind(args.mock_address).await?;
+    let mock_base = format!("http://{}", listener.local_addr()?);
+    let upstream_latency = Duration::from_millis(args.upstream_latency);
+    let signing_key = SigningKey::generate()?;
+    tokio::spawn(axum::serve(listener, mock_routes(upstream_latency, &signing_key)).into_future());
+    eprintln!("{}", backend_config_snippet(&args, &mock_base));
+
+    // 負荷をかける前にトークンを合成して、署名の時間を計測に含めない
+    let tokens = Arc::new(mint_tokens(&args, &signing_key)?);
+    let client = reqwest::Client::builder()
+        .pool_max_idle_per_host(args.concurrency)
+        .build()?;
+    wait_until_ready(&client, &args.target, Duration::from_secs(args.wait_ready)).await?;
+
+    eprintln!(
+        "Sending requests to {} for {}s with concurrency {}",
+        args.path, args.duration, args.concurrency
+    );
+    let url = args.target.join(&args.path)?;
+    let next_user = Arc::new(AtomicUsize::new(0));
+    let started_at = Instant::now();
+    let deadline = started_at + Duration::from_secs(args.duration);
+    let workers: Vec<_> = (0..args.concurrency)
+        .map(|_| {
+            let client = client.clone();
+            let url = url.clone();
+            let tokens = Arc::clone(&tokens);
+            let next_user = Arc::clone(&next_user);
+            tokio::spawn(async move {
+                let mut samples = Vec::new();
+                while Instant::now() < deadline {
+                    let token = &tokens[next_user.fetch_add(1, Ordering::Relaxed) % tokens.len()];
+                    let sent_at = Instant::now();
+                    let status = client
+                        .get(url.clone())
+                        .bearer_auth(token)
+                        .send()
+                        .await
+                        .ok()
+                        .map(|response| response.status().as_u16());
+                    samples.push(Sample {
+                        status,
+                        latency: sent_at.elapsed(),
+                    });
+                }
+                samples
+            })
+        })
+        .collect();
+    let mut samples = Vec::new();
+    for worker in workers {
+        samples.extend(worker.await?);
+    }
+    print_report(&samples, started_at.elapsed());
+    Ok(())
+}
+
+/// Entra IDのJWKsエンドポイントとトークンエンドポイント、Graph APIのモックのルートを作成する。
+fn mock_routes(upstream_latency: Duration, signing_key: &SigningKey) -> Router {
+    let jwks = json!({
+        "keys": [{
+            "kid": SIGNING_KEY_ID,
+            "kty": "RSA",
+            "use": "sig",
+            "alg": "RS256",
+            "n": signing_key.modulus,
+            "e": signing_key.exponent,
+        }]
+    });
+    Router::new()
+        .route(
+            "/{tenant}/discovery/v2.0/keys",
+            routing::get(|| async move { Json(jwks) }),
+        )
+        .route(
+            "/{tenant}/oauth2/v2.0/token",
+            routing::post(|State(latency): State<Duration>| async move {
+                tokio::time::sleep(latency).await;
+                Json(json!({
+                    "token_type": "Bearer",
+                    "expires_in": TOKEN_LIFETIME.as_secs(),
+                    "access_token": MOCK_GRAPH_ACCESS_TOKEN,
+                }))
+            }),
+        )
+        .route(
+            "/v1.0/me",
+            routing::get(|State(latency): State<Duration>| async move {
+                tokio::time::sleep(latency).await;
+                Json(json!({
+                    "id": "loadgen-user",
+                    "displayName": "Load Test User",
+                    "userPrincipalName": "loadgen@example.com",
+                }))
+            }),
+        )
+        .route(
+            "/v1.0/me/manager",
+            routing::get(|State(latency): State<Duration>| async move {
+                tokio::time::sleep(latency).await;
+                Json(json!({
+                    "id": "loadgen-manager",
+                    "displayName": "Load Test Manager",
+                }))
+            }),
+        )
+        .with_state(upstream_latency)
+}
+
+/// モックを使用するためのバックエンドの設定を返す。
+fn backend_config_snippet(args: &Args, mock_base: &str) -> String {
+    format!(
+        "Configure the backend to use the mocks:
+
+entra_id:
+  tenants:
+    - id: {tenant}
+      uri: {mock_base}/{tenant}/discovery/v2.0/keys
+      issuer: https://login.microsoftonline.com/{tenant}/v2.0
+      audience: {audience}
+graph:
+  authority_host: {mock_base}
+  # Disable the profile cache so that every request calls OBO and Graph
+  me_cache_ttl: 0
+resources:
+  graph:
+    scopes:
+      - https://graph.microsoft.com/User.Read
+    base_url: {mock_base}/v1.0
+",
+        tenant = args.tenant_id,
+        audience = args.audience,
+    )
+}
+
+/// ユーザーごとに、負荷試験用のアクセストークンを合成する。
+fn mint_tokens(args: &Args, signing_key: &SigningKey) -> anyhow::Result<Vec<String>> {
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some(SIGNING_KEY_ID.to_string());
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    (0..args.users.max(1))
+        .map(|user| {
+            let oid = format!("00000000-0000-0000-0000-{user:012}");
+            let claims = json!({
+                "aud": args.audience,
+                "iss": format!("https://login.microsoftonline.com/{}/v2.0", args.tenant_id),
+                "iat": now,
+                "nbf": now,
+                "exp": now + TOKEN_LIFETIME.as_secs(),
+                "oid": oid,
+                "sub": oid,
+                "tid": args.tenant_id,
+                "scp": "access_as_user",
+                "ver": "2.0",
+            });
+            Ok(jsonwebtoken::encode(
+                &header,
+                &claims,
+                &signing_key.encoding_key,
+            )?)
+        })
+        .collect()
+}
+
+/// バックエンドの`/readyz`が成功するまで待機する。
+async fn wait_until_ready(
+    client: &reqwest::Client,
+    target: &Url,
+    timeout: Duration,
+) -> anyhow::Result<()> {
+    let url = target.join("/readyz")?;
+    let deadline = Instant::now() + timeout;
+    eprintln!("Waiting for the backend to become ready: {url}");
+    loop {
+        if let Ok(response) = client.get(url.clone()).send().await
+            && response.status().is_success()
+        {
+            return Ok(());
+        }
+        if deadline <= Instant::now() {
+            anyhow::bail!("The backend did not become ready within {timeout:?}");
+        }
+        tokio::time::sleep(READINESS_POLL_INTERVAL).await;
+    }
+}
+
+/// スループットとレイテンシのパーセンタイルを出力する。
+fn print_report(samples: &[Sample], elapsed: Duration) {
+    if samples.is_empty() {
+        println!("No requests were sent");
+        return;
+    }
+    let mut latencies: Vec<Duration> = samples.iter().map(|sample| sample.latency).collect();
+    latencies.sort();
+    let percentile = |q: f64| latencies[((latencies.len() - 1) as f64 * q).round() as usize];
+    let mut statuses = std::collections::BTreeMap::new();
+    for sample in samples {
+        let label = sample
+            .status
+            .map_or_else(|| "error".to_string(), |status| status.to_string());
+        *statuses.entry(label).or_insert(0usize) += 1;
+    }
+    let succeeded = samples
+        .iter()
+        .filter(|sample| {
+            sample
+                .status
+                .is_some_and(|status| (200..300).contains(&status))
+        })
+        .count();
+
+    println!("Requests:   {} ({} succeeded)", samples.len(), succeeded);
+    println!(
+        "Throughput: {:.1} req/s",
+        samples.len() as f64 / elapsed.as_secs_f64()
+    );
+    println!(
+        "Latency:    p50 {:?}, p90 {:?}, p99 {:?}, max {:?}",
+        percentile(0.50),
+        percentile(0.90),
+        percentile(0.99),
+        latencies[latencies.len() - 1]
+    );
+    let statuses: Vec<String> = statuses
+        .iter()
+        .map(|(status, count)| format!("{status}: {count}"))
+        .collect();
+    println!("Statuses:   {}", statuses.join(", "));
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::{DecodingKey, Validation};
+
+    use super::*;
+
+    #[test]
+    fn minted_tokens_verify_with_mock_jwks() {
+        let args = Args::parse_from(["loadgen", "--users", "2"]);
+
+        let signing_key = SigningKey::generate().unwrap();
+
+        let tokens = mint_tokens(&args, &signing_key).unwrap();
+
+        let key =
+            DecodingKey::from_rsa_components(&signing_key.modulus, &signing_key.exponent).unwrap();
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_audience(&[&args.audience]);
+        for token in &tokens {
+            jsonwebtoken::decode::<serde_json::Value>(token, &key, &validation).unwrap();
+        }
+        assert_eq!(tokens.len(), 2);
+    }
+}
@@ -0,0 +1,34 @@
+use serde::Serialize;
+
+/// ビルド時に`built`が出力した情報
+mod built_info {
+    include!(concat!(env!("OUT_DIR"), "/built.rs"));
+}
+
+/// デプロイされているビルドを識別するための情報
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildInfo {
+    /// クレートのバージョン
+    pub version: &'static str,
+    /// ビルドしたコミットのSHA（gitリポジトリ外でビルドした場合は`None`）
+    pub git_sha: Option<&'static str>,
+    /// ビルドしたときに、コミットされていない変更があったか
+    pub git_dirty: Option<bool>,
+    /// ビルドした日時（RFC 2822形式）
+    pub built_at: &'static str,
+    /// ビルドプロファイル（`debug`、`release`など）
+    pub profile: &'static str,
+    /// 有効なcargoフィーチャー
+    pub features: &'static [&'static str],
+}
+
+/// このバイナリのビルド情報
+pub const BUILD_INFO: BuildInfo = BuildInfo {
+    version: built_info::PKG_VERSION,
+    git_sha: built_info::GIT_COMMIT_HASH,
+    git_dirty: built_info::GIT_DIRTY,
+    built_at: built_info::BUILT_TIME_UTC,
+    profile: built_info::PROFILE,
+    features: &built_info::FEATURES_LOWERCASE,
+};
@@ -0,0 +1,181 @@
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+/// サーキットブレーカーの状態
+enum CircuitState {
+    /// 呼び出しを許可している状態
+    Closed {
+        /// 連続して失敗した回数
+        consecutive_failures: u32,
+    },
+    /// 呼び出しを遮断している状態
+    Open {
+        /// 遮断を終了して、試行の呼び出しを許可する時刻
+        until: Instant,
+    },
+    /// 遮断期間が経過し、状態を確認するための試行の呼び出しを1つだけ許可している状態
+    HalfOpen {
+        /// 試行の呼び出しが結果を記録せずに中断された場合に、次の試行を許可する時刻
+        retry_at: Instant,
+    },
+}
+
+/// 外部サービスの障害時に呼び出しを遮断するサーキットブレーカー
+///
+/// 連続して失敗した回数がしきい値に達した場合に呼び出しを遮断して、障害中の外部サービスの応答を
+/// タイムアウトまで待たずに、すぐにエラーを返せるようにする。
+/// 遮断期間が経過した後、試行の呼び出しに成功した場合は遮断を解除し、失敗した場合は再び遮断する。
+pub struct CircuitBreaker {
+    /// メトリクスとログに使用する名前
+    name: &'static str,
+    /// 呼び出しを遮断する連続失敗回数
+    failure_threshold: u32,
+    /// 呼び出しを遮断する期間
+    open_duration: Duration,
+    /// 状態
+    state: Mutex<CircuitState>,
+}
+
+impl CircuitBreaker {
+    /// コンストラクタ
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - メトリクスとログに使用する名前
+    /// * `failure_threshold` - 呼び出しを遮断する連続失敗回数
+    /// * `open_duration` - 呼び出しを遮断する期間
+    pub fn new(name: &'static str, failure_threshold: u32, open_duration: Duration) -> Self {
+        metrics::gauge!(crate::metrics::CIRCUIT_BREAKER_OPEN, "name" => name).set(0.0);
+        Self {
+            name,
+            failure_threshold,
+            open_duration,
+            state: Mutex::new(CircuitState::Closed {
+                consecutive_failures: 0,
+            }),
+        }
+    }
+
+    /// 呼び出しを許可するかを判定する。
+    ///
+    /// # Returns
+    ///
+    /// * 呼び出しを許可する場合は`true`
+    ///
+    /// # Notes
+    ///
+    /// 遮断期間が経過している場合は、試行の呼び出しとして1つだけ許可する。
+    /// 試行の呼び出しが結果を記録せずに中断された場合に備えて、遮断期間が経過するたびに次の試行を許可する。
+    pub fn try_acquire(&self) -> bool {
+        let now = Instant::now();
+        let mut state = self.lock_state();
+        match *state {
+            CircuitState::Closed { .. } => true,
+            CircuitState::Open { until: at } | CircuitState::HalfOpen { retry_at: at }
+                if at <= now =>
+            {
+                *state = CircuitState::HalfOpen {
+                    retry_at: now + self.open_duration,
+                };
+                true
+            }
+            CircuitState::Open { .. } | CircuitState::HalfOpen { .. } => false,
+        }
+    }
+
+    /// 呼び出しを遮断しているかを返す。
+    ///
+    /// `try_acquire`と異なり、状態を変更しない。
+    pub fn is_open(&self) -> bool {
+        match *self.lock_state() {
+            CircuitState::Closed { .. } => false,
+            CircuitState::Open { until: at } | CircuitState::HalfOpen { retry_at: at } => {
+                Instant::now() < at
+            }
+        }
+    }
+
+    /// 呼び出しに成功したことを記録する。
+    pub fn record_success(&self) {
+        let mut state = self.lock_state();
+        if !matches!(*state, CircuitState::Closed { .. }) {
+            tracing::info!(name = self.name, "Circuit breaker closed");
+            metrics::gauge!(crate::metrics::CIRCUIT_BREAKER_OPEN, "name" => self.name).set(0.0);
+        }
+        *state = CircuitState::Closed {
+            consecutive_failures: 0,
+        };
+    }
+
+    /// 呼び出しに失敗したことを記録する。
+    pub fn record_failure(&self) {
+        let mut state = self.lock_state();
+        let consecutive_failures = match *state {
+            CircuitState::Closed {
+                consecutive_failures,
+            } => consecutive_failures.saturating_add(1),
+            // 試行の呼び出しに失敗した場合は、すぐに再び遮断する
+            CircuitState::HalfOpen { .. } => self.failure_threshold,
+            CircuitState::Open { .. } => return,
+        };
+        if consecutive_failures < self.failure_threshold {
+            *state = CircuitState::Closed {
+                consecutive_failures,
+            };
+            return;
+        }
+        tracing::warn!(
+            name = self.name,
+            open_duration_secs = self.open_duration.as_secs(),
+            "Circuit breaker opened"
+        );
+        metrics::gauge!(crate::metrics::CIRCUIT_BREAKER_OPEN, "name" => self.name).set(1.0);
+        *state = CircuitState::Open {
+            until: Instant::now() + self.open_duration,
+        };
+    }
+
+    /// 状態のロックを取得する。
+    ///
+    /// 状態の更新はパニックしないため、ロックが汚染されていても状態をそのまま使用する。
+    fn lock_state(&self) -> MutexGuard<'_, CircuitState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_after_threshold_and_allows_single_trial_after_open_duration() {
+        let breaker = CircuitBreaker::new("test", 2, Duration::ZERO);
+
+        breaker.record_failure();
+        assert!(!breaker.is_open());
+        breaker.record_failure();
+        assert!(matches!(*breaker.lock_state(), CircuitState::Open { .. }));
+
+        // 遮断期間が経過した後は、試行の呼び出しを許可する
+        assert!(breaker.try_acquire());
+        assert!(matches!(
+            *breaker.lock_state(),
+            CircuitState::HalfOpen { .. }
+        ));
+
+        // 試行の呼び出しに成功した場合は、遮断を解除する
+        breaker.record_success();
+        assert!(!breaker.is_open());
+        assert!(breaker.try_acquire());
+    }
+
+    #[test]
+    fn rejects_calls_while_open() {
+        let breaker = CircuitBreaker::new("test", 1, Duration::from_secs(60));
+
+        breaker.record_failure();
+
+        assert!(breaker.is_open());
+        assert!(!breaker.try_acquire());
+    }
+}
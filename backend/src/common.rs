@@ -1,7 +1,9 @@
-use axum::{
-    http::{HeaderValue, StatusCode},
-    response::IntoResponse,
-};
+#[cfg(feature = "server")]
+use axum::response::IntoResponse;
+#[cfg(feature = "server")]
+use http::HeaderValue;
+use http::StatusCode;
+#[cfg(feature = "server")]
 use serde::Serialize;
 
 pub type AppResult<T> = Result<T, RequestError>;
@@ -12,20 +14,74 @@ pub struct RequestError {
     pub message: String,
 }
 
+impl RequestError {
+    /// 認証に失敗したことを示す401エラーを作成する。
+    ///
+    /// トークンが存在しない、または無効な場合に使用する。
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - エラーメッセージ
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self {
+            code: StatusCode::UNAUTHORIZED,
+            message: message.into(),
+        }
+    }
+
+    /// 認可に失敗したことを示す403エラーを作成する。
+    ///
+    /// トークンは有効だが、必要な権限を持っていない場合に使用する。
+    ///
+    /// # Arguments
+    ///
+    /// * `missing` - 不足している権限（例: `role:Admin`）
+    pub fn forbidden<S: AsRef<str>>(missing: &[S]) -> Self {
+        let missing = missing.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+        Self {
+            code: StatusCode::FORBIDDEN,
+            message: format!("Missing required permissions: {}", missing.join(", ")),
+        }
+    }
+}
+
+/// エラーレスポンスのステータスコードに対応する`WWW-Authenticate`ヘッダの値を返す。
+///
+/// # Arguments
+///
+/// * `code` - エラーレスポンスのステータスコード
+///
+/// # Returns
+///
+/// * `WWW-Authenticate`ヘッダの値、認証と認可の失敗以外の場合は`None`
+///
+/// # Notes
+///
+/// RFC 6750に従い、認証の失敗と権限の不足を区別できるようにする。
+pub fn www_authenticate(code: StatusCode) -> Option<&'static str> {
+    match code {
+        StatusCode::UNAUTHORIZED => Some("Bearer"),
+        StatusCode::FORBIDDEN => Some(r#"Bearer error="insufficient_scope""#),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "server")]
 impl IntoResponse for RequestError {
     fn into_response(self) -> axum::response::Response {
         let status_code = self.code;
         let mut response = (self.code, axum::Json::<RequestErrorRaw>(self.into())).into_response();
-        if status_code == StatusCode::UNAUTHORIZED {
+        if let Some(value) = www_authenticate(status_code) {
             response.headers_mut().insert(
-                axum::http::header::WWW_AUTHENTICATE,
-                HeaderValue::from_static("Bearer"),
+                http::header::WWW_AUTHENTICATE,
+                HeaderValue::from_static(value),
             );
         }
         response
     }
 }
 
+#[cfg(feature = "server")]
 #[derive(Serialize)]
 struct RequestErrorRaw {
     code: u16,
@@ -33,6 +89,7 @@ struct RequestErrorRaw {
     message: String,
 }
 
+#[cfg(feature = "server")]
 impl From<RequestError> for RequestErrorRaw {
     fn from(err: RequestError) -> Self {
         Self {
@@ -46,3 +103,255 @@ impl From<RequestError> for RequestErrorRaw {
         }
     }
 }
+
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use std::time::Duration;
+
+    use http::header::WWW_AUTHENTICATE;
+    use serde_json::{Value, json};
+    use url::Url;
+
+    use super::*;
+    use crate::entra_id::{EntraIdError, IssuerTenant, Kid, TenantId};
+    use crate::graph::GraphError;
+    use crate::token_endpoint::{AadTokenError, TokenEndpointError};
+
+    /// エラーレスポンスのステータスコード、`WWW-Authenticate`ヘッダ、JSONボディを返す。
+    async fn render(err: impl Into<RequestError>) -> Value {
+        let response = err.into().into_response();
+        let status = response.status().as_u16();
+        let www_authenticate = response
+            .headers()
+            .get(WWW_AUTHENTICATE)
+            .map(|value| value.to_str().unwrap().to_string());
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        json!({
+            "status": status,
+            "www_authenticate": www_authenticate,
+            "body": serde_json::from_slice::<Value>(&body).unwrap(),
+        })
+    }
+
+    fn reqwest_error() -> reqwest::Error {
+        // 他のテストで登録済みの場合はエラーになるため、結果を無視する
+        let _ = crate::crypto::install_default_provider();
+        reqwest::Client::new().get("http://").build().unwrap_err()
+    }
+
+    fn jwt_error() -> jsonwebtoken::errors::Error {
+        jsonwebtoken::errors::ErrorKind::InvalidSignature.into()
+    }
+
+    fn jwks_uri() -> Url {
+        Url::parse("https://login.microsoftonline.com/tenant/discovery/v2.0/keys").unwrap()
+    }
+
+    fn aad_error(error: &str) -> AadTokenError {
+        AadTokenError {
+            error: error.into(),
+            error_description: Some("AADSTS50076: description".into()),
+            error_codes: vec![50076],
+            suberror: None,
+            correlation_id: Some("correlation-id".into()),
+        }
+    }
+
+    #[tokio::test]
+    async fn entra_id_error_responses() {
+        let tenant_id =
+            || TenantId::try_from("11111111-1111-1111-1111-111111111111".to_string()).unwrap();
+        let errors = [
+            ("initialize", EntraIdError::Initialize("initialize".into())),
+            (
+                "jwks_provider_init_error",
+                EntraIdError::JwksProviderInitError("init".into()),
+            ),
+            (
+                "jwks_fetch_error",
+                EntraIdError::JwksFetchError(reqwest_error(), jwks_uri()),
+            ),
+            (
+                "jwks_response_parse_error",
+                EntraIdError::JwksResponseParseError(jwks_uri(), reqwest_error()),
+            ),
+            (
+                "jwks_fetch_cancelled",
+                EntraIdError::JwksFetchCancelled(jwks_uri()),
+            ),
+            (
+                "jwks_refresh_timed_out",
+                EntraIdError::JwksRefreshTimedOut(tenant_id(), Duration::from_secs(2)),
+            ),
+            (
+                "jwks_fetch_retry_budget_exceeded",
+                EntraIdError::JwksFetchRetryBudgetExceeded(jwks_uri(), Duration::from_secs(10)),
+            ),
+            (
+                "decoding_key_not_found",
+                EntraIdError::DecodingKeyNotFound("kid".into()),
+            ),
+            (
+                "tenant_not_found",
+                EntraIdError::TenantNotFound(tenant_id()),
+            ),
+            ("tenant_disabled", EntraIdError::TenantDisabled(tenant_id())),
+            (
+                "token_header_decode_error",
+                EntraIdError::TokenHeaderDecodeError(jwt_error()),
+            ),
+            (
+                "token_header_missing_kid",
+                EntraIdError::TokenHeaderMissingKid("kid".into()),
+            ),
+            (
+                "unpinned_kid",
+                EntraIdError::UnpinnedKid(tenant_id(), Kid("kid".into())),
+            ),
+            (
+                "tenant_issuer_mismatch",
+                EntraIdError::TenantIssuerMismatch(tenant_id(), "https://issuer".into()),
+            ),
+            (
+                "disallowed_issuer_tenant",
+                EntraIdError::DisallowedIssuerTenant(IssuerTenant::Common),
+            ),
+            (
+                "unsupported_token_algorithm",
+                EntraIdError::UnsupportedTokenAlgorithm(jsonwebtoken::Algorithm::HS256),
+            ),
+            (
+                "verify_token_error",
+                EntraIdError::VerifyTokenError(jwt_error()),
+            ),
+            (
+                "create_decoding_key_error",
+                EntraIdError::CreateDecodingKeyError(Kid("kid".into()), jwt_error()),
+            ),
+            (
+                "invalid_token_format",
+                EntraIdError::InvalidTokenFormat("format".into()),
+            ),
+            (
+                "token_payload_decode_error",
+                EntraIdError::TokenPayloadDecodeError(base64::DecodeError::InvalidLength(1)),
+            ),
+            (
+                "token_payload_parse_error",
+                EntraIdError::TokenPayloadParseError(
+                    serde_json::from_str::<Value>("{").unwrap_err(),
+                ),
+            ),
+            (
+                "token_missing_issuer",
+                EntraIdError::TokenMissingIssuer(url::ParseError::EmptyHost),
+            ),
+            (
+                "invalid_issuer_format",
+                EntraIdError::InvalidIssuerFormat("iss".into()),
+            ),
+            (
+                "claims_mapping_rejected",
+                EntraIdError::ClaimsMappingRejected("claims".into()),
+            ),
+            (
+                "claims_mapping",
+                EntraIdError::ClaimsMapping("claims".into()),
+            ),
+            (
+                "claim_validation",
+                EntraIdError::ClaimValidation("claims".into()),
+            ),
+            (
+                "id_token_validation",
+                EntraIdError::IdTokenValidation("nonce".into()),
+            ),
+        ];
+        for (name, err) in errors {
+            insta::assert_json_snapshot!(format!("entra_id_error__{name}"), render(err).await);
+        }
+    }
+
+    #[tokio::test]
+    async fn token_endpoint_error_responses() {
+        let errors = [
+            ("request", TokenEndpointError::Request(reqwest_error())),
+            (
+                "interaction_required",
+                TokenEndpointError::ErrorResponse(
+                    StatusCode::BAD_REQUEST,
+                    aad_error("interaction_required"),
+                ),
+            ),
+            (
+                "invalid_grant",
+                TokenEndpointError::ErrorResponse(
+                    StatusCode::BAD_REQUEST,
+                    aad_error("invalid_grant"),
+                ),
+            ),
+            (
+                "temporarily_unavailable",
+                TokenEndpointError::ErrorResponse(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    aad_error("temporarily_unavailable"),
+                ),
+            ),
+            (
+                "unparsable_error_response",
+                TokenEndpointError::UnparsableErrorResponse(StatusCode::BAD_GATEWAY),
+            ),
+            (
+                "response_parse",
+                TokenEndpointError::ResponseParse(serde_json::from_str::<Value>("{").unwrap_err()),
+            ),
+        ];
+        for (name, err) in errors {
+            insta::assert_json_snapshot!(
+                format!("token_endpoint_error__{name}"),
+                render(err).await
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn graph_error_responses() {
+        let errors = [
+            ("request", GraphError::Request(reqwest_error())),
+            (
+                "error_status",
+                GraphError::ErrorStatus(StatusCode::NOT_FOUND),
+            ),
+            ("circuit_open", GraphError::CircuitOpen),
+        ];
+        for (name, err) in errors {
+            insta::assert_json_snapshot!(format!("graph_error__{name}"), render(err).await);
+        }
+    }
+
+    #[tokio::test]
+    async fn request_error_responses() {
+        let errors = [
+            (
+                "unauthorized",
+                RequestError::unauthorized("Invalid access token"),
+            ),
+            (
+                "forbidden",
+                RequestError::forbidden(&["role:Admin", "scope:Write"]),
+            ),
+            (
+                "gateway_timeout",
+                RequestError {
+                    code: StatusCode::GATEWAY_TIMEOUT,
+                    message: "Request deadline exceeded".into(),
+                },
+            ),
+        ];
+        for (name, err) in errors {
+            insta::assert_json_snapshot!(format!("request_error__{name}"), render(err).await);
+        }
+    }
+}
@@ -0,0 +1,448 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use secrecy::{ExposeSecret as _, SecretString};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use url::Url;
+
+use crate::{
+    config::ClientCredentialsRegistry,
+    entra_id::{Clock, SystemClock, TenantId},
+    token_endpoint::{TokenEndpointError, TokenResponse, request_token, token_endpoint_uri},
+    trace_context::TraceContext,
+};
+
+/// アプリケーション専用トークンを、有効期限のどれだけ前に更新するか
+///
+/// 有効期限の直前にトークンを使用すると、Graph APIに到達するまでに失効する可能性があるため、余裕を持って更新する。
+const TOKEN_RENEWAL_MARGIN: Duration = Duration::from_mins(5);
+
+/// キャッシュしたアプリケーション専用トークンを、有効期限のどれだけ前にバックグラウンドで更新するか
+///
+/// 呼び出し元がトークンの取得を待たないように、`TOKEN_RENEWAL_MARGIN`より前に更新する。
+const PROACTIVE_RENEWAL_MARGIN: Duration = Duration::from_mins(10);
+
+/// キャッシュしたアプリケーション専用トークンの有効期限を確認する間隔
+const RENEWAL_CHECK_INTERVAL: Duration = Duration::from_mins(1);
+
+/// トークンレスポンスに`expires_in`が含まれていない場合に仮定する有効期間
+const DEFAULT_TOKEN_LIFETIME: Duration = Duration::from_mins(60);
+
+/// キャッシュしたアプリケーション専用トークン
+struct CachedAppToken {
+    /// アクセストークン
+    access_token: SecretString,
+    /// アクセストークンの有効期限
+    expires_at: Instant,
+}
+
+/// テナントとスコープごとのアプリケーション専用トークンのキャッシュ
+///
+/// トークンを取得する間は、同じテナントとスコープのトークンを取得する他のタスクのみを待機させるため、
+/// キーごとに非同期のロックを持つ。
+type AppTokenSlot = Arc<tokio::sync::Mutex<Option<CachedAppToken>>>;
+
+/// テナントとスコープをキー、アプリケーション専用トークンのキャッシュを値としたハッシュマップ
+type AppTokenCache = HashMap<(TenantId, String), AppTokenSlot>;
+
+/// 非同期に取得するトークンレスポンス
+pub type AppTokenFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<TokenResponse, TokenEndpointError>> + Send + 'a>>;
+
+/// クライアント資格情報フローで、アプリケーション専用トークンを取得するフェッチャー
+///
+/// `ConfidentialClient`のキャッシュや更新の処理を、トークンエンドポイントにリクエストせずにテストできるように、
+/// トークンの取得を差し替えられるようにする。
+pub trait AppTokenFetcher: Send + Sync {
+    /// 指定したテナントとスコープのアプリケーション専用トークンを取得する。
+    ///
+    /// # Arguments
+    ///
+    /// * `tenant_id` - テナントID
+    /// * `scope` - スコープ
+    ///
+    /// # Returns
+    ///
+    /// * トークンレスポンス、またはエラー
+    fn fetch<'a>(&'a self, tenant_id: &'a TenantId, scope: &'a str) -> AppTokenFuture<'a>;
+}
+
+/// Entra IDのトークンエンドポイントにリクエストして、アプリケーション専用トークンを取得するフェッチャー
+pub struct ReqwestAppTokenFetcher {
+    /// HTTPクライアント
+    client: reqwest::Client,
+    /// テナントごとのクライアント資格情報
+    credentials: ClientCredentialsRegistry,
+    /// トークンエンドポイントを提供する認証機関のホスト
+    authority_host: Url,
+    /// トークンエンドポイントからの応答を待つタイムアウト
+    token_endpoint_timeout: Duration,
+}
+
+impl AppTokenFetcher for ReqwestAppTokenFetcher {
+    fn fetch<'a>(&'a self, tenant_id: &'a TenantId, scope: &'a str) -> AppTokenFuture<'a> {
+        Box::pin(async move {
+            let credentials = self.credentials.for_tenant(tenant_id);
+            let uri = token_endpoint_uri(&self.authority_host, tenant_id);
+            let params = [
+                ("grant_type", "client_credentials"),
+                ("client_id", &credentials.client_id.0),
+                ("client_secret", credentials.client_secret.expose_secret()),
+                ("scope", scope),
+            ];
+            request_token(
+                &self.client,
+                &uri,
+                &params,
+                self.token_endpoint_timeout,
+                &TraceContext::new_root(),
+            )
+            .await
+        })
+    }
+}
+
+/// 機密クライアント
+///
+/// クライアント資格情報フロー（client credentials grant）で、ユーザーのトークンを使用せずに
+/// アプリケーション専用トークンを取得する。
+/// バックグラウンドジョブなど、ユーザーのリクエストを伴わない処理からGraph APIを呼び出すために使用する。
+pub struct ConfidentialClient {
+    /// アプリケーション専用トークンを取得するフェッチャー
+    fetcher: Arc<dyn AppTokenFetcher>,
+    /// トークンの有効期限の判定に使用する時計
+    clock: Arc<dyn Clock>,
+    /// アプリケーション専用トークンのキャッシュ
+    cache: std::sync::Mutex<AppTokenCache>,
+}
+
+impl ConfidentialClient {
+    /// コンストラクタ
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - HTTPクライアント
+    /// * `credentials` - テナントごとのクライアント資格情報
+    /// * `authority_host` - トークンエンドポイントを提供する認証機関のホスト
+    /// * `token_endpoint_timeout` - トークンエンドポイントからの応答を待つタイムアウト
+    pub fn new(
+        client: reqwest::Client,
+        credentials: ClientCredentialsRegistry,
+        authority_host: Url,
+        token_endpoint_timeout: Duration,
+    ) -> Self {
+        let fetcher = ReqwestAppTokenFetcher {
+            client,
+            credentials,
+            authority_host,
+            token_endpoint_timeout,
+        };
+        Self::with_fetcher(Arc::new(fetcher), Arc::new(SystemClock))
+    }
+
+    /// フェッチャーと時計を指定して機密クライアントを作成する。
+    ///
+    /// # Arguments
+    ///
+    /// * `fetcher` - アプリケーション専用トークンを取得するフェッチャー
+    /// * `clock` - トークンの有効期限の判定に使用する時計
+    pub fn with_fetcher(fetcher: Arc<dyn AppTokenFetcher>, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            fetcher,
+            clock,
+            cache: std::sync::Mutex::new(AppTokenCache::new()),
+        }
+    }
+
+    /// 指定したテナントとスコープのアプリケーション専用トークンのキャッシュを返す。
+    fn slot(&self, tenant_id: &TenantId, scope: &str) -> AppTokenSlot {
+        let mut cache = self
+            .cache
+            .lock()
+            .expect("App token cache lock must not be poisoned");
+        Arc::clone(
+            cache
+                .entry((tenant_id.clone(), scope.to_string()))
+                .or_default(),
+        )
+    }
+
+    /// アプリケーション専用トークンを取得する。
+    ///
+    /// # Arguments
+    ///
+    /// * `tenant_id` - テナントID
+    /// * `scope` - スコープ（例: `https://graph.microsoft.com/.default`）
+    ///
+    /// # Returns
+    ///
+    /// * アプリケーション専用トークン、またはエラー
+    ///
+    /// # Notes
+    ///
+    /// キャッシュしたトークンが有効期限まで`TOKEN_RENEWAL_MARGIN`以上残っている場合は、キャッシュしたトークンを返す。
+    /// そうでない場合は、有効期限が切れる前に新しいトークンを取得してキャッシュを更新する。
+    ///
+    /// 同じトークンを同時に取得しないように、トークンを取得する間はテナントとスコープごとのロックを保持する。
+    /// 他のテナントやスコープのトークンの取得は待機させない。
+    pub async fn acquire_token(
+        &self,
+        tenant_id: &TenantId,
+        scope: &str,
+    ) -> Result<SecretString, TokenEndpointError> {
+        let slot = self.slot(tenant_id, scope);
+        let mut cached = slot.lock().await;
+        if let Some(token) = cached.as_ref()
+            && self.clock.now() + TOKEN_RENEWAL_MARGIN < token.expires_at
+        {
+            return Ok(token.access_token.clone());
+        }
+        let token = self.fetch_token(tenant_id, scope).await?;
+        let access_token = token.access_token.clone();
+        *cached = Some(token);
+        Ok(access_token)
+    }
+
+    /// トークンエンドポイントからアプリケーション専用トークンを取得する。
+    async fn fetch_token(
+        &self,
+        tenant_id: &TenantId,
+        scope: &str,
+    ) -> Result<CachedAppToken, TokenEndpointError> {
+        let requested_at = self.clock.now();
+        let token_response = self.fetcher.fetch(tenant_id, scope).await?;
+        let lifetime = token_response
+            .expires_in
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_TOKEN_LIFETIME);
+        tracing::info!(tenant_id = %tenant_id, scope = %scope, expires_in_secs = lifetime.as_secs(), "Acquired app-only access token");
+        Ok(CachedAppToken {
+            access_token: token_response.access_token,
+            expires_at: requested_at + lifetime,
+        })
+    }
+
+    /// 有効期限まで`PROACTIVE_RENEWAL_MARGIN`未満のアプリケーション専用トークンを更新する。
+    ///
+    /// # Notes
+    ///
+    /// 他のタスクが取得中のトークンは、そのタスクが更新するため更新しない。
+    /// 更新に失敗した場合は、キャッシュしたトークンを残して、次の確認時に再び更新を試行する。
+    async fn renew_expiring_tokens(&self) {
+        let slots: Vec<((TenantId, String), AppTokenSlot)> = self
+            .cache
+            .lock()
+            .expect("App token cache lock must not be poisoned")
+            .iter()
+            .map(|(key, slot)| (key.clone(), Arc::clone(slot)))
+            .collect();
+        for ((tenant_id, scope), slot) in slots {
+            let Ok(mut cached) = slot.try_lock() else {
+                continue;
+            };
+            let is_expiring = cached.as_ref().is_some_and(|token| {
+                token.expires_at <= self.clock.now() + PROACTIVE_RENEWAL_MARGIN
+            });
+            if !is_expiring {
+                continue;
+            }
+            match self.fetch_token(&tenant_id, &scope).await {
+                Ok(token) => *cached = Some(token),
+                Err(e) => {
+                    tracing::warn!(tenant_id = %tenant_id, scope = %scope, error = %e, "Failed to renew app-only access token");
+                }
+            }
+        }
+    }
+
+    /// 定期的に有効期限が近いアプリケーション専用トークンを更新するタスクを起動する。
+    ///
+    /// # Arguments
+    ///
+    /// * `shutdown` - タスクを停止するためのキャンセルトークン
+    ///
+    /// # Returns
+    ///
+    /// * 起動したタスクのハンドル
+    pub fn spawn_renewal(self: &Arc<Self>, shutdown: CancellationToken) -> JoinHandle<()> {
+        let client = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(RENEWAL_CHECK_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = shutdown.cancelled() => return,
+                    _ = interval.tick() => {}
+                }
+                let Some(client) = client.upgrade() else {
+                    return;
+                };
+                client.renew_expiring_tokens().await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    const TENANT_ID: &str = "11111111-1111-1111-1111-111111111111";
+    const SCOPE: &str = "https://graph.microsoft.com/.default";
+
+    /// 呼び出しごとに異なるアクセストークンを返し、`gate`が許可するまで応答しないフェッチャー
+    struct CountingAppTokenFetcher {
+        calls: AtomicUsize,
+        gate: tokio::sync::Semaphore,
+    }
+
+    impl CountingAppTokenFetcher {
+        fn new(permits: usize) -> Arc<Self> {
+            Arc::new(Self {
+                calls: AtomicUsize::new(0),
+                gate: tokio::sync::Semaphore::new(permits),
+            })
+        }
+    }
+
+    impl AppTokenFetcher for CountingAppTokenFetcher {
+        fn fetch<'a>(&'a self, tenant_id: &'a TenantId, _scope: &'a str) -> AppTokenFuture<'a> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move {
+                self.gate.acquire().await.unwrap().forget();
+                Ok(serde_json::from_value(serde_json::json!({
+                    "access_token": format!("{tenant_id}-{call}"),
+                    "expires_in": 3600,
+                }))
+                .unwrap())
+            })
+        }
+    }
+
+    /// テストから時刻を進められる時計
+    struct ManualClock {
+        now: std::sync::Mutex<Instant>,
+    }
+
+    impl ManualClock {
+        fn advance(&self, duration: Duration) {
+            *self.now.lock().unwrap() += duration;
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            *self.now.lock().unwrap()
+        }
+    }
+
+    fn tenant_id() -> TenantId {
+        TenantId::try_from(TENANT_ID.to_string()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn cached_token_is_returned_until_renewal_margin() {
+        let fetcher = CountingAppTokenFetcher::new(tokio::sync::Semaphore::MAX_PERMITS);
+        let clock = Arc::new(ManualClock {
+            now: std::sync::Mutex::new(Instant::now()),
+        });
+        let client = ConfidentialClient::with_fetcher(fetcher.clone(), clock.clone());
+        let acquire = || async {
+            client
+                .acquire_token(&tenant_id(), SCOPE)
+                .await
+                .ok()
+                .unwrap()
+                .expose_secret()
+                .to_string()
+        };
+
+        assert_eq!(acquire().await, format!("{TENANT_ID}-0"));
+        clock.advance(Duration::from_mins(54));
+        assert_eq!(acquire().await, format!("{TENANT_ID}-0"));
+
+        // 有効期限まで`TOKEN_RENEWAL_MARGIN`未満になった場合は、新しいトークンを取得する
+        clock.advance(Duration::from_mins(1) + Duration::from_secs(1));
+        assert_eq!(acquire().await, format!("{TENANT_ID}-1"));
+        assert_eq!(fetcher.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn concurrent_first_fetch_requests_token_once_per_key() {
+        let fetcher = CountingAppTokenFetcher::new(0);
+        let client = Arc::new(ConfidentialClient::with_fetcher(
+            fetcher.clone(),
+            Arc::new(SystemClock),
+        ));
+        let spawn_acquire = |tenant_id: TenantId| {
+            let client = Arc::clone(&client);
+            tokio::spawn(async move {
+                client
+                    .acquire_token(&tenant_id, SCOPE)
+                    .await
+                    .ok()
+                    .unwrap()
+                    .expose_secret()
+                    .to_string()
+            })
+        };
+
+        let requests: Vec<_> = (0..10).map(|_| spawn_acquire(tenant_id())).collect();
+        while fetcher.calls.load(Ordering::SeqCst) < 1 {
+            tokio::task::yield_now().await;
+        }
+
+        // 取得中のトークンがあっても、他のテナントのトークンの取得は待機しない
+        let other_tenant_id =
+            TenantId::try_from("22222222-2222-2222-2222-222222222222".to_string()).unwrap();
+        let other = spawn_acquire(other_tenant_id.clone());
+        while fetcher.calls.load(Ordering::SeqCst) < 2 {
+            tokio::task::yield_now().await;
+        }
+
+        fetcher.gate.add_permits(2);
+        assert_eq!(other.await.unwrap(), format!("{other_tenant_id}-1"));
+        for request in requests {
+            assert_eq!(request.await.unwrap(), format!("{TENANT_ID}-0"));
+        }
+        assert_eq!(fetcher.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn expiring_tokens_are_renewed_in_background() {
+        let fetcher = CountingAppTokenFetcher::new(tokio::sync::Semaphore::MAX_PERMITS);
+        let clock = Arc::new(ManualClock {
+            now: std::sync::Mutex::new(Instant::now()),
+        });
+        let client = ConfidentialClient::with_fetcher(fetcher.clone(), clock.clone());
+        client
+            .acquire_token(&tenant_id(), SCOPE)
+            .await
+            .ok()
+            .unwrap();
+
+        // 有効期限まで`PROACTIVE_RENEWAL_MARGIN`以上残っているトークンは更新しない
+        clock.advance(Duration::from_mins(49));
+        client.renew_expiring_tokens().await;
+        assert_eq!(fetcher.calls.load(Ordering::SeqCst), 1);
+
+        // 有効期限が近いトークンは、呼び出し元が要求する前に更新する
+        clock.advance(Duration::from_mins(2));
+        client.renew_expiring_tokens().await;
+        assert_eq!(fetcher.calls.load(Ordering::SeqCst), 2);
+        clock.advance(Duration::from_mins(5));
+        let token = client
+            .acquire_token(&tenant_id(), SCOPE)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(token.expose_secret(), format!("{TENANT_ID}-1"));
+        assert_eq!(fetcher.calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -1,59 +1,1067 @@
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
 use config::Config;
+use config::builder::{ConfigBuilder, DefaultState};
 use secrecy::SecretString;
-use serde::Deserialize;
+use serde::{Deserialize, de::DeserializeOwned};
+use url::Url;
+use zeroize::Zeroizing;
 
-use crate::entra_id::Tenant;
+use crate::authorization::implied_roles;
+use crate::entra_id::{
+    ConnectionPoolConfig, StartupDeadlinePolicy, Tenant, TenantId, ValidationOptions, is_guid,
+};
+use crate::graph::GRAPH_RESOURCE;
+use crate::tls_pinning::SpkiPin;
+use crate::token_endpoint::DEFAULT_AUTHORITY_HOST;
 
 type ConfigResult<T> = Result<T, ConfigError>;
 
+/// 重ね合わせる環境ごとの設定ファイルを選択する環境変数
+pub const APP_ENV_VAR: &str = "APP_ENV";
+
+/// 設定を上書きする環境変数の接頭辞
+const ENV_PREFIX: &str = "APP";
+
+/// 設定を上書きする環境変数の、接頭辞と設定キーの各階層の区切り文字
+///
+/// 設定キーが`_`を含むため、`__`で区切る。
+const ENV_SEPARATOR: &str = "__";
+
 #[derive(thiserror::Error, Debug)]
 pub enum ConfigError {
     #[error("{0}")]
     LoadError(config::ConfigError),
     #[error("{0}")]
-    DeserializeError(config::ConfigError),
+    Validation(String),
+    /// 設定の問題をすべて列挙したレポート
+    #[error("Invalid configuration:\n{}", format_problems(.0))]
+    Problems(Vec<String>),
+}
+
+/// 設定の問題を1行ずつ列挙した文字列を返す。
+fn format_problems(problems: &[String]) -> String {
+    problems
+        .iter()
+        .map(|problem| format!("  - {problem}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 検証結果がエラーの場合に、問題のリストに追加する。
+///
+/// # Arguments
+///
+/// * `problems` - 設定の問題のリスト
+/// * `result` - 検証結果
+fn collect_problem<T>(problems: &mut Vec<String>, result: ConfigResult<T>) {
+    match result {
+        Ok(_) => {}
+        Err(ConfigError::Problems(mut others)) => problems.append(&mut others),
+        Err(e) => problems.push(e.to_string()),
+    }
 }
 
 #[derive(Deserialize)]
 pub struct AppConfig {
     pub log_level: String,
+    #[serde(default)]
+    pub log_format: LogFormat,
+    pub log_file: Option<LogFileConfig>,
+    #[serde(default)]
+    pub trace_sampling: TraceSampling,
+    #[serde(default)]
+    pub http_debug_log: HttpDebugLogConfig,
+    #[serde(default)]
+    pub request_id: RequestIdConfig,
     pub web: WebConfig,
     pub entra_id: EntraIdConfig,
     pub client_credentials: ClientCredentials,
+    pub admin: AdminConfig,
+    #[serde(default)]
+    pub authorization: AuthorizationConfig,
+    #[serde(default)]
+    pub api_keys: ApiKeysConfig,
+    pub graph: GraphConfig,
+    /// 名前をキー、OBOでアクセストークンを取得して呼び出す下流リソースの設定を値としたハッシュマップ
+    #[serde(default)]
+    pub resources: HashMap<String, ResourceConfig>,
 }
 
 impl AppConfig {
-    pub fn load() -> ConfigResult<Self> {
-        let config = Config::builder()
-            .add_source(config::File::with_name("config.yaml"))
-            .build()
+    /// アプリケーション設定を読み込む。
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - 基本の設定ファイルのパス（例: `config.yaml`）
+    /// * `overrides` - コマンドラインで指定した設定の上書き
+    ///
+    /// # Returns
+    ///
+    /// * アプリケーション設定、またはエラー
+    ///
+    /// # Notes
+    ///
+    /// 次の順に設定を重ね合わせ、後に読み込んだ設定で先に読み込んだ設定を上書きする。
+    ///
+    /// 1. 基本の設定ファイル
+    /// 2. 環境変数`APP_ENV`を設定した場合は、基本の設定ファイルと同じディレクトリの`{ファイル名}.{APP_ENV}.yaml`
+    ///    （例: `config.staging.yaml`）
+    /// 3. `APP__`で始まる環境変数（例: `APP__WEB__PORT=8080`は`web.port`を上書きする）
+    /// 4. コマンドラインで指定した設定の上書き
+    pub fn load(path: &Path, overrides: &ConfigOverrides) -> ConfigResult<Self> {
+        let mut builder = Config::builder().add_source(config::File::from(path));
+        if let Some(app_env) = app_env()? {
+            builder = builder.add_source(config::File::from(env_config_path(path, &app_env)));
+        }
+        let builder = builder.add_source(
+            config::Environment::with_prefix(ENV_PREFIX)
+                .prefix_separator(ENV_SEPARATOR)
+                .separator(ENV_SEPARATOR)
+                .try_parsing(true),
+        );
+        let config = overrides
+            .apply(builder)
+            .and_then(|builder| builder.build())
             .map_err(ConfigError::LoadError)?;
-        config
-            .try_deserialize()
-            .map_err(ConfigError::DeserializeError)
+        let mut app_config = Self::deserialize_with_paths(config)?;
+        let mut problems = app_config.resolve_tenant_ids();
+        collect_problem(&mut problems, app_config.validate());
+        if problems.is_empty() {
+            Ok(app_config)
+        } else {
+            Err(ConfigError::Problems(problems))
+        }
+    }
+
+    /// 確認済みドメイン名で指定したテナントIDを、GUIDに解決する。
+    ///
+    /// クライアント資格情報やテナントレジストリがトークンの`tid`でテナントを参照できるように、
+    /// 他の設定を使用する前に解決する。
+    ///
+    /// # Returns
+    ///
+    /// * 解決できなかったテナントIDの問題のリスト
+    fn resolve_tenant_ids(&mut self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (index, tenant) in self.entra_id.tenants.iter_mut().enumerate() {
+            if let Err(e) = tenant.tenant.resolve_domain_name_id() {
+                problems.push(format!("entra_id.tenants[{index}].id: {e}"));
+            }
+        }
+        problems
+    }
+
+    /// 設定をデシリアライズし、失敗した場合は問題のある設定キーのパスを含むエラーを返す。
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - 重ね合わせた設定
+    ///
+    /// # Returns
+    ///
+    /// * アプリケーション設定、またはエラー
+    ///
+    /// # Notes
+    ///
+    /// デシリアライズは最初のエラーで中止されるため、失敗した場合は設定のセクションとテナントを1つずつデシリアライズして、
+    /// 問題のあるすべての設定キーを報告する。
+    fn deserialize_with_paths(config: Config) -> ConfigResult<Self> {
+        let error = match serde_path_to_error::deserialize::<_, Self>(config.clone()) {
+            Ok(app_config) => return Ok(app_config),
+            Err(e) => e,
+        };
+        let mut problems = Vec::new();
+        if let Ok(sections) = config.try_deserialize::<HashMap<String, config::Value>>() {
+            let mut sections: Vec<_> = sections.into_iter().collect();
+            sections.sort_by(|(a, _), (b, _)| a.cmp(b));
+            for (key, value) in sections {
+                let problem = match key.as_str() {
+                    "web" => section_problem::<WebConfig>(&key, value),
+                    "entra_id" => {
+                        problems.extend(entra_id_problems(value));
+                        continue;
+                    }
+                    "client_credentials" => section_problem::<ClientCredentials>(&key, value),
+                    "admin" => section_problem::<AdminConfig>(&key, value),
+                    "authorization" => section_problem::<AuthorizationConfig>(&key, value),
+                    "graph" => section_problem::<GraphConfig>(&key, value),
+                    "resources" => section_problem::<HashMap<String, ResourceConfig>>(&key, value),
+                    _ => None,
+                };
+                problems.extend(problem);
+            }
+        }
+        // 必須のセクションが存在しない場合など、セクションごとのデシリアライズで見つからなかった問題を報告する
+        let path = error.path().to_string();
+        let section = path.split(['.', '[']).next().unwrap_or_default();
+        if !problems.iter().any(|problem| problem.starts_with(section)) {
+            problems.insert(0, path_problem("", &path, error.inner()));
+        }
+        Err(ConfigError::Problems(problems))
+    }
+
+    /// 既知のロールとスコープを検証する。
+    ///
+    /// 既知のロールを指定した場合は、管理者ロール、`role_permissions`および`role_hierarchy`で参照するロールが、
+    /// すべて既知のロールに含まれているかを検証する。
+    fn validate_known_roles_and_scopes(&self) -> ConfigResult<()> {
+        let authorization = &self.authorization;
+        let mut problems = Vec::new();
+        for (field, names) in [
+            ("authorization.known_roles", &authorization.known_roles),
+            ("authorization.known_scopes", &authorization.known_scopes),
+        ] {
+            for name in names {
+                if name.is_empty() || name.chars().any(char::is_whitespace) {
+                    problems.push(format!("{field}: invalid name: '{name}'"));
+                }
+            }
+        }
+        if !authorization.known_roles.is_empty() {
+            let known: HashSet<_> = authorization.known_roles.iter().collect();
+            let mut referenced = vec![("admin.role", &self.admin.role)];
+            referenced.extend(
+                authorization
+                    .role_permissions
+                    .keys()
+                    .map(|role| ("authorization.role_permissions", role)),
+            );
+            for (role, lowers) in &authorization.role_hierarchy {
+                referenced.push(("authorization.role_hierarchy", role));
+                referenced.extend(
+                    lowers
+                        .iter()
+                        .map(|lower| ("authorization.role_hierarchy", lower)),
+                );
+            }
+            referenced.sort();
+            referenced.dedup();
+            for (field, role) in referenced {
+                if !known.contains(role) {
+                    problems.push(format!("{field}: unknown role: {role}"));
+                }
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Problems(problems))
+        }
+    }
+
+    /// アプリケーション設定を検証する。
+    ///
+    /// 設定の問題をすべて列挙して報告する。
+    fn validate(&self) -> ConfigResult<()> {
+        let mut problems = Vec::new();
+        if let TraceSampling::Ratio(ratio) = self.trace_sampling
+            && !(0.0..=1.0).contains(&ratio)
+        {
+            problems.push(format!(
+                "trace_sampling.ratio: must be between 0.0 and 1.0: {ratio}"
+            ));
+        }
+        collect_problem(&mut problems, self.web.validate());
+        let mut zero_timeouts: Vec<_> = self
+            .web
+            .route_timeouts
+            .iter()
+            .filter(|(_, timeout)| **timeout == 0)
+            .map(|(pattern, _)| pattern)
+            .collect();
+        zero_timeouts.sort();
+        for pattern in zero_timeouts {
+            problems.push(format!(
+                "web.route_timeouts.{pattern}: must be greater than zero"
+            ));
+        }
+        let mut token_sources: Vec<_> = self.web.token_sources.iter().collect();
+        token_sources.sort_by_key(|(pattern, _)| *pattern);
+        for (pattern, source) in token_sources {
+            if source.query.is_none() && source.cookie.is_none() {
+                problems.push(format!(
+                    "web.token_sources.{pattern}: query or cookie is required"
+                ));
+            }
+            for (field, name) in [("query", &source.query), ("cookie", &source.cookie)] {
+                if name.as_deref().is_some_and(|name| name.trim().is_empty()) {
+                    problems.push(format!(
+                        "web.token_sources.{pattern}.{field}: must not be empty"
+                    ));
+                }
+            }
+        }
+        let mut cyclic_roles: Vec<_> = self
+            .authorization
+            .role_hierarchy
+            .iter()
+            .filter(|(role, lowers)| {
+                lowers.iter().any(|lower| {
+                    implied_roles(&self.authorization.role_hierarchy, lower).contains(*role)
+                })
+            })
+            .map(|(role, _)| role)
+            .collect();
+        cyclic_roles.sort();
+        for role in cyclic_roles {
+            problems.push(format!(
+                "authorization.role_hierarchy.{role}: must not imply itself"
+            ));
+        }
+        collect_problem(&mut problems, self.validate_known_roles_and_scopes());
+        if !(0.0..=1.0).contains(&self.entra_id.success_audit_sample_rate) {
+            problems.push(format!(
+                "entra_id.success_audit_sample_rate: must be between 0.0 and 1.0: {}",
+                self.entra_id.success_audit_sample_rate
+            ));
+        }
+        if self.entra_id.unknown_kid_refresh_timeout == Some(0) {
+            problems.push("entra_id.unknown_kid_refresh_timeout: must be greater than zero".into());
+        }
+        if self.entra_id.unknown_kid_refresh_max_per_minute == Some(0) {
+            problems.push(
+                "entra_id.unknown_kid_refresh_max_per_minute: must be greater than zero".into(),
+            );
+        }
+        if !self.api_keys.route_groups.is_empty() && self.api_keys.keys.is_empty() {
+            problems.push("api_keys.keys: required when api_keys.route_groups is specified".into());
+        }
+        let mut names = HashSet::new();
+        for key in &self.api_keys.keys {
+            if key.name.trim().is_empty() {
+                problems.push("api_keys.keys.name: must not be empty".into());
+            } else if !names.insert(key.name.as_str()) {
+                problems.push(format!("api_keys.keys.name: duplicated: {}", key.name));
+            }
+        }
+        if let Some(circuit_breaker) = self.graph.circuit_breaker.as_ref() {
+            if circuit_breaker.failure_threshold == 0 {
+                problems.push(
+                    "graph.circuit_breaker.failure_threshold: must be greater than zero".into(),
+                );
+            }
+            if circuit_breaker.open_duration == 0 {
+                problems
+                    .push("graph.circuit_breaker.open_duration: must be greater than zero".into());
+            }
+        }
+        if let Some(log_file) = self.log_file.as_ref() {
+            collect_problem(&mut problems, log_file.validate());
+        }
+        self.validate_resources(&mut problems);
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Problems(problems))
+        }
+    }
+
+    /// 下流リソースの設定を検証する。
+    ///
+    /// Graph APIのスコープは、`graph.scopes`と`resources.graph.scopes`のいずれか一方で指定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `problems` - 見つけた問題を追加する、設定の問題のリスト
+    fn validate_resources(&self, problems: &mut Vec<String>) {
+        if !self.resources.contains_key(GRAPH_RESOURCE) {
+            collect_problem(
+                problems,
+                validate_scopes("graph.scopes", &self.graph.scopes),
+            );
+        } else if !self.graph.scopes.is_empty() {
+            problems.push(format!(
+                "graph.scopes: must not be set when resources.{GRAPH_RESOURCE} is configured"
+            ));
+        }
+        let mut names: Vec<_> = self.resources.keys().collect();
+        names.sort();
+        for name in names {
+            let resource = &self.resources[name];
+            if resource.scopes.is_empty() && resource.audience.is_none() {
+                problems.push(format!(
+                    "resources.{name}: either scopes or audience is required"
+                ));
+                continue;
+            }
+            collect_problem(
+                problems,
+                validate_scopes(&format!("resources.{name}.scopes"), &resource.scopes()),
+            );
+        }
+    }
+}
+
+/// 設定のセクションをデシリアライズして、問題がある場合はパスを含むメッセージを返す。
+///
+/// # Arguments
+///
+/// * `prefix` - セクションのパス
+/// * `value` - セクションの値
+///
+/// # Returns
+///
+/// * 問題がある場合はメッセージ、ない場合は`None`
+fn section_problem<T: DeserializeOwned>(prefix: &str, value: config::Value) -> Option<String> {
+    serde_path_to_error::deserialize::<_, T>(value)
+        .err()
+        .map(|e| path_problem(prefix, &e.path().to_string(), e.inner()))
+}
+
+/// `entra_id`セクションを、テナントを1つずつデシリアライズして、問題のリストを返す。
+///
+/// # Arguments
+///
+/// * `value` - `entra_id`セクションの値
+///
+/// # Returns
+///
+/// * 設定の問題のリスト
+///
+/// # Notes
+///
+/// テナント設定はテナントを`flatten`で展開するため、テナント設定のエラーにはテナント内のパスが含まれない。
+/// テナントとしてもデシリアライズして、問題のある設定キーを特定する。
+fn entra_id_problems(value: config::Value) -> Vec<String> {
+    let mut problems = Vec::new();
+    let Ok(mut table) = value.into_table() else {
+        return vec!["entra_id: must be a table".into()];
+    };
+    if let Some(tenants) = table.insert(
+        "tenants".into(),
+        config::Value::new(None, config::ValueKind::Array(Vec::new())),
+    ) && let Ok(tenants) = tenants.into_array()
+    {
+        for (index, tenant) in tenants.into_iter().enumerate() {
+            let prefix = format!("entra_id.tenants[{index}]");
+            if let Some(problem) = section_problem::<Tenant>(&prefix, tenant.clone())
+                .or_else(|| section_problem::<TenantConfig>(&prefix, tenant))
+            {
+                problems.push(problem);
+            }
+        }
+    }
+    // テナント以外の問題を報告するため、テナントを空にしてデシリアライズする
+    problems.extend(section_problem::<EntraIdConfig>(
+        "entra_id",
+        config::Value::new(None, config::ValueKind::Table(table)),
+    ));
+    problems
+}
+
+/// デシリアライズのエラーを、問題のある設定キーのパスを含むメッセージにする。
+///
+/// # Arguments
+///
+/// * `prefix` - デシリアライズした設定のパス（ルートの場合は空文字列）
+/// * `path` - デシリアライズした設定内の、問題のある設定キーのパス
+/// * `error` - デシリアライズのエラー
+///
+/// # Returns
+///
+/// * `entra_id.tenants[2].uri: relative URL without a base`の形式のメッセージ
+fn path_problem(prefix: &str, path: &str, error: &config::ConfigError) -> String {
+    let path = match (prefix.is_empty(), path == ".") {
+        (true, _) => path.to_string(),
+        (false, true) => prefix.to_string(),
+        (false, false) => format!("{prefix}.{path}"),
+    };
+    format!("{path}: {}", describe_error(error))
+}
+
+/// `config`クレートのエラーを、設定キーを除いたメッセージにする。
+///
+/// # Arguments
+///
+/// * `error` - `config`クレートのエラー
+///
+/// # Returns
+///
+/// * 設定キーを除き、値を読み込んだ設定ファイルや環境変数を含むメッセージ
+///
+/// # Notes
+///
+/// `config`クレートのエラーに含まれる設定キーは、親の設定キーを含まないため、パスと重複して紛らわしい。
+fn describe_error(error: &config::ConfigError) -> String {
+    let (message, origin) = match error {
+        config::ConfigError::Type {
+            origin,
+            unexpected,
+            expected,
+            ..
+        } => (
+            format!("invalid type: {unexpected}, expected {expected}"),
+            origin,
+        ),
+        config::ConfigError::At { error, origin, .. } => (describe_error(error), origin),
+        _ => return error.to_string(),
+    };
+    match origin {
+        Some(origin) => format!("{message} in {origin}"),
+        None => message,
+    }
+}
+
+impl WebConfig {
+    /// Webサーバーが待ち受けるソケットアドレスを返す。
+    ///
+    /// # Returns
+    ///
+    /// * `bind_address`の各IPアドレスと`port`を組み合わせたソケットアドレス、またはエラー
+    pub fn bind_addresses(&self) -> ConfigResult<Vec<SocketAddr>> {
+        parse_bind_addresses("web.bind_address", self.bind_address.as_deref(), self.port)
+    }
+
+    /// Webサーバー設定を検証する。
+    ///
+    /// 設定の問題をすべて列挙して報告する。
+    fn validate(&self) -> ConfigResult<()> {
+        let mut problems = Vec::new();
+        collect_problem(&mut problems, self.bind_addresses());
+        if let Some(rate_limit) = self.public_rate_limit.as_ref()
+            && (rate_limit.rps == 0 || rate_limit.burst == 0)
+        {
+            problems.push("web.public_rate_limit: rps and burst must be greater than zero".into());
+        }
+        if let Some(operational) = self.operational.as_ref() {
+            match operational.listen_addresses() {
+                Ok(OperationalListenAddresses::Tcp(addresses))
+                    if addresses.iter().any(|address| address.port() == self.port) =>
+                {
+                    problems.push(format!(
+                        "web.operational.port: must be different from web.port: {}",
+                        self.port
+                    ));
+                }
+                result => collect_problem(&mut problems, result),
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Problems(problems))
+        }
     }
 }
 
+/// 運用リスナーの設定
+///
+/// `port`と`unix_socket`のいずれか一方を指定する。
+#[derive(Deserialize)]
+pub struct OperationalListenerConfig {
+    /// 運用リスナーのポート
+    pub port: Option<u16>,
+
+    /// 運用リスナーが待ち受けるIPアドレス（`web.bind_address`と同じ形式、省略した場合は`0.0.0.0`）
+    pub bind_address: Option<String>,
+
+    /// 運用リスナーが待ち受けるUnixドメインソケットのパス
+    pub unix_socket: Option<PathBuf>,
+}
+
+/// 運用リスナーが待ち受けるアドレス
+pub enum OperationalListenAddresses {
+    /// TCPのソケットアドレス
+    Tcp(Vec<SocketAddr>),
+    /// Unixドメインソケットのパス
+    Unix(PathBuf),
+}
+
+impl OperationalListenerConfig {
+    /// 運用リスナーが待ち受けるアドレスを返す。
+    ///
+    /// # Returns
+    ///
+    /// * 運用リスナーが待ち受けるアドレス、またはエラー
+    pub fn listen_addresses(&self) -> ConfigResult<OperationalListenAddresses> {
+        match (self.port, self.unix_socket.as_ref()) {
+            (Some(port), None) => Ok(OperationalListenAddresses::Tcp(parse_bind_addresses(
+                "web.operational.bind_address",
+                self.bind_address.as_deref(),
+                port,
+            )?)),
+            (None, Some(path)) if self.bind_address.is_none() => {
+                if cfg!(not(unix)) {
+                    return Err(ConfigError::Validation(
+                        "web.operational.unix_socket: Unix domain sockets are not supported on this platform".into(),
+                    ));
+                }
+                Ok(OperationalListenAddresses::Unix(path.clone()))
+            }
+            (None, Some(_)) => Err(ConfigError::Validation(
+                "web.operational.bind_address: cannot be used with unix_socket".into(),
+            )),
+            _ => Err(ConfigError::Validation(
+                "web.operational: specify either port or unix_socket".into(),
+            )),
+        }
+    }
+}
+
+/// カンマで区切ったIPアドレスとポートから、待ち受けるソケットアドレスを返す。
+///
+/// # Arguments
+///
+/// * `key` - エラーメッセージに含める設定のキー
+/// * `bind_address` - カンマで区切ったIPアドレス（省略した場合は`0.0.0.0`）
+/// * `port` - ポート
+///
+/// # Returns
+///
+/// * 各IPアドレスと`port`を組み合わせたソケットアドレス、またはエラー
+fn parse_bind_addresses(
+    key: &str,
+    bind_address: Option<&str>,
+    port: u16,
+) -> ConfigResult<Vec<SocketAddr>> {
+    let Some(bind_address) = bind_address else {
+        return Ok(vec![SocketAddr::from((Ipv4Addr::UNSPECIFIED, port))]);
+    };
+    let mut addresses = Vec::new();
+    for address in bind_address.split(',').map(str::trim) {
+        // IPv6アドレスは`[::]`のように角括弧で囲んで指定できる
+        let ip = address
+            .strip_prefix('[')
+            .and_then(|address| address.strip_suffix(']'))
+            .unwrap_or(address);
+        let ip: IpAddr = ip.parse().map_err(|_| {
+            ConfigError::Validation(format!(
+                "{key}: invalid IP address: {address:?} (e.g. 0.0.0.0, [::], 192.168.0.10)"
+            ))
+        })?;
+        let address = SocketAddr::from((ip, port));
+        if addresses.contains(&address) {
+            return Err(ConfigError::Validation(format!(
+                "{key}: duplicated address: {address}"
+            )));
+        }
+        addresses.push(address);
+    }
+    Ok(addresses)
+}
+
+/// コマンドラインで指定した設定の上書き
+#[derive(Debug, Default)]
+pub struct ConfigOverrides {
+    /// Webサーバーが待ち受けるポート（`web.port`）
+    pub port: Option<u16>,
+    /// ログレベル（`log_level`）
+    pub log_level: Option<String>,
+}
+
+impl ConfigOverrides {
+    /// 設定ビルダーに上書きを適用する。
+    ///
+    /// # Arguments
+    ///
+    /// * `builder` - 設定ビルダー
+    ///
+    /// # Returns
+    ///
+    /// * 上書きを適用した設定ビルダー、またはエラー
+    fn apply(
+        &self,
+        builder: ConfigBuilder<DefaultState>,
+    ) -> Result<ConfigBuilder<DefaultState>, config::ConfigError> {
+        builder
+            .set_override_option("web.port", self.port)?
+            .set_override_option("log_level", self.log_level.clone())
+    }
+}
+
+/// 基本の設定ファイルに重ね合わせる、環境ごとの設定ファイルのパスを返す。
+///
+/// # Arguments
+///
+/// * `path` - 基本の設定ファイルのパス
+/// * `app_env` - 環境名
+///
+/// # Returns
+///
+/// * 基本の設定ファイルと同じディレクトリの`{ファイル名}.{環境名}.yaml`
+fn env_config_path(path: &Path, app_env: &str) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+    path.with_file_name(format!("{stem}.{app_env}.yaml"))
+}
+
+/// 環境変数`APP_ENV`から、重ね合わせる環境ごとの設定ファイルの環境名を返す。
+///
+/// # Returns
+///
+/// * 環境名、環境変数が設定されていない場合は`None`、またはエラー
+///
+/// # Notes
+///
+/// 環境名は設定ファイルのパスに使用するため、英数字、`-`、`_`のみを許可する。
+fn app_env() -> ConfigResult<Option<String>> {
+    let app_env = match std::env::var(APP_ENV_VAR) {
+        Ok(app_env) => app_env,
+        Err(std::env::VarError::NotPresent) => return Ok(None),
+        Err(e) => {
+            return Err(ConfigError::Validation(format!("{APP_ENV_VAR}: {e}")));
+        }
+    };
+    if app_env.is_empty()
+        || !app_env
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(ConfigError::Validation(format!(
+            "{APP_ENV_VAR}: must consist of ASCII alphanumerics, '-' or '_': {app_env:?}"
+        )));
+    }
+    Ok(Some(app_env))
+}
+
+/// OBOで要求するスコープを検証する。
+///
+/// # Arguments
+///
+/// * `key` - 設定キー
+/// * `scopes` - スコープ
+///
+/// # Notes
+///
+/// OBOで要求するスコープは、`https://graph.microsoft.com/User.Read`のように、リソースを識別するURIを含む形式でなければならない。
+fn validate_scopes(key: &str, scopes: &[String]) -> ConfigResult<()> {
+    if scopes.is_empty() {
+        return Err(ConfigError::Validation(format!(
+            "{key}: at least one scope is required"
+        )));
+    }
+    for scope in scopes {
+        if scope.is_empty() || scope.chars().any(char::is_whitespace) {
+            return Err(ConfigError::Validation(format!(
+                "{key}: scope must not be empty or contain whitespace: '{scope}'"
+            )));
+        }
+        if !(scope.starts_with("https://") || scope.starts_with("api://")) {
+            return Err(ConfigError::Validation(format!(
+                "{key}: scope must be qualified with a resource URI (e.g. https://graph.microsoft.com/User.Read): '{scope}'"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// ログの出力形式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Bunyan形式のJSON
+    #[default]
+    Json,
+    /// 複数行で出力する人間が読みやすい形式
+    Pretty,
+    /// 1行で出力する人間が読みやすい形式
+    Compact,
+}
+
+/// ファイルへのログ出力設定
+///
+/// 標準出力に加えて、ローテーションするファイルにもログを出力する。
+#[derive(Clone, Deserialize)]
+pub struct LogFileConfig {
+    /// ログファイルを出力するディレクトリ
+    pub directory: PathBuf,
+    /// ログファイル名の接頭辞
+    ///
+    /// 時間でローテーションする場合、ログファイル名は`{接頭辞}.{日時}`の形式となる。
+    /// サイズでローテーションする場合、出力中のログファイル名は`{接頭辞}`、
+    /// ローテーションしたログファイル名は`{接頭辞}.{番号}`の形式となる。
+    pub file_name_prefix: String,
+    /// ローテーション間隔
+    #[serde(default)]
+    pub rotation: LogRotation,
+    /// ローテーションするログファイルの最大サイズ（バイト）
+    ///
+    /// `rotation`が`size`の場合に必須で、それ以外の場合は指定できない。
+    pub max_size_bytes: Option<u64>,
+    /// 保持するログファイルの最大数
+    ///
+    /// 省略した場合は、古いログファイルを削除しない。
+    pub max_files: Option<usize>,
+}
+
+impl LogFileConfig {
+    /// ファイルへのログ出力設定を検証する。
+    fn validate(&self) -> ConfigResult<()> {
+        let mut problems = Vec::new();
+        match (self.rotation, self.max_size_bytes) {
+            (LogRotation::Size, None) => problems
+                .push("log_file.max_size_bytes: required when log_file.rotation is size".into()),
+            (LogRotation::Size, Some(0)) => {
+                problems.push("log_file.max_size_bytes: must be greater than zero".into())
+            }
+            (LogRotation::Size, Some(_)) | (_, None) => {}
+            (_, Some(_)) => problems.push(
+                "log_file.max_size_bytes: only allowed when log_file.rotation is size".into(),
+            ),
+        }
+        if self.max_files == Some(0) {
+            problems.push("log_file.max_files: must be greater than zero".into());
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Problems(problems))
+        }
+    }
+}
+
+/// ログファイルのローテーション間隔
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Minutely,
+    Hourly,
+    #[default]
+    Daily,
+    Weekly,
+    Never,
+    /// ログファイルが`max_size_bytes`を超えるときにローテーションする。
+    Size,
+}
+
+/// `http_request`スパンのサンプリング設定
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceSampling {
+    /// すべてのリクエストでスパンを作成する
+    #[default]
+    Always,
+    /// 指定した割合（0.0から1.0）のリクエストでスパンを作成する
+    Ratio(f64),
+    /// 1秒あたりに作成するスパンの最大数
+    RatePerSecond(u32),
+}
+
+/// 失敗したリクエストのデバッグログ設定
+///
+/// ステージング環境での連携の問題を調査するため、エラーステータスを返したリクエストについて、
+/// 指定したヘッダと切り詰めたボディをログに出力する。
+/// `Authorization`ヘッダ、クライアントシークレット、トークンなどの機密情報は、設定にかかわらず常に伏せ字にする。
+#[derive(Debug, Clone, Deserialize)]
+pub struct HttpDebugLogConfig {
+    /// デバッグログを出力するか
+    #[serde(default)]
+    pub enabled: bool,
+    /// ログに出力するヘッダ名
+    #[serde(default)]
+    pub headers: Vec<String>,
+    /// ログに出力するボディの最大バイト数
+    #[serde(default = "default_http_debug_log_max_body_bytes")]
+    pub max_body_bytes: usize,
+}
+
+impl Default for HttpDebugLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            headers: Vec::new(),
+            max_body_bytes: default_http_debug_log_max_body_bytes(),
+        }
+    }
+}
+
+fn default_http_debug_log_max_body_bytes() -> usize {
+    1024
+}
+
+/// リクエストIDの設定
+///
+/// リクエストIDは`x-request-id`ヘッダで受け渡しし、レスポンスにも同じヘッダで返す。
+#[derive(Debug, Clone, Deserialize)]
+pub struct RequestIdConfig {
+    /// リクエストに含まれる`x-request-id`ヘッダの値をリクエストIDとして使用するか
+    ///
+    /// APIゲートウェイなどがリクエストIDを割り当てる場合に`true`にする。
+    /// `false`の場合、またはヘッダの値が不正な場合は、リクエストIDを生成する。
+    #[serde(default)]
+    pub trust_incoming: bool,
+    /// リクエストIDとして受け入れる`x-request-id`ヘッダの値の最大長
+    #[serde(default = "default_request_id_max_length")]
+    pub max_length: usize,
+}
+
+impl Default for RequestIdConfig {
+    fn default() -> Self {
+        Self {
+            trust_incoming: false,
+            max_length: default_request_id_max_length(),
+        }
+    }
+}
+
+fn default_request_id_max_length() -> usize {
+    128
+}
+
 #[derive(Deserialize)]
 pub struct WebConfig {
     pub port: u16,
+
+    /// Webサーバーが待ち受けるIPアドレス
+    ///
+    /// `0.0.0.0`、`[::]`、特定のインターフェースのIPアドレスを指定でき、カンマで区切って複数指定できる。
+    /// 省略した場合は`0.0.0.0`とする。
+    pub bind_address: Option<String>,
+
+    /// メトリクス、ヘルスチェック、管理者APIのみを公開する運用リスナーの設定
+    ///
+    /// 設定した場合、メトリクスと管理者APIは`port`では公開しない。
+    pub operational: Option<OperationalListenerConfig>,
+
+    /// tokioランタイムのワーカースレッド数
+    ///
+    /// 省略した場合は、tokioの既定値（CPUのコア数）とする。
+    pub worker_threads: Option<usize>,
+
+    /// tokioランタイムのブロッキングスレッドの最大数
+    ///
+    /// 省略した場合は、tokioの既定値（512）とする。
+    pub max_blocking_threads: Option<usize>,
+
+    /// ルートのパターン（例: `/api/me`）をキー、ハンドラーの処理に許可する時間（ミリ秒）を値としたハッシュマップ
+    ///
+    /// 指定しなかったルートにはタイムアウトを設定しない。タイムアウトした場合は504を返す。
+    #[serde(default)]
+    pub route_timeouts: HashMap<String, u64>,
+
+    /// ルートのパターン（例: `/api/me`）をキー、`Authorization`ヘッダ以外からBearerトークンを取得する方法を値としたハッシュマップ
+    ///
+    /// `Authorization`ヘッダを設定できないEventSourceや一部のWebSocketクライアントのために、保護されたルートにのみ指定できる。
+    /// 指定しなかったルートでは、`Authorization`ヘッダからのみBearerトークンを取得する。
+    #[serde(default)]
+    pub token_sources: HashMap<String, TokenSourceConfig>,
+
+    /// ヘルスチェックなど、認証を必要としない公開ルートの、接続元のIPアドレスごとのレート制限
+    ///
+    /// 省略した場合は制限しない。
+    pub public_rate_limit: Option<RateLimitConfig>,
+}
+
+/// レート制限設定
+#[derive(Clone, Deserialize)]
+pub struct RateLimitConfig {
+    /// 1秒あたりに許可するリクエスト数
+    pub rps: u32,
+
+    /// 連続して許可するリクエストの最大数
+    pub burst: u32,
+}
+
+/// `Authorization`ヘッダ以外からBearerトークンを取得する方法
+///
+/// `Authorization`ヘッダが存在する場合は、`Authorization`ヘッダを優先する。
+///
+/// # Notes
+///
+/// クエリパラメーターのトークンは、プロキシのアクセスログやブラウザの履歴に記録される可能性があり、
+/// クッキーのトークンはCSRFの対象になるため、既定ではどちらも使用しない。
+#[derive(Clone, Deserialize)]
+pub struct TokenSourceConfig {
+    /// Bearerトークンを取得するクエリパラメーターの名前
+    pub query: Option<String>,
+
+    /// Bearerトークンを取得するクッキーの名前
+    pub cookie: Option<String>,
 }
 
 #[derive(Deserialize)]
 pub struct EntraIdConfig {
     /// テナントベクタ
-    pub tenants: Vec<Tenant>,
+    pub tenants: Vec<TenantConfig>,
+
+    /// テナント固有の検証オプションで指定しなかった項目に使用する検証オプション
+    #[serde(default)]
+    pub validation: ValidationOptions,
+
+    /// 発行者を省略したテナントに使用する、`{tenantid}`を含む発行者テンプレート
+    ///
+    /// 例: `https://login.microsoftonline.com/{tenantid}/v2.0`
+    pub issuer_template: Option<String>,
 
     /// キャッシュしたJWK公開鍵のTTL（秒）
     pub jwk_cache_ttl: u64,
 
+    /// TTLを超過したJWK公開鍵を、リフレッシュを試行しながら検証に使用し続ける猶予期間（秒）
+    ///
+    /// リフレッシュに失敗し続けている間にのみ適用する。省略した場合は猶予期間を設けない。
+    #[serde(default)]
+    pub jwk_cache_stale_grace: u64,
+
     /// 定期的にバックグラウンドですべてのテナントのJWK公開鍵をリフレッシュする間隔（秒）
     pub refresh_jwks_interval: u64,
 
+    /// `refresh_jwks_interval`に指定できる最小値（秒）
+    ///
+    /// 省略した場合は1800秒（30分）とする。既定値より短い値は、テスト環境やステージング環境でのみ指定すること。
+    pub min_refresh_jwks_interval: Option<u64>,
+
     /// kidを基にテナントのJWK公開鍵を得られなかったときに、そのテナントのJWK公開鍵が最後にリフレッシュされてから、
     /// 次にリフレッシュするまでの最小時間（秒）
     pub refresh_tenant_jwks_interval: u64,
 
+    /// 未知のkidを契機としたJWK公開鍵のリフレッシュの完了を、テナントごとに同時に待機できるリクエストの最大数
+    ///
+    /// 上限を超えたリクエストは、リフレッシュを待機せずに401エラーとする。省略した場合は制限しない。
+    pub unknown_kid_refresh_max_waiters: Option<usize>,
+
+    /// 未知のkidを契機として、テナントごとに1分間にJWK公開鍵をリフレッシュできる最大回数
+    ///
+    /// 上限を超えたリクエストは、リフレッシュせずに401エラーとする。省略した場合は制限しない。
+    pub unknown_kid_refresh_max_per_minute: Option<u32>,
+
+    /// 未知のkidを契機としたJWK公開鍵のリフレッシュを、リクエストの処理中に待機する制限時間（ミリ秒）
+    ///
+    /// 制限時間を超過したリクエストは503エラーとし、リフレッシュはバックグラウンドで継続する。
+    /// 省略した場合は、再試行を含めてリフレッシュが完了するまで待機する。
+    pub unknown_kid_refresh_timeout: Option<u64>,
+
+    /// 他のタスクによるJWK公開鍵のリフレッシュの完了を待機する制限時間（秒）
+    ///
+    /// リフレッシュを担当するタスクがパニックした場合などに、待機し続けないようにする。
+    /// 再試行を含めたJWK公開鍵の取得にかかる時間より長く設定すること。省略した場合は120秒とする。
+    pub refresh_wait_timeout: Option<u64>,
+
+    /// 検証に成功したトークンの`oid`、`tid`、`azp`およびkidを、監査ログに出力する割合（0.0から1.0）
+    ///
+    /// 省略した場合は出力しない。
+    #[serde(default)]
+    pub success_audit_sample_rate: f64,
+
+    /// `organizations`エンドポイントが発行したトークンを、`tid`が登録済みのテナントを示す場合に受け入れるかどうか
+    ///
+    /// 省略した場合は受け入れない。`common`エンドポイントが発行したトークンは常に拒否する。
+    #[serde(default)]
+    pub accept_organizations_issuer: bool,
+
+    /// 起動時にすべてのテナントのJWK公開鍵を取得する期限（秒）
+    ///
+    /// 省略した場合は期限を設けない。
+    pub startup_fetch_deadline: Option<u64>,
+
+    /// 起動時にすべてのテナントのJWK公開鍵を取得する期限を超過したときの方針
+    #[serde(default)]
+    pub startup_deadline_policy: StartupDeadlinePolicy,
+
+    /// Entra IDのJWKsエンドポイントに接続するHTTPクライアントのコネクションプール設定
+    #[serde(default)]
+    pub jwks_pool: HttpPoolConfig,
+
+    /// Entra IDのJWKsエンドポイントのサーバー証明書に要求する公開鍵（SubjectPublicKeyInfo）のSHA-256ピン
+    ///
+    /// 証明書チェーンのいずれかの公開鍵がピンと一致しない場合は接続を拒否する。省略した場合はピンを検証しない。
+    pub jwks_tls_spki_pins: Option<Vec<SpkiPin>>,
+
     /// Entra IDのJWKsエンドポイントに接続する際のタイムアウト（秒）
     pub connection_timeout: u64,
 
@@ -66,6 +1074,11 @@ pub struct EntraIdConfig {
     /// Entra IDのJWKsエンドポイントへにリクエストする再試行の待機時間（ミリ秒）
     pub jwks_request_retry_initial_wait: u64,
 
+    /// Entra IDのJWKsエンドポイントのホスト名の名前解決に失敗したときに、最初に再試行するまで待機する時間（ミリ秒）
+    ///
+    /// 省略した場合は50ミリ秒とする。
+    pub jwks_request_retry_dns_initial_wait: Option<u64>,
+
     /// Entra IDのJWKsエンドポイントに再試行リクエストを送信するまでに待機する時間を増加させる乗数
     ///
     /// 待機時間は、`initial_wait * (multiplier ^ (attempt_number - 1))`で計算される
@@ -80,14 +1093,676 @@ pub struct EntraIdConfig {
 
     ///Entra IDのJWKsエンドポイントに再試行リクエストを送信するまでに待機する最大時間（秒）
     pub jwks_request_retry_max_wait: u64,
+
+    /// Entra IDのJWKsエンドポイントから、再試行を含めてJWK公開鍵セットを取得する制限時間（秒）
+    ///
+    /// 代替のJWKsエンドポイントへの切り替えを含めた制限時間で、最大試行回数に達していなくても、制限時間を超過した場合は
+    /// 取得を中止する。省略した場合は制限しない。
+    pub jwks_request_retry_max_total_duration: Option<u64>,
 }
 
+/// テナント設定
 #[derive(Clone, Deserialize)]
+pub struct TenantConfig {
+    /// テナント
+    #[serde(flatten)]
+    pub tenant: Tenant,
+
+    /// テナント固有のクライアント資格情報
+    ///
+    /// テナントごとにアプリケーションを登録している場合に指定する。
+    /// 指定しなかった場合は、`client_credentials`に設定したクライアント資格情報を使用する。
+    pub client_credentials: Option<ClientCredentials>,
+}
+
+/// アプリケーション（クライアント）ID
+///
+/// Entra IDに登録したアプリケーションのGUIDを指定する。
+#[derive(Clone, Deserialize)]
+#[serde(try_from = "String")]
 pub struct ClientId(pub String);
 
+impl TryFrom<String> for ClientId {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if is_guid(&value) {
+            Ok(Self(value))
+        } else {
+            Err(format!("Invalid client ID '{value}': must be a GUID"))
+        }
+    }
+}
+
 /// クライアント資格情報
+///
+/// クライアントシークレットは、`client_secret`に直接指定するか、DockerやKubernetesのシークレットをマウントしたファイルのパスを
+/// `client_secret_file`に指定する。
 #[derive(Clone, Deserialize)]
+#[serde(try_from = "ClientCredentialsConfig")]
 pub struct ClientCredentials {
     pub client_id: ClientId,
     pub client_secret: SecretString,
+    /// クライアントシークレットを読み込んだファイルのパス（`client_secret`に直接指定した場合は`None`）
+    pub client_secret_file: Option<PathBuf>,
+}
+
+/// 設定ファイルに記述するクライアント資格情報
+///
+/// `client_secret`と`client_secret_file`のいずれか一方を指定する。
+#[derive(Deserialize)]
+struct ClientCredentialsConfig {
+    client_id: ClientId,
+    /// クライアントシークレット
+    client_secret: Option<SecretString>,
+    /// クライアントシークレットを格納したファイルのパス
+    client_secret_file: Option<PathBuf>,
+}
+
+impl TryFrom<ClientCredentialsConfig> for ClientCredentials {
+    type Error = String;
+
+    fn try_from(config: ClientCredentialsConfig) -> Result<Self, Self::Error> {
+        let client_secret = match (config.client_secret, config.client_secret_file.as_deref()) {
+            (Some(client_secret), None) => client_secret,
+            (None, Some(path)) => read_secret_file(path)?,
+            (Some(_), Some(_)) => {
+                return Err("client_secret and client_secret_file cannot be used together".into());
+            }
+            (None, None) => {
+                return Err("either client_secret or client_secret_file is required".into());
+            }
+        };
+        Ok(Self {
+            client_id: config.client_id,
+            client_secret,
+            client_secret_file: config.client_secret_file,
+        })
+    }
+}
+
+/// ファイルから秘密情報を読み込む。
+///
+/// # Arguments
+///
+/// * `path` - 秘密情報を格納したファイルのパス
+///
+/// # Returns
+///
+/// * 前後の空白と改行を取り除いた秘密情報、またはエラー
+///
+/// # Notes
+///
+/// `echo`やエディタで作成したファイルやKubernetesのシークレットは末尾に改行を含むことがあるため、前後の空白と改行を取り除く。
+fn read_secret_file(path: &Path) -> Result<SecretString, String> {
+    let contents = Zeroizing::new(
+        std::fs::read_to_string(path)
+            .map_err(|e| format!("client_secret_file: failed to read {}: {e}", path.display()))?,
+    );
+    let secret = contents.trim();
+    if secret.is_empty() {
+        return Err(format!("client_secret_file: {} is empty", path.display()));
+    }
+    Ok(SecretString::from(secret))
+}
+
+/// 管理者API設定
+#[derive(Clone, Deserialize)]
+pub struct AdminConfig {
+    /// 管理者APIの呼び出しに必要なアプリケーションロール
+    pub role: String,
+}
+
+/// APIキー認証設定
+///
+/// Entra IDのトークンを取得できないサービス間の呼び出し元のために、指定したルートグループで、
+/// Bearerトークンに加えて`X-API-Key`ヘッダのAPIキーによる認証を受け付ける。
+/// `route_groups`を指定しなかった場合は、APIキー認証を使用しない。
+#[derive(Clone, Default, Deserialize)]
+pub struct ApiKeysConfig {
+    /// APIキー認証を受け付けるルートグループ
+    #[serde(default)]
+    pub route_groups: Vec<ApiKeyRouteGroup>,
+
+    /// APIキー
+    #[serde(default)]
+    pub keys: Vec<ApiKeyConfig>,
+}
+
+/// APIキー認証を受け付けるルートグループ
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiKeyRouteGroup {
+    /// 保護されたAPI（`/api`）
+    Api,
+    /// 管理者API（`/api/admin`）
+    Admin,
+}
+
+/// APIキー
+///
+/// APIキーそのものではなく、APIキーのSHA-256ハッシュを設定する。
+#[derive(Clone, Deserialize)]
+pub struct ApiKeyConfig {
+    /// 呼び出し元の名前（ログで使用）
+    pub name: String,
+
+    /// APIキーのSHA-256ハッシュ（16進数）
+    pub sha256: ApiKeyHash,
+
+    /// 呼び出し元に付与するアプリケーション内部の権限
+    #[serde(default)]
+    pub permissions: Vec<String>,
+}
+
+/// APIキーのSHA-256ハッシュ
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct ApiKeyHash(pub [u8; 32]);
+
+impl TryFrom<String> for ApiKeyHash {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let invalid = || format!("Invalid API key hash '{value}': must be 64 hexadecimal digits");
+        if value.len() != 64 || !value.is_ascii() {
+            return Err(invalid());
+        }
+        let mut hash = [0u8; 32];
+        for (byte, digits) in hash.iter_mut().zip(value.as_bytes().chunks(2)) {
+            let digits = std::str::from_utf8(digits).map_err(|_| invalid())?;
+            *byte = u8::from_str_radix(digits, 16).map_err(|_| invalid())?;
+        }
+        Ok(Self(hash))
+    }
+}
+
+/// 認可設定
+#[derive(Clone, Deserialize)]
+pub struct AuthorizationConfig {
+    /// Entra IDのアプリケーションロールをキー、ロールに付与するアプリケーション内部の権限を値としたハッシュマップ
+    #[serde(default)]
+    pub role_permissions: HashMap<String, Vec<String>>,
+
+    /// Entra IDのグループのオブジェクトIDをキー、グループに付与するアプリケーション内部の権限を値としたハッシュマップ
+    #[serde(default)]
+    pub group_permissions: HashMap<String, Vec<String>>,
+
+    /// Entra IDのアプリケーションロールをキー、ロールが包含する下位ロールを値としたハッシュマップ
+    ///
+    /// 例えば`Admin`が`Writer`を、`Writer`が`Reader`を包含する場合、`Admin`には`Writer`と`Reader`の権限も付与する。
+    #[serde(default)]
+    pub role_hierarchy: HashMap<String, Vec<String>>,
+
+    /// アプリ登録のマニフェストで定義したアプリケーションロール
+    ///
+    /// 指定した場合、設定で参照するロールがすべて含まれているかを起動時に検証し、
+    /// 含まれていないロールを持つトークンを受け取った場合は警告する。
+    #[serde(default)]
+    pub known_roles: Vec<String>,
+
+    /// アプリ登録で公開した委任スコープ
+    #[serde(default)]
+    pub known_scopes: Vec<String>,
+
+    /// グループの超過時にGraph APIで確認したグループのメンバーシップをキャッシュする期間（秒）
+    #[serde(default = "default_group_membership_cache_ttl")]
+    pub group_membership_cache_ttl: u64,
+
+    /// トークンの検証に成功した後に評価する認可ポリシー
+    #[serde(default)]
+    pub policy: PolicyConfig,
+}
+
+/// 認可ポリシー設定
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyConfig {
+    /// すべてのリクエストを許可する
+    #[default]
+    AllowAll,
+    /// Open Policy AgentのData APIで評価する
+    OpaHttp {
+        /// ポリシーのData APIのURL（例: `http://localhost:8181/v1/data/backend/allow`）
+        url: Url,
+    },
+}
+
+impl Default for AuthorizationConfig {
+    fn default() -> Self {
+        Self {
+            role_permissions: HashMap::new(),
+            group_permissions: HashMap::new(),
+            role_hierarchy: HashMap::new(),
+            known_roles: Vec::new(),
+            known_scopes: Vec::new(),
+            group_membership_cache_ttl: default_group_membership_cache_ttl(),
+            policy: PolicyConfig::default(),
+        }
+    }
+}
+
+fn default_group_membership_cache_ttl() -> u64 {
+    300
+}
+
+/// テナントごとのクライアント資格情報
+#[derive(Clone)]
+pub struct ClientCredentialsRegistry {
+    /// テナント固有のクライアント資格情報が設定されていない場合に使用するクライアント資格情報
+    default: ClientCredentials,
+    /// テナントIDをキー、テナント固有のクライアント資格情報を値としたハッシュマップ
+    tenants: HashMap<TenantId, ClientCredentials>,
+}
+
+impl ClientCredentialsRegistry {
+    /// コンストラクタ
+    ///
+    /// # Arguments
+    ///
+    /// * `default` - テナント固有のクライアント資格情報が設定されていない場合に使用するクライアント資格情報
+    /// * `tenants` - テナント設定
+    pub fn new(default: ClientCredentials, tenants: &[TenantConfig]) -> Self {
+        let tenants = tenants
+            .iter()
+            .filter_map(|tenant| {
+                tenant
+                    .client_credentials
+                    .clone()
+                    .map(|credentials| (tenant.tenant.id.clone(), credentials))
+            })
+            .collect();
+        Self { default, tenants }
+    }
+
+    /// 指定したテナントで使用するクライアント資格情報を返す。
+    ///
+    /// # Arguments
+    ///
+    /// * `tenant_id` - テナントID
+    ///
+    /// # Returns
+    ///
+    /// * テナント固有のクライアント資格情報、設定されていない場合は既定のクライアント資格情報
+    pub fn for_tenant(&self, tenant_id: &TenantId) -> &ClientCredentials {
+        self.tenants.get(tenant_id).unwrap_or(&self.default)
+    }
+}
+
+/// OBOでアクセストークンを取得して呼び出す下流リソースの設定
+#[derive(Debug, Clone, Deserialize)]
+pub struct ResourceConfig {
+    /// OBOで要求するスコープ（省略した場合は`{audience}/.default`）
+    #[serde(default)]
+    pub scopes: Vec<String>,
+
+    /// 下流リソースのベースURL
+    pub base_url: Option<Url>,
+
+    /// 下流リソースのアプリケーションID URI（例: `api://<client id>`）
+    pub audience: Option<String>,
+}
+
+impl ResourceConfig {
+    /// OBOで要求するスコープを返す。
+    ///
+    /// # Returns
+    ///
+    /// * `scopes`、省略した場合は`{audience}/.default`
+    pub fn scopes(&self) -> Vec<String> {
+        match (&self.audience, self.scopes.is_empty()) {
+            (Some(audience), true) => {
+                vec![format!("{}/.default", audience.trim_end_matches('/'))]
+            }
+            _ => self.scopes.clone(),
+        }
+    }
+
+    /// OBOのリクエストの`scope`パラメーターに指定する、空白区切りのスコープを返す。
+    pub fn scope(&self) -> String {
+        self.scopes().join(" ")
+    }
+}
+
+/// 名前で下流リソースの設定を参照するレジストリ
+#[derive(Clone)]
+pub struct ResourceRegistry {
+    /// 名前をキー、下流リソースの設定を値としたハッシュマップ
+    resources: HashMap<String, ResourceConfig>,
+}
+
+impl ResourceRegistry {
+    /// コンストラクタ
+    ///
+    /// # Arguments
+    ///
+    /// * `resources` - 名前をキー、下流リソースの設定を値としたハッシュマップ
+    /// * `graph` - Graph API設定
+    ///
+    /// # Notes
+    ///
+    /// `resources`にGraph APIが登録されていない場合は、`graph.scopes`からGraph APIを登録する。
+    pub fn new(mut resources: HashMap<String, ResourceConfig>, graph: &GraphConfig) -> Self {
+        resources
+            .entry(GRAPH_RESOURCE.to_string())
+            .or_insert_with(|| ResourceConfig {
+                scopes: graph.scopes.clone(),
+                base_url: None,
+                audience: None,
+            });
+        Self { resources }
+    }
+
+    /// 指定した名前の下流リソースの設定を返す。
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - 下流リソースの名前
+    ///
+    /// # Returns
+    ///
+    /// * 下流リソースの設定、登録されていない場合は`None`
+    pub fn get(&self, name: &str) -> Option<&ResourceConfig> {
+        self.resources.get(name)
+    }
+}
+
+/// Graph API設定
+#[derive(Clone, Deserialize)]
+pub struct GraphConfig {
+    /// OBOでGraph API用のアクセストークンを取得する際に要求するスコープ
+    ///
+    /// `resources.graph`でGraph APIを設定した場合は省略する。
+    #[serde(default)]
+    pub scopes: Vec<String>,
+
+    /// `/me`で取得したプロファイルをユーザーごとにキャッシュする期間（秒）
+    ///
+    /// `0`の場合はキャッシュしない。
+    #[serde(default = "default_me_cache_ttl")]
+    pub me_cache_ttl: u64,
+
+    /// Entra IDのトークンエンドポイント（OBOおよびクライアント資格情報フロー）を提供する認証機関のホスト
+    ///
+    /// ソブリンクラウド（例: `https://login.microsoftonline.us`）や、負荷試験でモックに置き換える場合に指定する。
+    #[serde(default = "default_authority_host")]
+    pub authority_host: Url,
+
+    /// Entra IDのトークンエンドポイント（OBOおよびクライアント資格情報フロー）からの応答を待つタイムアウト（秒）
+    #[serde(default = "default_outbound_timeout")]
+    pub token_endpoint_timeout: u64,
+
+    /// Graph APIからの応答を待つタイムアウト（秒）
+    #[serde(default = "default_outbound_timeout")]
+    pub graph_timeout: u64,
+
+    /// OBOでEntra IDのトークンエンドポイントにリクエストする際の再試行設定
+    #[serde(default)]
+    pub token_endpoint_retry: TokenEndpointRetryConfig,
+
+    /// Graph APIの障害時に呼び出しを遮断するサーキットブレーカーの設定（省略した場合は遮断しない）
+    pub circuit_breaker: Option<GraphCircuitBreakerConfig>,
+
+    /// Entra IDのトークンエンドポイントやGraph APIを呼び出すHTTPクライアントのコネクションプール設定
+    #[serde(default)]
+    pub pool: HttpPoolConfig,
+}
+
+fn default_me_cache_ttl() -> u64 {
+    60
+}
+
+fn default_authority_host() -> Url {
+    Url::parse(DEFAULT_AUTHORITY_HOST).expect("Default authority host must be a valid URL")
+}
+
+fn default_outbound_timeout() -> u64 {
+    10
+}
+
+/// Graph APIのサーキットブレーカーの設定
+#[derive(Debug, Clone, Deserialize)]
+pub struct GraphCircuitBreakerConfig {
+    /// 呼び出しを遮断する連続失敗回数
+    #[serde(default = "default_circuit_breaker_failure_threshold")]
+    pub failure_threshold: u32,
+
+    /// 呼び出しを遮断する期間（秒）
+    #[serde(default = "default_circuit_breaker_open_duration")]
+    pub open_duration: u64,
+
+    /// 呼び出しを遮断している間、`/me`でトークンのクレームから作成したプロファイルを返すか
+    ///
+    /// `false`の場合は、`503 Service Unavailable`を返す。
+    #[serde(default)]
+    pub fallback_to_claims: bool,
+}
+
+fn default_circuit_breaker_failure_threshold() -> u32 {
+    5
+}
+
+fn default_circuit_breaker_open_duration() -> u64 {
+    30
+}
+
+/// OBOでEntra IDのトークンエンドポイントにリクエストする際の再試行設定
+///
+/// クライアントのリクエストを待たせるため、JWKsエンドポイントよりも試行回数と待機時間を小さくする。
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TokenEndpointRetryConfig {
+    /// 最大試行回数（`1`の場合は再試行しない）
+    pub max_attempts: u32,
+
+    /// 最初に再試行するまでに待機する時間（ミリ秒）
+    pub initial_wait: u64,
+
+    /// 再試行するまでに待機する時間を増加させる乗数
+    pub backoff_multiplier: f64,
+
+    /// 再試行するまでに待機する時間に乗算するランダムなジッターの最小値
+    pub wait_jitter_min: f64,
+
+    /// 再試行するまでに待機する時間に乗算するランダムなジッターの最大値
+    pub wait_jitter_max: f64,
+
+    /// 再試行するまでに待機する最大時間（ミリ秒）
+    pub max_wait: u64,
+}
+
+impl Default for TokenEndpointRetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 2,
+            initial_wait: 100,
+            backoff_multiplier: 2.0,
+            wait_jitter_min: 0.8,
+            wait_jitter_max: 1.2,
+            max_wait: 1000,
+        }
+    }
+}
+
+/// HTTPクライアントのコネクションプール設定
+///
+/// 省略した項目は、reqwestの既定値を使用する。
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct HttpPoolConfig {
+    /// ホストごとに保持するアイドル状態のコネクションの最大数
+    pub pool_max_idle_per_host: Option<usize>,
+
+    /// アイドル状態のコネクションを閉じるまでの時間（秒）
+    pub pool_idle_timeout: Option<u64>,
+
+    /// TCPキープアライブの間隔（秒）
+    pub tcp_keepalive: Option<u64>,
+}
+
+impl From<&HttpPoolConfig> for ConnectionPoolConfig {
+    fn from(config: &HttpPoolConfig) -> Self {
+        Self {
+            max_idle_per_host: config.pool_max_idle_per_host,
+            idle_timeout: config.pool_idle_timeout.map(Duration::from_secs),
+            tcp_keepalive: config.tcp_keepalive.map(Duration::from_secs),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 有効なテナント
+    const VALID_TENANT: &str = r#"
+    - id: 11111111-1111-1111-1111-111111111111
+      uri: https://login.microsoftonline.com/11111111-1111-1111-1111-111111111111/discovery/v2.0/keys
+      issuer: https://login.microsoftonline.com/11111111-1111-1111-1111-111111111111/v2.0
+      audience: api://backend"#;
+
+    /// テナントと追加の設定を含む設定ファイルの内容を作成する。
+    fn config_yaml(tenants: &str, extra: &str) -> String {
+        format!(
+            r#"
+log_level: info
+web:
+  port: 8000
+entra_id:
+  tenants:{tenants}
+  jwk_cache_ttl: 172800
+  refresh_jwks_interval: 3600
+  refresh_tenant_jwks_interval: 300
+  connection_timeout: 3
+  timeout: 10
+  jwks_request_max_attempts: 2
+  jwks_request_retry_initial_wait: 200
+  jwks_request_retry_backoff_multiplier: 2.0
+  jwks_request_retry_wait_jitter_min: 0.8
+  jwks_request_retry_wait_jitter_max: 1.2
+  jwks_request_retry_max_wait: 60
+client_credentials:
+  client_id: 00000000-0000-0000-0000-000000000001
+  client_secret: secret
+graph:
+  scopes:
+    - https://graph.microsoft.com/User.Read
+  me_cache_ttl: 60
+  token_endpoint_timeout: 10
+  graph_timeout: 10
+admin:
+  role: Admin
+{extra}"#
+        )
+    }
+
+    fn deserialize(yaml: &str) -> ConfigResult<AppConfig> {
+        let config = Config::builder()
+            .add_source(config::File::from_str(yaml, config::FileFormat::Yaml))
+            .build()
+            .unwrap();
+        AppConfig::deserialize_with_paths(config)
+    }
+
+    fn problems<T>(result: ConfigResult<T>) -> Vec<String> {
+        match result {
+            Err(ConfigError::Problems(problems)) => problems,
+            Err(e) => panic!("unexpected error: {e}"),
+            Ok(_) => panic!("configuration must be invalid"),
+        }
+    }
+
+    #[test]
+    fn valid_config_is_accepted() {
+        let app_config = deserialize(&config_yaml(VALID_TENANT, "")).unwrap();
+
+        assert!(app_config.validate().is_ok());
+    }
+
+    #[test]
+    fn reports_every_broken_tenant_with_path() {
+        let tenants = format!(
+            r#"{VALID_TENANT}
+    - id: not-a-guid
+      uri: https://login.microsoftonline.com/common/discovery/v2.0/keys
+      issuer: https://login.microsoftonline.com/common/v2.0
+      audience: api://backend
+    - id: 33333333-3333-3333-3333-333333333333
+      uri: https://login.microsoftonline.com/33333333-3333-3333-3333-333333333333/discovery/v2.0/keys
+      issuer: https://login.microsoftonline.com/33333333-3333-3333-3333-333333333333/v2.0
+    - id: 44444444-4444-4444-4444-444444444444
+      uri: keys
+      issuer: https://login.microsoftonline.com/44444444-4444-4444-4444-444444444444/v2.0
+      audience: api://backend"#
+        );
+
+        let problems = problems(deserialize(&config_yaml(&tenants, "")));
+
+        assert_eq!(problems.len(), 3, "{problems:?}");
+        assert!(problems[0].starts_with("entra_id.tenants[1].id: Invalid tenant ID 'not-a-guid'"));
+        assert_eq!(
+            problems[1],
+            "entra_id.tenants[2]: missing configuration field \"audience\""
+        );
+        assert!(problems[2].starts_with("entra_id.tenants[3].uri: "));
+    }
+
+    #[test]
+    fn reports_problems_across_sections() {
+        let extra = "resources:\n  orders:\n    scopes: invalid\n";
+        let tenants = r#"
+    - id: not-a-guid
+      uri: https://login.microsoftonline.com/common/discovery/v2.0/keys
+      issuer: https://login.microsoftonline.com/common/v2.0
+      audience: api://backend"#;
+        let yaml = config_yaml(tenants, extra).replace("port: 8000", "port: not-a-port");
+
+        let problems = problems(deserialize(&yaml));
+
+        assert_eq!(problems.len(), 3, "{problems:?}");
+        assert!(problems[0].starts_with("entra_id.tenants[0].id: "));
+        assert!(problems[1].starts_with("resources.orders"));
+        assert!(problems[2].starts_with("web.port: "));
+    }
+
+    #[test]
+    fn validation_reports_every_problem() {
+        let extra = r#"
+trace_sampling:
+  ratio: 1.5
+log_file:
+  directory: logs
+  file_name_prefix: backend.log
+  rotation: daily
+  max_size_bytes: 1024
+  max_files: 0
+"#;
+        let app_config = deserialize(&config_yaml(VALID_TENANT, extra)).unwrap();
+
+        let problems = problems(app_config.validate());
+
+        assert_eq!(
+            problems,
+            vec![
+                "trace_sampling.ratio: must be between 0.0 and 1.0: 1.5",
+                "log_file.max_size_bytes: only allowed when log_file.rotation is size",
+                "log_file.max_files: must be greater than zero",
+            ]
+        );
+    }
+
+    #[test]
+    fn size_rotation_requires_max_size_bytes() {
+        let extra = r#"
+log_file:
+  directory: logs
+  file_name_prefix: backend.log
+  rotation: size
+"#;
+        let app_config = deserialize(&config_yaml(VALID_TENANT, extra)).unwrap();
+
+        assert_eq!(
+            problems(app_config.validate()),
+            vec!["log_file.max_size_bytes: required when log_file.rotation is size"]
+        );
+    }
 }
@@ -0,0 +1,157 @@
+use serde_json::{Value, json};
+
+use crate::config::{APP_ENV_VAR, AppConfig, ClientCredentials, PolicyConfig};
+use crate::redaction::{REDACTED, redact_url};
+
+/// 起動時にログへ出力する、実際に適用された設定の要約を作成する。
+///
+/// # Arguments
+///
+/// * `config` - アプリケーション設定
+///
+/// # Returns
+///
+/// * 設定の要約
+///
+/// # Notes
+///
+/// 環境ごとの設定ファイル、環境変数、コマンドラインによる上書きが適用されたかを運用者が確認できるようにする。
+/// 秘密情報を出力しないように、出力する項目を列挙し、クライアントシークレットやURLのパスワードは伏せ字にする。
+pub fn config_summary(config: &AppConfig) -> Value {
+    let entra_id = &config.entra_id;
+    let graph = &config.graph;
+    json!({
+        "app_env": std::env::var(APP_ENV_VAR).ok(),
+        "log_level": config.log_level,
+        "log_format": format!("{:?}", config.log_format),
+        "trace_sampling": format!("{:?}", config.trace_sampling),
+        "http_debug_log": config.http_debug_log.enabled,
+        "request_id": {
+            "trust_incoming": config.request_id.trust_incoming,
+            "max_length": config.request_id.max_length,
+        },
+        "web": {
+            "port": config.web.port,
+            "bind_address": config.web.bind_address,
+            "operational": config.web.operational.as_ref().map(|operational| json!({
+                "port": operational.port,
+                "bind_address": operational.bind_address,
+                "unix_socket": operational.unix_socket,
+            })),
+            "worker_threads": config.web.worker_threads,
+            "max_blocking_threads": config.web.max_blocking_threads,
+            "route_timeouts": config.web.route_timeouts,
+            "public_rate_limit": config.web.public_rate_limit.as_ref().map(|rate_limit| json!({
+                "rps": rate_limit.rps,
+                "burst": rate_limit.burst,
+            })),
+            "token_sources": config.web.token_sources.iter().map(|(pattern, source)| (pattern.clone(), json!({
+                "query": source.query,
+                "cookie": source.cookie,
+            }))).collect::<serde_json::Map<_, _>>(),
+        },
+        "entra_id": {
+            "tenants": entra_id.tenants.iter().map(|tenant| json!({
+                "id": tenant.tenant.id.as_str(),
+                "name": tenant.tenant.name,
+                "enabled": tenant.tenant.enabled,
+                "jwks_uri": redact_url(&tenant.tenant.uri),
+                "fallback_jwks_uris": tenant.tenant.fallback_uris.iter().map(redact_url).collect::<Vec<_>>(),
+                "issuers": tenant.tenant.issuers,
+                "audience": tenant.tenant.audience,
+                "pinned_kids": tenant.tenant.pinned_kids,
+                "client_credentials": tenant.client_credentials.as_ref().map(client_credentials_summary),
+            })).collect::<Vec<_>>(),
+            "issuer_template": entra_id.issuer_template,
+            "jwk_cache_ttl": entra_id.jwk_cache_ttl,
+            "jwk_cache_stale_grace": entra_id.jwk_cache_stale_grace,
+            "refresh_jwks_interval": entra_id.refresh_jwks_interval,
+            "min_refresh_jwks_interval": entra_id.min_refresh_jwks_interval,
+            "refresh_tenant_jwks_interval": entra_id.refresh_tenant_jwks_interval,
+            "unknown_kid_refresh_max_waiters": entra_id.unknown_kid_refresh_max_waiters,
+            "unknown_kid_refresh_max_per_minute": entra_id.unknown_kid_refresh_max_per_minute,
+            "unknown_kid_refresh_timeout": entra_id.unknown_kid_refresh_timeout,
+            "refresh_wait_timeout": entra_id.refresh_wait_timeout,
+            "success_audit_sample_rate": entra_id.success_audit_sample_rate,
+            "accept_organizations_issuer": entra_id.accept_organizations_issuer,
+            "startup_fetch_deadline": entra_id.startup_fetch_deadline,
+            "startup_deadline_policy": format!("{:?}", entra_id.startup_deadline_policy),
+            "jwks_tls_spki_pins": entra_id.jwks_tls_spki_pins.as_ref().map(Vec::len),
+            "connection_timeout": entra_id.connection_timeout,
+            "timeout": entra_id.timeout,
+            "jwks_request_max_attempts": entra_id.jwks_request_max_attempts,
+        },
+        "client_credentials": client_credentials_summary(&config.client_credentials),
+        "admin_role": config.admin.role,
+        "authorization": {
+            "roles": config.authorization.role_permissions.len(),
+            "groups": config.authorization.group_permissions.len(),
+            "role_hierarchy": config.authorization.role_hierarchy,
+            "known_roles": config.authorization.known_roles,
+            "known_scopes": config.authorization.known_scopes,
+            "group_membership_cache_ttl": config.authorization.group_membership_cache_ttl,
+            "policy": match &config.authorization.policy {
+                PolicyConfig::AllowAll => json!("allow_all"),
+                PolicyConfig::OpaHttp { url } => json!({ "opa_http": redact_url(url) }),
+            },
+        },
+        "api_keys": {
+            "route_groups": config.api_keys.route_groups.iter().map(|group| format!("{group:?}")).collect::<Vec<_>>(),
+            "keys": config.api_keys.keys.iter().map(|key| json!({
+                "name": key.name,
+                "sha256": REDACTED,
+                "permissions": key.permissions,
+            })).collect::<Vec<_>>(),
+        },
+        "graph": {
+            "scopes": graph.scopes,
+            "me_cache_ttl": graph.me_cache_ttl,
+            "authority_host": redact_url(&graph.authority_host),
+            "token_endpoint_timeout": graph.token_endpoint_timeout,
+            "graph_timeout": graph.graph_timeout,
+            "token_endpoint_max_attempts": graph.token_endpoint_retry.max_attempts,
+            "circuit_breaker": graph.circuit_breaker.as_ref().map(|circuit_breaker| json!({
+                "failure_threshold": circuit_breaker.failure_threshold,
+                "open_duration": circuit_breaker.open_duration,
+                "fallback_to_claims": circuit_breaker.fallback_to_claims,
+            })),
+        },
+        "resources": config.resources.iter().map(|(name, resource)| {
+            (name.clone(), json!({
+                "scopes": resource.scopes(),
+                "base_url": resource.base_url.as_ref().map(redact_url),
+            }))
+        }).collect::<serde_json::Map<_, _>>(),
+    })
+}
+
+/// クライアント資格情報の要約を作成する。
+///
+/// クライアントシークレットは常に伏せ字にし、ファイルから読み込んだ場合はファイルのパスを出力する。
+fn client_credentials_summary(credentials: &ClientCredentials) -> Value {
+    json!({
+        "client_id": credentials.client_id.0,
+        "client_secret": REDACTED,
+        "client_secret_file": credentials.client_secret_file,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ClientId;
+
+    #[test]
+    fn client_credentials_summary_does_not_contain_secret() {
+        let credentials = ClientCredentials {
+            client_id: ClientId("client".into()),
+            client_secret: "very-secret".into(),
+            client_secret_file: None,
+        };
+
+        let summary = client_credentials_summary(&credentials).to_string();
+
+        assert!(summary.contains("client"));
+        assert!(!summary.contains("very-secret"));
+    }
+}
@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use rustls::crypto::CryptoProvider;
+
+#[cfg(all(feature = "crypto-aws-lc", feature = "crypto-ring"))]
+compile_error!(
+    "Features crypto-aws-lc (or crypto-fips) and crypto-ring are mutually exclusive; build with --no-default-features to select crypto-ring"
+);
+
+#[cfg(not(any(feature = "crypto-aws-lc", feature = "crypto-ring")))]
+compile_error!("One of the features crypto-aws-lc, crypto-fips or crypto-ring must be enabled");
+
+/// 暗号プロバイダの名前
+#[cfg(feature = "crypto-fips")]
+pub const PROVIDER_NAME: &str = "aws-lc-rs (FIPS)";
+/// 暗号プロバイダの名前
+#[cfg(all(feature = "crypto-aws-lc", not(feature = "crypto-fips")))]
+pub const PROVIDER_NAME: &str = "aws-lc-rs";
+/// 暗号プロバイダの名前
+#[cfg(feature = "crypto-ring")]
+pub const PROVIDER_NAME: &str = "ring";
+
+/// 暗号プロバイダ関連のエラー
+#[derive(Debug, thiserror::Error)]
+pub enum CryptoProviderError {
+    /// 別の暗号プロバイダがすでにプロセスの既定として登録されている
+    #[error("Another crypto provider is already installed")]
+    AlreadyInstalled,
+
+    /// FIPSモードが要求されたが、暗号プロバイダがFIPSモードで動作していない
+    #[error("Crypto provider {0} is not operating in FIPS mode")]
+    NotFips(&'static str),
+}
+
+/// フィーチャーで選択したTLSの暗号プロバイダを返す。
+///
+/// # Returns
+///
+/// * TLSの暗号プロバイダ
+pub fn tls_provider() -> CryptoProvider {
+    #[cfg(feature = "crypto-fips")]
+    return rustls::crypto::default_fips_provider();
+    #[cfg(all(feature = "crypto-aws-lc", not(feature = "crypto-fips")))]
+    return rustls::crypto::aws_lc_rs::default_provider();
+    #[cfg(feature = "crypto-ring")]
+    return rustls::crypto::ring::default_provider();
+}
+
+/// フィーチャーで選択した暗号プロバイダを、プロセスの既定として登録する。
+///
+/// # Returns
+///
+/// * 登録に成功した場合は`Ok(())`、またはエラー
+///
+/// # Notes
+///
+/// reqwestは、HTTPクライアントの構築時にプロセスの既定の暗号プロバイダを使用するため、
+/// HTTPクライアントを構築する前に呼び出すこと。
+/// JWTの署名検証に使用する暗号プロバイダは、jsonwebtokenのフィーチャーでコンパイル時に選択される。
+/// `crypto-fips`を有効にした場合は、FIPSモードで動作していない場合にエラーを返す。
+pub fn install_default_provider() -> Result<(), CryptoProviderError> {
+    let provider = tls_provider();
+    if cfg!(feature = "crypto-fips") && !provider.fips() {
+        return Err(CryptoProviderError::NotFips(PROVIDER_NAME));
+    }
+    provider
+        .install_default()
+        .map_err(|_: Arc<CryptoProvider>| CryptoProviderError::AlreadyInstalled)
+}
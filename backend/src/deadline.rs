@@ -0,0 +1,157 @@
+use std::convert::Infallible;
+use std::time::Duration;
+
+use axum::extract::{FromRequestParts, Request};
+use axum::http::{HeaderMap, StatusCode, request::Parts};
+use tokio::time::Instant;
+
+use crate::common::{AppResult, RequestError};
+
+/// クライアントが許容する残り時間を受け取るヘッダ
+///
+/// 値は`grpc-timeout`ヘッダと同じ形式（例: `500m`は500ミリ秒、`2S`は2秒）で指定する。
+pub const X_REQUEST_DEADLINE: &str = "x-request-deadline";
+
+/// gRPCクライアントやプロキシが残り時間を伝搬するヘッダ
+const GRPC_TIMEOUT: &str = "grpc-timeout";
+
+/// `grpc-timeout`形式の値の最大桁数
+const MAX_TIMEOUT_DIGITS: usize = 8;
+
+/// クライアントから伝搬されたリクエストの期限
+///
+/// 期限を過ぎても処理を続けるとクライアントが待てない結果のために上流の呼び出しを消費するため、
+/// OBOやGraph APIの呼び出しを期限で打ち切り、504を返す。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RequestDeadline(Option<Instant>);
+
+impl RequestDeadline {
+    /// リクエストのヘッダから期限を作成する。
+    ///
+    /// # Arguments
+    ///
+    /// * `headers` - リクエストのヘッダ
+    /// * `received_at` - リクエストを受信した時刻
+    ///
+    /// # Returns
+    ///
+    /// * リクエストの期限（ヘッダが存在しない、または不正な場合は期限なし）
+    fn from_headers(headers: &HeaderMap, received_at: Instant) -> Self {
+        let Some((name, value)) = [X_REQUEST_DEADLINE, GRPC_TIMEOUT]
+            .into_iter()
+            .find_map(|name| headers.get(name).map(|value| (name, value)))
+        else {
+            return Self(None);
+        };
+        match value.to_str().ok().and_then(parse_timeout) {
+            Some(timeout) => Self(Some(received_at + timeout)),
+            None => {
+                tracing::warn!(header = %name, value = ?value, "Ignoring invalid request deadline header");
+                Self(None)
+            }
+        }
+    }
+
+    /// 期限までに処理を完了させる。
+    ///
+    /// # Arguments
+    ///
+    /// * `future` - OBOやGraph APIの呼び出しなどの処理
+    ///
+    /// # Returns
+    ///
+    /// * 処理の結果、または期限を過ぎた場合は504エラー
+    pub async fn run<F: Future>(&self, future: F) -> AppResult<F::Output> {
+        let Some(deadline) = self.0 else {
+            return Ok(future.await);
+        };
+        tokio::time::timeout_at(deadline, future)
+            .await
+            .map_err(|_| {
+                tracing::warn!("Request deadline exceeded");
+                RequestError {
+                    code: StatusCode::GATEWAY_TIMEOUT,
+                    message: "Request deadline exceeded".into(),
+                }
+            })
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for RequestDeadline {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        // `attach_request_deadline`ミドルウェアが受信時に作成した期限を優先する
+        if let Some(deadline) = parts.extensions.get::<RequestDeadline>() {
+            return Ok(*deadline);
+        }
+        Ok(Self::from_headers(&parts.headers, Instant::now()))
+    }
+}
+
+/// 受信したリクエストの期限を、リクエストの拡張に付与するミドルウェア
+///
+/// 期限をリクエストの受信時刻から計算するため、できるだけ外側に適用する。
+pub async fn attach_request_deadline(mut request: Request) -> Request {
+    let deadline = RequestDeadline::from_headers(request.headers(), Instant::now());
+    request.extensions_mut().insert(deadline);
+    request
+}
+
+/// `grpc-timeout`形式の値をパースする。
+///
+/// # Arguments
+///
+/// * `value` - 8桁以内の正の整数と単位（`H`、`M`、`S`、`m`、`u`、`n`）で構成された値
+///
+/// # Returns
+///
+/// * 残り時間、不正な場合は`None`
+fn parse_timeout(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let unit_at = value.len().checked_sub(1)?;
+    let (digits, unit) = value.split_at(unit_at);
+    if digits.is_empty()
+        || digits.len() > MAX_TIMEOUT_DIGITS
+        || !digits.bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    let amount: u64 = digits.parse().ok()?;
+    match unit {
+        "H" => Some(Duration::from_secs(amount * 60 * 60)),
+        "M" => Some(Duration::from_secs(amount * 60)),
+        "S" => Some(Duration::from_secs(amount)),
+        "m" => Some(Duration::from_millis(amount)),
+        "u" => Some(Duration::from_micros(amount)),
+        "n" => Some(Duration::from_nanos(amount)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_timeout_accepts_grpc_timeout_format() {
+        assert_eq!(parse_timeout("500m"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_timeout("2S"), Some(Duration::from_secs(2)));
+        assert_eq!(parse_timeout("1H"), Some(Duration::from_secs(3600)));
+        assert_eq!(parse_timeout("m"), None);
+        assert_eq!(parse_timeout("123456789m"), None);
+        assert_eq!(parse_timeout("-1S"), None);
+        assert_eq!(parse_timeout("5s"), None);
+    }
+
+    #[tokio::test]
+    async fn run_fails_with_gateway_timeout_after_deadline() {
+        let deadline = RequestDeadline(Some(Instant::now()));
+
+        let result = deadline
+            .run(tokio::time::sleep(Duration::from_secs(1)))
+            .await;
+
+        assert_eq!(result.unwrap_err().code, StatusCode::GATEWAY_TIMEOUT);
+    }
+}
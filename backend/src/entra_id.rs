@@ -1,26 +1,70 @@
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::time::{Duration, Instant};
 
 use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use http::StatusCode;
 use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, decode_header};
+use rand::Rng as _;
 use rand::distr::{Distribution as _, Uniform};
 use secrecy::{ExposeSecret, SecretString};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use sha2::{Digest as _, Sha256, Sha384, Sha512};
+use tokio::sync::futures::OwnedNotified;
 use tokio::sync::{Mutex, Notify, RwLock};
+use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
 use url::Url;
 
+use crate::common::RequestError;
+use crate::tls_pinning::{SpkiPin, pinned_tls_config};
+
 /// JWTのピリオドで区切られた部分の数
 const JWT_PARTS_COUNT: usize = 3;
 
-/// 定期的にバックグラウンドで全てのテナントのJWK公開鍵をリフレッシュする最小間隔
+/// `Authorization`ヘッダのBearerスキーム
+const BEARER_SCHEME: &str = "Bearer ";
+
+/// 定期的にバックグラウンドで全てのテナントのJWK公開鍵をリフレッシュする最小間隔の既定値
 ///
 /// `EntraIdTokenVerifier`の`new`メソッドを呼び出されたとき、すべてのテナントのJWK公開鍵を
 /// 取得した後、JWK公開鍵をバックグラウンドでリフレッシュするタスクを実行する。
 /// このとき、バックグラウンドタスクが、すぐにJWK公開鍵をリフレッシュしないようにするための最小間隔。
-const MIN_BACKGROUND_JWKS_REFRESH_INTERVAL: Duration = Duration::from_mins(30);
+/// テスト環境やステージング環境では、ビルダーの`min_refresh_jwks_interval`で上書きできる。
+const DEFAULT_MIN_BACKGROUND_JWKS_REFRESH_INTERVAL: Duration = Duration::from_mins(30);
+
+/// バックグラウンドで全てのテナントのJWK公開鍵をリフレッシュする間隔に加えるジッターの割合
+///
+/// リフレッシュ間隔を`interval * (1 ± ratio)`の範囲でランダムに変化させ、レプリカ間でリフレッシュのタイミングが
+/// 揃わないようにする。
+const BACKGROUND_JWKS_REFRESH_JITTER_RATIO: f64 = 0.1;
+
+/// バックグラウンドでのJWK公開鍵のリフレッシュに連続して失敗したテナントを、リフレッシュ対象から外す最大サイクル数
+///
+/// 連続して失敗したテナントは、`2 ^ (連続失敗回数 - 1) - 1`サイクルの間リフレッシュ対象から外すが、その上限。
+const MAX_BACKGROUND_JWKS_REFRESH_BACKOFF_CYCLES: u32 = 7;
+
+/// バックグラウンドでのJWK公開鍵のリフレッシュの連続失敗回数が、この回数以上になった場合にエラーレベルでログを出力する。
+const BACKGROUND_JWKS_REFRESH_FAILURE_ESCALATION_THRESHOLD: u32 = 3;
+
+/// パニックしたJWK公開鍵のリフレッシュタスクを再起動するまでの待機時間の初期値
+const INITIAL_BACKGROUND_TASK_RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// パニックしたJWK公開鍵のリフレッシュタスクを再起動するまでの待機時間の最大値
+const MAX_BACKGROUND_TASK_RESTART_DELAY: Duration = Duration::from_mins(5);
+
+/// 名前解決に失敗したときに、最初に再試行するまで待機する時間の既定値
+///
+/// Podの起動直後にDNSサーバーの準備が整う前など、名前解決の失敗は短時間で解消することが多いため、
+/// 通常の再試行より短い待機時間から再試行する。
+const DEFAULT_DNS_RETRY_INITIAL_WAIT: Duration = Duration::from_millis(50);
+
+/// 他のタスクによるJWK公開鍵のリフレッシュの完了を待機する制限時間の既定値
+const DEFAULT_REFRESH_WAIT_TIMEOUT: Duration = Duration::from_mins(2);
 
 /// Entra ID関連の処理の結果型
 pub type EntraIdResult<T> = Result<T, EntraIdError>;
@@ -44,6 +88,18 @@ pub enum EntraIdError {
     /// JWK公開鍵セットのパースに失敗
     JwksResponseParseError(Url, reqwest::Error),
 
+    /// シャットダウンにより、指定したテナントのJWK公開鍵セットの取得を中止
+    #[error("Fetching JWKs from {0} was cancelled by shutdown")]
+    JwksFetchCancelled(Url),
+
+    /// JWK公開鍵のリフレッシュ、またはその完了の待機が、制限時間内に完了しなかった
+    #[error("JWKs refresh for tenant {0} did not complete within {1:?}")]
+    JwksRefreshTimedOut(TenantId, Duration),
+
+    /// 再試行を含めたJWK公開鍵セットの取得が、再試行の制限時間を超過したため中止
+    #[error("Fetching JWKs from {0} exceeded the total retry duration of {1:?}")]
+    JwksFetchRetryBudgetExceeded(Url, Duration),
+
     /// 特定のテナントに、特定のkidを持つJWK公開鍵が存在しない
     #[error("{0}")]
     DecodingKeyNotFound(String),
@@ -52,6 +108,10 @@ pub enum EntraIdError {
     #[error("Tenant not found in registry: {0}")]
     TenantNotFound(TenantId),
 
+    /// 指定したテナントが無効
+    #[error("Tenant is disabled: {0}")]
+    TenantDisabled(TenantId),
+
     /// トークンのヘッダのデコードに失敗
     #[error("Failed to decode JWT header:{0}")]
     TokenHeaderDecodeError(#[from] jsonwebtoken::errors::Error),
@@ -60,6 +120,14 @@ pub enum EntraIdError {
     #[error("{0}")]
     TokenHeaderMissingKid(String),
 
+    /// テナントでピン留めしていないkidのJWK公開鍵で署名されたトークン
+    #[error("Key ID is not pinned for tenant {0}: {1}")]
+    UnpinnedKid(TenantId, Kid),
+
+    /// トークンの`iss`が、`tid`などで特定したテナントの発行者と一致しない
+    #[error("Issuer does not match tenant {0}: {1}")]
+    TenantIssuerMismatch(TenantId, String),
+
     /// 許可していない発行者のテナント
     #[error("Disallowed issuer tenant: {0}")]
     DisallowedIssuerTenant(IssuerTenant),
@@ -85,7 +153,15 @@ pub enum EntraIdError {
     TokenPayloadDecodeError(base64::DecodeError),
 
     /// JWTペイロードのパースエラー
-    #[error("JWT payload parse failed: {0}")]
+    ///
+    /// `serde_json::Error`のメッセージには、ペイロードに含まれるクレームの値が含まれる可能性があるため、
+    /// エラーの分類と位置のみを出力する。
+    #[error(
+        "JWT payload parse failed: {:?} error at line {} column {}",
+        .0.classify(),
+        .0.line(),
+        .0.column()
+    )]
     TokenPayloadParseError(serde_json::Error),
 
     /// JWTトークンにissが存在しない
@@ -95,11 +171,85 @@ pub enum EntraIdError {
     /// issパースエラー
     #[error("Invalid issuer format: {0}")]
     InvalidIssuerFormat(String),
+
+    /// クレームを変換するフックがユーザーを拒否
+    #[error("Claims mapper rejected the user: {0}")]
+    ClaimsMappingRejected(String),
+
+    /// クレームの変換に失敗
+    #[error("Failed to map claims: {0}")]
+    ClaimsMapping(String),
+
+    /// アプリケーション固有のクレームの検証に失敗
+    #[error("Claim validation failed: {0}")]
+    ClaimValidation(String),
+
+    /// IDトークンの`nonce`、`at_hash`または`c_hash`の検証に失敗
+    #[error("ID token validation failed: {0}")]
+    IdTokenValidation(String),
+}
+
+impl EntraIdError {
+    /// JWK公開鍵セットの取得を再試行できるエラーかどうかを返す。
+    ///
+    /// # Returns
+    ///
+    /// * タイムアウト、接続エラー、サーバーエラーまたはレートリミットエラーで取得に失敗した場合、
+    ///   または再試行の制限時間を超過した場合は`true`
+    ///
+    /// # Notes
+    ///
+    /// 再試行の制限時間を超過したJWKsエンドポイントは応答しないものとして、次のJWKsエンドポイントに切り替える。
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            EntraIdError::JwksFetchError(e, _) => is_retryable_error(e),
+            EntraIdError::JwksFetchRetryBudgetExceeded(..) => true,
+            _ => false,
+        }
+    }
+
+    /// JWKsエンドポイントのホスト名の名前解決に失敗したエラーかどうかを返す。
+    pub fn is_dns_failure(&self) -> bool {
+        match self {
+            EntraIdError::JwksFetchError(e, _) => is_dns_error(e),
+            _ => false,
+        }
+    }
+}
+
+impl From<EntraIdError> for RequestError {
+    /// トークンが無効な場合は401エラー、JWK公開鍵を取得できないなどEntra IDとの連携に失敗した場合は503エラーに変換する。
+    /// クレームを変換するフックがユーザーを拒否した場合は403エラー、変換に失敗した場合は503エラーに変換する。
+    ///
+    /// トークンの内容をレスポンスに含めないように、トークンが無効な理由はレスポンスに含めない。
+    fn from(err: EntraIdError) -> Self {
+        match err {
+            EntraIdError::Initialize(_)
+            | EntraIdError::JwksProviderInitError(_)
+            | EntraIdError::JwksFetchError(..)
+            | EntraIdError::JwksResponseParseError(..)
+            | EntraIdError::JwksFetchCancelled(_)
+            | EntraIdError::JwksFetchRetryBudgetExceeded(..)
+            | EntraIdError::JwksRefreshTimedOut(..)
+            | EntraIdError::CreateDecodingKeyError(..) => RequestError {
+                code: StatusCode::SERVICE_UNAVAILABLE,
+                message: "Unable to verify access token at this time".into(),
+            },
+            EntraIdError::ClaimsMappingRejected(_) => RequestError {
+                code: StatusCode::FORBIDDEN,
+                message: "User is not allowed to access this application".into(),
+            },
+            EntraIdError::ClaimsMapping(_) => RequestError {
+                code: StatusCode::SERVICE_UNAVAILABLE,
+                message: "Unable to resolve user context at this time".into(),
+            },
+            _ => RequestError::unauthorized("Invalid access token"),
+        }
+    }
 }
 
 /// JWTのクレーム
-#[allow(dead_code)]
-#[derive(Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     /// 購読者（audience）
     pub aud: String,
@@ -111,13 +261,260 @@ pub struct Claims {
     pub oid: String,
     /// サブジェクト
     pub sub: String,
+    /// テナントID
+    pub tid: Option<String>,
     /// ロール
     pub roles: Option<Vec<String>>,
+    /// 所属するグループのオブジェクトID
+    pub groups: Option<Vec<String>>,
+    /// 委任されたスコープ（アプリケーションのトークンには含まれない）
+    #[serde(default, skip_serializing_if = "Scopes::is_empty")]
+    pub scp: Scopes,
+    /// トークンに含めきれなかったクレームの名前とそのソース
+    ///
+    /// ユーザーが所属するグループが多すぎる場合（グループの超過）、`groups`の代わりに`{"groups": "src1"}`が含まれる。
+    #[serde(rename = "_claim_names")]
+    pub claim_names: Option<HashMap<String, String>>,
+    /// 上記以外のクレーム
+    ///
+    /// アプリケーション固有のクレームの検証で、`employeeId`などの任意のクレームを参照するために使用する。
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+impl Claims {
+    /// 委任されたスコープを返す。
+    pub fn scopes(&self) -> &Scopes {
+        &self.scp
+    }
+
+    /// トークンを要求したクライアントアプリケーションのIDを返す。
+    ///
+    /// v2.0のトークンでは`azp`クレーム、v1.0のトークンでは`appid`クレームから取得する。
+    pub fn client_app_id(&self) -> Option<&str> {
+        ["azp", "appid"]
+            .iter()
+            .find_map(|name| self.extra.get(*name)?.as_str())
+    }
+
+    /// グループの超過によって、トークンに`groups`クレームが含まれていないかを判定する。
+    pub fn has_groups_overage(&self) -> bool {
+        self.claim_names
+            .as_ref()
+            .is_some_and(|names| names.contains_key("groups"))
+    }
+}
+
+/// `scp`クレームの、空白で区切られたスコープの集合
+///
+/// `User.Read`と`user.read`のように大文字と小文字だけが異なるスコープは、Entra IDと同様に同じスコープとみなす。
+/// 部分文字列では一致しないため、`Files.Read`は`Files.ReadWrite`に一致しない。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub struct Scopes(HashSet<String>);
+
+impl From<String> for Scopes {
+    fn from(value: String) -> Self {
+        Self(
+            value
+                .split_ascii_whitespace()
+                .map(str::to_ascii_lowercase)
+                .collect(),
+        )
+    }
+}
+
+impl From<Scopes> for String {
+    fn from(scopes: Scopes) -> Self {
+        let mut scopes: Vec<_> = scopes.0.into_iter().collect();
+        scopes.sort();
+        scopes.join(" ")
+    }
+}
+
+impl Scopes {
+    /// 指定したスコープを含むかを、大文字と小文字を区別せずに判定する。
+    pub fn contains(&self, scope: &str) -> bool {
+        self.0.contains(&scope.to_ascii_lowercase())
+    }
+
+    /// スコープを含まないかを判定する。
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// 小文字に正規化したスコープの集合を返す。
+    pub fn as_set(&self) -> &HashSet<String> {
+        &self.0
+    }
+}
+
+/// IDトークンのクレーム
+///
+/// IDトークンの`oid`や`tid`は、要求したスコープによっては含まれないため省略可能とする。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdTokenClaims {
+    /// 購読者（audience、SPAなどのクライアントのクライアントID）
+    pub aud: String,
+    /// 発行者（issuer）
+    pub iss: String,
+    /// 有効期限（expiration）
+    pub exp: usize,
+    /// サブジェクト
+    pub sub: String,
+    /// オブジェクトID
+    pub oid: Option<String>,
+    /// テナントID
+    pub tid: Option<String>,
+    /// 認証リクエストで指定したnonce
+    pub nonce: Option<String>,
+    /// アクセストークンのハッシュ
+    pub at_hash: Option<String>,
+    /// 認可コードのハッシュ
+    pub c_hash: Option<String>,
+    /// 上記以外のクレーム
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+/// IDトークンの検証で照合する値
+pub struct IdTokenExpectations<'a> {
+    /// IDトークンの対象者（SPAなどのクライアントのクライアントID）
+    pub client_id: &'a str,
+    /// 認証リクエストで指定したnonce
+    pub nonce: &'a str,
+    /// IDトークンと同時に発行されたアクセストークン（`at_hash`を検証する場合に指定）
+    pub access_token: Option<&'a SecretString>,
+    /// IDトークンと同時に発行された認可コード（`c_hash`を検証する場合に指定）
+    pub code: Option<&'a SecretString>,
+}
+
+/// 検証済みのクレームを、アプリケーション固有の情報で拡張した認証コンテキスト
+///
+/// 認証に成功したリクエストのエクステンションに挿入される。
+#[derive(Debug, Clone)]
+pub struct AuthContext {
+    /// 検証済みのクレーム
+    pub claims: Claims,
+    /// アプリケーション固有の属性（例: `oid`から検索した内部ユーザーID）
+    pub attributes: HashMap<String, String>,
+}
+
+impl From<Claims> for AuthContext {
+    fn from(claims: Claims) -> Self {
+        Self {
+            claims,
+            attributes: HashMap::new(),
+        }
+    }
+}
+
+/// 検証済みのBearerトークンと、その認証コンテキスト
+///
+/// トークンを検証したミドルウェアやエクストラクタが、リクエストのエクステンションに挿入する。
+/// 後続のミドルウェアやエクストラクタは、このエクステンションが存在する場合はトークンを再検証せずに再利用するため、
+/// トークンの検証はリクエストごとに1回だけ実行される。
+#[derive(Clone)]
+pub struct VerifiedToken {
+    /// 認証コンテキスト
+    pub context: AuthContext,
+    /// 検証したBearerトークン
+    pub access_token: BearerToken,
+}
+
+/// アプリケーション固有のクレームの検証
+///
+/// 標準の検証（署名、有効期限、発行者、対象者）に成功した後に実行され、エラーを返した場合はトークンを無効とする。
+pub type ClaimValidator = fn(&Claims) -> Result<(), String>;
+
+/// 非同期に変換したクレームの結果
+pub type ClaimsMapperFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<AuthContext, ClaimsMappingError>> + Send + 'a>>;
+
+/// クレームを変換するフックのエラー
+#[derive(Debug, thiserror::Error)]
+pub enum ClaimsMappingError {
+    /// ユーザーを拒否（内部ユーザーが存在しない、無効化されているなど）
+    ///
+    /// リクエストは403エラーとなる。
+    #[error("{0}")]
+    Rejected(String),
+
+    /// 内部ユーザーの検索に失敗したなど、クレームを変換できない
+    ///
+    /// リクエストは503エラーとなる。
+    #[error("{0}")]
+    Failed(String),
+}
+
+impl From<ClaimsMappingError> for EntraIdError {
+    fn from(err: ClaimsMappingError) -> Self {
+        match err {
+            ClaimsMappingError::Rejected(message) => EntraIdError::ClaimsMappingRejected(message),
+            ClaimsMappingError::Failed(message) => EntraIdError::ClaimsMapping(message),
+        }
+    }
+}
+
+/// トークンの検証に成功した後に、クレームを認証コンテキストに変換するフック
+///
+/// `oid`から内部ユーザーIDを検索して属性に追加するなど、アプリケーション固有の情報でクレームを拡張するために使用する。
+/// ユーザーを拒否した場合は403エラー、変換に失敗した場合は503エラーとなる。
+pub trait ClaimsMapper: Send + Sync {
+    /// クレームを認証コンテキストに変換する。
+    ///
+    /// # Arguments
+    ///
+    /// * `claims` - 検証済みのクレーム
+    ///
+    /// # Returns
+    ///
+    /// * 認証コンテキスト、またはエラー
+    fn map(&self, claims: Claims) -> ClaimsMapperFuture<'_>;
 }
 
 /// テナントID
+///
+/// 設定ファイルでは、GUID（例: `72f988bf-86f1-41af-91ab-2d7cd011db47`）、またはテナントの確認済みドメイン名
+/// （例: `contoso.onmicrosoft.com`）を指定する。
+/// テナントIDの誤りは、トークンの検証が401で失敗するまで判明しないため、設定の読み込み時に形式を検証する。
+///
+/// トークンの`tid`は小文字のGUIDであるため、大文字を含むGUIDは小文字に正規化する。
+/// 確認済みドメイン名は`tid`と一致することがないため、テナントで`allow_domain_name_id`を指定した場合にのみ受け入れ、
+/// 発行者に含まれるGUIDに解決する（[`Tenant::resolve_domain_name_id`]を参照）。
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize)]
-pub struct TenantId(pub String);
+#[serde(try_from = "String")]
+pub struct TenantId(String);
+
+impl TenantId {
+    /// テナントIDの文字列を返す。
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// テナントIDが確認済みドメイン名かどうかを判定する。
+    ///
+    /// # Returns
+    ///
+    /// * GUIDではなく、確認済みドメイン名であれば`true`
+    pub fn is_domain_name(&self) -> bool {
+        !is_guid(&self.0)
+    }
+}
+
+impl TryFrom<String> for TenantId {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if is_guid(&value) || is_domain_name(&value) {
+            Ok(Self(value.to_ascii_lowercase()))
+        } else {
+            Err(format!(
+                "Invalid tenant ID '{value}': must be a GUID or a domain name such as contoso.onmicrosoft.com"
+            ))
+        }
+    }
+}
 
 impl std::fmt::Display for TenantId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -130,25 +527,364 @@ impl std::fmt::Display for TenantId {
 pub struct Tenant {
     /// テナントID
     pub id: TenantId,
+    /// テナントの表示名
+    ///
+    /// ログやメトリクスのラベルで、GUIDの代わりに使用する。
+    pub name: Option<String>,
+    /// テナントが有効か
+    ///
+    /// 侵害されたテナントや利用を終了したテナントを、設定を削除せずに無効にするために使用する。
+    /// 無効なテナントが発行したトークンは拒否し、JWK公開鍵の取得やリフレッシュも行わない。
+    #[serde(default = "default_tenant_enabled")]
+    pub enabled: bool,
     /// JWK公開鍵セットを取得するURI
     pub uri: Url,
+    /// `uri`からJWK公開鍵セットを取得できなかったときに、順に試行するURI
+    ///
+    /// 地域ごとのエンドポイントなどを指定して、Entra IDの地域的な障害に備える。
+    /// `uri`からの取得が再試行できるエラーで失敗した場合にのみ使用する。
+    #[serde(default)]
+    pub fallback_uris: Vec<Url>,
     /// トークンの発行者
-    pub issuer: String,
+    ///
+    /// v1とv2のトークンや地域ごとのエンドポイントなど、1つのテナントが複数の発行者の値を提示する場合があるため、
+    /// 複数の発行者を指定できる。
+    /// 後方互換性のため、設定ファイルでは`issuer`に1つの文字列を指定することもできる。
+    /// 省略した場合は、トークン検証者全体の発行者テンプレートをテナントIDで展開した値を使用する。
+    #[serde(
+        default,
+        alias = "issuer",
+        deserialize_with = "deserialize_one_or_many"
+    )]
+    pub issuers: Vec<String>,
     /// トークンの購読者
     pub audience: String,
+    /// テナント固有の検証オプション
+    ///
+    /// 指定しなかった項目は、トークン検証者全体の検証オプションを使用する。
+    #[serde(default)]
+    pub validation: ValidationOptions,
+    /// 検証に使用を許可するJWK公開鍵のkid（キーのピン留め）
+    ///
+    /// 指定した場合は、指定したkid以外のJWK公開鍵で署名されたトークンを拒否し、JWK公開鍵キャッシュにも追加しない。
+    /// 省略した場合は、JWKsエンドポイントが返すすべてのJWK公開鍵を使用する。
+    #[serde(default)]
+    pub pinned_kids: Option<Vec<String>>,
+    /// テナントIDに確認済みドメイン名（例: `contoso.onmicrosoft.com`）を指定することを許可するか
+    ///
+    /// トークンの`tid`はGUIDであるため、確認済みドメイン名のテナントIDは`tid`と一致しない。
+    /// 許可した場合は、`issuers`にGUIDを含む発行者を指定する必要があり、テナントIDをそのGUIDに解決する。
+    #[serde(default)]
+    pub allow_domain_name_id: bool,
+}
+
+impl Tenant {
+    /// JWK公開鍵セットを取得するURIを、試行する順に返す。
+    pub fn jwks_uris(&self) -> impl Iterator<Item = &Url> {
+        std::iter::once(&self.uri).chain(&self.fallback_uris)
+    }
+
+    /// 指定したkidのJWK公開鍵を、検証に使用できるかを判定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `kid` - JWK公開鍵のkid
+    ///
+    /// # Returns
+    ///
+    /// * キーをピン留めしていない場合、またはピン留めしたkidに含まれる場合は`true`
+    pub fn accepts_kid(&self, kid: &str) -> bool {
+        self.pinned_kids
+            .as_ref()
+            .is_none_or(|pinned| pinned.iter().any(|pinned| pinned == kid))
+    }
+
+    /// JWKsエンドポイントから取得したJWK公開鍵のうち、検証に使用できるJWK公開鍵を返す。
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - JWKsエンドポイントから取得したJWK公開鍵
+    ///
+    /// # Returns
+    ///
+    /// * ピン留めしたkidのJWK公開鍵、キーをピン留めしていない場合はすべてのJWK公開鍵
+    ///
+    /// # Notes
+    ///
+    /// ピン留めしたkidのJWK公開鍵が1つも含まれていない場合は、警告を出力する。
+    fn accepted_keys(&self, keys: Vec<Jwk>) -> Vec<Jwk> {
+        if self.pinned_kids.is_none() {
+            return keys;
+        }
+        let fetched = keys.len();
+        let accepted: Vec<Jwk> = keys
+            .into_iter()
+            .filter(|key| self.accepts_kid(&key.kid))
+            .collect();
+        if accepted.is_empty() {
+            tracing::warn!(
+                tenant = %self.label(),
+                fetched = fetched,
+                "None of the pinned kids were found in the JWKs response"
+            );
+        } else if accepted.len() < fetched {
+            tracing::debug!(
+                tenant = %self.label(),
+                ignored = fetched - accepted.len(),
+                "Ignored JWKs that are not pinned"
+            );
+        }
+        accepted
+    }
+
+    /// 確認済みドメイン名で指定したテナントIDを、発行者に含まれるGUIDに解決する。
+    ///
+    /// テナントIDがGUIDの場合は何もしない。
+    /// 解決した場合、表示名を指定していなければ、確認済みドメイン名を表示名にする。
+    ///
+    /// # Returns
+    ///
+    /// * 解決に成功した場合は`Ok(())`、`allow_domain_name_id`を指定していない場合や、
+    ///   発行者から1つのGUIDを特定できない場合はエラーメッセージ
+    pub fn resolve_domain_name_id(&mut self) -> Result<(), String> {
+        if !self.id.is_domain_name() {
+            return Ok(());
+        }
+        if !self.allow_domain_name_id {
+            return Err(format!(
+                "domain name '{}' never matches the 'tid' claim, use the tenant GUID or set allow_domain_name_id",
+                self.id
+            ));
+        }
+        // すべての発行者が同じGUIDを含む場合にのみ解決する
+        let guids: Vec<Option<TenantId>> = self
+            .issuers
+            .iter()
+            .map(|issuer| {
+                extract_issuer_from_iss(issuer)
+                    .ok()
+                    .filter(|tenant_id| is_guid(&tenant_id.0))
+            })
+            .collect();
+        let Some(Some(guid)) = guids.first().cloned() else {
+            return Err(format!(
+                "domain name '{}' requires issuers that contain the tenant GUID",
+                self.id
+            ));
+        };
+        if guids.iter().any(|other| other.as_ref() != Some(&guid)) {
+            return Err(format!(
+                "domain name '{}' requires issuers that contain the same tenant GUID",
+                self.id
+            ));
+        }
+        let domain_name = std::mem::replace(&mut self.id, guid);
+        self.name.get_or_insert(domain_name.0);
+        Ok(())
+    }
+
+    /// ログやメトリクスのラベルに使用するテナントの名前を返す。
+    ///
+    /// # Returns
+    ///
+    /// * 表示名、表示名が設定されていない場合はテナントID
+    pub fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.id.0)
+    }
+}
+
+fn default_tenant_enabled() -> bool {
+    true
+}
+
+/// 1つの文字列、または文字列のリストをデシリアライズする。
+fn deserialize_one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => Ok(vec![value]),
+        OneOrMany::Many(values) if values.is_empty() => {
+            Err(serde::de::Error::custom("at least one issuer is required"))
+        }
+        OneOrMany::Many(values) => Ok(values),
+    }
+}
+
+/// 発行者テンプレートのテナントIDのプレースホルダー
+pub const TENANT_ID_PLACEHOLDER: &str = "{tenantid}";
+
+/// 既定の時刻のずれの許容（秒）
+const DEFAULT_LEEWAY_SECS: u64 = 60;
+
+/// トークンの検証オプション
+///
+/// トークン検証者全体の既定値と、テナント固有の値の両方に使用する。
+/// テナント固有の値で指定しなかった項目は、トークン検証者全体の値を使用し、それも指定されていない場合は既定値を使用する。
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ValidationOptions {
+    /// 有効期限などの時刻を検証する際に許容する時刻のずれ（秒、既定値は60秒）
+    pub leeway: Option<u64>,
+    /// 許可する署名アルゴリズム（既定値は`RS256`のみ）
+    ///
+    /// Entra IDのJWK公開鍵はRSA鍵であるため、RSA署名アルゴリズム（`RS256`、`RS384`、`RS512`、`PS256`、`PS384`、`PS512`）のみ指定できる。
+    pub algorithms: Option<Vec<Algorithm>>,
+    /// トークンに含まれていなければならないクレーム（既定値は`exp`のみ）
+    pub required_claims: Option<Vec<String>>,
+}
+
+impl ValidationOptions {
+    /// 指定しなかった項目を、`fallback`の値で補完した検証オプションを返す。
+    ///
+    /// # Arguments
+    ///
+    /// * `fallback` - 補完に使用する検証オプション
+    ///
+    /// # Returns
+    ///
+    /// * 補完した検証オプション
+    pub fn or(&self, fallback: &ValidationOptions) -> ValidationOptions {
+        ValidationOptions {
+            leeway: self.leeway.or(fallback.leeway),
+            algorithms: self
+                .algorithms
+                .clone()
+                .or_else(|| fallback.algorithms.clone()),
+            required_claims: self
+                .required_claims
+                .clone()
+                .or_else(|| fallback.required_claims.clone()),
+        }
+    }
+
+    /// 検証オプションから、`jsonwebtoken`の検証パラメーターを作成する。
+    ///
+    /// # Arguments
+    ///
+    /// * `alg` - トークンのヘッダに記録されたアルゴリズム
+    ///
+    /// # Returns
+    ///
+    /// * 検証パラメーター、またはアルゴリズムが許可されていない場合はエラー
+    fn to_validation(&self, alg: Algorithm) -> EntraIdResult<Validation> {
+        let algorithms = self
+            .algorithms
+            .clone()
+            .unwrap_or_else(|| vec![Algorithm::RS256]);
+        if !algorithms.contains(&alg) {
+            return Err(EntraIdError::UnsupportedTokenAlgorithm(alg));
+        }
+        let mut validation = Validation::new(alg);
+        validation.algorithms = algorithms;
+        validation.leeway = self.leeway.unwrap_or(DEFAULT_LEEWAY_SECS);
+        if let Some(required_claims) = &self.required_claims {
+            // `jsonwebtoken`が検証できる登録済みクレームのみを渡し、それ以外は`missing_claims`で検証する
+            let spec_claims: Vec<&str> = required_claims
+                .iter()
+                .map(String::as_str)
+                .filter(|claim| REGISTERED_CLAIMS.contains(claim))
+                .collect();
+            validation.set_required_spec_claims(&spec_claims);
+        }
+        Ok(validation)
+    }
+
+    /// 必須のクレームのうち、クレームに含まれていないクレームを返す。
+    ///
+    /// # Arguments
+    ///
+    /// * `claims` - 検証済みのクレーム
+    ///
+    /// # Returns
+    ///
+    /// * 含まれていないクレームの名前
+    fn missing_claims(&self, claims: &Claims) -> Vec<String> {
+        let Some(required_claims) = &self.required_claims else {
+            return Vec::new();
+        };
+        let claims = serde_json::to_value(claims).unwrap_or_default();
+        required_claims
+            .iter()
+            .filter(|name| {
+                claims
+                    .get(name.as_str())
+                    .is_none_or(|value| value.is_null())
+            })
+            .cloned()
+            .collect()
+    }
 }
 
+/// `jsonwebtoken`が必須であるかを検証できる登録済みクレーム
+const REGISTERED_CLAIMS: &[&str] = &["exp", "nbf", "aud", "iss", "sub"];
+
+/// RSA署名アルゴリズム
+const RSA_ALGORITHMS: &[Algorithm] = &[
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+];
+
 /// テナントレジストリ
 type TenantRegistry = HashMap<TenantId, Tenant>;
 
+/// テナントのリストからテナントレジストリを作成する。
+///
+/// # Arguments
+///
+/// * `tenants` - テナントのリスト
+///
+/// # Returns
+///
+/// * テナントレジストリ、またはテナントIDを解決できない場合やテナントIDが重複している場合はエラー
+///
+/// # Notes
+///
+/// 設定をコピーして貼り付けたときに、後のテナントが前のテナントを黙って上書きしないように、
+/// 重複したテナントIDを検出した時点で、両方のテナントを示してエラーにする。
+fn tenant_registry(tenants: Vec<Tenant>) -> EntraIdResult<TenantRegistry> {
+    let mut registry = TenantRegistry::new();
+    let mut indexes = HashMap::new();
+    for (index, mut tenant) in tenants.into_iter().enumerate() {
+        tenant
+            .resolve_domain_name_id()
+            .map_err(|e| EntraIdError::Initialize(format!("tenants[{index}].id: {e}").into()))?;
+        if let Some(existing) = registry.get(&tenant.id) {
+            return Err(EntraIdError::Initialize(
+                format!(
+                    "Duplicate tenant ID {}: tenants[{}] ({}) and tenants[{}] ({})",
+                    tenant.id,
+                    indexes[&tenant.id],
+                    existing.label(),
+                    index,
+                    tenant.label()
+                )
+                .into(),
+            ));
+        }
+        indexes.insert(tenant.id.clone(), index);
+        registry.insert(tenant.id.clone(), tenant);
+    }
+    Ok(registry)
+}
+
 /// JWK (Json Web Key)
 ///
 /// JWKは、JWT（JSON Web Token）の署名を検証するための公開鍵をJSONで表現したものである。
 /// JWKは、JWTを発行するEntra IDが公開している。
 /// バックエンドは、このJWKを使用して、受信したJWTの署名を検証する。
-#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
-struct Jwk {
+pub struct Jwk {
     /// JWK公開鍵を識別するID
     pub kid: String,
     /// JWK公開鍵の種類（RSAなど）
@@ -164,28 +900,35 @@ struct Jwk {
     pub use_: Option<String>,
 }
 
-/// キャッシュしたJWK
-#[derive(Debug)]
-struct CachedJwk {
-    /// JWK公開鍵
-    jwk: Jwk,
-    /// JWK公開鍵を最後に確認した時刻
-    last_seen_at: Instant,
+/// JWK公開鍵キャッシュのTTLやリフレッシュの最小間隔の判定に使用する時計
+///
+/// TTLの超過やリフレッシュのクールダウンを、実際の時間の経過を待たずにテストできるように、現在時刻の取得を差し替えられるようにする。
+pub trait Clock: Send + Sync {
+    /// 現在時刻を返す。
+    fn now(&self) -> Instant;
 }
 
-impl From<Jwk> for CachedJwk {
-    fn from(jwk: Jwk) -> Self {
-        let now = Instant::now();
-        Self {
-            jwk,
-            last_seen_at: now,
-        }
+/// システムの単調増加する時計
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
     }
 }
 
+/// キャッシュしたJWK
+#[derive(Debug)]
+struct CachedJwk {
+    /// JWK公開鍵
+    jwk: Jwk,
+    /// JWK公開鍵を最後に確認した時刻
+    last_seen_at: Instant,
+}
+
 /// JWK公開鍵のキーID
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct Kid(String);
+pub struct Kid(pub(crate) String);
 
 impl std::fmt::Display for Kid {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -202,14 +945,142 @@ type CachedJwkMap = HashMap<Kid, CachedJwk>;
 /// テナントをキー、JWK公開鍵セットを値としたハッシュマップ
 type TenantJwksCache = HashMap<TenantId, CachedJwkMap>;
 
+/// 非同期に取得するJWK公開鍵セット
+pub type JwksFuture<'a> = Pin<Box<dyn Future<Output = EntraIdResult<JwksResponse>> + Send + 'a>>;
+
+/// JWKsエンドポイントからJWK公開鍵セットを取得するフェッチャー
+///
+/// `JwksProvider`のキャッシュやリフレッシュ、再試行の処理を、HTTPサーバーを起動せずにテストできるように、
+/// JWK公開鍵セットの取得を差し替えられるようにする。
+pub trait JwksFetcher: Send + Sync {
+    /// 指定したJWKsエンドポイントからJWK公開鍵セットを1回だけ取得する。
+    ///
+    /// # Arguments
+    ///
+    /// * `jwks_uri` - JWKsエンドポイントのURI
+    ///
+    /// # Returns
+    ///
+    /// * JWK公開鍵セット、またはエラー
+    ///
+    /// # Notes
+    ///
+    /// 再試行とシャットダウン時の中止は`JwksProvider`が行う。
+    /// `EntraIdError::is_retryable`が`true`を返すエラーを返した場合は、再試行設定に従って再試行する。
+    fn fetch<'a>(&'a self, jwks_uri: &'a Url) -> JwksFuture<'a>;
+}
+
+/// reqwestでJWKsエンドポイントからJWK公開鍵セットを取得するフェッチャー
+pub struct ReqwestJwksFetcher {
+    /// HTTPクライアント
+    client: reqwest::Client,
+}
+
+impl ReqwestJwksFetcher {
+    /// コンストラクタ
+    ///
+    /// # Arguments
+    ///
+    /// * `connection_timeout` - Entra IDのJWKsエンドポイントに接続する際のタイムアウト
+    /// * `timeout` - Entra IDのJWKsエンドポイントからの応答を待つタイムアウト
+    /// * `pool_config` - Entra IDのJWKsエンドポイントに接続するHTTPクライアントのコネクションプール設定
+    /// * `tls_config` - Entra IDのJWKsエンドポイントに接続する際のTLSクライアント設定（省略した場合はreqwestの既定）
+    pub fn new(
+        connection_timeout: Duration,
+        timeout: Duration,
+        pool_config: &ConnectionPoolConfig,
+        tls_config: Option<rustls::ClientConfig>,
+    ) -> EntraIdResult<Self> {
+        let mut builder = pool_config.apply(
+            reqwest::Client::builder()
+                .connect_timeout(connection_timeout)
+                .timeout(timeout),
+        );
+        if let Some(tls_config) = tls_config {
+            builder = builder.tls_backend_preconfigured(tls_config);
+        }
+        let client = builder
+            .build()
+            .map_err(|e| EntraIdError::JwksProviderInitError(e.to_string()))?;
+        Ok(Self { client })
+    }
+}
+
+impl JwksFetcher for ReqwestJwksFetcher {
+    fn fetch<'a>(&'a self, jwks_uri: &'a Url) -> JwksFuture<'a> {
+        Box::pin(async move {
+            self.client
+                .get(jwks_uri.as_str())
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .map_err(|e| EntraIdError::JwksFetchError(e, jwks_uri.clone()))?
+                .json::<JwksResponse>()
+                .await
+                .map_err(|e| EntraIdError::JwksResponseParseError(jwks_uri.clone(), e))
+        })
+    }
+}
+
 /// Entra IDから取得したJWS公開鍵セットを提供
 #[derive(Clone)]
 struct JwksProvider {
-    /// HTTPクライアント
-    client: reqwest::Client,
+    /// JWK公開鍵セットを取得するフェッチャー
+    fetcher: Arc<dyn JwksFetcher>,
 
     /// Entra IDからJWKsを取得する際の再試行設定
     retry_config: RetryConfig,
+
+    /// シャットダウン時に、JWK公開鍵セットの取得と再試行の待機を中止するためのキャンセルトークン
+    shutdown: CancellationToken,
+}
+
+/// 起動時にすべてのテナントのJWK公開鍵を取得する期限を超過したときの方針
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StartupDeadlinePolicy {
+    /// 起動を中止する
+    #[default]
+    Abort,
+    /// JWK公開鍵を取得できていないテナントを劣化状態として起動を継続する
+    Degrade,
+}
+
+/// HTTPクライアントのコネクションプール設定
+///
+/// 設定しなかった項目は、reqwestの既定値を使用する。
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionPoolConfig {
+    /// ホストごとに保持するアイドル状態のコネクションの最大数
+    pub max_idle_per_host: Option<usize>,
+    /// アイドル状態のコネクションを閉じるまでの時間
+    pub idle_timeout: Option<Duration>,
+    /// TCPキープアライブの間隔
+    pub tcp_keepalive: Option<Duration>,
+}
+
+impl ConnectionPoolConfig {
+    /// HTTPクライアントビルダーにコネクションプール設定を適用する。
+    ///
+    /// # Arguments
+    ///
+    /// * `builder` - HTTPクライアントビルダー
+    ///
+    /// # Returns
+    ///
+    /// * コネクションプール設定を適用したHTTPクライアントビルダー
+    pub fn apply(&self, mut builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+        if let Some(max_idle_per_host) = self.max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max_idle_per_host);
+        }
+        if let Some(idle_timeout) = self.idle_timeout {
+            builder = builder.pool_idle_timeout(idle_timeout);
+        }
+        if let Some(tcp_keepalive) = self.tcp_keepalive {
+            builder = builder.tcp_keepalive(tcp_keepalive);
+        }
+        builder
+    }
 }
 
 /// 再試行設定
@@ -219,10 +1090,14 @@ pub struct RetryConfig {
     max_attempts: u32,
     /// 最初の待機時間
     initial_wait: Duration,
+    /// 名前解決に失敗したときの最初の待機時間
+    dns_initial_wait: Duration,
     /// 待機時間の増加乗数
     backoff_multiplier: f64,
     /// 最大待機時間
     max_wait: Duration,
+    /// 再試行を含めた、1回の取得の制限時間
+    max_total_duration: Option<Duration>,
     /// ジッター分布（待機時間に乗算されるランダム係数）
     jitter_dist: Uniform<f64>,
 }
@@ -248,12 +1123,12 @@ impl RetryConfig {
     ) -> EntraIdResult<Self> {
         if max_attempts == 0 {
             return Err(EntraIdError::Initialize(
-                "JWKs request max attempts must be greater than zero".into(),
+                "Request max attempts must be greater than zero".into(),
             ));
         }
         if backoff_multiplier < 1.0 {
             return Err(EntraIdError::Initialize(
-                "JWKs request retry backoff multiplier must be at least 1.0".into(),
+                "Request retry backoff multiplier must be at least 1.0".into(),
             ));
         }
         if jitter_min < 0.0 || jitter_max < 0.0 || jitter_min > jitter_max {
@@ -263,16 +1138,19 @@ impl RetryConfig {
         }
         if max_wait.is_zero() {
             return Err(EntraIdError::Initialize(
-                "JWKs request retry max wait must be greater than zero".into(),
+                "Request retry max wait must be greater than zero".into(),
             ));
         }
 
         Ok(Self {
             max_attempts,
             initial_wait,
+            dns_initial_wait: DEFAULT_DNS_RETRY_INITIAL_WAIT,
             backoff_multiplier,
             max_wait,
-            jitter_dist: Uniform::new(jitter_min, jitter_max).map_err(|e| {
+            max_total_duration: None,
+            // ジッターの最小値と最大値が等しい場合（ジッターなし）も許可するため、最大値を含む分布を作成する
+            jitter_dist: Uniform::new_inclusive(jitter_min, jitter_max).map_err(|e| {
                 EntraIdError::JwksProviderInitError(format!(
                     "Failed to create jitter distribution: {}",
                     e
@@ -281,22 +1159,147 @@ impl RetryConfig {
         })
     }
 
-    fn calculate_delay(&self, attempts: u32) -> Duration {
-        let mut delay_millis = self.initial_wait.as_millis() as f64
+    /// 名前解決に失敗したときの最初の待機時間を設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `wait` - 名前解決に失敗したときの最初の待機時間
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス
+    ///
+    /// # Notes
+    ///
+    /// 設定しなかった場合は50ミリ秒とする。2回目以降の待機時間は、通常の再試行と同じ乗数で増加する。
+    pub fn dns_initial_wait(mut self, wait: Duration) -> Self {
+        self.dns_initial_wait = wait;
+        self
+    }
+
+    /// 再試行と代替のJWKsエンドポイントへの切り替えを含めた、1回の取得の制限時間を設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `duration` - 再試行と代替のJWKsエンドポイントへの切り替えを含めた、1回の取得の制限時間
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス
+    ///
+    /// # Notes
+    ///
+    /// 乗数や最大試行回数が大きい場合、再試行が数分にわたって続き、その間リフレッシュを担当するタスクが
+    /// 他のリクエストを待機させ続けるため、最大試行回数に達していなくても制限時間で取得を中止する。
+    /// 設定しなかった場合は、最大試行回数のみで再試行を制限する。
+    pub fn max_total_duration(mut self, duration: Duration) -> EntraIdResult<Self> {
+        if duration.is_zero() {
+            return Err(EntraIdError::Initialize(
+                "Request retry max total duration must be greater than zero".into(),
+            ));
+        }
+        self.max_total_duration = Some(duration);
+        Ok(self)
+    }
+
+    /// 最大試行回数を返す。
+    #[cfg(feature = "obo")]
+    pub(crate) fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// 指定した試行回数の後に、再試行するまで待機する時間を返す。
+    ///
+    /// # Arguments
+    ///
+    /// * `attempts` - これまでの試行回数
+    ///
+    /// # Returns
+    ///
+    /// * ジッターを加えた指数バックオフの待機時間（最大待機時間を上限とする）
+    pub(crate) fn calculate_delay(&self, attempts: u32) -> Duration {
+        self.calculate_delay_from(self.initial_wait, attempts)
+    }
+
+    /// 名前解決に失敗した試行の後に、再試行するまで待機する時間を返す。
+    ///
+    /// # Arguments
+    ///
+    /// * `attempts` - これまでの試行回数
+    ///
+    /// # Returns
+    ///
+    /// * 名前解決に失敗したときの最初の待機時間から計算した、ジッターを加えた指数バックオフの待機時間
+    pub(crate) fn calculate_dns_delay(&self, attempts: u32) -> Duration {
+        self.calculate_delay_from(self.dns_initial_wait, attempts)
+    }
+
+    /// 最初の待機時間から、指定した試行回数の後に再試行するまで待機する時間を計算する。
+    fn calculate_delay_from(&self, initial_wait: Duration, attempts: u32) -> Duration {
+        let mut delay_millis = initial_wait.as_millis() as f64
             * self
                 .backoff_multiplier
                 .powf(attempts.saturating_sub(1) as f64);
         // ランダムジッターを追加して、同時に再試行が発生するのを防ぐ
         let jitter: f64 = self.jitter_dist.sample(&mut rand::rng());
         delay_millis *= jitter;
+        // 試行回数が多い場合は累乗が無限大になるが、`as`による変換は飽和するため、無限大は`u64::MAX`、
+        // 初期の待機時間またはジッターが0の場合に生じるNaNは0になり、最大待機時間を上限とする
         Duration::from_millis(delay_millis as u64).min(self.max_wait)
     }
 }
 
 /// JWK公開鍵セットのレスポンス
 #[derive(Deserialize)]
-struct JwksResponse {
-    keys: Vec<Jwk>,
+pub struct JwksResponse {
+    /// JWK公開鍵のリスト
+    pub keys: Vec<Jwk>,
+}
+
+/// GUIDの形式（`xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx`、`x`は16進数）かどうかを判定する。
+///
+/// # Arguments
+///
+/// * `value` - 判定する文字列
+///
+/// # Returns
+///
+/// * GUIDの形式であれば`true`
+pub(crate) fn is_guid(value: &str) -> bool {
+    const HYPHEN_POSITIONS: [usize; 4] = [8, 13, 18, 23];
+    value.len() == 36
+        && value.bytes().enumerate().all(|(i, b)| {
+            if HYPHEN_POSITIONS.contains(&i) {
+                b == b'-'
+            } else {
+                b.is_ascii_hexdigit()
+            }
+        })
+}
+
+/// ドメイン名の形式かどうかを判定する。
+///
+/// # Arguments
+///
+/// * `value` - 判定する文字列
+///
+/// # Returns
+///
+/// * 英数字とハイフンで構成された2つ以上のラベルをピリオドで区切り、最後のラベルが英字のみであれば`true`
+fn is_domain_name(value: &str) -> bool {
+    let labels: Vec<&str> = value.split('.').collect();
+    labels.len() >= 2
+        && labels.iter().all(|label| {
+            (1..=63).contains(&label.len())
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        })
+        && labels
+            .last()
+            .is_some_and(|tld| tld.bytes().all(|b| b.is_ascii_alphabetic()))
 }
 
 /// 再試行可能なエラーかどうかを判定する。
@@ -311,18 +1314,55 @@ struct JwksResponse {
 /// # Returns
 ///
 /// 再試行可能なエラーであればtrue、そうでなければfalse
-fn is_retryable_error(e: &reqwest::Error) -> bool {
+pub(crate) fn is_retryable_error(e: &reqwest::Error) -> bool {
     if e.is_timeout() {
         return true;
     }
     if e.is_connect() {
         return true;
     }
-    match e.status() {
-        Some(status) if status.is_server_error() => true,
-        Some(status) if status == reqwest::StatusCode::TOO_MANY_REQUESTS => true,
-        _ => false,
+    e.status().is_some_and(is_retryable_status)
+}
+
+/// 名前解決に失敗したエラーかどうかを判定する。
+///
+/// # Arguments
+///
+/// * `e` - reqwestのエラー
+///
+/// # Returns
+///
+/// 名前解決に失敗したエラーであればtrue、そうでなければfalse
+///
+/// # Notes
+///
+/// reqwestは名前解決の失敗を接続エラーとして報告するため、エラーの原因をたどって、hyper-utilが名前解決の失敗に
+/// 使用する`dns error`のエラーが含まれているかで判定する。
+pub(crate) fn is_dns_error(e: &reqwest::Error) -> bool {
+    if !e.is_connect() {
+        return false;
+    }
+    let mut source = std::error::Error::source(e);
+    while let Some(cause) = source {
+        if cause.to_string() == "dns error" {
+            return true;
+        }
+        source = cause.source();
     }
+    false
+}
+
+/// 再試行可能なステータスコードかどうかを判定する。
+///
+/// # Arguments
+///
+/// * `status` - ステータスコード
+///
+/// # Returns
+///
+/// サーバーエラーまたはレートリミットエラーであればtrue、そうでなければfalse
+pub(crate) fn is_retryable_status(status: reqwest::StatusCode) -> bool {
+    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
 }
 
 impl JwksProvider {
@@ -330,114 +1370,431 @@ impl JwksProvider {
     ///
     /// # Arguments
     ///
-    /// * `connection_timeout` - Entra IDのJWKsエンドポイントに接続する際のタイムアウト
-    /// * `timeout` - Entra IDのJWKsエンドポイントからの応答を待つタイムアウト
+    /// * `fetcher` - JWK公開鍵セットを取得するフェッチャー
     /// * `retry_config` - Entra IDのJWKsエンドポイントからJWK公開鍵セットを取得する際の再試行設定
+    /// * `shutdown` - シャットダウン時に、JWK公開鍵セットの取得と再試行の待機を中止するためのキャンセルトークン
     fn new(
-        connection_timeout: Duration,
-        timeout: Duration,
+        fetcher: Arc<dyn JwksFetcher>,
         retry_config: RetryConfig,
-    ) -> EntraIdResult<Self> {
-        let builder = reqwest::Client::builder()
-            .connect_timeout(connection_timeout)
-            .timeout(timeout);
-        let client = builder
-            .build()
-            .map_err(|e| EntraIdError::JwksProviderInitError(e.to_string()))?;
-        Ok(Self {
-            client,
+        shutdown: CancellationToken,
+    ) -> Self {
+        Self {
+            fetcher,
             retry_config,
-        })
+            shutdown,
+        }
+    }
+
+    /// テナントのJWKsエンドポイントからJWK公開鍵セットを取得する。
+    ///
+    /// # Arguments
+    ///
+    /// * `tenant` - テナント
+    ///
+    /// # Returns
+    ///
+    /// * JWK公開鍵セット
+    ///
+    /// # Notes
+    ///
+    /// `uri`からの取得が再試行できるエラーで失敗した場合は、`fallback_uris`を順に試行する。
+    /// 再試行できないエラーの場合は、JWKsエンドポイントを切り替えても解決しないため、直ちにエラーを返す。
+    ///
+    /// 再試行設定に制限時間を設定した場合は、すべてのJWKsエンドポイントの試行を合わせて制限時間内に収める。
+    /// 応答しないJWKsエンドポイントが制限時間を使い切らないように、各JWKsエンドポイントには、残りの制限時間を
+    /// 残りのJWKsエンドポイントの数で等分した時間を割り当て、超過した場合は次のJWKsエンドポイントに切り替える。
+    async fn fetch_tenant_jwks(&self, tenant: &Tenant) -> EntraIdResult<JwksResponse> {
+        let Some(max_total_duration) = self.retry_config.max_total_duration else {
+            return self.fetch_tenant_jwks_until(tenant, None).await;
+        };
+        let deadline = tokio::time::Instant::now() + max_total_duration;
+        match tokio::time::timeout_at(
+            deadline,
+            self.fetch_tenant_jwks_until(tenant, Some(deadline)),
+        )
+        .await
+        {
+            Ok(result) => result,
+            Err(_) => {
+                tracing::warn!(
+                    tenant = %tenant.label(),
+                    max_total_duration_ms = %max_total_duration.as_millis(),
+                    "Gave up fetching JWKs due to total retry duration"
+                );
+                Err(EntraIdError::JwksFetchRetryBudgetExceeded(
+                    tenant.uri.clone(),
+                    max_total_duration,
+                ))
+            }
+        }
+    }
+
+    /// テナントのJWKsエンドポイントを順に試行して、JWK公開鍵セットを取得する。
+    ///
+    /// # Arguments
+    ///
+    /// * `tenant` - テナント
+    /// * `deadline` - すべてのJWKsエンドポイントの試行を終える期限
+    ///
+    /// # Returns
+    ///
+    /// * JWK公開鍵セット
+    async fn fetch_tenant_jwks_until(
+        &self,
+        tenant: &Tenant,
+        deadline: Option<tokio::time::Instant>,
+    ) -> EntraIdResult<JwksResponse> {
+        let uris: Vec<&Url> = tenant.jwks_uris().collect();
+        for (index, jwks_uri) in uris.iter().enumerate() {
+            let remaining_uris = (uris.len() - index) as u32;
+            let result = match deadline {
+                Some(deadline) => {
+                    let budget = deadline.saturating_duration_since(tokio::time::Instant::now())
+                        / remaining_uris;
+                    self.fetch_jwks_within(jwks_uri, budget).await
+                }
+                None => self.fetch_jwks_with_retry(jwks_uri).await,
+            };
+            match result {
+                Err(e) if e.is_retryable() && 1 < remaining_uris => {
+                    tracing::warn!(
+                        tenant = %tenant.label(),
+                        error = %e,
+                        "Failed to fetch JWKs from {}, trying next JWKs URI",
+                        jwks_uri
+                    );
+                }
+                result => return result,
+            }
+        }
+        // `jwks_uris`は少なくとも`uri`を返すため、最後のJWKsエンドポイントの結果を返すループを抜けることはない
+        unreachable!("Tenant has no JWKs URI")
     }
 
-    /// 指定したJWKsエンドポイントからJWK公開鍵セットを取得する。
+    /// 指定したJWKsエンドポイントから、制限時間内にJWK公開鍵セットを取得する。
     ///
     /// # Arguments
     ///
     /// * `jwks_uri` - JWKsエンドポイントのURI
+    /// * `budget` - 再試行を含めた制限時間
     ///
     /// # Returns
     ///
     /// * JWK公開鍵セット
-    async fn fetch_jwks(&self, jwks_uri: &Url) -> EntraIdResult<JwksResponse> {
+    ///
+    /// # Notes
+    ///
+    /// 制限時間を超過した時点で、送信中のリクエストや再試行の待機を中止する。
+    async fn fetch_jwks_within(
+        &self,
+        jwks_uri: &Url,
+        budget: Duration,
+    ) -> EntraIdResult<JwksResponse> {
+        match tokio::time::timeout(budget, self.fetch_jwks_with_retry(jwks_uri)).await {
+            Ok(result) => result,
+            Err(_) => {
+                tracing::warn!(
+                    budget_ms = %budget.as_millis(),
+                    "Gave up fetching JWKs from {} due to retry duration",
+                    jwks_uri
+                );
+                Err(EntraIdError::JwksFetchRetryBudgetExceeded(
+                    jwks_uri.clone(),
+                    budget,
+                ))
+            }
+        }
+    }
+
+    /// 指定したJWKsエンドポイントから、再試行設定に従って再試行しながらJWK公開鍵セットを取得する。
+    ///
+    /// # Notes
+    ///
+    /// シャットダウンを開始した場合は、送信中のリクエストや再試行の待機を中止して、すぐにエラーを返す。
+    async fn fetch_jwks_with_retry(&self, jwks_uri: &Url) -> EntraIdResult<JwksResponse> {
         let mut attempts = 0;
         let mut delay = Duration::ZERO;
 
         loop {
             attempts += 1;
-            let response = self
-                .client
-                .get(jwks_uri.as_str())
-                .send()
-                .await
-                .map_err(|e| EntraIdError::JwksFetchError(e, jwks_uri.clone()))?;
-            match response.error_for_status() {
-                Ok(response) => {
-                    let jwks_response = response
-                        .json::<JwksResponse>()
-                        .await
-                        .map_err(|e| EntraIdError::JwksResponseParseError(jwks_uri.clone(), e))?;
+            let result = tokio::select! {
+                _ = self.shutdown.cancelled() => {
+                    return Err(EntraIdError::JwksFetchCancelled(jwks_uri.clone()));
+                }
+                result = self.fetcher.fetch(jwks_uri) => result,
+            };
+            match result {
+                Ok(jwks_response) => {
                     if jwks_response.keys.is_empty() {
                         tracing::warn!("JWKs response from {} contains no keys", jwks_uri,);
                     }
                     return Ok(jwks_response);
                 }
                 Err(e) => {
-                    let retryable = is_retryable_error(&e);
+                    let retryable = e.is_retryable();
+                    let dns_failure = e.is_dns_failure();
+                    if dns_failure {
+                        metrics::counter!(
+                            crate::metrics::JWKS_DNS_FAILURES_TOTAL,
+                            "host" => jwks_uri.host_str().unwrap_or_default().to_string(),
+                        )
+                        .increment(1);
+                    }
                     tracing::warn!(
-                        error = %e, attempts = %attempts, delay_ms = %delay.as_millis(),
+                        error = %e, attempts = %attempts, delay_ms = %delay.as_millis(), dns_failure = dns_failure,
                         "Failed to fetch JWKs from {}, retryable: {}, max attempts: {}",
                         jwks_uri, retryable, self.retry_config.max_attempts
                     );
                     if !retryable || attempts >= self.retry_config.max_attempts {
-                        return Err(EntraIdError::JwksFetchError(e, jwks_uri.clone()));
+                        return Err(e);
                     }
                     // 試行回数に対して指数関数的に待機時間を増加させる（指数バックオフ）
-                    delay = self.retry_config.calculate_delay(attempts);
+                    //
+                    // 名前解決の失敗は短時間で解消することが多いため、専用の短い待機時間から再試行する。
+                    delay = if dns_failure {
+                        self.retry_config.calculate_dns_delay(attempts)
+                    } else {
+                        self.retry_config.calculate_delay(attempts)
+                    };
                     // リクエストの再試行を待機
-                    tokio::time::sleep(delay).await;
+                    tokio::select! {
+                        _ = self.shutdown.cancelled() => {
+                            tracing::info!("Cancelled retrying to fetch JWKs from {} due to shutdown", jwks_uri);
+                            return Err(EntraIdError::JwksFetchCancelled(jwks_uri.clone()));
+                        }
+                        _ = tokio::time::sleep(delay) => {}
+                    }
                 }
             }
         }
     }
 }
 
+/// キャッシュに存在しないkidを契機としたJWK公開鍵のリフレッシュの制限
+///
+/// 未知のkidを持つトークンを大量に送信されたときに、リフレッシュの完了を待機するリクエストが積み上がったり、
+/// Entra IDへのリクエストが増えたりしないように、テナントごとに制限する。
+/// 制限を超えたリクエストは、リフレッシュを待機せずにJWK公開鍵が見つからないものとして扱う。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UnknownKidRefreshLimits {
+    /// テナントごとに、リフレッシュの完了を同時に待機できるリクエストの最大数
+    pub max_waiters: Option<usize>,
+    /// テナントごとに、1分間に未知のkidを契機としてリフレッシュできる最大回数
+    pub max_refreshes_per_minute: Option<u32>,
+}
+
+/// 未知のkidを契機としたリフレッシュの回数を数える期間
+const UNKNOWN_KID_REFRESH_WINDOW: Duration = Duration::from_mins(1);
+
+/// リフレッシュの完了を待機しているリクエストの数を、待機の終了時またはキャンセル時に減らすガード
+struct RefreshWaiterGuard(Arc<AtomicUsize>);
+
+impl Drop for RefreshWaiterGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
 /// JWK公開鍵キャッシュのリフレッシュ状態
 struct JwksCacheRefreshState {
     /// 最後にリフレッシュした時刻
     last_refreshed_at: Option<Instant>,
 
+    /// 最後にリフレッシュを試行した時刻
+    ///
+    /// 失敗した試行も含むため、TTLを超過したJWK公開鍵を使用したときに、Entra IDの障害中にリフレッシュを繰り返さないようにするために使用する。
+    last_attempted_at: Option<Instant>,
+
+    /// 最後にリフレッシュに失敗した時刻
+    last_failed_at: Option<Instant>,
+
+    /// リフレッシュに連続して失敗した回数
+    consecutive_failures: u32,
+
     /// リフレッシュ中かどうか
     refreshing: bool,
 
+    /// リフレッシュする権限を与えるたびに増加する世代
+    ///
+    /// 制限時間を超過して回復されたリフレッシュと、その後に開始されたリフレッシュを区別するために使用する。
+    generation: u64,
+
     /// リフレッシュ完了を待機しているタスクを通知するための`Notify`
     ///
     /// 同じテナントでJWK公開鍵が見つからない場合、複数のリクエストが同時にJWK公開鍵のリフレッシュを要求する可能性がある。
     /// その際、リフレッシュを担当しないタスクはこの`Notify`を待機するため、同一の`Notify`を共有できるよう`Arc`でラップする。
     notify: Arc<Notify>,
+
+    /// リフレッシュの完了を待機しているリクエストの数
+    waiters: Arc<AtomicUsize>,
+
+    /// 直近1分間に、未知のkidを契機としてリフレッシュを開始した時刻
+    unknown_kid_refreshes: VecDeque<Instant>,
 }
 
 impl Default for JwksCacheRefreshState {
     fn default() -> Self {
         Self {
             last_refreshed_at: None,
+            last_attempted_at: None,
+            last_failed_at: None,
+            consecutive_failures: 0,
             refreshing: false,
+            generation: 0,
             notify: Arc::new(Notify::new()),
+            waiters: Arc::new(AtomicUsize::new(0)),
+            unknown_kid_refreshes: VecDeque::new(),
         }
     }
 }
 
-/// テナントごとのJWK公開鍵キャッシュのリフレッシュ状態を保持するハッシュマップ
-type TenantJwksCacheRefreshStates = HashMap<TenantId, JwksCacheRefreshState>;
+/// バックグラウンドでのJWK公開鍵のリフレッシュの失敗状態
+///
+/// 連続して失敗しているテナントに対して、バックグラウンドのリフレッシュのサイクルごとにEntra IDへリクエストし続けないように、
+/// 指数バックオフでリフレッシュ対象から外すサイクル数を決める。
+#[derive(Default)]
+struct BackgroundRefreshBackoff {
+    /// 連続して失敗した回数
+    consecutive_failures: u32,
+    /// リフレッシュ対象から外す残りのサイクル数
+    remaining_skip_cycles: u32,
+}
+
+impl BackgroundRefreshBackoff {
+    /// 今回のサイクルでリフレッシュをスキップするかを判定する。
+    ///
+    /// # Returns
+    ///
+    /// * スキップする場合は`true`
+    fn should_skip(&mut self) -> bool {
+        if self.remaining_skip_cycles == 0 {
+            return false;
+        }
+        self.remaining_skip_cycles -= 1;
+        true
+    }
+
+    /// リフレッシュの失敗を記録して、次にリフレッシュするまでにスキップするサイクル数を決める。
+    ///
+    /// # Returns
+    ///
+    /// * 連続して失敗した回数
+    fn record_failure(&mut self) -> u32 {
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        self.remaining_skip_cycles = 2u32
+            .saturating_pow(self.consecutive_failures - 1)
+            .saturating_sub(1)
+            .min(MAX_BACKGROUND_JWKS_REFRESH_BACKOFF_CYCLES);
+        self.consecutive_failures
+    }
+}
+
+/// テナントごとのJWK公開鍵キャッシュのリフレッシュ状態を保持するハッシュマップ
+type TenantJwksCacheRefreshStates = HashMap<TenantId, JwksCacheRefreshState>;
+
+/// テナントのJWK公開鍵キャッシュをリフレッシュする権限
+///
+/// `acquire_refresh`メソッドでリフレッシュする権限を得たタスクが保持する。
+/// リフレッシュを完了する前にパニックやキャンセルで破棄された場合は、権限を得たテナントのリフレッシュフラグのみを解除して、
+/// 待機しているタスクに通知する。
+struct RefreshPermit {
+    /// テナントごとのJWK公開鍵キャッシュのリフレッシュ状態
+    states: Arc<Mutex<TenantJwksCacheRefreshStates>>,
+    /// リフレッシュする権限を得たテナントのテナントID
+    tenant_id: TenantId,
+    /// リフレッシュする権限を得たときのリフレッシュの世代
+    generation: u64,
+    /// リフレッシュを完了して、リフレッシュフラグを解除したかどうか
+    released: bool,
+}
+
+impl RefreshPermit {
+    /// この権限で開始したリフレッシュが、テナントの現在のリフレッシュかどうかを返す。
+    ///
+    /// # Returns
+    ///
+    /// * 制限時間を超過して回復されていない場合は`true`
+    fn is_current(&self, state: &JwksCacheRefreshState) -> bool {
+        state.refreshing && state.generation == self.generation
+    }
+
+    /// リフレッシュを完了する前に破棄された権限のテナントの、リフレッシュフラグを解除して待機しているタスクに通知する。
+    ///
+    /// 制限時間を超過して回復された後に、新しいリフレッシュが開始されている場合は何もしない。
+    fn abandon(states: &mut TenantJwksCacheRefreshStates, tenant_id: &TenantId, generation: u64) {
+        if let Some(state) = states.get_mut(tenant_id)
+            && state.refreshing
+            && state.generation == generation
+        {
+            state.refreshing = false;
+            state.notify.notify_waiters();
+        }
+    }
+}
+
+impl Drop for RefreshPermit {
+    fn drop(&mut self) {
+        if self.released {
+            return;
+        }
+        tracing::warn!(
+            tenant = %self.tenant_id,
+            "JWKs refresh for tenant was abandoned before completion, clearing the refreshing flag"
+        );
+        // `drop`ではロックを待機できないため、ロックを取得できなかった場合は別のタスクで解除する
+        match self.states.try_lock() {
+            Ok(mut states) => Self::abandon(&mut states, &self.tenant_id, self.generation),
+            Err(_) => {
+                if tokio::runtime::Handle::try_current().is_ok() {
+                    let states = Arc::clone(&self.states);
+                    let tenant_id = self.tenant_id.clone();
+                    let generation = self.generation;
+                    spawn_named_task("jwks-refresh-abandon", async move {
+                        Self::abandon(&mut *states.lock().await, &tenant_id, generation);
+                    });
+                }
+            }
+        }
+    }
+}
 
 /// テナントごとのJWK公開鍵のキャッシュ
 struct JwksCache {
     /// テナントごとのJWK公開鍵キャッシュ
     entries: RwLock<TenantJwksCache>,
     /// テナントごとのJWK公開鍵キャッシュのリフレッシュ状態
-    refresh_states: Mutex<TenantJwksCacheRefreshStates>,
+    ///
+    /// リフレッシュする権限がリフレッシュフラグを解除できるように、`Arc`でラップする。
+    refresh_states: Arc<Mutex<TenantJwksCacheRefreshStates>>,
     /// JWK公開鍵キャッシュのTTL
     ttl: Duration,
+    /// TTLを超過したJWK公開鍵を、リフレッシュを試行しながら検証に使用し続ける猶予期間
+    stale_grace: Duration,
+    /// JWK公開鍵キャッシュの統計情報のカウンター
+    counters: JwksCacheCounters,
+    /// TTLやリフレッシュの最小間隔の判定に使用する時計
+    clock: Arc<dyn Clock>,
+}
+
+/// JWK公開鍵キャッシュの統計情報のカウンター
+#[derive(Default)]
+struct JwksCacheCounters {
+    /// キャッシュからJWK公開鍵を得られた回数
+    hits: AtomicU64,
+    /// キャッシュからTTLを超過したJWK公開鍵を得られた回数（`hits`に含まれる）
+    stale_hits: AtomicU64,
+    /// キャッシュからJWK公開鍵を得られなかった回数
+    misses: AtomicU64,
+    /// テナントのJWK公開鍵のリフレッシュに成功した回数
+    refreshes: AtomicU64,
+    /// テナントのJWK公開鍵のリフレッシュに失敗した回数
+    refresh_failures: AtomicU64,
+    /// 他のスレッドのリフレッシュ完了を待機した回数
+    waits: AtomicU64,
+    /// 最小リフレッシュ間隔を経過していなかったため、リフレッシュしなかった回数
+    cooldown_skips: AtomicU64,
+    /// 未知のkidを契機としたリフレッシュの制限を超えたため、リフレッシュもその待機もしなかった回数
+    throttles: AtomicU64,
 }
 
 /// Bearerトークン
@@ -445,9 +1802,43 @@ struct JwksCache {
 #[derive(Clone)]
 pub struct BearerToken(pub SecretString);
 
+impl BearerToken {
+    /// `Authorization`ヘッダの値からBearerトークンを取得する。
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - `Authorization`ヘッダの値（例: `Bearer eyJ...`）
+    ///
+    /// # Returns
+    ///
+    /// * Bearerトークン、またはBearerスキームでないか、トークンが空の場合は`None`
+    ///
+    /// # Notes
+    ///
+    /// axum以外のフレームワークでも使用できるように、ヘッダの型に依存せずに文字列から取得する。
+    pub fn from_authorization(value: &str) -> Option<Self> {
+        let scheme = value.get(..BEARER_SCHEME.len())?;
+        if !scheme.eq_ignore_ascii_case(BEARER_SCHEME) {
+            return None;
+        }
+        let token = value[BEARER_SCHEME.len()..].trim();
+        if token.is_empty() {
+            return None;
+        }
+        Some(Self(SecretString::from(token)))
+    }
+}
+
+impl std::fmt::Display for BearerToken {
+    /// トークンをログやエラーに出力しないように、常に伏せ字で出力する。
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(crate::redaction::REDACTED)
+    }
+}
+
 /// JWK公開鍵キャッシュのリフレッシュ結果
-#[derive(PartialEq, Eq)]
-enum JwksCacheRefreshResult {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JwksCacheRefreshResult {
     /// リフレッシュした
     Refreshed,
     /// 最近リフレッシュされていたため、リフレッシュしなかった
@@ -456,6 +1847,104 @@ enum JwksCacheRefreshResult {
     WaitedForRefresh,
     /// 現在のスレッドがリフレッシュする権限を得た
     GrantedRefreshPermission,
+    /// 未知のkidを契機としたリフレッシュの制限を超えたため、リフレッシュもその待機もしなかった
+    Throttled,
+}
+
+/// テナントのJWK公開鍵キャッシュのリフレッシュ状態を確認した結果
+enum RefreshAcquisition {
+    /// 最近リフレッシュされていたか制限を超えたため、リフレッシュも待機もしない
+    Skipped(JwksCacheRefreshResult),
+    /// 他のタスクによるリフレッシュの完了を待機する
+    Wait(OwnedNotified, RefreshWaiterGuard),
+    /// リフレッシュする権限を得た（リフレッシュの完了を待機するための`Notified`を含む）
+    Granted(OwnedNotified, RefreshPermit),
+}
+
+/// キャッシュしたJWK公開鍵のスナップショット
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedJwkSnapshot {
+    /// JWK公開鍵のキーID
+    pub kid: String,
+    /// JWK公開鍵を最後に確認してからの経過時間（秒）
+    pub last_seen_age_secs: u64,
+}
+
+/// テナントのJWK公開鍵キャッシュのスナップショット
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TenantJwksCacheSnapshot {
+    /// テナントID
+    pub tenant_id: String,
+    /// テナントが有効か
+    pub enabled: bool,
+    /// キャッシュしているJWK公開鍵
+    pub keys: Vec<CachedJwkSnapshot>,
+    /// 最後にリフレッシュしてからの経過時間（秒）
+    ///
+    /// 起動後にリフレッシュしていない場合は`None`
+    pub last_refreshed_age_secs: Option<u64>,
+    /// リフレッシュ中かどうか
+    pub refreshing: bool,
+}
+
+/// テナントのJWK公開鍵キャッシュの統計情報
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TenantJwksCacheStats {
+    /// テナントID
+    pub tenant_id: String,
+    /// キャッシュしているJWK公開鍵の数
+    pub key_count: usize,
+}
+
+/// JWK公開鍵キャッシュの統計情報
+///
+/// 各カウンターは、トークン検証者を構築してからの累計値である。
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JwksCacheStats {
+    /// キャッシュからJWK公開鍵を得られた回数
+    pub hits: u64,
+    /// キャッシュからTTLを超過したJWK公開鍵を得られた回数（`hits`に含まれる）
+    pub stale_hits: u64,
+    /// キャッシュからJWK公開鍵を得られなかった回数
+    pub misses: u64,
+    /// テナントのJWK公開鍵のリフレッシュに成功した回数
+    pub refreshes: u64,
+    /// テナントのJWK公開鍵のリフレッシュに失敗した回数
+    pub refresh_failures: u64,
+    /// 他のスレッドのリフレッシュ完了を待機した回数
+    pub waits: u64,
+    /// 最小リフレッシュ間隔を経過していなかったため、リフレッシュしなかった回数
+    pub cooldown_skips: u64,
+    /// 未知のkidを契機としたリフレッシュの制限を超えたため、リフレッシュもその待機もしなかった回数
+    pub throttles: u64,
+    /// テナントIDの昇順に並べた、テナントごとの統計情報
+    pub tenants: Vec<TenantJwksCacheStats>,
+}
+
+/// テナントの健全性
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TenantHealth {
+    /// テナントの表示名（表示名が設定されていない場合はテナントID）
+    pub tenant: String,
+    /// テナントが有効か
+    pub enabled: bool,
+    /// 直近のJWK公開鍵のリフレッシュに失敗しているか
+    pub degraded: bool,
+    /// JWK公開鍵のリフレッシュに連続して失敗した回数
+    pub consecutive_failures: u32,
+    /// 最後にリフレッシュに成功してからの経過時間（秒）
+    ///
+    /// 起動後にリフレッシュしていない場合は`None`
+    pub last_success_age_secs: Option<u64>,
+    /// 最後にリフレッシュに失敗してからの経過時間（秒）
+    ///
+    /// 起動後にリフレッシュに失敗していない場合は`None`
+    pub last_failure_age_secs: Option<u64>,
 }
 
 /// Entra IDトークン検証者
@@ -470,6 +1959,28 @@ pub struct EntraIdTokenVerifier {
     refresh_jwks_interval: Duration,
     /// テナントのキャッシュされたJWK公開鍵がリフレッシュされてから、次にリフレッシュされるまでの最小時間
     refresh_tenant_jwks_interval: Duration,
+    /// キャッシュに存在しないkidを契機としたJWK公開鍵のリフレッシュの制限
+    unknown_kid_refresh_limits: UnknownKidRefreshLimits,
+    /// キャッシュに存在しないkidを契機としたJWK公開鍵のリフレッシュを、リクエストの処理中に待機する制限時間
+    unknown_kid_refresh_timeout: Option<Duration>,
+    /// 他のタスクによるJWK公開鍵のリフレッシュの完了を待機する制限時間
+    refresh_wait_timeout: Duration,
+    /// 検証に成功したトークンを監査ログに出力する割合（0.0から1.0）
+    success_audit_sample_rate: f64,
+    /// `organizations`エンドポイントが発行したトークンを、`tid`が登録済みのテナントを示す場合に受け入れるかどうか
+    accept_organizations_issuer: bool,
+    /// 検証済みのクレームを認証コンテキストに変換するフック
+    claims_mapper: Option<Arc<dyn ClaimsMapper>>,
+    /// アプリケーション固有のクレームの検証
+    claim_validators: Vec<ClaimValidator>,
+    /// テナント固有の検証オプションで指定しなかった項目に使用する検証オプション
+    validation_options: ValidationOptions,
+    /// バックグラウンドタスクを停止するためのキャンセルトークン
+    shutdown: CancellationToken,
+    /// バックグラウンドで定期的にJWK公開鍵をリフレッシュするタスクのハンドル
+    ///
+    /// `shutdown`メソッドでタスクの終了を待機した後は`None`になる。
+    background_task: Mutex<Option<JoinHandle<()>>>,
 }
 
 impl EntraIdTokenVerifier {
@@ -479,53 +1990,161 @@ impl EntraIdTokenVerifier {
     ///
     /// * `tenants` - テナントのリスト
     /// * `jwk_cache_ttl` - キャッシュしたJWK公開鍵のTTL
+    /// * `jwk_cache_stale_grace` - TTLを超過したJWK公開鍵を、リフレッシュを試行しながら検証に使用し続ける猶予期間
     /// * `refresh_jwks_interval` - 定期的にバックグラウンドですべてのテナントのJWK公開鍵をリフレッシュする間隔（秒）
+    /// * `background_refresh` - 定期的にバックグラウンドでJWK公開鍵をリフレッシュするタスクを起動するかどうか
     /// * `refresh_tenant_jwks_interval`
     ///   - kidを基にテナントのJWK公開鍵を得られなかったときに、そのテナントのJWK公開鍵が最後にリフレッシュされてから、
     ///     次にリフレッシュするまでの最小時間
-    /// * `entra_id_connection_timeout` - Entra IDのJWKsエンドポイントに接続する際のタイムアウト
-    /// * `entra_id_timeout` - Entra IDのJWKsエンドポイントからの応答を待つタイムアウト
+    /// * `unknown_kid_refresh_limits` - キャッシュに存在しないkidを契機としたJWK公開鍵のリフレッシュの制限
+    /// * `unknown_kid_refresh_timeout`
+    ///   - キャッシュに存在しないkidを契機としたJWK公開鍵のリフレッシュを、リクエストの処理中に待機する制限時間
+    /// * `refresh_wait_timeout` - 他のタスクによるJWK公開鍵のリフレッシュの完了を待機する制限時間
+    /// * `success_audit_sample_rate` - 検証に成功したトークンを監査ログに出力する割合（0.0から1.0）
+    /// * `accept_organizations_issuer`
+    ///   - `organizations`エンドポイントが発行したトークンを、`tid`が登録済みのテナントを示す場合に受け入れるかどうか
+    /// * `jwks_fetcher` - Entra IDのJWKsエンドポイントからJWK公開鍵セットを取得するフェッチャー
     /// * `retry_config` - Entra IDのJWKsエンドポイントからJWK公開鍵セットを取得する際の再試行設定
     /// * `shutdown` - バックグラウンドタスクを停止するためのキャンセルトークン
+    /// * `startup_fetch_deadline` - 起動時にすべてのテナントのJWK公開鍵を取得する期限
+    /// * `startup_deadline_policy` - 起動時にすべてのテナントのJWK公開鍵を取得する期限を超過したときの方針
+    /// * `claims_mapper` - 検証済みのクレームを認証コンテキストに変換するフック
+    /// * `claim_validators` - アプリケーション固有のクレームの検証
+    /// * `validation_options` - テナント固有の検証オプションで指定しなかった項目に使用する検証オプション
+    /// * `issuer_template` - 発行者を省略したテナントに使用する、`{tenantid}`を含む発行者テンプレート
+    /// * `clock` - JWK公開鍵キャッシュのTTLやリフレッシュの最小間隔の判定に使用する時計
     #[allow(clippy::too_many_arguments)]
     async fn new(
-        tenants: Vec<Tenant>,
+        mut tenants: Vec<Tenant>,
         jwk_cache_ttl: Duration,
+        jwk_cache_stale_grace: Duration,
         refresh_jwks_interval: Duration,
+        background_refresh: bool,
         refresh_tenant_jwks_interval: Duration,
-        entra_id_connection_timeout: Duration,
-        entra_id_timeout: Duration,
+        unknown_kid_refresh_limits: UnknownKidRefreshLimits,
+        unknown_kid_refresh_timeout: Option<Duration>,
+        refresh_wait_timeout: Duration,
+        success_audit_sample_rate: f64,
+        accept_organizations_issuer: bool,
+        jwks_fetcher: Arc<dyn JwksFetcher>,
         retry_config: RetryConfig,
         shutdown: CancellationToken,
+        startup_fetch_deadline: Option<Duration>,
+        startup_deadline_policy: StartupDeadlinePolicy,
+        claims_mapper: Option<Arc<dyn ClaimsMapper>>,
+        claim_validators: Vec<ClaimValidator>,
+        validation_options: ValidationOptions,
+        issuer_template: Option<String>,
+        clock: Arc<dyn Clock>,
     ) -> EntraIdResult<Arc<Self>> {
-        // テナントレジストリを初期化
-        let mut tenant_registry = TenantRegistry::new();
-        for tenant in tenants.into_iter() {
-            tenant_registry.insert(tenant.id.clone(), tenant);
+        // 発行者を省略したテナントの発行者を、発行者テンプレートから展開
+        for tenant in tenants
+            .iter_mut()
+            .filter(|tenant| tenant.issuers.is_empty())
+        {
+            let template = issuer_template.as_ref().ok_or_else(|| {
+                EntraIdError::Initialize(
+                    format!(
+                        "Issuer is not set for tenant {} and no issuer template is configured",
+                        tenant.id
+                    )
+                    .into(),
+                )
+            })?;
+            tenant.issuers = vec![template.replace(TENANT_ID_PLACEHOLDER, &tenant.id.0)];
+        }
+
+        // 検証オプションのアルゴリズムを検証
+        for options in tenants
+            .iter()
+            .map(|tenant| &tenant.validation)
+            .chain(std::iter::once(&validation_options))
+        {
+            if let Some(alg) = options
+                .algorithms
+                .iter()
+                .flatten()
+                .find(|alg| !RSA_ALGORITHMS.contains(alg))
+            {
+                return Err(EntraIdError::Initialize(
+                    format!("Unsupported validation algorithm: {alg:?}").into(),
+                ));
+            }
         }
 
+        // テナントレジストリを初期化
+        let tenant_registry = tenant_registry(tenants)?;
+
         // JWKsプロバイダを初期化
-        let provider =
-            JwksProvider::new(entra_id_connection_timeout, entra_id_timeout, retry_config)?;
+        let provider = JwksProvider::new(jwks_fetcher, retry_config, shutdown.clone());
 
         // テナントごとのJWK公開鍵キャッシュを初期化
+        //
+        // 期限を設定した場合、テナントの数や再試行の設定によって起動が長時間ブロックされないように、
+        // すべてのテナントのJWK公開鍵の取得を期限内に終えられなかった場合は、方針に従って起動を中止するか、
+        // JWK公開鍵を取得できていないテナントを劣化状態として起動を継続する。
+        let deadline =
+            startup_fetch_deadline.map(|deadline| tokio::time::Instant::now() + deadline);
         let mut tenant_jwks_cache = TenantJwksCache::new();
         let mut tenant_refresh_states = HashMap::new();
-        for (tenant_id, tenant) in &tenant_registry {
-            // テナントごとのJWK公開鍵を取得して、初期化時は取得に失敗した場合に失敗させる（fail-fast）
-            let jwks = provider.fetch_jwks(&tenant.uri).await?;
-            let cached_jwks: Vec<CachedJwk> = jwks.keys.into_iter().map(|key| key.into()).collect();
+        let mut deadline_exceeded = false;
+        for (tenant_id, tenant) in tenant_registry.iter().filter(|(_, tenant)| tenant.enabled) {
             let mut cached_jwk_map = CachedJwkMap::new();
-            for cached_jwk in cached_jwks {
-                cached_jwk_map.insert(Kid(cached_jwk.jwk.kid.clone()), cached_jwk);
+            let mut refresh_state = JwksCacheRefreshState::default();
+            let fetched = if deadline_exceeded {
+                None
+            } else {
+                match deadline {
+                    Some(deadline) => {
+                        tokio::time::timeout_at(deadline, provider.fetch_tenant_jwks(tenant))
+                            .await
+                            .ok()
+                    }
+                    None => Some(provider.fetch_tenant_jwks(tenant).await),
+                }
+            };
+            match fetched {
+                Some(jwks) => {
+                    // テナントごとのJWK公開鍵を取得して、初期化時は取得に失敗した場合に失敗させる（fail-fast）
+                    for key in tenant.accepted_keys(jwks?.keys) {
+                        cached_jwk_map.insert(
+                            Kid(key.kid.clone()),
+                            CachedJwk {
+                                jwk: key,
+                                last_seen_at: clock.now(),
+                            },
+                        );
+                    }
+                }
+                None => {
+                    deadline_exceeded = true;
+                    if startup_deadline_policy == StartupDeadlinePolicy::Abort {
+                        return Err(EntraIdError::Initialize(
+                            format!(
+                                "Startup JWKs fetch deadline exceeded before fetching JWKs for tenant {}",
+                                tenant.label()
+                            )
+                            .into(),
+                        ));
+                    }
+                    tracing::warn!(
+                        tenant = %tenant.label(),
+                        "Startup JWKs fetch deadline exceeded, marking tenant as degraded"
+                    );
+                    refresh_state.last_failed_at = Some(clock.now());
+                    refresh_state.consecutive_failures = 1;
+                }
             }
             tenant_jwks_cache.insert(tenant_id.clone(), cached_jwk_map);
-            tenant_refresh_states.insert(tenant_id.clone(), JwksCacheRefreshState::default());
+            tenant_refresh_states.insert(tenant_id.clone(), refresh_state);
         }
         let cache = JwksCache {
             entries: RwLock::new(tenant_jwks_cache),
             ttl: jwk_cache_ttl,
-            refresh_states: Mutex::new(tenant_refresh_states),
+            stale_grace: jwk_cache_stale_grace,
+            counters: JwksCacheCounters::default(),
+            refresh_states: Arc::new(Mutex::new(tenant_refresh_states)),
+            clock,
         };
 
         // ArcでラップしたEntraIdTokenVerifierインスタンスを作成
@@ -535,13 +2154,30 @@ impl EntraIdTokenVerifier {
             cache,
             refresh_jwks_interval,
             refresh_tenant_jwks_interval,
+            unknown_kid_refresh_limits,
+            unknown_kid_refresh_timeout,
+            refresh_wait_timeout,
+            success_audit_sample_rate,
+            accept_organizations_issuer,
+            claims_mapper,
+            claim_validators,
+            validation_options,
+            shutdown: shutdown.clone(),
+            background_task: Mutex::new(None),
         });
 
         // 定期的にJWK公開鍵キャッシュをリフレッシュするタスクをバックグラウンドで起動
-        let cloned_instance = Arc::clone(&instance);
-        cloned_instance
-            .run_refresh_jwks_cache_task_in_background(shutdown)
-            .await?;
+        if background_refresh {
+            let cloned_instance = Arc::clone(&instance);
+            let background_task = cloned_instance
+                .run_refresh_jwks_cache_task_in_background(shutdown)
+                .await?;
+            *instance.background_task.lock().await = Some(background_task);
+        } else {
+            tracing::info!(
+                "Background JWKs refresh is disabled; JWKs are refreshed only on demand"
+            );
+        }
 
         Ok(instance)
     }
@@ -555,12 +2191,33 @@ impl EntraIdTokenVerifier {
     ///
     /// # Returns
     ///
-    /// * 見つかった場合は公開鍵、見つからなかった場合はNone
-    async fn find_decoding_key(&self, tenant_id: &TenantId, key_id: &Kid) -> Option<DecodingKey> {
+    /// * 見つかった場合は公開鍵とTTLを超過しているかどうか、見つからなかった場合はNone
+    ///
+    /// # Notes
+    ///
+    /// TTLと猶予期間を超過したJWK公開鍵は、キャッシュから削除されていなくても見つからなかったものとして扱う。
+    /// バックグラウンドのリフレッシュを無効にした場合はキャッシュのクリーンアップが実行されないため、失効したJWK公開鍵で
+    /// トークンを検証し続けないように、ここで有効期限を判定する。
+    async fn find_decoding_key(
+        &self,
+        tenant_id: &TenantId,
+        key_id: &Kid,
+    ) -> Option<(DecodingKey, bool)> {
         let cache = self.cache.entries.read().await;
         let cached_jwk_map = cache.get(tenant_id)?;
         let cached_jwk = cached_jwk_map.get(key_id)?;
-        decoding_key_from_jwk(&cached_jwk.jwk).ok()
+        let age = self
+            .cache
+            .clock
+            .now()
+            .duration_since(cached_jwk.last_seen_at);
+        if self.cache.ttl + self.cache.stale_grace <= age {
+            return None;
+        }
+        let is_stale = age >= self.cache.ttl;
+        decoding_key_from_jwk(&cached_jwk.jwk)
+            .ok()
+            .map(|key| (key, is_stale))
     }
 
     /// 指定したテナントIDとJWK公開鍵のキーIDに対応するDecodingKeyを返す。
@@ -573,25 +2230,54 @@ impl EntraIdTokenVerifier {
     /// # Returns
     ///
     /// * JWK公開鍵、またはエラー
+    ///
+    /// # Notes
+    ///
+    /// TTLを超過したJWK公開鍵が見つかった場合、そのJWK公開鍵を検証に使用しつつ、バックグラウンドでテナントのJWK公開鍵の
+    /// リフレッシュを試行する（stale-while-revalidate）。
+    /// これにより、Entra IDのJWKsエンドポイントの障害がTTLより長く続いても、猶予期間内はAPIを提供し続けられる。
+    ///
+    /// JWK公開鍵が見つからずにリフレッシュする場合、`unknown_kid_refresh_timeout`を設定していれば、制限時間を超過した
+    /// 時点でリフレッシュの完了を待たずにエラーを返し、リフレッシュはバックグラウンドで継続する。
     async fn get_decoding_key(
-        &self,
+        self: &Arc<Self>,
         tenant_id: &TenantId,
         key_id: &Kid,
     ) -> EntraIdResult<DecodingKey> {
         // テナントIDとキーのIDからJWK公開鍵を取得
-        if let Some(key) = self.find_decoding_key(tenant_id, key_id).await {
+        if let Some((key, is_stale)) = self.find_decoding_key(tenant_id, key_id).await {
+            self.cache.counters.hits.fetch_add(1, Ordering::Relaxed);
+            if is_stale {
+                self.cache
+                    .counters
+                    .stale_hits
+                    .fetch_add(1, Ordering::Relaxed);
+                self.revalidate_stale_jwks_in_background(tenant_id).await;
+            }
             return Ok(key);
         }
+        self.cache.counters.misses.fetch_add(1, Ordering::Relaxed);
 
         // JWK公開鍵を得られなかった場合は、テナントのJWK公開鍵キャッシュを条件付きでリフレッシュ
         //
         // テナントのJWK公開鍵キャッシュのリフレッシュに失敗しても、他のスレッドでリフレッシュに成功している可能性
         // があるため、失敗を無視してJWK公開鍵を取得を再試行する。
-        let _ = self.maybe_refresh_tenant_jwks_cache(tenant_id).await;
+        match self.unknown_kid_refresh_timeout {
+            Some(timeout) => {
+                self.refresh_for_unknown_kid_within(tenant_id, timeout)
+                    .await?
+            }
+            None => {
+                let _ = self
+                    .maybe_refresh_tenant_jwks_cache(tenant_id, false, true)
+                    .await;
+            }
+        }
 
         // JWK公開鍵の取得を再試行
         self.find_decoding_key(tenant_id, key_id)
             .await
+            .map(|(key, _)| key)
             .ok_or_else(|| {
                 EntraIdError::DecodingKeyNotFound(format!(
                     "DecodingKey not found for tenant_id: {}, key_id: {}",
@@ -600,7 +2286,59 @@ impl EntraIdTokenVerifier {
             })
     }
 
-    /// 指定したテナントIDのJWK公開鍵を取得して、既存のキャッシュに追加する。
+    /// 未知のkidを契機として、テナントのJWK公開鍵キャッシュのリフレッシュを制限時間内で待機する。
+    ///
+    /// # Arguments
+    ///
+    /// * `tenant_id` - テナントID
+    /// * `timeout` - リフレッシュの完了を待機する制限時間
+    ///
+    /// # Returns
+    ///
+    /// * 制限時間内にリフレッシュが完了した場合、またはリフレッシュしなかった場合は`Ok(())`
+    ///
+    /// # Notes
+    ///
+    /// リクエストの処理を打ち切ってもリフレッシュが中断されないように、リフレッシュする権限を得たリクエストのみが
+    /// 別のタスクでリフレッシュを開始する。したがって、リフレッシュするタスクはテナントごとに最大1つとなる。
+    /// 他のリクエストは、テナントの`Notify`で、リフレッシュの完了を制限時間まで待機するだけで、タスクを起動しない。
+    async fn refresh_for_unknown_kid_within(
+        self: &Arc<Self>,
+        tenant_id: &TenantId,
+        timeout: Duration,
+    ) -> EntraIdResult<()> {
+        let (notified, waiter_guard) = match self.acquire_refresh(tenant_id, false, true).await {
+            RefreshAcquisition::Skipped(_) => return Ok(()),
+            RefreshAcquisition::Wait(notified, guard) => (notified, Some(guard)),
+            RefreshAcquisition::Granted(notified, permit) => {
+                let verifier = Arc::clone(self);
+                let refreshing_tenant_id = tenant_id.clone();
+                spawn_named_task("jwks-unknown-kid-refresh", async move {
+                    let _ = verifier
+                        .refresh_with_permission(&refreshing_tenant_id, permit)
+                        .await;
+                });
+                (notified, None)
+            }
+        };
+        if tokio::time::timeout(timeout, notified).await.is_err() {
+            tracing::warn!(
+                tenant = %self.tenant_label(tenant_id),
+                timeout_ms = %timeout.as_millis(),
+                "JWKs refresh triggered by unknown kid did not complete in time, continuing in background"
+            );
+            return Err(EntraIdError::JwksRefreshTimedOut(
+                tenant_id.clone(),
+                timeout,
+            ));
+        }
+        if waiter_guard.is_some() {
+            self.record_refresh_wait(tenant_id);
+        }
+        Ok(())
+    }
+
+    /// TTLを超過したJWK公開鍵を使用したテナントのJWK公開鍵を、バックグラウンドでリフレッシュする。
     ///
     /// # Arguments
     ///
@@ -608,14 +2346,58 @@ impl EntraIdTokenVerifier {
     ///
     /// # Notes
     ///
+    /// リフレッシュ中のテナントや、最後にリフレッシュを試行してから最小リフレッシュ間隔を経過していないテナントは、
+    /// リフレッシュしない。これにより、Entra IDの障害中にリクエストごとにリフレッシュを試行しないようにする。
+    async fn revalidate_stale_jwks_in_background(self: &Arc<Self>, tenant_id: &TenantId) {
+        {
+            let states = self.cache.refresh_states.lock().await;
+            let is_due = states.get(tenant_id).is_some_and(|state| {
+                !state.refreshing
+                    && state.last_attempted_at.is_none_or(|last_attempted_at| {
+                        self.cache.clock.now().duration_since(last_attempted_at)
+                            >= self.refresh_tenant_jwks_interval
+                    })
+            });
+            if !is_due {
+                return;
+            }
+        }
+        tracing::info!(
+            tenant = %self.tenant_label(tenant_id),
+            "Using stale JWK within grace period, revalidating JWKs in background"
+        );
+        let verifier = Arc::clone(self);
+        let tenant_id = tenant_id.clone();
+        spawn_named_task("jwks-stale-revalidation", async move {
+            if let Err(e) = verifier
+                .maybe_refresh_tenant_jwks_cache(&tenant_id, false, false)
+                .await
+            {
+                tracing::warn!(
+                    tenant = %verifier.tenant_label(&tenant_id),
+                    error = %e,
+                    "Error revalidating stale JWKs for tenant"
+                );
+            }
+        });
+    }
+
+    /// 指定したテナントIDのJWK公開鍵を取得して、既存のキャッシュに追加する。
+    ///
+    /// # Arguments
+    ///
+    /// * `permit` - `acquire_refresh`メソッドで得たリフレッシュする権限
+    ///
+    /// # Notes
+    ///
     /// このメソッドは、新たにテナントのJWK公開鍵を取得し、既存のキャッシュに同じ`kid`を持つJWK公開鍵が存在する場合は、
     /// `last_seen_at`を更新し、存在しない場合はキャッシュに追加する。
     ///
-    /// したがって、既存のキャッシュに古いJWK公開鍵があっても、それらは削除されない。
-    ///
-    /// 古いJWK公開鍵の削除は、バックグラウンドで実行することを想定した`refresh_jwks_cache_periodically`メソッド
-    /// から呼び出される`cleanup_expired_jwks_cache`メソッドで行われる。
-    async fn refresh_tenant_jwks_cache(&self, tenant_id: &TenantId) -> EntraIdResult<()> {
+    /// 取得したJWK公開鍵セットに含まれなくなったJWK公開鍵は、ローテーションまたは失効したものとして、TTLや猶予期間に
+    /// かかわらず直ちに削除する。
+    /// ただし、取得したJWK公開鍵セットに受け入れるJWK公開鍵が1つもない場合は、検証できなくなることを避けるため削除しない。
+    async fn refresh_tenant_jwks_cache(&self, permit: &RefreshPermit) -> EntraIdResult<()> {
+        let tenant_id = &permit.tenant_id;
         // テナント情報を取得
         let tenant = self
             .registry
@@ -623,15 +2405,48 @@ impl EntraIdTokenVerifier {
             .ok_or_else(|| EntraIdError::TenantNotFound(tenant_id.clone()))?;
 
         // テナントのJWK公開鍵をフェッチ
-        let fetched = self.provider.fetch_jwks(&tenant.uri).await?;
+        let fetched = self.provider.fetch_tenant_jwks(tenant).await?;
 
         // 取得したJWK公開鍵が、既存のキャッシュに存在するかを確認し、存在する場合は`last_seen_at`を更新し、
         // 存在しない場合はキャッシュに追加
-        let now = Instant::now();
+        let now = self.cache.clock.now();
         let mut cache = self.cache.entries.write().await;
+        // 制限時間を超過して回復されたリフレッシュが、新しいリフレッシュで更新したキャッシュを上書きしないようにする
+        let is_current = self
+            .cache
+            .refresh_states
+            .lock()
+            .await
+            .get(tenant_id)
+            .is_some_and(|state| permit.is_current(state));
+        if !is_current {
+            return Err(EntraIdError::JwksRefreshTimedOut(
+                tenant_id.clone(),
+                self.refresh_wait_timeout,
+            ));
+        }
         match cache.get_mut(tenant_id) {
             Some(cached_jwk_map) => {
-                for key in fetched.keys {
+                let accepted = tenant.accepted_keys(fetched.keys);
+                if accepted.is_empty() {
+                    tracing::warn!(
+                        tenant = %self.tenant_label(tenant_id),
+                        "Fetched JWKs contain no accepted keys, retaining cached keys"
+                    );
+                } else {
+                    cached_jwk_map.retain(|kid, _| {
+                        let listed = accepted.iter().any(|key| key.kid == kid.0);
+                        if !listed {
+                            tracing::info!(
+                                tenant = %self.tenant_label(tenant_id),
+                                kid = %kid,
+                                "Evict JWK no longer listed by JWKs endpoint"
+                            );
+                        }
+                        listed
+                    });
+                }
+                for key in accepted {
                     cached_jwk_map
                         .entry(Kid(key.kid.clone()))
                         .and_modify(|managed| {
@@ -645,7 +2460,7 @@ impl EntraIdTokenVerifier {
             }
             None => {
                 tracing::warn!(
-                    tenant_id = %tenant_id,
+                    tenant = %self.tenant_label(tenant_id),
                     "Tenant JWKs cache entry not found when refreshing"
                 );
             }
@@ -659,11 +2474,13 @@ impl EntraIdTokenVerifier {
     /// # Arguments
     ///
     /// * `tenant_id` - テナントID
+    /// * `bypass_cooldown` - 最小リフレッシュ間隔を無視してリフレッシュするかどうか
     ///
     /// # Notes
     ///
     /// 指定したテナントのJWK公開鍵が、最後にリフレッシュされてから`refresh_tenant_jwks_interval`を超えていなければ、
     /// リフレッシュ頻度が多くなることを避けるため、リフレッシュしない。
+    /// ただし、`bypass_cooldown`が`true`の場合は、最小リフレッシュ間隔を確認しない。
     ///
     /// 現在のスレッドが、指定したテナントのJWK公開鍵をリフレッシュ中であることを確認した場合、他のスレッドがリフレッシュを
     /// 完了するまで待機する。
@@ -672,494 +2489,2909 @@ impl EntraIdTokenVerifier {
     /// ことで、現在のスレッドが他のスレッドを待機させた後、リフレッシュする。
     /// JWK公開鍵のリフレッシュが終了したとき、成功または失敗をにかかわらず、リフレッシュフラグを解除し、他のスレッドに通知して
     /// 待機を解除する。
+    ///
+    /// `unknown_kid`が`true`の場合は、`unknown_kid_refresh_limits`に従って、リフレッシュの完了を待機するリクエストの数と
+    /// 1分間にリフレッシュする回数を制限する。制限を超えた場合は、リフレッシュもその待機もせずに直ちに返す。
     async fn maybe_refresh_tenant_jwks_cache(
         &self,
         tenant_id: &TenantId,
+        bypass_cooldown: bool,
+        unknown_kid: bool,
     ) -> EntraIdResult<JwksCacheRefreshResult> {
+        match self
+            .acquire_refresh(tenant_id, bypass_cooldown, unknown_kid)
+            .await
+        {
+            RefreshAcquisition::Skipped(result) => Ok(result),
+            RefreshAcquisition::Wait(notified, _guard) => {
+                // リフレッシュを担当するタスクがパニックした場合などに待機し続けないように、制限時間を設ける
+                if tokio::time::timeout(self.refresh_wait_timeout, notified)
+                    .await
+                    .is_err()
+                {
+                    self.record_refresh_event(tenant_id, "wait_timeout");
+                    self.recover_stuck_refresh(tenant_id).await;
+                    return Err(EntraIdError::JwksRefreshTimedOut(
+                        tenant_id.clone(),
+                        self.refresh_wait_timeout,
+                    ));
+                }
+                Ok(self.record_refresh_wait(tenant_id))
+            }
+            RefreshAcquisition::Granted(_, permit) => {
+                self.refresh_with_permission(tenant_id, permit).await
+            }
+        }
+    }
+
+    /// テナントのJWK公開鍵キャッシュのリフレッシュ状態を確認して、リフレッシュ、待機またはスキップのいずれかに決める。
+    ///
+    /// # Arguments
+    ///
+    /// * `tenant_id` - テナントID
+    /// * `bypass_cooldown` - 最小リフレッシュ間隔を無視してリフレッシュするかどうか
+    /// * `unknown_kid` - 未知のkidを契機としたリフレッシュかどうか
+    ///
+    /// # Returns
+    ///
+    /// * リフレッシュ、待機またはスキップのいずれか
+    ///
+    /// # Notes
+    ///
+    /// 待機またはリフレッシュに決めた場合は、ロックを解放した後にリフレッシュが完了しても通知を受け取れるように、
+    /// ロックを保持している間に`Notified`を作成して返す。
+    /// リフレッシュに決めた場合、呼び出し元は返された権限を`refresh_with_permission`メソッドに渡してリフレッシュしなければならない。
+    async fn acquire_refresh(
+        &self,
+        tenant_id: &TenantId,
+        bypass_cooldown: bool,
+        unknown_kid: bool,
+    ) -> RefreshAcquisition {
+        let limits = if unknown_kid {
+            self.unknown_kid_refresh_limits
+        } else {
+            UnknownKidRefreshLimits::default()
+        };
         // テナントのJWK公開鍵キャッシュのリフレッシュ状態を確認
-        let result = {
-            let now = Instant::now();
-            let mut states = self.cache.refresh_states.lock().await;
-            let state = states
-                .entry(tenant_id.clone())
-                .or_insert(JwksCacheRefreshState::default());
-            if let Some(last_refreshed_at) = state.last_refreshed_at
-                && now.duration_since(last_refreshed_at) < self.refresh_tenant_jwks_interval
+        let now = self.cache.clock.now();
+        let mut states = self.cache.refresh_states.lock().await;
+        let state = states
+            .entry(tenant_id.clone())
+            .or_insert(JwksCacheRefreshState::default());
+        let result = if !bypass_cooldown
+            && let Some(last_refreshed_at) = state.last_refreshed_at
+            && now.duration_since(last_refreshed_at) < self.refresh_tenant_jwks_interval
+        {
+            // 最後にリフレッシュしてから、最小リフレッシュ間隔を超えていなければリフレッシュしない
+            tracing::info!(tenant = %self.tenant_label(tenant_id), "Skip JWK refresh due to cool down");
+            JwksCacheRefreshResult::RecentlyRefreshed
+        } else if state.refreshing {
+            if limits
+                .max_waiters
+                .is_some_and(|max| max <= state.waiters.load(Ordering::Relaxed))
+            {
+                // 待機しているリクエストが上限に達している場合は、待機しない
+                JwksCacheRefreshResult::Throttled
+            } else {
+                // 現在、他のスレッドがリフレッシュしている場合は、そのリフレッシュの完了を待機
+                //
+                // 待機中にリクエストがキャンセルされても待機数が減るように、ガードで待機数を管理する。
+                state.waiters.fetch_add(1, Ordering::Relaxed);
+                let guard = RefreshWaiterGuard(Arc::clone(&state.waiters));
+                return RefreshAcquisition::Wait(state.notify.clone().notified_owned(), guard);
+            }
+        } else {
+            // 直近1分間のリフレッシュ回数が上限に達している場合は、リフレッシュしない
+            while state
+                .unknown_kid_refreshes
+                .front()
+                .is_some_and(|started_at| {
+                    UNKNOWN_KID_REFRESH_WINDOW <= now.duration_since(*started_at)
+                })
+            {
+                state.unknown_kid_refreshes.pop_front();
+            }
+            if limits
+                .max_refreshes_per_minute
+                .is_some_and(|max| max as usize <= state.unknown_kid_refreshes.len())
             {
-                // 最後にリフレッシュしてから、最小リフレッシュ間隔を超えていなければリフレッシュしない
-                tracing::info!( tenant_id = %tenant_id, "Skip JWK refresh due to cool down");
-                JwksCacheRefreshResult::RecentlyRefreshed
-            } else if state.refreshing {
-                // 現在、他のスレッドがリフレッシュしている場合、明示的にロックを解放して、他のスレッドがリフレシュするまで待機
-                let notify = state.notify.clone();
-                drop(states);
-                notify.notified().await;
-                JwksCacheRefreshResult::WaitedForRefresh
+                JwksCacheRefreshResult::Throttled
             } else {
                 // リフレッシュしていない場合は、このスレッドがリフレッシュを担当
+                if unknown_kid {
+                    state.unknown_kid_refreshes.push_back(now);
+                }
                 state.refreshing = true;
-                JwksCacheRefreshResult::GrantedRefreshPermission
+                state.generation = state.generation.wrapping_add(1);
+                state.last_attempted_at = Some(now);
+                let permit = RefreshPermit {
+                    states: Arc::clone(&self.cache.refresh_states),
+                    tenant_id: tenant_id.clone(),
+                    generation: state.generation,
+                    released: false,
+                };
+                return RefreshAcquisition::Granted(state.notify.clone().notified_owned(), permit);
             }
         };
-        // このスレッドがリフレッシュしない場合は、結果を返して終了
-        if result != JwksCacheRefreshResult::GrantedRefreshPermission {
-            return Ok(result);
+        drop(states);
+        // このスレッドがリフレッシュも待機もしない場合は、結果を記録して返す
+        match result {
+            JwksCacheRefreshResult::RecentlyRefreshed => {
+                self.cache
+                    .counters
+                    .cooldown_skips
+                    .fetch_add(1, Ordering::Relaxed);
+                self.record_refresh_event(tenant_id, "cooldown_skip");
+            }
+            _ => {
+                self.cache
+                    .counters
+                    .throttles
+                    .fetch_add(1, Ordering::Relaxed);
+                self.record_refresh_event(tenant_id, "throttle");
+                tracing::warn!(
+                    tenant = %self.tenant_label(tenant_id),
+                    "Skip JWK refresh triggered by unknown kid due to refresh limits"
+                );
+            }
         }
+        RefreshAcquisition::Skipped(result)
+    }
+
+    /// 他のタスクによるリフレッシュの完了を待機したことを記録する。
+    ///
+    /// # Returns
+    ///
+    /// * `JwksCacheRefreshResult::WaitedForRefresh`
+    fn record_refresh_wait(&self, tenant_id: &TenantId) -> JwksCacheRefreshResult {
+        self.cache.counters.waits.fetch_add(1, Ordering::Relaxed);
+        self.record_refresh_event(tenant_id, "wait");
+        JwksCacheRefreshResult::WaitedForRefresh
+    }
 
+    /// `acquire_refresh`メソッドでリフレッシュする権限を得たテナントのJWK公開鍵キャッシュをリフレッシュする。
+    ///
+    /// # Arguments
+    ///
+    /// * `tenant_id` - テナントID
+    /// * `permit` - `acquire_refresh`メソッドで得たリフレッシュする権限
+    ///
+    /// # Returns
+    ///
+    /// * リフレッシュ結果
+    ///
+    /// # Notes
+    ///
+    /// 成功または失敗にかかわらず、リフレッシュフラグを解除し、待機しているタスクに通知する。
+    /// リフレッシュ中にパニックまたはキャンセルされた場合は、権限の破棄時にこのテナントのリフレッシュフラグのみを解除する。
+    ///
+    /// 制限時間を超過して回復された後に完了したリフレッシュは、新しいリフレッシュの状態を上書きしないように、
+    /// 結果を破棄して`EntraIdError::JwksRefreshTimedOut`を返す。
+    async fn refresh_with_permission(
+        &self,
+        tenant_id: &TenantId,
+        mut permit: RefreshPermit,
+    ) -> EntraIdResult<JwksCacheRefreshResult> {
         // テナントのJWK公開鍵キャッシュをリフレッシュ
         //
         // 運用中のリフレッシュはベストエフォートとし、失敗しても処理を継続する。
-        let result = self.refresh_tenant_jwks_cache(tenant_id).await;
-        let last_refreshed_at = if result.is_ok() {
-            Some(Instant::now())
-        } else {
-            None
-        };
+        let result = self.refresh_tenant_jwks_cache(&permit).await;
 
         // リフレッシュが終了したため、リフレッシュ中でない状態に変更した後、他のスレッドにリフレッシュが完了したこと通知
         let mut states = self.cache.refresh_states.lock().await;
         // このメソッドの最初の方でテナントのJWK公開鍵キャッシュのリフレッシュ状態の確認、または登録がされているため、単にアンラップ
         let state = states.get_mut(tenant_id).unwrap();
+        // 制限時間を超過して回復されたリフレッシュが、その後に開始された新しいリフレッシュの状態を上書きしないようにする
+        if !permit.is_current(state) {
+            permit.released = true;
+            drop(states);
+            tracing::warn!(
+                tenant = %self.tenant_label(tenant_id),
+                "JWKs refresh for tenant completed after being superseded by a newer refresh, discarding the result"
+            );
+            return Err(EntraIdError::JwksRefreshTimedOut(
+                tenant_id.clone(),
+                self.refresh_wait_timeout,
+            ));
+        }
         // リフレッシュ状態を解除
         state.refreshing = false;
-        // リフレッシュに成功した場合は、最後にリフレッシュした時刻を更新
-        if last_refreshed_at.is_some() {
-            state.last_refreshed_at = last_refreshed_at;
+        // リフレッシュに成功した場合は、最後にリフレッシュした時刻を更新して連続失敗回数をリセットし、
+        // 失敗した場合は、最後に失敗した時刻と連続失敗回数を更新
+        if result.is_ok() {
+            state.last_refreshed_at = Some(self.cache.clock.now());
+            state.consecutive_failures = 0;
+        } else {
+            state.last_failed_at = Some(self.cache.clock.now());
+            state.consecutive_failures = state.consecutive_failures.saturating_add(1);
         }
         // 待機しているタスクに通知して、待機状態を解除
         state.notify.notify_waiters();
+        permit.released = true;
+        drop(states);
+
+        if result.is_ok() {
+            self.cache
+                .counters
+                .refreshes
+                .fetch_add(1, Ordering::Relaxed);
+            self.record_refresh_event(tenant_id, "refresh");
+        } else {
+            self.cache
+                .counters
+                .refresh_failures
+                .fetch_add(1, Ordering::Relaxed);
+            self.record_refresh_event(tenant_id, "failure");
+        }
 
         result.map(|_| JwksCacheRefreshResult::Refreshed)
     }
 
-    /// JWK公開鍵を最後に確認した時刻が、指定された時間を超えた場合、そのJWK公開鍵をキャッシュから削除する。
+    /// リフレッシュの完了を待機する制限時間を超過したときに、リフレッシュ中のまま停止したテナントの状態を回復する。
+    ///
+    /// # Arguments
+    ///
+    /// * `tenant_id` - テナントID
     ///
     /// # Notes
     ///
-    /// TTLを超過した場合でも、テナントで`last_seen_at`が最も新しいJWK公開鍵は最低1つ残す。
-    async fn cleanup_expired_jwks_cache(&self) {
-        let mut cache = self.cache.entries.write().await;
-        let now = Instant::now();
-
-        // テナントごとにJWK公開鍵のキャッシュを走査
-        for (tenant_id, jwks) in cache.iter_mut() {
-            // 現在キャッシュしているJWK公開鍵を保持
-            let mut original = std::mem::take(jwks);
-            // 現在キャッシュしているJWK公開鍵からTTLを超えていないJWK公開鍵を取得
-            let mut retained: CachedJwkMap = original
-                .drain()
-                .filter(|(_, jwk)| now.duration_since(jwk.last_seen_at) < self.cache.ttl)
-                .collect();
-            // テナントのJWK公開鍵がすべて削除されないようにする安全策として、テナントにTTLを超えていないJWK公開鍵が存在せず、
-            // 現在のキャッシュにそのテナントのJWK公開鍵が存在する場合、last_seen_atが最も新しいJWK公開鍵を1つ残す
-            if retained.is_empty()
-                && let Some((kid, jwk)) =
-                    original.into_iter().max_by_key(|(_, jwk)| jwk.last_seen_at)
-            {
-                tracing::warn!(
-                    tenant_id = %tenant_id,
-                    kid = %kid,
-                    "All JWKs expired by TTL, retaining the most recent one as a safety measures"
-                );
-                retained.insert(kid, jwk);
-            }
-            *jwks = retained;
+    /// リフレッシュを開始してから制限時間を超過してもリフレッシュ中の場合は、リフレッシュを担当するタスクがパニックした
+    /// ものとみなして、リフレッシュフラグを解除し、待機しているタスクに通知する。
+    /// 制限時間内に他のタスクが新たにリフレッシュを開始した場合は、何もしない。
+    ///
+    /// 再試行中などでリフレッシュが遅れて完了した場合でも、リフレッシュの世代が異なるため、回復後に開始された
+    /// リフレッシュの状態やキャッシュを上書きしない。
+    async fn recover_stuck_refresh(&self, tenant_id: &TenantId) {
+        let now = self.cache.clock.now();
+        let mut states = self.cache.refresh_states.lock().await;
+        let Some(state) = states.get_mut(tenant_id) else {
+            return;
+        };
+        let is_stuck = state.refreshing
+            && state.last_attempted_at.is_none_or(|last_attempted_at| {
+                self.refresh_wait_timeout <= now.duration_since(last_attempted_at)
+            });
+        if is_stuck {
+            tracing::error!(
+                tenant = %self.tenant_label(tenant_id),
+                timeout_ms = %self.refresh_wait_timeout.as_millis(),
+                "JWKs refresh for tenant did not complete in time, clearing the refreshing flag"
+            );
+            state.refreshing = false;
+            state.notify.notify_waiters();
         }
     }
 
-    /// バックグラウンドで定期的にJWK公開鍵をリフレッシュするタスクを起動する。
-    async fn run_refresh_jwks_cache_task_in_background(
-        self: Arc<Self>,
-        shutdown: CancellationToken,
-    ) -> EntraIdResult<()> {
-        let mut interval = tokio::time::interval(self.refresh_jwks_interval);
-        tokio::spawn(async move {
-            loop {
-                tokio::select! {
-                    _ = shutdown.cancelled() => {
-                        tracing::info!("JWKs refresh task is shutting down");
-                        break;
-                    }
-                    _ = interval.tick() => {
-                        tracing::info!("Refresh all tenants JWKs cache");
-                        // すべてのテナントについて、キャッシュしているJWK公開鍵をリフレッシュ
-                        for tenant_id in self.registry.keys() {
-                            // テナントのJWK公開鍵をリフレッシュ
-                            //
-                            // テナントのJWK公開鍵のリフレッシュに失敗しても無視して、次のテナントのJWK公開鍵のリフレッシュに進む。
-                            if let Err(e) = self.maybe_refresh_tenant_jwks_cache(tenant_id).await {
-                                tracing::warn!(tenant_id = %tenant_id, error = %e, "Error refreshing JWKs for tenant");
-                            }
-                        }
-                        // TTLを超えたJWK公開鍵をキャッシュから削除
-                        tracing::info!("Cleanup expired JWKs cache");
-                        self.cleanup_expired_jwks_cache().await;
-                    }
-                }
-            }
-        });
-
-        Ok(())
+    /// テナントのJWK公開鍵キャッシュのリフレッシュで発生した事象を、メトリクスに記録する。
+    ///
+    /// # Arguments
+    ///
+    /// * `tenant_id` - テナントID
+    /// * `event` - 事象（`refresh`、`failure`、`cooldown_skip`、`wait`、`wait_timeout`、`throttle`）
+    ///
+    /// # Notes
+    ///
+    /// `cooldown_skip`や`wait`の急増は、鍵のローテーションや、偽造したkidを持つトークンによる探索の兆候となる。
+    fn record_refresh_event(&self, tenant_id: &TenantId, event: &'static str) {
+        metrics::counter!(
+            crate::metrics::JWKS_REFRESH_EVENTS_TOTAL,
+            "tenant" => self.tenant_label(tenant_id),
+            "event" => event,
+        )
+        .increment(1);
     }
 
-    /// JWTを検証する。
+    /// 最小リフレッシュ間隔を無視して、指定したテナントのJWK公開鍵を強制的にリフレッシュする。
     ///
     /// # Arguments
     ///
-    /// * `token` - 検証するJWT
-    /// * `jwks` - Entra IDから取得したJWK公開鍵セット
+    /// * `tenant_id` - テナントID
     ///
     /// # Returns
     ///
-    /// * 検証に成功した場合は検証に成功したJWTから取得したクレーム
-    pub async fn verify_token(&self, token: &BearerToken) -> EntraIdResult<Claims> {
-        // JWTヘッダーをデコード
-        //
-        // このデコード結果はアルゴリズムとkidを取得するためだけに使用する。
-        // JWTは、このメソッドの後の方で検証するため、検証が成功するまで他の用途で使用してはならない。
-        let header =
-            decode_header(token.0.expose_secret()).map_err(EntraIdError::TokenHeaderDecodeError)?;
-
-        // アルゴリズムを検証
-        //
-        // Entra IDはRS256以外のRSA署名アルゴリズムをサポートしていない。
-        if header.alg != Algorithm::RS256 {
-            return Err(EntraIdError::UnsupportedTokenAlgorithm(header.alg));
+    /// * リフレッシュ結果、またはエラー
+    ///
+    /// # Notes
+    ///
+    /// 鍵のローテーションによって検証に失敗している場合など、障害対応で使用することを想定している。
+    /// 他のスレッドがリフレッシュ中の場合は、そのリフレッシュの完了を待機する。
+    pub async fn force_refresh_tenant_jwks(
+        &self,
+        tenant_id: &TenantId,
+    ) -> EntraIdResult<JwksCacheRefreshResult> {
+        match self.registry.get(tenant_id) {
+            None => return Err(EntraIdError::TenantNotFound(tenant_id.clone())),
+            Some(tenant) if !tenant.enabled => {
+                return Err(EntraIdError::TenantDisabled(tenant_id.clone()));
+            }
+            Some(_) => {}
         }
-        // kidを取得できるか確認
-        let kid = header.kid.ok_or_else(|| {
-            EntraIdError::TokenHeaderMissingKid("JWT header missing 'kid'".into())
-        })?;
-
-        // JWTペイロードをデコードしてiss、audおよびtidを取得
-        let unverified_claims = extract_payload(token)?;
+        self.maybe_refresh_tenant_jwks_cache(tenant_id, true, false)
+            .await
+    }
 
-        // JWTのペイロード部分をデコードして発行者を特定
-        let issuer = specify_issuer(&unverified_claims)?;
-        let tenant_id = if let IssuerTenant::Tenant(tenant_id) = issuer {
-            tenant_id
-        } else {
-            return Err(EntraIdError::DisallowedIssuerTenant(issuer));
-        };
+    /// テナントごとの健全性を返す。
+    ///
+    /// # Returns
+    ///
+    /// * テナントの表示名の昇順に並べたテナントの健全性
+    ///
+    /// # Notes
+    ///
+    /// 無効なテナントはJWK公開鍵をリフレッシュしないため、常に劣化していないものとして扱う。
+    pub async fn tenant_health(&self) -> Vec<TenantHealth> {
+        let now = self.cache.clock.now();
+        let states = self.cache.refresh_states.lock().await;
+        let mut health: Vec<TenantHealth> = self
+            .registry
+            .iter()
+            .map(|(tenant_id, tenant)| {
+                let state = states.get(tenant_id);
+                let consecutive_failures = state.map_or(0, |state| state.consecutive_failures);
+                TenantHealth {
+                    tenant: tenant.label().to_string(),
+                    enabled: tenant.enabled,
+                    degraded: tenant.enabled && 0 < consecutive_failures,
+                    consecutive_failures,
+                    last_success_age_secs: state
+                        .and_then(|state| state.last_refreshed_at)
+                        .map(|at| now.duration_since(at).as_secs()),
+                    last_failure_age_secs: state
+                        .and_then(|state| state.last_failed_at)
+                        .map(|at| now.duration_since(at).as_secs()),
+                }
+            })
+            .collect();
+        health.sort_by(|a, b| a.tenant.cmp(&b.tenant));
+        health
+    }
 
-        // テナントレジストリからテナントを取得
-        let tenant = self
+    /// JWK公開鍵キャッシュの統計情報を返す。
+    ///
+    /// # Returns
+    ///
+    /// * JWK公開鍵キャッシュの統計情報
+    pub async fn cache_stats(&self) -> JwksCacheStats {
+        let counters = &self.cache.counters;
+        let entries = self.cache.entries.read().await;
+        let mut tenants: Vec<TenantJwksCacheStats> = self
             .registry
-            .get(&tenant_id)
-            .ok_or_else(|| EntraIdError::TenantNotFound(tenant_id.clone()))?;
+            .keys()
+            .map(|tenant_id| TenantJwksCacheStats {
+                tenant_id: tenant_id.0.clone(),
+                key_count: entries.get(tenant_id).map_or(0, |jwks| jwks.len()),
+            })
+            .collect();
+        tenants.sort_by(|a, b| a.tenant_id.cmp(&b.tenant_id));
+        JwksCacheStats {
+            hits: counters.hits.load(Ordering::Relaxed),
+            stale_hits: counters.stale_hits.load(Ordering::Relaxed),
+            misses: counters.misses.load(Ordering::Relaxed),
+            refreshes: counters.refreshes.load(Ordering::Relaxed),
+            refresh_failures: counters.refresh_failures.load(Ordering::Relaxed),
+            waits: counters.waits.load(Ordering::Relaxed),
+            cooldown_skips: counters.cooldown_skips.load(Ordering::Relaxed),
+            throttles: counters.throttles.load(Ordering::Relaxed),
+            tenants,
+        }
+    }
 
-        // JWK公開鍵セットからkidに対応するJWK公開鍵を取得
-        let kid = Kid(kid);
-        let decoding_key = self.get_decoding_key(&tenant_id, &kid).await?;
+    /// テナントごとのJWK公開鍵キャッシュのスナップショットを返す。
+    ///
+    /// # Returns
+    ///
+    /// * テナントIDの昇順に並べたJWK公開鍵キャッシュのスナップショット
+    pub async fn cache_snapshot(&self) -> Vec<TenantJwksCacheSnapshot> {
+        let now = self.cache.clock.now();
+        let entries = self.cache.entries.read().await;
+        let states = self.cache.refresh_states.lock().await;
+        let mut snapshots: Vec<TenantJwksCacheSnapshot> = self
+            .registry
+            .iter()
+            .map(|(tenant_id, tenant)| {
+                let mut keys: Vec<CachedJwkSnapshot> = entries
+                    .get(tenant_id)
+                    .map(|jwks| {
+                        jwks.iter()
+                            .map(|(kid, cached)| CachedJwkSnapshot {
+                                kid: kid.0.clone(),
+                                last_seen_age_secs: now
+                                    .duration_since(cached.last_seen_at)
+                                    .as_secs(),
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                keys.sort_by(|a, b| a.kid.cmp(&b.kid));
+                let state = states.get(tenant_id);
+                TenantJwksCacheSnapshot {
+                    tenant_id: tenant_id.0.clone(),
+                    enabled: tenant.enabled,
+                    keys,
+                    last_refreshed_age_secs: state
+                        .and_then(|state| state.last_refreshed_at)
+                        .map(|at| now.duration_since(at).as_secs()),
+                    refreshing: state.is_some_and(|state| state.refreshing),
+                }
+            })
+            .collect();
+        snapshots.sort_by(|a, b| a.tenant_id.cmp(&b.tenant_id));
+        snapshots
+    }
 
-        // 検証パラメーターを設定
-        let mut validation = Validation::new(Algorithm::RS256);
-        validation.set_audience(&[&tenant.audience]);
-        validation.set_issuer(&[&tenant.issuer]);
+    /// JWK公開鍵を最後に確認した時刻が、指定された時間を超えた場合、そのJWK公開鍵をキャッシュから削除する。
+    ///
+    /// # Notes
+    ///
+    /// リフレッシュに失敗し続けているテナントでは、TTLを超過したJWK公開鍵を猶予期間（`stale_grace`）を経過するまでは
+    /// 削除しない。リフレッシュに成功しているテナントには猶予期間を適用しない。
+    /// 猶予期間を経過した場合でも、テナントで`last_seen_at`が最も新しいJWK公開鍵は最低1つ残す。
+    async fn cleanup_expired_jwks_cache(&self) {
+        // リフレッシュに失敗し続けているテナント
+        let failing_tenants: HashSet<TenantId> = self
+            .cache
+            .refresh_states
+            .lock()
+            .await
+            .iter()
+            .filter(|(_, state)| 0 < state.consecutive_failures)
+            .map(|(tenant_id, _)| tenant_id.clone())
+            .collect();
+        let mut cache = self.cache.entries.write().await;
+        let now = self.cache.clock.now();
 
-        // デコードと検証
-        let token_data = decode::<Claims>(token.0.expose_secret(), &decoding_key, &validation)
-            .map_err(EntraIdError::VerifyTokenError)?;
-        Ok(token_data.claims)
+        // テナントごとにJWK公開鍵のキャッシュを走査
+        for (tenant_id, jwks) in cache.iter_mut() {
+            // 現在キャッシュしているJWK公開鍵を、TTL（と猶予期間）を超えていないJWK公開鍵と超えたJWK公開鍵に分ける
+            let expires_after = if failing_tenants.contains(tenant_id) {
+                self.cache.ttl + self.cache.stale_grace
+            } else {
+                self.cache.ttl
+            };
+            let (mut retained, expired): (CachedJwkMap, CachedJwkMap) = std::mem::take(jwks)
+                .into_iter()
+                .partition(|(_, jwk)| now.duration_since(jwk.last_seen_at) < expires_after);
+            // テナントのJWK公開鍵がすべて削除されないようにする安全策として、テナントに猶予期間を超えていないJWK公開鍵が存在せず、
+            // 現在のキャッシュにそのテナントのJWK公開鍵が存在する場合、last_seen_atが最も新しいJWK公開鍵を1つ残す
+            if retained.is_empty()
+                && let Some((kid, jwk)) =
+                    expired.into_iter().max_by_key(|(_, jwk)| jwk.last_seen_at)
+            {
+                tracing::warn!(
+                    tenant = %self.tenant_label(tenant_id),
+                    kid = %kid,
+                    "All JWKs expired by TTL and grace period, retaining the most recent one as a safety measures"
+                );
+                retained.insert(kid, jwk);
+            }
+            *jwks = retained;
+        }
     }
-}
 
-/// Entra IDトークン検証者ビルダー
-#[derive(Default)]
-pub struct EntraIdTokenVerifierBuilder {
-    tenants: Option<Vec<Tenant>>,
-    jwk_cache_ttl: Option<Duration>,
-    refresh_jwks_interval: Option<Duration>,
-    refresh_tenant_jwks_interval: Option<Duration>,
-    entra_id_connection_timeout: Option<Duration>,
-    entra_id_timeout: Option<Duration>,
-    retry_config: Option<RetryConfig>,
-    shutdown: Option<CancellationToken>,
-}
+    /// バックグラウンドで定期的にJWK公開鍵をリフレッシュするタスクを起動する。
+    ///
+    /// # Notes
+    ///
+    /// すべてのレプリカが起動時から同じ間隔でリフレッシュすると、Entra IDへのリクエストが同時に集中する。
+    /// これを避けるため、最初のリフレッシュはリフレッシュ間隔内のランダムな時点まで遅らせる。
+    /// なお、起動時にすべてのテナントのJWK公開鍵を取得しているため、最初のリフレッシュを遅らせても問題ない。
+    ///
+    /// リフレッシュするタスクがパニックした場合、JWK公開鍵がリフレッシュされないままにならないように、
+    /// パニックをログとメトリクスに記録した後、指数バックオフで待機してからタスクを再起動する。
+    async fn run_refresh_jwks_cache_task_in_background(
+        self: Arc<Self>,
+        shutdown: CancellationToken,
+    ) -> EntraIdResult<JoinHandle<()>> {
+        let startup_offset = self
+            .refresh_jwks_interval
+            .mul_f64(rand::rng().random_range(0.0..1.0));
+        tracing::info!(
+            startup_offset_secs = startup_offset.as_secs(),
+            "Scheduled the first JWKs refresh"
+        );
+        let handle = spawn_named_task("jwks-refresh-supervisor", async move {
+            let mut first_delay = startup_offset;
+            let mut restart_delay = INITIAL_BACKGROUND_TASK_RESTART_DELAY;
+            loop {
+                let started_at = Instant::now();
+                let task = spawn_named_task(
+                    "jwks-refresh",
+                    Arc::clone(&self)
+                        .refresh_jwks_cache_periodically(shutdown.clone(), first_delay),
+                );
+                let e = match task.await {
+                    Ok(()) => break,
+                    Err(e) if e.is_panic() => e,
+                    Err(e) => {
+                        tracing::error!(error = %e, "JWKs refresh task was cancelled unexpectedly");
+                        break;
+                    }
+                };
+                metrics::counter!(crate::metrics::BACKGROUND_JWKS_REFRESH_TASK_PANICS_TOTAL)
+                    .increment(1);
+                // リフレッシュ中にパニックした場合、リフレッシュしていたテナントのリフレッシュフラグは、
+                // リフレッシュする権限の破棄時に解除されるため、ここでは他のテナントのリフレッシュ状態に触れない
+                // 十分に長く動作した後のパニックであれば、再起動までの待機時間を初期値に戻す
+                if MAX_BACKGROUND_TASK_RESTART_DELAY <= started_at.elapsed() {
+                    restart_delay = INITIAL_BACKGROUND_TASK_RESTART_DELAY;
+                }
+                tracing::error!(
+                    error = %e,
+                    restart_delay_secs = restart_delay.as_secs(),
+                    "JWKs refresh task panicked, restarting"
+                );
+                tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    _ = tokio::time::sleep(restart_delay) => {}
+                }
+                restart_delay = (restart_delay * 2).min(MAX_BACKGROUND_TASK_RESTART_DELAY);
+                // パニックによってリフレッシュが遅れているため、再起動後はすぐにリフレッシュする
+                first_delay = Duration::ZERO;
+            }
+        });
 
-impl EntraIdTokenVerifierBuilder {
-    /// テナントを設定する。
+        Ok(handle)
+    }
+
+    /// 定期的にすべてのテナントのJWK公開鍵をリフレッシュする。
     ///
     /// # Arguments
     ///
-    /// * `tenants` - テナントのリスト
+    /// * `shutdown` - リフレッシュを停止するためのキャンセルトークン
+    /// * `first_delay` - 最初のリフレッシュまでの待機時間
     ///
-    /// # Returns
+    /// # Notes
     ///
-    /// * 自身のインスタンス
-    pub fn tenants(mut self, tenants: Vec<Tenant>) -> EntraIdResult<Self> {
-        if tenants.is_empty() {
-            return Err(EntraIdError::Initialize(
-                "Tenants list cannot be empty".into(),
-            ));
+    /// リフレッシュ間隔にはジッターを加え、レプリカ間でリフレッシュのタイミングが揃わないようにする。
+    /// リフレッシュに連続して失敗したテナントは、指数バックオフで一定のサイクル数リフレッシュ対象から外す。
+    async fn refresh_jwks_cache_periodically(
+        self: Arc<Self>,
+        shutdown: CancellationToken,
+        first_delay: Duration,
+    ) {
+        let mut delay = first_delay;
+        let mut backoffs: HashMap<TenantId, BackgroundRefreshBackoff> = HashMap::new();
+        loop {
+            tokio::select! {
+                _ = shutdown.cancelled() => {
+                    tracing::info!("JWKs refresh task is shutting down");
+                    break;
+                }
+                _ = tokio::time::sleep(delay) => {
+                    delay = jittered_interval(self.refresh_jwks_interval);
+                    tracing::info!("Refresh all tenants JWKs cache");
+                    // すべてのテナントについて、キャッシュしているJWK公開鍵をリフレッシュ
+                    for (tenant_id, tenant) in self.registry.iter().filter(|(_, tenant)| tenant.enabled) {
+                        let backoff = backoffs.entry(tenant_id.clone()).or_default();
+                        if backoff.should_skip() {
+                            tracing::info!(
+                                tenant = %tenant.label(),
+                                consecutive_failures = backoff.consecutive_failures,
+                                remaining_skip_cycles = backoff.remaining_skip_cycles,
+                                "Skip JWKs refresh for tenant due to backoff"
+                            );
+                            continue;
+                        }
+                        // テナントのJWK公開鍵をリフレッシュ
+                        //
+                        // テナントのJWK公開鍵のリフレッシュに失敗しても無視して、次のテナントのJWK公開鍵のリフレッシュに進む。
+                        match self.maybe_refresh_tenant_jwks_cache(tenant_id, false, false).await {
+                            Ok(_) => {
+                                if backoff.consecutive_failures > 0 {
+                                    tracing::info!(
+                                        tenant = %tenant.label(),
+                                        consecutive_failures = backoff.consecutive_failures,
+                                        "JWKs refresh for tenant recovered"
+                                    );
+                                }
+                                *backoff = BackgroundRefreshBackoff::default();
+                            }
+                            Err(e) => {
+                                let failures = backoff.record_failure();
+                                metrics::counter!(
+                                    crate::metrics::BACKGROUND_JWKS_REFRESH_FAILURES_TOTAL,
+                                    "tenant" => tenant.label().to_string(),
+                                )
+                                .increment(1);
+                                if failures >= BACKGROUND_JWKS_REFRESH_FAILURE_ESCALATION_THRESHOLD {
+                                    tracing::error!(
+                                        tenant = %tenant.label(),
+                                        consecutive_failures = failures,
+                                        skip_cycles = backoff.remaining_skip_cycles,
+                                        error = %e,
+                                        "Repeatedly failed to refresh JWKs for tenant"
+                                    );
+                                } else {
+                                    tracing::warn!(
+                                        tenant = %tenant.label(),
+                                        consecutive_failures = failures,
+                                        skip_cycles = backoff.remaining_skip_cycles,
+                                        error = %e,
+                                        "Error refreshing JWKs for tenant"
+                                    );
+                                }
+                            }
+                        }
+                        metrics::gauge!(
+                            crate::metrics::BACKGROUND_JWKS_REFRESH_CONSECUTIVE_FAILURES,
+                            "tenant" => tenant.label().to_string(),
+                        )
+                        .set(backoff.consecutive_failures as f64);
+                    }
+                    // TTLを超えたJWK公開鍵をキャッシュから削除
+                    tracing::info!("Cleanup expired JWKs cache");
+                    self.cleanup_expired_jwks_cache().await;
+                }
+            }
         }
-        self.tenants = Some(tenants);
-        Ok(self)
     }
 
-    /// JWK公開鍵キャッシュのTTLを設定する。
-    ///
-    /// # Arguments
-    ///
-    /// * `ttl` - JWK公開鍵キャッシュのTTL
+    /// バックグラウンドタスクを停止して、終了するまで待機する。
     ///
-    /// # Returns
+    /// # Notes
     ///
-    /// * 自身のインスタンス
-    pub fn jwk_cache_ttl(mut self, ttl: Duration) -> EntraIdResult<Self> {
-        if ttl.is_zero() {
-            return Err(EntraIdError::Initialize(
-                "JWK cache TTL must be greater than zero".into(),
-            ));
+    /// 複数回呼び出した場合、2回目以降はタスクの終了を待機しない。
+    pub async fn shutdown(&self) {
+        self.shutdown.cancel();
+        let Some(handle) = self.background_task.lock().await.take() else {
+            return;
+        };
+        match handle.await {
+            Ok(()) => tracing::info!("JWKs refresh task has been stopped"),
+            Err(e) => tracing::error!(error = %e, "JWKs refresh task terminated abnormally"),
         }
-        self.jwk_cache_ttl = Some(ttl);
-        Ok(self)
     }
 
-    /// 定期的にバックグラウンドですべてのテナントのJWK公開鍵をリフレッシュする間隔を設定する。
+    /// ログやメトリクスのラベルに使用するテナントの名前を返す。
     ///
     /// # Arguments
     ///
-    /// * `interval` - JWK公開鍵リフレッシュ間隔
+    /// * `tenant_id` - テナントID
     ///
     /// # Returns
     ///
-    /// * 自身のインスタンス
-    pub fn refresh_jwks_interval(mut self, interval: Duration) -> EntraIdResult<Self> {
-        if interval.is_zero() {
-            return Err(EntraIdError::Initialize(
-                "Refresh JWKs interval must be greater than zero".into(),
-            ));
-        }
-        if interval < MIN_BACKGROUND_JWKS_REFRESH_INTERVAL {
-            return Err(EntraIdError::Initialize(
-                format!(
-                    "Refresh JWKs interval must be at least {:?}",
-                    MIN_BACKGROUND_JWKS_REFRESH_INTERVAL
-                )
-                .into(),
-            ));
-        }
-        self.refresh_jwks_interval = Some(interval);
-        Ok(self)
+    /// * テナントの表示名、表示名が設定されていない場合はテナントID、テナントレジストリに存在しない場合は
+    ///   `UNKNOWN_TENANT_LABEL`
+    ///
+    /// # Notes
+    ///
+    /// テナントIDは検証前のトークンの`tid`や`iss`から取得するため、登録されていないテナントIDをそのまま返すと、
+    /// 偽造したトークンでメトリクスの系列を際限なく増やせる。
+    pub fn tenant_label(&self, tenant_id: &TenantId) -> String {
+        self.registry.get(tenant_id).map_or_else(
+            || crate::metrics::UNKNOWN_TENANT_LABEL.to_string(),
+            |tenant| tenant.label().to_string(),
+        )
     }
 
-    /// テナントのJWK公開鍵がリフレッシュされてから、次にリフレッシュされるまでの最小時間を設定する。
+    /// JWTを検証する。
     ///
     /// # Arguments
     ///
-    /// * `interval` - テナントJWK公開鍵リフレッシュ最小間隔
+    /// * `token` - 検証するJWT
     ///
     /// # Returns
     ///
-    /// * 自身のインスタンス
-    pub fn refresh_tenant_jwks_interval(mut self, interval: Duration) -> EntraIdResult<Self> {
-        if interval.is_zero() {
-            return Err(EntraIdError::Initialize(
-                "Refresh tenant JWKs interval must be greater than zero".into(),
-            ));
-        }
-        self.refresh_tenant_jwks_interval = Some(interval);
-        Ok(self)
+    /// * 検証に成功した場合は検証に成功したJWTから取得したクレーム
+    pub async fn verify_token(self: &Arc<Self>, token: &BearerToken) -> EntraIdResult<Claims> {
+        let started_at = Instant::now();
+        let (tenant_id, result) =
+            match identify_token_tenant(token, self.accept_organizations_issuer) {
+                Ok((tenant_id, kid, alg, iss)) => {
+                    let result = self
+                        .verify_token_for_tenant(token, &tenant_id, &kid, alg, &iss)
+                        .await;
+                    if let Ok(claims) = &result {
+                        self.audit_verified_token(claims, &kid);
+                    }
+                    (Some(tenant_id), result)
+                }
+                Err(e) => {
+                    if let EntraIdError::DisallowedIssuerTenant(issuer) = &e {
+                        metrics::counter!(
+                            crate::metrics::DISALLOWED_ISSUER_TENANT_TOTAL,
+                            "issuer" => issuer.to_string(),
+                        )
+                        .increment(1);
+                    }
+                    (None, Err(e))
+                }
+            };
+        metrics::histogram!(
+            crate::metrics::VERIFY_TOKEN_DURATION_SECONDS,
+            "tenant" => tenant_id.map_or_else(|| crate::metrics::UNKNOWN_TENANT_LABEL.to_string(), |id| self.tenant_label(&id)),
+            "outcome" => crate::metrics::outcome_label(&result),
+        )
+        .record(started_at.elapsed().as_secs_f64());
+        result
     }
 
-    /// Entra IDのJWKsエンドポイントに接続する際のタイムアウトを設定する。
+    /// 検証に成功したトークンを、設定した割合でサンプリングして監査ログに出力する。
     ///
     /// # Arguments
     ///
-    /// * `timeout` - 接続タイムアウト
-    ///
-    /// # Returns
-    ///
-    /// * 自身のインスタンス
-    pub fn entra_id_connection_timeout(mut self, timeout: Duration) -> EntraIdResult<Self> {
-        if timeout.is_zero() {
-            return Err(EntraIdError::Initialize(
-                "Entra ID connection timeout must be greater than zero".into(),
-            ));
+    /// * `claims` - 検証に成功したトークンのクレーム
+    /// * `kid` - 検証に使用したJWK公開鍵のkid
+    fn audit_verified_token(&self, claims: &Claims, kid: &Kid) {
+        if self.success_audit_sample_rate <= 0.0
+            || !rand::rng().random_bool(self.success_audit_sample_rate)
+        {
+            return;
         }
-        self.entra_id_connection_timeout = Some(timeout);
-        Ok(self)
+        tracing::info!(
+            oid = %claims.oid,
+            tid = claims.tid.as_deref().unwrap_or_default(),
+            azp = claims.client_app_id().unwrap_or_default(),
+            kid = %kid,
+            "Token verified"
+        );
     }
 
-    /// Entra IDのJWKsエンドポイントからの応答を待つタイムアウトを設定する。
+    /// IDトークンを検証する。
     ///
     /// # Arguments
     ///
-    /// * `timeout` - 応答待機タイムアウト
+    /// * `id_token` - 検証するIDトークン
+    /// * `expected` - IDトークンの検証で照合する値
     ///
     /// # Returns
     ///
-    /// * 自身のインスタンス
-    pub fn entra_id_timeout(mut self, timeout: Duration) -> EntraIdResult<Self> {
-        if timeout.is_zero() {
-            return Err(EntraIdError::Initialize(
-                "Entra ID timeout must be greater than zero".into(),
-            ));
+    /// * 検証に成功した場合はIDトークンのクレーム
+    ///
+    /// # Notes
+    ///
+    /// 署名、有効期限および発行者はアクセストークンと同様に検証し、対象者は`expected.client_id`で検証する。
+    /// `nonce`は必須とし、`at_hash`と`c_hash`は、照合する値を指定してIDトークンにクレームが含まれている場合に検証する。
+    /// アクセストークン向けの必須のクレームとアプリケーション固有のクレームの検証は行わない。
+    pub async fn verify_id_token(
+        self: &Arc<Self>,
+        id_token: &BearerToken,
+        expected: &IdTokenExpectations<'_>,
+    ) -> EntraIdResult<IdTokenClaims> {
+        let (tenant_id, kid, alg, iss) =
+            identify_token_tenant(id_token, self.accept_organizations_issuer)?;
+        let (claims, _) = self
+            .decode_for_tenant::<IdTokenClaims>(
+                id_token,
+                &tenant_id,
+                &kid,
+                alg,
+                &iss,
+                Some(expected.client_id),
+            )
+            .await?;
+
+        // nonceを検証して、リプレイ攻撃を防ぐ
+        if claims.nonce.as_deref() != Some(expected.nonce) {
+            return Err(EntraIdError::IdTokenValidation("nonce mismatch".into()));
         }
-        self.entra_id_timeout = Some(timeout);
-        Ok(self)
+        // アクセストークンと認可コードが、IDトークンと同時に発行されたものであるかを検証
+        for (name, claim, value) in [
+            ("at_hash", &claims.at_hash, expected.access_token),
+            ("c_hash", &claims.c_hash, expected.code),
+        ] {
+            if let (Some(claim), Some(value)) = (claim, value)
+                && *claim != left_half_hash(alg, value.expose_secret())
+            {
+                return Err(EntraIdError::IdTokenValidation(format!("{name} mismatch")));
+            }
+        }
+        Ok(claims)
     }
 
-    /// Entra IDのJWKsエンドポイントへのリトライ設定を設定する。
+    /// JWTを検証して、クレームを認証コンテキストに変換する。
     ///
     /// # Arguments
     ///
-    /// * `retry_config` - リトライ設定
+    /// * `token` - 検証するJWT
     ///
     /// # Returns
     ///
-    /// * 自身のインスタンス
-    pub fn retry_config(mut self, retry_config: RetryConfig) -> Self {
-        self.retry_config = Some(retry_config);
-        self
+    /// * 検証と変換に成功した場合は認証コンテキスト
+    ///
+    /// # Notes
+    ///
+    /// クレームを変換するフックが設定されていない場合は、属性を持たない認証コンテキストを返す。
+    pub async fn authenticate(self: &Arc<Self>, token: &BearerToken) -> EntraIdResult<AuthContext> {
+        let claims = self.verify_token(token).await?;
+        match &self.claims_mapper {
+            Some(mapper) => Ok(mapper.map(claims).await?),
+            None => Ok(claims.into()),
+        }
     }
 
-    /// バックグラウンドタスクを停止するためのキャンセルトークンを設定する。
+    /// 発行者のテナントを特定したJWTを検証する。
     ///
     /// # Arguments
     ///
-    /// * `shutdown` - キャンセルトークン
+    /// * `token` - 検証するJWT
+    /// * `tenant_id` - JWTの発行者のテナントID
+    /// * `kid` - JWTのヘッダに記録されたkid
+    /// * `iss` - 検証していないJWTの`iss`
     ///
     /// # Returns
     ///
-    /// * 自身のインスタンス
-    pub fn shutdown(mut self, shutdown: CancellationToken) -> Self {
-        self.shutdown = Some(shutdown);
-        self
+    /// * 検証に成功した場合は検証に成功したJWTから取得したクレーム
+    async fn verify_token_for_tenant(
+        self: &Arc<Self>,
+        token: &BearerToken,
+        tenant_id: &TenantId,
+        kid: &Kid,
+        alg: Algorithm,
+        iss: &str,
+    ) -> EntraIdResult<Claims> {
+        let (claims, options) = self
+            .decode_for_tenant::<Claims>(token, tenant_id, kid, alg, iss, None)
+            .await?;
+
+        // 必須のクレームを検証
+        let missing = options.missing_claims(&claims);
+        if !missing.is_empty() {
+            return Err(EntraIdError::ClaimValidation(format!(
+                "Missing required claims: {}",
+                missing.join(", ")
+            )));
+        }
+
+        // アプリケーション固有のクレームを検証
+        for validator in &self.claim_validators {
+            validator(&claims).map_err(EntraIdError::ClaimValidation)?;
+        }
+        Ok(claims)
     }
 
-    /// Entra IDトークン検証者を構築する。
+    /// 発行者のテナントを特定したJWTの署名と登録済みクレームを検証して、クレームをデコードする。
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - 検証するJWT
+    /// * `tenant_id` - JWTの発行者のテナントID
+    /// * `kid` - JWTのヘッダに記録されたkid
+    /// * `alg` - JWTのヘッダに記録されたアルゴリズム
+    /// * `iss` - 検証していないJWTの`iss`
+    /// * `audience` - 対象者（`None`の場合はテナントの対象者）
     ///
     /// # Returns
     ///
-    /// * Entra IDトークン検証者、またはエラー
-    pub async fn build(self) -> EntraIdResult<Arc<EntraIdTokenVerifier>> {
-        let tenants = self
-            .tenants
-            .ok_or_else(|| EntraIdError::Initialize("Tenants list is not set".into()))?;
-        let jwk_cache_ttl = self
-            .jwk_cache_ttl
-            .ok_or_else(|| EntraIdError::Initialize("JWK cache TTL is not set".into()))?;
-        let refresh_jwks_interval = self
-            .refresh_jwks_interval
-            .ok_or_else(|| EntraIdError::Initialize("Refresh JWKs interval is not set".into()))?;
-        let refresh_tenant_jwks_interval = self.refresh_tenant_jwks_interval.ok_or_else(|| {
-            EntraIdError::Initialize("Refresh tenant JWKs interval is not set".into())
-        })?;
-        let entra_id_connection_timeout = self.entra_id_connection_timeout.ok_or_else(|| {
-            EntraIdError::Initialize("Entra ID connection timeout is not set".into())
-        })?;
-        let entra_id_timeout = self
-            .entra_id_timeout
-            .ok_or_else(|| EntraIdError::Initialize("Entra ID timeout is not set".into()))?;
-        let retry_config = self
-            .retry_config
-            .ok_or_else(|| EntraIdError::Initialize("Retry config is not set".into()))?;
-        let shutdown = self
-            .shutdown
-            .ok_or_else(|| EntraIdError::Initialize("Shutdown token is not set".into()))?;
-        EntraIdTokenVerifier::new(
-            tenants,
-            jwk_cache_ttl,
-            refresh_jwks_interval,
-            refresh_tenant_jwks_interval,
-            entra_id_connection_timeout,
-            entra_id_timeout,
-            retry_config,
-            shutdown,
-        )
-        .await
+    /// * デコードしたクレームと、検証に使用した検証オプション
+    async fn decode_for_tenant<T: DeserializeOwned>(
+        self: &Arc<Self>,
+        token: &BearerToken,
+        tenant_id: &TenantId,
+        kid: &Kid,
+        alg: Algorithm,
+        iss: &str,
+        audience: Option<&str>,
+    ) -> EntraIdResult<(T, ValidationOptions)> {
+        // テナントレジストリからテナントを取得
+        let tenant = self
+            .registry
+            .get(tenant_id)
+            .ok_or_else(|| EntraIdError::TenantNotFound(tenant_id.clone()))?;
+        if !tenant.enabled {
+            return Err(EntraIdError::TenantDisabled(tenant_id.clone()));
+        }
+        // tidで特定したテナントと、issが示すテナントが一致するかを検証
+        //
+        // 署名の検証でもissを検証するが、別のテナントの発行者を示すトークンを`tid`で任意のテナントに振り向ける
+        // テナントの混同を、JWK公開鍵キャッシュを参照する前に専用のエラーで拒否する。
+        // `organizations`が発行したトークンを受け入れる場合は、テナントの発行者の`organizations`版と照合する。
+        let issuers = if self.accept_organizations_issuer
+            && matches!(
+                extract_issuer_from_iss(iss),
+                Err(EntraIdError::DisallowedIssuerTenant(
+                    IssuerTenant::Organizations
+                ))
+            ) {
+            organizations_issuers(&tenant.issuers)
+        } else {
+            tenant.issuers.clone()
+        };
+        if !issuers.iter().any(|issuer| issuer == iss) {
+            return Err(EntraIdError::TenantIssuerMismatch(
+                tenant_id.clone(),
+                iss.to_string(),
+            ));
+        }
+        // ピン留めしていないkidの場合は、JWK公開鍵キャッシュのリフレッシュを発生させないように、キャッシュを参照する前に拒否
+        if !tenant.accepts_kid(&kid.0) {
+            return Err(EntraIdError::UnpinnedKid(tenant_id.clone(), kid.clone()));
+        }
+
+        // JWK公開鍵セットからkidに対応するJWK公開鍵を取得
+        let decoding_key = self.get_decoding_key(tenant_id, kid).await?;
+
+        // テナント固有の検証オプションを、トークン検証者全体の検証オプションで補完して検証パラメーターを設定
+        let options = tenant.validation.or(&self.validation_options);
+        let mut validation = options.to_validation(alg)?;
+        validation.set_audience(&[audience.unwrap_or(&tenant.audience)]);
+        validation.set_issuer(&issuers);
+
+        // デコードと検証
+        let token_data = decode::<T>(token.0.expose_secret(), &decoding_key, &validation)
+            .map_err(EntraIdError::VerifyTokenError)?;
+        Ok((token_data.claims, options))
     }
 }
 
-/// JWK公開鍵から復号鍵を取得する。
+/// リフレッシュ間隔にジッターを加える。
 ///
 /// # Arguments
 ///
-/// * `jwk` - JWK公開鍵
+/// * `interval` - リフレッシュ間隔
 ///
 /// # Returns
 ///
-/// * 復号鍵、またはエラー
-fn decoding_key_from_jwk(jwk: &Jwk) -> EntraIdResult<DecodingKey> {
-    DecodingKey::from_rsa_components(&jwk.n, &jwk.e)
-        .map_err(|e| EntraIdError::CreateDecodingKeyError(Kid(jwk.kid.clone()), e))
+/// * `interval * (1 ± BACKGROUND_JWKS_REFRESH_JITTER_RATIO)`の範囲のランダムな間隔
+fn jittered_interval(interval: Duration) -> Duration {
+    let factor = rand::rng().random_range(
+        (1.0 - BACKGROUND_JWKS_REFRESH_JITTER_RATIO)..=(1.0 + BACKGROUND_JWKS_REFRESH_JITTER_RATIO),
+    );
+    interval.mul_f64(factor)
 }
 
-/// JWTのペイロード部分をデコードした検証されていないクレーム
-#[derive(Deserialize)]
-struct UnverifiedClaims {
-    /// 発行者（issuer）
-    iss: String,
-    /// テナントID
-    tid: Option<String>,
+/// 名前を付けたタスクを起動する。
+///
+/// # Arguments
+///
+/// * `name` - タスクの名前
+/// * `future` - タスクで実行するフューチャー
+///
+/// # Returns
+///
+/// * 起動したタスクのハンドル
+///
+/// # Notes
+///
+/// タスクに名前を付けられるのは、`tokio-console`フィーチャーを有効にして、`--cfg tokio_unstable`でビルドした場合のみで、
+/// それ以外の場合は名前を付けずにタスクを起動する。
+fn spawn_named_task<F>(name: &str, future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    #[cfg(all(feature = "tokio-console", tokio_unstable))]
+    {
+        tokio::task::Builder::new()
+            .name(name)
+            .spawn(future)
+            .expect("Failed to spawn task")
+    }
+    #[cfg(not(all(feature = "tokio-console", tokio_unstable)))]
+    {
+        let _ = name;
+        tokio::spawn(future)
+    }
 }
 
-/// JWTのペイロード部分をデコードして検証されていないクレームを抽出する。
-fn extract_payload(token: &BearerToken) -> EntraIdResult<UnverifiedClaims> {
-    let parts: Vec<&str> = token.0.expose_secret().split('.').collect();
-    if parts.len() != JWT_PARTS_COUNT {
-        return Err(EntraIdError::InvalidTokenFormat(
-            "Invalid JWT format".into(),
-        ));
+/// IDトークンの`at_hash`や`c_hash`と照合するハッシュを計算する。
+///
+/// # Arguments
+///
+/// * `alg` - IDトークンのヘッダに記録されたアルゴリズム
+/// * `value` - アクセストークンまたは認可コード
+///
+/// # Returns
+///
+/// * アルゴリズムに対応するハッシュ関数で計算したハッシュの左半分を、Base64URLエンコードした文字列
+fn left_half_hash(alg: Algorithm, value: &str) -> String {
+    let digest = match alg {
+        Algorithm::RS384 | Algorithm::PS384 => Sha384::digest(value).to_vec(),
+        Algorithm::RS512 | Algorithm::PS512 => Sha512::digest(value).to_vec(),
+        _ => Sha256::digest(value).to_vec(),
+    };
+    URL_SAFE_NO_PAD.encode(&digest[..digest.len() / 2])
+}
+
+/// 検証していないJWTから、発行者のテナントIDとkidを特定する。
+///
+/// # Arguments
+///
+/// * `token` - JWT
+/// * `accept_organizations` - `organizations`が発行したトークンを、`tid`で特定したテナントのトークンとして受け入れるかどうか
+///
+/// # Returns
+///
+/// * 発行者のテナントID、kid、アルゴリズムと`iss`、またはエラー
+fn identify_token_tenant(
+    token: &BearerToken,
+    accept_organizations: bool,
+) -> EntraIdResult<(TenantId, Kid, Algorithm, String)> {
+    // JWTヘッダーをデコード
+    //
+    // このデコード結果はアルゴリズムとkidを取得するためだけに使用する。
+    // JWTは、この関数の呼び出し元で検証するため、検証が成功するまで他の用途で使用してはならない。
+    let header =
+        decode_header(token.0.expose_secret()).map_err(EntraIdError::TokenHeaderDecodeError)?;
+
+    // アルゴリズムを検証
+    //
+    // Entra IDのJWK公開鍵はRSA鍵であるため、RSA署名アルゴリズム以外は拒否する。
+    // テナントで許可するアルゴリズムは、テナントを特定した後に検証オプションで検証する。
+    if !RSA_ALGORITHMS.contains(&header.alg) {
+        return Err(EntraIdError::UnsupportedTokenAlgorithm(header.alg));
     }
+    // kidを取得できるか確認
+    let kid = header
+        .kid
+        .ok_or_else(|| EntraIdError::TokenHeaderMissingKid("JWT header missing 'kid'".into()))?;
 
-    let payload = parts[1];
-    let decoded = URL_SAFE_NO_PAD
-        .decode(payload)
-        .map_err(EntraIdError::TokenPayloadDecodeError)?;
+    // JWTペイロードをデコードしてiss、audおよびtidを取得
+    let unverified_claims = extract_payload(token)?;
 
-    serde_json::from_slice(&decoded).map_err(EntraIdError::TokenPayloadParseError)
+    // JWTのペイロード部分をデコードして発行者を特定
+    let issuer = specify_issuer(&unverified_claims, accept_organizations)?;
+    let tenant_id = if let IssuerTenant::Tenant(tenant_id) = issuer {
+        tenant_id
+    } else {
+        return Err(EntraIdError::DisallowedIssuerTenant(issuer));
+    };
+
+    Ok((tenant_id, Kid(kid), header.alg, unverified_claims.iss))
 }
 
-/// 発行者（issuer）に基づいてテナントを識別する列挙型
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum IssuerTenant {
-    Tenant(TenantId),
-    Organizations,
-    Common,
+/// Entra IDトークン検証者ビルダー
+#[derive(Default)]
+pub struct EntraIdTokenVerifierBuilder {
+    tenants: Option<Vec<Tenant>>,
+    jwk_cache_ttl: Option<Duration>,
+    jwk_cache_stale_grace: Option<Duration>,
+    refresh_jwks_interval: Option<Duration>,
+    min_refresh_jwks_interval: Option<Duration>,
+    refresh_tenant_jwks_interval: Option<Duration>,
+    unknown_kid_refresh_limits: UnknownKidRefreshLimits,
+    unknown_kid_refresh_timeout: Option<Duration>,
+    refresh_wait_timeout: Option<Duration>,
+    success_audit_sample_rate: f64,
+    accept_organizations_issuer: bool,
+    entra_id_connection_timeout: Option<Duration>,
+    entra_id_timeout: Option<Duration>,
+    retry_config: Option<RetryConfig>,
+    pool_config: ConnectionPoolConfig,
+    jwks_tls_spki_pins: Option<Vec<SpkiPin>>,
+    jwks_fetcher: Option<Arc<dyn JwksFetcher>>,
+    background_refresh: Option<bool>,
+    shutdown: Option<CancellationToken>,
+    startup_fetch_deadline: Option<Duration>,
+    startup_deadline_policy: StartupDeadlinePolicy,
+    claims_mapper: Option<Arc<dyn ClaimsMapper>>,
+    claim_validators: Vec<ClaimValidator>,
+    validation_options: ValidationOptions,
+    issuer_template: Option<String>,
+    clock: Option<Arc<dyn Clock>>,
 }
 
-impl std::fmt::Display for IssuerTenant {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            IssuerTenant::Tenant(tenant_id) => write!(f, "{}", tenant_id),
-            IssuerTenant::Organizations => write!(f, "organizations"),
-            IssuerTenant::Common => write!(f, "common"),
+impl EntraIdTokenVerifierBuilder {
+    /// テナントを設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `tenants` - テナントのリスト
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス
+    pub fn tenants(mut self, tenants: Vec<Tenant>) -> EntraIdResult<Self> {
+        if tenants.is_empty() {
+            return Err(EntraIdError::Initialize(
+                "Tenants list cannot be empty".into(),
+            ));
+        }
+        if let Some(tenant) = tenants
+            .iter()
+            .find(|tenant| tenant.pinned_kids.as_ref().is_some_and(Vec::is_empty))
+        {
+            return Err(EntraIdError::Initialize(
+                format!(
+                    "Pinned kids for tenant {} must not be empty",
+                    tenant.label()
+                )
+                .into(),
+            ));
         }
+        self.tenants = Some(tenants);
+        Ok(self)
     }
-}
 
-fn specify_issuer(unverified_claims: &UnverifiedClaims) -> EntraIdResult<IssuerTenant> {
-    // tidが記録されていれば、それがテナントID
-    if let Some(tid) = unverified_claims.tid.as_ref() {
-        return Ok(IssuerTenant::Tenant(TenantId(tid.clone())));
+    /// JWK公開鍵キャッシュのTTLを設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `ttl` - JWK公開鍵キャッシュのTTL
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス
+    pub fn jwk_cache_ttl(mut self, ttl: Duration) -> EntraIdResult<Self> {
+        if ttl.is_zero() {
+            return Err(EntraIdError::Initialize(
+                "JWK cache TTL must be greater than zero".into(),
+            ));
+        }
+        self.jwk_cache_ttl = Some(ttl);
+        Ok(self)
     }
-    // issからテナントIDを抽出
-    // iss: https://login.microsoftonline.com/{tenant-id}/v2.0
-    let tenant_id = extract_issuer_from_iss(&unverified_claims.iss)?;
-    Ok(IssuerTenant::Tenant(tenant_id))
-}
 
-pub fn extract_issuer_from_iss(iss: &str) -> EntraIdResult<TenantId> {
-    // issからテナントIDを抽出
-    // iss: https://login.microsoftonline.com/{tenant-id}/v2.0
-    let uri = Url::parse(iss).map_err(EntraIdError::TokenMissingIssuer)?;
+    /// TTLを超過したJWK公開鍵を、リフレッシュを試行しながら検証に使用し続ける猶予期間を設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `grace` - 猶予期間
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス
+    ///
+    /// # Notes
+    ///
+    /// 猶予期間は、テナントのJWK公開鍵のリフレッシュに失敗し続けている間にのみ適用する。リフレッシュに成功した場合、
+    /// JWKsエンドポイントが公開しなくなったJWK公開鍵は猶予期間にかかわらず直ちに削除する。
+    ///
+    /// 設定しなかった場合は猶予期間を設けず、TTLを超過したJWK公開鍵は、テナントで最も新しいJWK公開鍵を除いて
+    /// バックグラウンドのリフレッシュ時に削除される。
+    /// TTLと猶予期間を超過したJWK公開鍵は、キャッシュから削除される前でも検証に使用せず、リフレッシュを試行する。
+    pub fn jwk_cache_stale_grace(mut self, grace: Duration) -> Self {
+        self.jwk_cache_stale_grace = Some(grace);
+        self
+    }
 
-    let mut segments = uri
-        .path_segments()
-        .ok_or(EntraIdError::InvalidIssuerFormat(
-            "No path segments in iss".into(),
-        ))?;
-    let first = segments.next().ok_or(EntraIdError::InvalidIssuerFormat(
-        "Empty path in iss".into(),
-    ))?;
-    match first {
-        "common" => Err(EntraIdError::DisallowedIssuerTenant(IssuerTenant::Common)),
-        "organizations" => Err(EntraIdError::DisallowedIssuerTenant(
-            IssuerTenant::Organizations,
-        )),
-        tenant_id => Ok(TenantId(tenant_id.to_string())),
+    /// 定期的にバックグラウンドですべてのテナントのJWK公開鍵をリフレッシュする間隔を設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - JWK公開鍵リフレッシュ間隔
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス
+    ///
+    /// # Notes
+    ///
+    /// 最小間隔（`min_refresh_jwks_interval`）との比較は、`build`メソッドで行う。
+    pub fn refresh_jwks_interval(mut self, interval: Duration) -> EntraIdResult<Self> {
+        if interval.is_zero() {
+            return Err(EntraIdError::Initialize(
+                "Refresh JWKs interval must be greater than zero".into(),
+            ));
+        }
+        self.refresh_jwks_interval = Some(interval);
+        Ok(self)
+    }
+
+    /// 定期的にバックグラウンドですべてのテナントのJWK公開鍵をリフレッシュする間隔の最小値を上書きする。
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - JWK公開鍵リフレッシュ間隔の最小値
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス
+    ///
+    /// # Notes
+    ///
+    /// 設定しなかった場合は30分とする。Entra IDへの過剰なリクエストを避けるため、
+    /// 既定値より短い値はテスト環境やステージング環境でのみ指定すること。
+    pub fn min_refresh_jwks_interval(mut self, interval: Duration) -> Self {
+        self.min_refresh_jwks_interval = Some(interval);
+        self
+    }
+
+    /// 定期的にバックグラウンドでJWK公開鍵をリフレッシュするかどうかを設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - バックグラウンドでリフレッシュするかどうか（設定しなかった場合は`true`）
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス
+    ///
+    /// # Notes
+    ///
+    /// テストや短時間で終了するCLIのように、バックグラウンドタスクを起動したくない場合に`false`を指定する。
+    /// `false`の場合、`refresh_jwks_interval`は不要になり、JWK公開鍵は、kidが見つからないときとTTLを超過したときにのみ
+    /// リフレッシュする。
+    /// ただし、TTLと猶予期間を超過したJWK公開鍵をキャッシュから削除する処理もバックグラウンドタスクで行うため、
+    /// 長時間稼働するサーバーでは`false`を指定しないこと。
+    pub fn background_refresh(mut self, enabled: bool) -> Self {
+        self.background_refresh = Some(enabled);
+        self
+    }
+
+    /// テナントのJWK公開鍵がリフレッシュされてから、次にリフレッシュされるまでの最小時間を設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - テナントJWK公開鍵リフレッシュ最小間隔
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス
+    pub fn refresh_tenant_jwks_interval(mut self, interval: Duration) -> EntraIdResult<Self> {
+        if interval.is_zero() {
+            return Err(EntraIdError::Initialize(
+                "Refresh tenant JWKs interval must be greater than zero".into(),
+            ));
+        }
+        self.refresh_tenant_jwks_interval = Some(interval);
+        Ok(self)
+    }
+
+    /// キャッシュに存在しないkidを契機としたJWK公開鍵のリフレッシュの制限を設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `limits` - テナントごとの待機リクエスト数とリフレッシュ回数の制限
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス
+    ///
+    /// # Notes
+    ///
+    /// 設定しなかった場合は、最小リフレッシュ間隔によってのみリフレッシュの頻度を制限する。
+    pub fn unknown_kid_refresh_limits(
+        mut self,
+        limits: UnknownKidRefreshLimits,
+    ) -> EntraIdResult<Self> {
+        if limits.max_refreshes_per_minute == Some(0) {
+            return Err(EntraIdError::Initialize(
+                "Max unknown kid refreshes per minute must be greater than zero".into(),
+            ));
+        }
+        self.unknown_kid_refresh_limits = limits;
+        Ok(self)
+    }
+
+    /// キャッシュに存在しないkidを契機としたJWK公開鍵のリフレッシュを、リクエストの処理中に待機する制限時間を設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - リフレッシュを待機する制限時間
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス
+    ///
+    /// # Notes
+    ///
+    /// 制限時間を超過したリクエストは503エラーとし、リフレッシュはバックグラウンドで継続する。
+    /// バックグラウンドで継続するリフレッシュはテナントごとに最大1つで、他のリクエストはその完了を待機するだけとする。
+    /// 設定しなかった場合は、再試行を含めてリフレッシュが完了するまで待機する。
+    pub fn unknown_kid_refresh_timeout(mut self, timeout: Duration) -> EntraIdResult<Self> {
+        if timeout.is_zero() {
+            return Err(EntraIdError::Initialize(
+                "Unknown kid refresh timeout must be greater than zero".into(),
+            ));
+        }
+        self.unknown_kid_refresh_timeout = Some(timeout);
+        Ok(self)
+    }
+
+    /// 他のタスクによるJWK公開鍵のリフレッシュの完了を待機する制限時間を設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - リフレッシュの完了を待機する制限時間
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス
+    ///
+    /// # Notes
+    ///
+    /// 制限時間を超過した場合は待機を中止し、リフレッシュを開始してから制限時間を超過してもリフレッシュ中のテナントは、
+    /// リフレッシュを担当するタスクがパニックしたものとみなしてリフレッシュフラグを解除する。
+    /// 再試行の制限時間（`RetryConfig::max_total_duration`）を設定した場合は、それより長くなければならず、
+    /// `build`メソッドで検証する。設定しなかった場合は2分とする。
+    pub fn refresh_wait_timeout(mut self, timeout: Duration) -> EntraIdResult<Self> {
+        if timeout.is_zero() {
+            return Err(EntraIdError::Initialize(
+                "Refresh wait timeout must be greater than zero".into(),
+            ));
+        }
+        self.refresh_wait_timeout = Some(timeout);
+        Ok(self)
+    }
+
+    /// 検証に成功したトークンを監査ログに出力する割合を設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `rate` - 監査ログに出力する割合（0.0から1.0）
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス
+    ///
+    /// # Notes
+    ///
+    /// 利用状況の分析のため、`oid`、`tid`、`azp`およびkidをinfoレベルで出力する。トークンは出力しない。
+    /// 設定しなかった場合は出力しない。
+    pub fn success_audit_sample_rate(mut self, rate: f64) -> EntraIdResult<Self> {
+        if !(0.0..=1.0).contains(&rate) {
+            return Err(EntraIdError::Initialize(
+                format!("Success audit sample rate must be between 0.0 and 1.0: {rate}").into(),
+            ));
+        }
+        self.success_audit_sample_rate = rate;
+        Ok(self)
+    }
+
+    /// `organizations`エンドポイントが発行したトークンを受け入れるかどうかを設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `accept` - 受け入れるかどうか（設定しなかった場合は`false`）
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス
+    ///
+    /// # Notes
+    ///
+    /// `true`の場合、`iss`が`organizations`を示すトークンを、`tid`が登録済みのテナントを示す場合に限り、
+    /// そのテナントのトークンとして検証する。`tid`を含まないトークンと、`common`が発行したトークンは引き続き拒否する。
+    pub fn accept_organizations_issuer(mut self, accept: bool) -> Self {
+        self.accept_organizations_issuer = accept;
+        self
+    }
+
+    /// Entra IDのJWKsエンドポイントに接続する際のタイムアウトを設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - 接続タイムアウト
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス
+    pub fn entra_id_connection_timeout(mut self, timeout: Duration) -> EntraIdResult<Self> {
+        if timeout.is_zero() {
+            return Err(EntraIdError::Initialize(
+                "Entra ID connection timeout must be greater than zero".into(),
+            ));
+        }
+        self.entra_id_connection_timeout = Some(timeout);
+        Ok(self)
+    }
+
+    /// Entra IDのJWKsエンドポイントからの応答を待つタイムアウトを設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - 応答待機タイムアウト
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス
+    pub fn entra_id_timeout(mut self, timeout: Duration) -> EntraIdResult<Self> {
+        if timeout.is_zero() {
+            return Err(EntraIdError::Initialize(
+                "Entra ID timeout must be greater than zero".into(),
+            ));
+        }
+        self.entra_id_timeout = Some(timeout);
+        Ok(self)
+    }
+
+    /// Entra IDのJWKsエンドポイントへのリトライ設定を設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `retry_config` - リトライ設定
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス
+    pub fn retry_config(mut self, retry_config: RetryConfig) -> Self {
+        self.retry_config = Some(retry_config);
+        self
+    }
+
+    /// Entra IDのJWKsエンドポイントに接続するHTTPクライアントのコネクションプール設定を設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `pool_config` - コネクションプール設定
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス
+    pub fn pool_config(mut self, pool_config: ConnectionPoolConfig) -> Self {
+        self.pool_config = pool_config;
+        self
+    }
+
+    /// Entra IDのJWKsエンドポイントのサーバー証明書に要求する公開鍵のピンを設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `pins` - 許可する公開鍵のピン（いずれかが証明書チェーンの公開鍵と一致すれば接続を許可する）
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス、またはピンが空の場合はエラー
+    ///
+    /// # Notes
+    ///
+    /// 証明書の更新で接続できなくなることを防ぐため、中間証明書のピンや更新後の証明書のピンなど、予備のピンも指定すること。
+    pub fn jwks_tls_spki_pins(mut self, pins: Vec<SpkiPin>) -> EntraIdResult<Self> {
+        if pins.is_empty() {
+            return Err(EntraIdError::Initialize(
+                "JWKs TLS SPKI pins must not be empty".into(),
+            ));
+        }
+        self.jwks_tls_spki_pins = Some(pins);
+        Ok(self)
+    }
+
+    /// JWK公開鍵セットを取得するフェッチャーを設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `fetcher` - JWK公開鍵セットを取得するフェッチャー
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス
+    ///
+    /// # Notes
+    ///
+    /// 設定した場合は、Entra IDへの接続に関する設定（タイムアウト、コネクションプール設定、公開鍵のピン）を使用しない。
+    pub fn jwks_fetcher(mut self, fetcher: Arc<dyn JwksFetcher>) -> Self {
+        self.jwks_fetcher = Some(fetcher);
+        self
+    }
+
+    /// JWK公開鍵キャッシュのTTLやリフレッシュの最小間隔の判定に使用する時計を設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `clock` - 時計（設定しなかった場合は`SystemClock`）
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// バックグラウンドタスクを停止するためのキャンセルトークンを設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `shutdown` - キャンセルトークン
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス
+    pub fn shutdown(mut self, shutdown: CancellationToken) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    /// 起動時にすべてのテナントのJWK公開鍵を取得する期限と、期限を超過したときの方針を設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `deadline` - 起動時にすべてのテナントのJWK公開鍵を取得する期限
+    /// * `policy` - 期限を超過したときの方針
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス
+    ///
+    /// # Notes
+    ///
+    /// 設定しなかった場合は期限を設けず、すべてのテナントのJWK公開鍵を取得するまで起動をブロックする。
+    pub fn startup_fetch_deadline(
+        mut self,
+        deadline: Duration,
+        policy: StartupDeadlinePolicy,
+    ) -> EntraIdResult<Self> {
+        if deadline.is_zero() {
+            return Err(EntraIdError::Initialize(
+                "Startup JWKs fetch deadline must be greater than zero".into(),
+            ));
+        }
+        self.startup_fetch_deadline = Some(deadline);
+        self.startup_deadline_policy = policy;
+        Ok(self)
+    }
+
+    /// 検証済みのクレームを認証コンテキストに変換するフックを設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `claims_mapper` - クレームを変換するフック
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス
+    pub fn claims_mapper(mut self, claims_mapper: Arc<dyn ClaimsMapper>) -> Self {
+        self.claims_mapper = Some(claims_mapper);
+        self
+    }
+
+    /// アプリケーション固有のクレームの検証を追加する。
+    ///
+    /// 複数回呼び出した場合は、追加した順にすべての検証を実行する。
+    ///
+    /// # Arguments
+    ///
+    /// * `validator` - クレームの検証（例: `employeeId`クレームが存在することを確認する関数）
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス
+    pub fn claim_validator(mut self, validator: ClaimValidator) -> Self {
+        self.claim_validators.push(validator);
+        self
+    }
+
+    /// テナント固有の検証オプションで指定しなかった項目に使用する検証オプションを設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `validation_options` - 検証オプション
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス
+    pub fn validation_options(mut self, validation_options: ValidationOptions) -> Self {
+        self.validation_options = validation_options;
+        self
+    }
+
+    /// 発行者を省略したテナントに使用する発行者テンプレートを設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `template` - `{tenantid}`を含む発行者テンプレート（例: `https://login.microsoftonline.com/{tenantid}/v2.0`）
+    ///
+    /// # Returns
+    ///
+    /// * 自身のインスタンス、またはテンプレートに`{tenantid}`が含まれていない場合はエラー
+    pub fn issuer_template(mut self, template: String) -> EntraIdResult<Self> {
+        if !template.contains(TENANT_ID_PLACEHOLDER) {
+            return Err(EntraIdError::Initialize(
+                format!("Issuer template must contain {TENANT_ID_PLACEHOLDER}: {template}").into(),
+            ));
+        }
+        self.issuer_template = Some(template);
+        Ok(self)
+    }
+
+    /// Entra IDトークン検証者を構築する。
+    ///
+    /// # Returns
+    ///
+    /// * Entra IDトークン検証者、またはエラー
+    pub async fn build(self) -> EntraIdResult<Arc<EntraIdTokenVerifier>> {
+        let tenants = self
+            .tenants
+            .ok_or_else(|| EntraIdError::Initialize("Tenants list is not set".into()))?;
+        let jwk_cache_ttl = self
+            .jwk_cache_ttl
+            .ok_or_else(|| EntraIdError::Initialize("JWK cache TTL is not set".into()))?;
+        let background_refresh = self.background_refresh.unwrap_or(true);
+        let refresh_jwks_interval = match self.refresh_jwks_interval {
+            Some(interval) => interval,
+            // バックグラウンドでリフレッシュしない場合、リフレッシュ間隔は使用しない
+            None if !background_refresh => DEFAULT_MIN_BACKGROUND_JWKS_REFRESH_INTERVAL,
+            None => {
+                return Err(EntraIdError::Initialize(
+                    "Refresh JWKs interval is not set".into(),
+                ));
+            }
+        };
+        let min_refresh_jwks_interval = self
+            .min_refresh_jwks_interval
+            .unwrap_or(DEFAULT_MIN_BACKGROUND_JWKS_REFRESH_INTERVAL);
+        if background_refresh && refresh_jwks_interval < min_refresh_jwks_interval {
+            return Err(EntraIdError::Initialize(
+                format!(
+                    "Refresh JWKs interval must be at least {:?}",
+                    min_refresh_jwks_interval
+                )
+                .into(),
+            ));
+        }
+        if min_refresh_jwks_interval < DEFAULT_MIN_BACKGROUND_JWKS_REFRESH_INTERVAL {
+            tracing::warn!(
+                min_refresh_jwks_interval = ?min_refresh_jwks_interval,
+                default = ?DEFAULT_MIN_BACKGROUND_JWKS_REFRESH_INTERVAL,
+                "Minimum refresh JWKs interval is overridden below the default; do not use this in production"
+            );
+        }
+        let refresh_tenant_jwks_interval = self.refresh_tenant_jwks_interval.ok_or_else(|| {
+            EntraIdError::Initialize("Refresh tenant JWKs interval is not set".into())
+        })?;
+        let jwks_fetcher = match self.jwks_fetcher {
+            Some(fetcher) => fetcher,
+            None => {
+                let entra_id_connection_timeout =
+                    self.entra_id_connection_timeout.ok_or_else(|| {
+                        EntraIdError::Initialize("Entra ID connection timeout is not set".into())
+                    })?;
+                let entra_id_timeout = self.entra_id_timeout.ok_or_else(|| {
+                    EntraIdError::Initialize("Entra ID timeout is not set".into())
+                })?;
+                let jwks_tls_config = self
+                    .jwks_tls_spki_pins
+                    .map(pinned_tls_config)
+                    .transpose()
+                    .map_err(|e| EntraIdError::JwksProviderInitError(e.to_string()))?;
+                Arc::new(ReqwestJwksFetcher::new(
+                    entra_id_connection_timeout,
+                    entra_id_timeout,
+                    &self.pool_config,
+                    jwks_tls_config,
+                )?)
+            }
+        };
+        let retry_config = self
+            .retry_config
+            .ok_or_else(|| EntraIdError::Initialize("Retry config is not set".into()))?;
+        let shutdown = self
+            .shutdown
+            .ok_or_else(|| EntraIdError::Initialize("Shutdown token is not set".into()))?;
+        // 再試行中のリフレッシュを、リフレッシュ中のまま停止したものとみなして回復しないようにする
+        let refresh_wait_timeout = self
+            .refresh_wait_timeout
+            .unwrap_or(DEFAULT_REFRESH_WAIT_TIMEOUT);
+        if let Some(max_total_duration) = retry_config.max_total_duration
+            && refresh_wait_timeout <= max_total_duration
+        {
+            return Err(EntraIdError::Initialize(
+                format!(
+                    "Refresh wait timeout ({:?}) must be longer than the retry max total duration ({:?})",
+                    refresh_wait_timeout, max_total_duration
+                )
+                .into(),
+            ));
+        }
+        EntraIdTokenVerifier::new(
+            tenants,
+            jwk_cache_ttl,
+            self.jwk_cache_stale_grace.unwrap_or_default(),
+            refresh_jwks_interval,
+            background_refresh,
+            refresh_tenant_jwks_interval,
+            self.unknown_kid_refresh_limits,
+            self.unknown_kid_refresh_timeout,
+            refresh_wait_timeout,
+            self.success_audit_sample_rate,
+            self.accept_organizations_issuer,
+            jwks_fetcher,
+            retry_config,
+            shutdown,
+            self.startup_fetch_deadline,
+            self.startup_deadline_policy,
+            self.claims_mapper,
+            self.claim_validators,
+            self.validation_options,
+            self.issuer_template,
+            self.clock.unwrap_or_else(|| Arc::new(SystemClock)),
+        )
+        .await
+    }
+}
+
+/// JWK公開鍵から復号鍵を取得する。
+///
+/// # Arguments
+///
+/// * `jwk` - JWK公開鍵
+///
+/// # Returns
+///
+/// * 復号鍵、またはエラー
+fn decoding_key_from_jwk(jwk: &Jwk) -> EntraIdResult<DecodingKey> {
+    DecodingKey::from_rsa_components(&jwk.n, &jwk.e)
+        .map_err(|e| EntraIdError::CreateDecodingKeyError(Kid(jwk.kid.clone()), e))
+}
+
+/// JWTのペイロード部分をデコードした検証されていないクレーム
+#[derive(Deserialize)]
+pub(crate) struct UnverifiedClaims {
+    /// 発行者（issuer）
+    iss: String,
+    /// テナントID
+    tid: Option<String>,
+}
+
+/// JWTのペイロード部分をデコードして検証されていないクレームを抽出する。
+pub(crate) fn extract_payload(token: &BearerToken) -> EntraIdResult<UnverifiedClaims> {
+    let parts: Vec<&str> = token.0.expose_secret().split('.').collect();
+    if parts.len() != JWT_PARTS_COUNT {
+        return Err(EntraIdError::InvalidTokenFormat(
+            "Invalid JWT format".into(),
+        ));
+    }
+
+    let payload = parts[1];
+    let decoded = URL_SAFE_NO_PAD
+        .decode(payload)
+        .map_err(EntraIdError::TokenPayloadDecodeError)?;
+
+    serde_json::from_slice(&decoded).map_err(EntraIdError::TokenPayloadParseError)
+}
+
+/// 発行者（issuer）に基づいてテナントを識別する列挙型
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IssuerTenant {
+    Tenant(TenantId),
+    Organizations,
+    Common,
+}
+
+impl std::fmt::Display for IssuerTenant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IssuerTenant::Tenant(tenant_id) => write!(f, "{}", tenant_id),
+            IssuerTenant::Organizations => write!(f, "organizations"),
+            IssuerTenant::Common => write!(f, "common"),
+        }
+    }
+}
+
+fn specify_issuer(
+    unverified_claims: &UnverifiedClaims,
+    accept_organizations: bool,
+) -> EntraIdResult<IssuerTenant> {
+    // issからテナントIDを抽出
+    // iss: https://login.microsoftonline.com/{tenant-id}/v2.0
+    let iss_tenant = extract_issuer_from_iss(&unverified_claims.iss);
+    // 共通エンドポイントが発行したトークンは、tidが記録されていても拒否
+    //
+    // ただし、`organizations`を受け入れる場合は、tidで特定したテナントのトークンとして扱う。
+    if let Err(EntraIdError::DisallowedIssuerTenant(issuer)) = iss_tenant {
+        return match (issuer, unverified_claims.tid.as_ref()) {
+            (IssuerTenant::Organizations, Some(tid)) if accept_organizations => {
+                Ok(IssuerTenant::Tenant(tid_tenant_id(tid)?))
+            }
+            (issuer, _) => Err(EntraIdError::DisallowedIssuerTenant(issuer)),
+        };
+    }
+    // tidが記録されていれば、それがテナントID
+    if let Some(tid) = unverified_claims.tid.as_ref() {
+        return Ok(IssuerTenant::Tenant(tid_tenant_id(tid)?));
+    }
+    Ok(IssuerTenant::Tenant(iss_tenant?))
+}
+
+/// テナントの発行者のテナントIDを`organizations`に置き換えた発行者を返す。
+///
+/// # Arguments
+///
+/// * `issuers` - テナントの発行者
+///
+/// # Returns
+///
+/// * `https://login.microsoftonline.com/{tenant-id}/v2.0`を`https://login.microsoftonline.com/organizations/v2.0`に
+///   置き換えた発行者（URLとして解析できない発行者は除く）
+fn organizations_issuers(issuers: &[String]) -> Vec<String> {
+    issuers
+        .iter()
+        .filter_map(|issuer| {
+            let mut uri = Url::parse(issuer).ok()?;
+            let rest = uri
+                .path_segments()?
+                .skip(1)
+                .map(str::to_string)
+                .collect::<Vec<_>>();
+            uri.path_segments_mut()
+                .ok()?
+                .clear()
+                .push("organizations")
+                .extend(rest);
+            Some(uri.to_string())
+        })
+        .collect()
+}
+
+pub fn extract_issuer_from_iss(iss: &str) -> EntraIdResult<TenantId> {
+    // issからテナントIDを抽出
+    // iss: https://login.microsoftonline.com/{tenant-id}/v2.0
+    let uri = Url::parse(iss).map_err(EntraIdError::TokenMissingIssuer)?;
+
+    let mut segments = uri
+        .path_segments()
+        .ok_or(EntraIdError::InvalidIssuerFormat(
+            "No path segments in iss".into(),
+        ))?;
+    let first = segments.next().ok_or(EntraIdError::InvalidIssuerFormat(
+        "Empty path in iss".into(),
+    ))?;
+    match first {
+        "common" => Err(EntraIdError::DisallowedIssuerTenant(IssuerTenant::Common)),
+        "organizations" => Err(EntraIdError::DisallowedIssuerTenant(
+            IssuerTenant::Organizations,
+        )),
+        tenant_id => TenantId::try_from(tenant_id.to_string())
+            .map_err(|e| EntraIdError::InvalidIssuerFormat(format!("Invalid tenant in iss: {e}"))),
+    }
+}
+
+/// `tid`クレームからテナントIDを作成する。
+///
+/// # Arguments
+///
+/// * `tid` - `tid`クレームの値
+///
+/// # Returns
+///
+/// * 正規化したテナントID、またはテナントIDの形式でない場合はエラー
+fn tid_tenant_id(tid: &str) -> EntraIdResult<TenantId> {
+    TenantId::try_from(tid.to_string())
+        .map_err(|e| EntraIdError::InvalidTokenFormat(format!("Invalid 'tid' claim: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bearer_token_display_is_redacted() {
+        let token = BearerToken(SecretString::from(
+            "eyJhbGciOiJSUzI1NiJ9.eyJzdWIiOiJ1c2VyIn0.c2ln",
+        ));
+
+        assert_eq!(token.to_string(), crate::redaction::REDACTED);
+    }
+
+    #[test]
+    fn tenant_id_accepts_only_guid_or_domain_name() {
+        let parse = |value: &str| TenantId::try_from(value.to_string());
+
+        assert!(parse("72f988bf-86f1-41af-91ab-2d7cd011db47").is_ok());
+        assert!(parse("contoso.onmicrosoft.com").is_ok());
+        assert!(parse("72f988bf-86f1-41af-91ab-2d7cd011db4").is_err());
+        assert!(parse("72f988bf-86f1-41af-91ab-2d7cd011db4g").is_err());
+        assert!(parse("72f988bf86f141af91ab2d7cd011db47").is_err());
+        assert!(parse("contoso").is_err());
+        assert!(parse("contoso.123").is_err());
+        assert!(parse("").is_err());
+    }
+
+    #[test]
+    fn issuer_tenant_from_tid_is_normalized_and_validated() {
+        let issuer = |tid: &str| {
+            specify_issuer(
+                &UnverifiedClaims {
+                    iss: "https://login.microsoftonline.com/72f988bf-86f1-41af-91ab-2d7cd011db47/v2.0"
+                        .into(),
+                    tid: Some(tid.into()),
+                },
+                false,
+            )
+        };
+
+        assert_eq!(
+            issuer("72F988BF-86F1-41AF-91AB-2D7CD011DB47").unwrap(),
+            IssuerTenant::Tenant(TenantId("72f988bf-86f1-41af-91ab-2d7cd011db47".into()))
+        );
+        assert!(matches!(
+            issuer("not a tenant"),
+            Err(EntraIdError::InvalidTokenFormat(_))
+        ));
+    }
+
+    #[test]
+    fn tenant_id_guid_is_normalized_to_lowercase() {
+        let tenant_id =
+            TenantId::try_from("72F988BF-86F1-41AF-91AB-2D7CD011DB47".to_string()).unwrap();
+
+        assert_eq!(
+            tenant_id,
+            TenantId("72f988bf-86f1-41af-91ab-2d7cd011db47".into())
+        );
+    }
+
+    #[test]
+    fn tenant_domain_name_id_is_resolved_only_when_allowed() {
+        let tenant = |allow: bool, issuers: serde_json::Value| -> Tenant {
+            serde_json::from_value(serde_json::json!({
+                "id": "Contoso.onmicrosoft.com",
+                "uri": "https://login.microsoftonline.com/contoso.onmicrosoft.com/discovery/v2.0/keys",
+                "issuers": issuers,
+                "audience": "api://backend",
+                "allow_domain_name_id": allow,
+            }))
+            .unwrap()
+        };
+        let guid_issuers = serde_json::json!([
+            "https://login.microsoftonline.com/72f988bf-86f1-41af-91ab-2d7cd011db47/v2.0",
+            "https://sts.windows.net/72F988BF-86F1-41AF-91AB-2D7CD011DB47/",
+        ]);
+
+        // 許可していない場合は、GUIDを使用するように促す
+        let err = tenant(false, guid_issuers.clone())
+            .resolve_domain_name_id()
+            .unwrap_err();
+        assert!(err.contains("use the tenant GUID"), "{err}");
+
+        // 許可した場合は、発行者に含まれるGUIDに解決し、ドメイン名を表示名にする
+        let mut resolved = tenant(true, guid_issuers);
+        resolved.resolve_domain_name_id().unwrap();
+        assert_eq!(
+            resolved.id,
+            TenantId("72f988bf-86f1-41af-91ab-2d7cd011db47".into())
+        );
+        assert_eq!(resolved.label(), "contoso.onmicrosoft.com");
+
+        // 発行者から1つのGUIDを特定できない場合は拒否する
+        for issuers in [
+            serde_json::json!(["https://login.microsoftonline.com/contoso.onmicrosoft.com/v2.0"]),
+            serde_json::json!([
+                "https://login.microsoftonline.com/72f988bf-86f1-41af-91ab-2d7cd011db47/v2.0",
+                "https://login.microsoftonline.com/11111111-1111-1111-1111-111111111111/v2.0",
+            ]),
+        ] {
+            assert!(tenant(true, issuers).resolve_domain_name_id().is_err());
+        }
+    }
+
+    #[test]
+    fn scp_claim_is_parsed_into_case_insensitive_scope_set() {
+        let claims: Claims = serde_json::from_value(serde_json::json!({
+            "aud": "api://backend",
+            "iss": "https://login.microsoftonline.com/tenant/v2.0",
+            "exp": 0,
+            "oid": "user-oid",
+            "sub": "user-sub",
+            "scp": "User.Read  Files.ReadWrite",
+        }))
+        .unwrap();
+
+        let scopes = claims.scopes();
+        assert_eq!(scopes.as_set().len(), 2);
+        assert!(scopes.contains("user.read"));
+        assert!(scopes.contains("Files.ReadWrite"));
+        assert!(!scopes.contains("Files.Read"));
+        assert!(!claims.extra.contains_key("scp"));
+        assert_eq!(
+            serde_json::to_value(&claims).unwrap()["scp"],
+            "files.readwrite user.read"
+        );
+    }
+
+    #[test]
+    fn recorded_jwks_response_can_be_parsed() {
+        let jwks: JwksResponse = serde_json::from_str(crate::fixtures::JWKS).unwrap();
+
+        assert_eq!(jwks.keys.len(), 2);
+        for jwk in &jwks.keys {
+            assert_eq!(jwk.kty, "RSA");
+            assert_eq!(jwk.use_.as_deref(), Some("sig"));
+            DecodingKey::from_rsa_components(&jwk.n, &jwk.e).unwrap();
+        }
+    }
+
+    #[test]
+    fn payload_parse_error_does_not_contain_claim_values() {
+        // issに文字列以外の値を含むペイロード
+        //
+        // `serde_json::Error`のメッセージには`invalid type: integer `987654321``のように値が含まれる。
+        let payload = URL_SAFE_NO_PAD.encode(r#"{"iss":987654321}"#);
+        let token = BearerToken(SecretString::from(format!(
+            "eyJhbGciOiJSUzI1NiJ9.{payload}.c2ln"
+        )));
+
+        let err = extract_payload(&token).err().unwrap();
+
+        assert!(matches!(err, EntraIdError::TokenPayloadParseError(_)));
+        assert!(!err.to_string().contains("987654321"));
+    }
+
+    #[test]
+    fn background_refresh_backoff_grows_exponentially_and_is_capped() {
+        let mut backoff = BackgroundRefreshBackoff::default();
+        let mut skipped = Vec::new();
+        for _ in 0..6 {
+            backoff.record_failure();
+            let mut cycles = 0;
+            while backoff.should_skip() {
+                cycles += 1;
+            }
+            skipped.push(cycles);
+        }
+
+        assert_eq!(skipped, vec![0, 1, 3, 7, 7, 7]);
+    }
+
+    #[test]
+    fn tenant_accepts_only_pinned_kids_when_pinned() {
+        let tenant = |pinned_kids: serde_json::Value| -> Tenant {
+            serde_json::from_value(serde_json::json!({
+                "id": "11111111-1111-1111-1111-111111111111",
+                "uri": "https://login.microsoftonline.com/11111111-1111-1111-1111-111111111111/discovery/v2.0/keys",
+                "audience": "api://backend",
+                "pinned_kids": pinned_kids,
+            }))
+            .unwrap()
+        };
+
+        let pinned = tenant(serde_json::json!(["kid-1"]));
+        assert!(pinned.accepts_kid("kid-1"));
+        assert!(!pinned.accepts_kid("kid-2"));
+
+        let unpinned = tenant(serde_json::Value::Null);
+        assert!(unpinned.accepts_kid("kid-2"));
+    }
+
+    #[test]
+    fn tenant_registry_rejects_duplicate_tenant_ids() {
+        let tenant = |name: &str| -> Tenant {
+            serde_json::from_value(serde_json::json!({
+                "id": "11111111-1111-1111-1111-111111111111",
+                "name": name,
+                "uri": "https://login.microsoftonline.com/11111111-1111-1111-1111-111111111111/discovery/v2.0/keys",
+                "audience": "api://backend",
+            }))
+            .unwrap()
+        };
+
+        let err = tenant_registry(vec![tenant("primary"), tenant("copy")])
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            err.to_string(),
+            "Duplicate tenant ID 11111111-1111-1111-1111-111111111111: tenants[0] (primary) and tenants[1] (copy)"
+        );
+    }
+
+    /// JWKsエンドポイントのパスに応じたステータスを返し、要求されたURIを記録するフェッチャー
+    struct StatusJwksFetcher {
+        requested: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl JwksFetcher for StatusJwksFetcher {
+        fn fetch<'a>(&'a self, jwks_uri: &'a Url) -> JwksFuture<'a> {
+            self.requested
+                .lock()
+                .unwrap()
+                .push(jwks_uri.path().to_string());
+            Box::pin(async move {
+                // 応答しないJWKsエンドポイント
+                if jwks_uri.path() == "/hang" {
+                    std::future::pending::<()>().await;
+                }
+                let status: u16 = jwks_uri.path().trim_start_matches('/').parse().unwrap();
+                if status == 200 {
+                    return Ok(JwksResponse { keys: vec![] });
+                }
+                let response = http::Response::builder()
+                    .status(status)
+                    .body(Vec::new())
+                    .unwrap();
+                let e = reqwest::Response::from(response)
+                    .error_for_status()
+                    .unwrap_err();
+                Err(EntraIdError::JwksFetchError(e, jwks_uri.clone()))
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn tenant_jwks_are_fetched_from_fallback_uri_only_on_retryable_error() {
+        let fetch = |uri: &str, fallback_uris: &[&str]| {
+            let tenant: Tenant = serde_json::from_value(serde_json::json!({
+                "id": "11111111-1111-1111-1111-111111111111",
+                "uri": uri,
+                "fallback_uris": fallback_uris,
+                "audience": "api://backend",
+            }))
+            .unwrap();
+            async move {
+                let fetcher = Arc::new(StatusJwksFetcher {
+                    requested: std::sync::Mutex::new(Vec::new()),
+                });
+                let retry_config = RetryConfig::new(
+                    1,
+                    Duration::from_millis(1),
+                    2.0,
+                    0.5,
+                    1.5,
+                    Duration::from_millis(1),
+                )
+                .unwrap();
+                let provider =
+                    JwksProvider::new(fetcher.clone(), retry_config, CancellationToken::new());
+                let result = provider.fetch_tenant_jwks(&tenant).await;
+                let requested = fetcher.requested.lock().unwrap().clone();
+                (result.is_ok(), requested)
+            }
+        };
+
+        assert_eq!(
+            fetch("https://a.example/503", &["https://b.example/200"]).await,
+            (true, vec!["/503".to_string(), "/200".to_string()])
+        );
+        assert_eq!(
+            fetch("https://a.example/404", &["https://b.example/200"]).await,
+            (false, vec!["/404".to_string()])
+        );
+        assert_eq!(
+            fetch("https://a.example/503", &["https://b.example/502"]).await,
+            (false, vec!["/503".to_string(), "/502".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn tenant_jwks_fetch_is_bounded_by_total_duration_and_fails_over_on_timeout() {
+        let fetch = |fallback_uri: &'static str| async move {
+            let tenant: Tenant = serde_json::from_value(serde_json::json!({
+                "id": "11111111-1111-1111-1111-111111111111",
+                "uri": "https://a.example/hang",
+                "fallback_uris": [fallback_uri],
+                "audience": "api://backend",
+            }))
+            .unwrap();
+            let fetcher = Arc::new(StatusJwksFetcher {
+                requested: std::sync::Mutex::new(Vec::new()),
+            });
+            let retry_config = RetryConfig::new(
+                3,
+                Duration::from_millis(1),
+                2.0,
+                0.5,
+                1.5,
+                Duration::from_millis(1),
+            )
+            .unwrap()
+            .max_total_duration(Duration::from_millis(200))
+            .unwrap();
+            let provider =
+                JwksProvider::new(fetcher.clone(), retry_config, CancellationToken::new());
+            let started_at = Instant::now();
+            let result = provider.fetch_tenant_jwks(&tenant).await;
+            let requested = fetcher.requested.lock().unwrap().clone();
+            (result, requested, started_at.elapsed())
+        };
+
+        // 応答しない`uri`の制限時間を超過した場合は、代替のJWKsエンドポイントに切り替える
+        let (result, requested, _) = fetch("https://b.example/200").await;
+        assert!(result.is_ok());
+        assert_eq!(requested, vec!["/hang".to_string(), "/200".to_string()]);
+
+        // すべてのJWKsエンドポイントが応答しない場合でも、合計で制限時間内に中止する
+        let (result, requested, elapsed) = fetch("https://b.example/hang").await;
+        assert!(matches!(
+            result,
+            Err(EntraIdError::JwksFetchRetryBudgetExceeded(..))
+        ));
+        assert_eq!(requested, vec!["/hang".to_string(), "/hang".to_string()]);
+        assert!(elapsed < Duration::from_millis(400), "elapsed: {elapsed:?}");
+    }
+
+    /// 呼び出しごとに、用意した結果を順に返すフェッチャー
+    struct CannedJwksFetcher {
+        results: std::sync::Mutex<Vec<EntraIdResult<Vec<&'static str>>>>,
+    }
+
+    impl JwksFetcher for CannedJwksFetcher {
+        fn fetch<'a>(&'a self, _jwks_uri: &'a Url) -> JwksFuture<'a> {
+            let result = self.results.lock().unwrap().remove(0);
+            Box::pin(async move {
+                let keys = result?
+                    .into_iter()
+                    .map(|kid| Jwk {
+                        kid: kid.to_string(),
+                        kty: "RSA".to_string(),
+                        n: "AQAB".to_string(),
+                        e: "AQAB".to_string(),
+                        alg: Some("RS256".to_string()),
+                        use_: Some("sig".to_string()),
+                    })
+                    .collect();
+                Ok(JwksResponse { keys })
+            })
+        }
+    }
+
+    /// テストから時刻を進められる時計
+    struct ManualClock {
+        now: std::sync::Mutex<Instant>,
+    }
+
+    impl ManualClock {
+        fn advance(&self, duration: Duration) {
+            *self.now.lock().unwrap() += duration;
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            *self.now.lock().unwrap()
+        }
+    }
+
+    async fn build_verifier(
+        results: Vec<EntraIdResult<Vec<&'static str>>>,
+    ) -> EntraIdResult<Arc<EntraIdTokenVerifier>> {
+        build_verifier_with_clock(results, Arc::new(SystemClock)).await
+    }
+
+    async fn build_verifier_with_clock(
+        results: Vec<EntraIdResult<Vec<&'static str>>>,
+        clock: Arc<dyn Clock>,
+    ) -> EntraIdResult<Arc<EntraIdTokenVerifier>> {
+        build_verifier_with(results, clock, Ok).await
+    }
+
+    /// 既定の設定を`configure`で上書きしてトークン検証者を構築する。
+    async fn build_verifier_with(
+        results: Vec<EntraIdResult<Vec<&'static str>>>,
+        clock: Arc<dyn Clock>,
+        configure: impl FnOnce(
+            EntraIdTokenVerifierBuilder,
+        ) -> EntraIdResult<EntraIdTokenVerifierBuilder>,
+    ) -> EntraIdResult<Arc<EntraIdTokenVerifier>> {
+        let tenant: Tenant = serde_json::from_value(serde_json::json!({
+            "id": "11111111-1111-1111-1111-111111111111",
+            "uri": "https://login.microsoftonline.com/11111111-1111-1111-1111-111111111111/discovery/v2.0/keys",
+            "issuer": "https://login.microsoftonline.com/11111111-1111-1111-1111-111111111111/v2.0",
+            "audience": "api://backend",
+        }))
+        .unwrap();
+        let fetcher = CannedJwksFetcher {
+            results: std::sync::Mutex::new(results),
+        };
+        let builder = EntraIdTokenVerifierBuilder::default()
+            .tenants(vec![tenant])?
+            .jwk_cache_ttl(Duration::from_hours(1))?
+            .background_refresh(false)
+            .refresh_tenant_jwks_interval(Duration::from_mins(5))?
+            .retry_config(RetryConfig::new(
+                3,
+                Duration::from_millis(1),
+                2.0,
+                0.5,
+                1.5,
+                Duration::from_millis(1),
+            )?)
+            .jwks_fetcher(Arc::new(fetcher))
+            .clock(clock)
+            .shutdown(CancellationToken::new());
+        configure(builder)?.build().await
+    }
+
+    #[tokio::test]
+    async fn verifier_caches_jwks_from_fetcher() {
+        let verifier = build_verifier(vec![Ok(vec!["kid-1", "kid-2"])])
+            .await
+            .ok()
+            .unwrap();
+
+        let stats = verifier.cache_stats().await;
+        assert_eq!(stats.tenants[0].key_count, 2);
+        verifier.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn tenant_label_maps_unregistered_tenant_to_unknown() {
+        let verifier = build_verifier(vec![Ok(vec!["kid-1"])]).await.ok().unwrap();
+
+        assert_eq!(
+            verifier.tenant_label(&TenantId("11111111-1111-1111-1111-111111111111".into())),
+            "11111111-1111-1111-1111-111111111111"
+        );
+        assert_eq!(
+            verifier.tenant_label(&TenantId("00000000-0000-0000-0000-000000000099".into())),
+            crate::metrics::UNKNOWN_TENANT_LABEL
+        );
+        verifier.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn verifier_rejects_token_whose_issuer_does_not_match_tid_tenant() {
+        let verifier = build_verifier(vec![Ok(vec!["kid-1"])]).await.ok().unwrap();
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256","kid":"kid-1"}"#);
+        let payload = URL_SAFE_NO_PAD.encode(
+            r#"{"iss":"https://login.microsoftonline.com/22222222-2222-2222-2222-222222222222/v2.0","tid":"11111111-1111-1111-1111-111111111111"}"#,
+        );
+        let token = BearerToken(SecretString::from(format!("{header}.{payload}.c2ln")));
+
+        let err = verifier.verify_token(&token).await.err().unwrap();
+
+        assert!(matches!(err, EntraIdError::TenantIssuerMismatch(..)));
+        verifier.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn verifier_accepts_organizations_issuer_only_when_opted_in() {
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256","kid":"kid-1"}"#);
+        let payload = URL_SAFE_NO_PAD.encode(
+            r#"{"iss":"https://login.microsoftonline.com/organizations/v2.0","tid":"11111111-1111-1111-1111-111111111111"}"#,
+        );
+        let token = BearerToken(SecretString::from(format!("{header}.{payload}.c2ln")));
+
+        let verifier = build_verifier(vec![Ok(vec!["kid-1"])]).await.ok().unwrap();
+        let err = verifier.verify_token(&token).await.err().unwrap();
+        assert!(matches!(
+            err,
+            EntraIdError::DisallowedIssuerTenant(IssuerTenant::Organizations)
+        ));
+        verifier.shutdown().await;
+
+        // 受け入れる場合は、tidで特定したテナントの発行者の`organizations`版と照合して、署名の検証に進む
+        let verifier = build_verifier_with(vec![Ok(vec!["kid-1"])], Arc::new(SystemClock), |b| {
+            Ok(b.accept_organizations_issuer(true))
+        })
+        .await
+        .ok()
+        .unwrap();
+        let err = verifier.verify_token(&token).await.err().unwrap();
+        assert!(matches!(err, EntraIdError::VerifyTokenError(..)));
+        verifier.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn verifier_fails_fast_when_fetcher_fails_without_retry() {
+        let url = Url::parse(
+            "https://login.microsoftonline.com/11111111-1111-1111-1111-111111111111/discovery/v2.0/keys",
+        )
+        .unwrap();
+
+        let err = build_verifier(vec![Err(EntraIdError::JwksFetchCancelled(url))])
+            .await
+            .err()
+            .unwrap();
+
+        assert!(matches!(err, EntraIdError::JwksFetchCancelled(_)));
+    }
+
+    #[tokio::test]
+    async fn tenant_refresh_is_skipped_until_cooldown_elapses() {
+        let clock = Arc::new(ManualClock {
+            now: std::sync::Mutex::new(Instant::now()),
+        });
+        let verifier = build_verifier_with_clock(
+            vec![Ok(vec!["kid-1"]), Ok(vec!["kid-1"]), Ok(vec!["kid-1"])],
+            clock.clone(),
+        )
+        .await
+        .ok()
+        .unwrap();
+        let tenant_id = TenantId("11111111-1111-1111-1111-111111111111".to_string());
+        let refresh = || verifier.maybe_refresh_tenant_jwks_cache(&tenant_id, false, false);
+
+        assert_eq!(
+            refresh().await.ok(),
+            Some(JwksCacheRefreshResult::Refreshed)
+        );
+        clock.advance(Duration::from_mins(4));
+        assert_eq!(
+            refresh().await.ok(),
+            Some(JwksCacheRefreshResult::RecentlyRefreshed)
+        );
+        clock.advance(Duration::from_mins(1));
+        assert_eq!(
+            refresh().await.ok(),
+            Some(JwksCacheRefreshResult::Refreshed)
+        );
+        verifier.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn unknown_kid_refreshes_beyond_limits_are_throttled() {
+        let clock = Arc::new(ManualClock {
+            now: std::sync::Mutex::new(Instant::now()),
+        });
+        let verifier = build_verifier_with(
+            vec![Ok(vec!["kid-1"]), Ok(vec!["kid-1"]), Ok(vec!["kid-1"])],
+            clock.clone(),
+            |builder| {
+                builder
+                    .refresh_tenant_jwks_interval(Duration::from_secs(1))?
+                    .unknown_kid_refresh_limits(UnknownKidRefreshLimits {
+                        max_waiters: Some(1),
+                        max_refreshes_per_minute: Some(1),
+                    })
+            },
+        )
+        .await
+        .ok()
+        .unwrap();
+        let tenant_id = TenantId("11111111-1111-1111-1111-111111111111".to_string());
+        let refresh =
+            |unknown_kid| verifier.maybe_refresh_tenant_jwks_cache(&tenant_id, false, unknown_kid);
+
+        // 1分間に1回までリフレッシュする
+        assert_eq!(
+            refresh(true).await.ok(),
+            Some(JwksCacheRefreshResult::Refreshed)
+        );
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(
+            refresh(true).await.ok(),
+            Some(JwksCacheRefreshResult::Throttled)
+        );
+        clock.advance(Duration::from_mins(1));
+        assert_eq!(
+            refresh(true).await.ok(),
+            Some(JwksCacheRefreshResult::Refreshed)
+        );
+
+        // 待機しているリクエストが上限に達している場合は待機しない
+        clock.advance(Duration::from_secs(2));
+        {
+            let mut states = verifier.cache.refresh_states.lock().await;
+            let state = states.get_mut(&tenant_id).unwrap();
+            state.refreshing = true;
+            state.waiters.store(1, Ordering::Relaxed);
+        }
+        assert_eq!(
+            refresh(true).await.ok(),
+            Some(JwksCacheRefreshResult::Throttled)
+        );
+        assert_eq!(verifier.cache_stats().await.throttles, 2);
+        verifier.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn refresh_evicts_keys_no_longer_listed() {
+        let verifier = build_verifier(vec![Ok(vec!["kid-1", "kid-2"]), Ok(vec!["kid-2"])])
+            .await
+            .ok()
+            .unwrap();
+        let tenant_id = TenantId("11111111-1111-1111-1111-111111111111".to_string());
+
+        verifier
+            .maybe_refresh_tenant_jwks_cache(&tenant_id, true, false)
+            .await
+            .ok()
+            .unwrap();
+
+        let cache = verifier.cache.entries.read().await;
+        let jwks = cache.get(&tenant_id).unwrap();
+        assert!(!jwks.contains_key(&Kid("kid-1".into())));
+        assert!(jwks.contains_key(&Kid("kid-2".into())));
+        drop(cache);
+        verifier.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn cleanup_applies_stale_grace_only_while_refresh_failing() {
+        let clock = Arc::new(ManualClock {
+            now: std::sync::Mutex::new(Instant::now()),
+        });
+        let verifier =
+            build_verifier_with(vec![Ok(vec!["kid-1", "kid-2"])], clock.clone(), |builder| {
+                Ok(builder.jwk_cache_stale_grace(Duration::from_hours(1)))
+            })
+            .await
+            .ok()
+            .unwrap();
+        let tenant_id = TenantId("11111111-1111-1111-1111-111111111111".to_string());
+        let key_count = || async { verifier.cache.entries.read().await[&tenant_id].len() };
+        clock.advance(Duration::from_hours(1) + Duration::from_secs(1));
+
+        // リフレッシュに失敗し続けている間は、猶予期間内のJWK公開鍵を残す
+        verifier
+            .cache
+            .refresh_states
+            .lock()
+            .await
+            .get_mut(&tenant_id)
+            .unwrap()
+            .consecutive_failures = 1;
+        verifier.cleanup_expired_jwks_cache().await;
+        assert_eq!(key_count().await, 2);
+
+        // リフレッシュに失敗していない場合は、TTLを超過したJWK公開鍵を最も新しい1つを除いて削除する
+        verifier
+            .cache
+            .refresh_states
+            .lock()
+            .await
+            .get_mut(&tenant_id)
+            .unwrap()
+            .consecutive_failures = 0;
+        verifier.cleanup_expired_jwks_cache().await;
+        assert_eq!(key_count().await, 1);
+        verifier.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn keys_beyond_stale_grace_are_not_used_without_background_refresh() {
+        let clock = Arc::new(ManualClock {
+            now: std::sync::Mutex::new(Instant::now()),
+        });
+        let url = Url::parse(
+            "https://login.microsoftonline.com/11111111-1111-1111-1111-111111111111/discovery/v2.0/keys",
+        )
+        .unwrap();
+        let verifier = build_verifier_with(
+            vec![
+                Ok(vec!["kid-1"]),
+                Err(EntraIdError::JwksFetchCancelled(url)),
+            ],
+            clock.clone(),
+            |builder| {
+                Ok(builder
+                    .background_refresh(false)
+                    .jwk_cache_stale_grace(Duration::from_hours(1)))
+            },
+        )
+        .await
+        .ok()
+        .unwrap();
+        let tenant_id = TenantId("11111111-1111-1111-1111-111111111111".to_string());
+        let kid = Kid("kid-1".into());
+
+        // TTLを超過しても、猶予期間内はTTLを超過したJWK公開鍵として使用する
+        clock.advance(Duration::from_hours(1) + Duration::from_secs(1));
+        assert!(
+            verifier
+                .find_decoding_key(&tenant_id, &kid)
+                .await
+                .is_some_and(|(_, is_stale)| is_stale)
+        );
+
+        // 猶予期間を超過したJWK公開鍵は、キャッシュに残っていても見つからないものとしてリフレッシュする
+        clock.advance(Duration::from_hours(1));
+        let err = verifier
+            .get_decoding_key(&tenant_id, &kid)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, EntraIdError::DecodingKeyNotFound(_)));
+        assert!(verifier.cache.entries.read().await[&tenant_id].contains_key(&kid));
+        let stats = verifier.cache_stats().await;
+        assert_eq!((stats.misses, stats.refresh_failures), (1, 1));
+        verifier.shutdown().await;
+    }
+
+    /// 起動時の取得を除いて、`gate`が許可するまで応答しないフェッチャー
+    struct GatedJwksFetcher {
+        calls: AtomicUsize,
+        gate: tokio::sync::Semaphore,
+    }
+
+    impl JwksFetcher for GatedJwksFetcher {
+        fn fetch<'a>(&'a self, _jwks_uri: &'a Url) -> JwksFuture<'a> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move {
+                if 0 < call {
+                    self.gate.acquire().await.unwrap().forget();
+                }
+                Ok(JwksResponse { keys: vec![] })
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn concurrent_unknown_kid_requests_start_only_one_refresh() {
+        let fetcher = Arc::new(GatedJwksFetcher {
+            calls: AtomicUsize::new(0),
+            gate: tokio::sync::Semaphore::new(0),
+        });
+        let verifier = build_verifier_with(vec![], Arc::new(SystemClock), {
+            let fetcher = fetcher.clone();
+            |builder| {
+                builder
+                    .jwks_fetcher(fetcher)
+                    .unknown_kid_refresh_timeout(Duration::from_millis(50))
+            }
+        })
+        .await
+        .ok()
+        .unwrap();
+        let tenant_id = TenantId("11111111-1111-1111-1111-111111111111".to_string());
+
+        let requests = (0..20).map(|i| {
+            let verifier = Arc::clone(&verifier);
+            let tenant_id = tenant_id.clone();
+            tokio::spawn(async move {
+                verifier
+                    .get_decoding_key(&tenant_id, &Kid(format!("forged-{i}")))
+                    .await
+            })
+        });
+        for request in requests.collect::<Vec<_>>() {
+            assert!(matches!(
+                request.await.unwrap(),
+                Err(EntraIdError::JwksRefreshTimedOut(..))
+            ));
+        }
+
+        // 起動時の取得と、未知のkidを契機とした1回のリフレッシュのみ
+        assert_eq!(fetcher.calls.load(Ordering::SeqCst), 2);
+        assert!(verifier.cache.refresh_states.lock().await[&tenant_id].refreshing);
+
+        // リフレッシュが完了すると、リフレッシュ状態を解除する
+        fetcher.gate.add_permits(1);
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while verifier.cache.refresh_states.lock().await[&tenant_id].refreshing {
+                tokio::task::yield_now().await;
+            }
+        })
+        .await
+        .unwrap();
+        verifier.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn abandoned_refresh_clears_only_its_own_refreshing_flag() {
+        let fetcher = Arc::new(GatedJwksFetcher {
+            calls: AtomicUsize::new(0),
+            gate: tokio::sync::Semaphore::new(0),
+        });
+        let verifier = build_verifier_with(vec![], Arc::new(SystemClock), {
+            let fetcher = fetcher.clone();
+            |builder| Ok(builder.jwks_fetcher(fetcher))
+        })
+        .await
+        .ok()
+        .unwrap();
+        let tenant_id = TenantId("11111111-1111-1111-1111-111111111111".to_string());
+        // 他のタスクがリフレッシュしている別のテナント
+        let other_tenant_id = TenantId("22222222-2222-2222-2222-222222222222".to_string());
+        verifier
+            .cache
+            .refresh_states
+            .lock()
+            .await
+            .entry(other_tenant_id.clone())
+            .or_default()
+            .refreshing = true;
+
+        // リフレッシュ中にキャンセルされた場合は、リフレッシュする権限の破棄時にリフレッシュフラグを解除する
+        let refresh = verifier.maybe_refresh_tenant_jwks_cache(&tenant_id, true, false);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), refresh)
+                .await
+                .is_err()
+        );
+
+        let states = verifier.cache.refresh_states.lock().await;
+        assert!(!states[&tenant_id].refreshing);
+        assert!(states[&other_tenant_id].refreshing);
+        drop(states);
+        verifier.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn stuck_refresh_is_recovered_after_wait_timeout() {
+        let clock = Arc::new(ManualClock {
+            now: std::sync::Mutex::new(Instant::now()),
+        });
+        let verifier = build_verifier_with(
+            vec![Ok(vec!["kid-1"]), Ok(vec!["kid-1"])],
+            clock.clone(),
+            |builder| builder.refresh_wait_timeout(Duration::from_millis(10)),
+        )
+        .await
+        .ok()
+        .unwrap();
+        let tenant_id = TenantId("11111111-1111-1111-1111-111111111111".to_string());
+        // リフレッシュを担当するタスクが、通知する前にパニックした状態
+        {
+            let mut states = verifier.cache.refresh_states.lock().await;
+            let state = states.get_mut(&tenant_id).unwrap();
+            state.refreshing = true;
+            state.last_attempted_at = Some(clock.now());
+        }
+        clock.advance(Duration::from_secs(1));
+
+        let err = verifier
+            .maybe_refresh_tenant_jwks_cache(&tenant_id, true, false)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, EntraIdError::JwksRefreshTimedOut(..)));
+        assert_eq!(
+            verifier
+                .maybe_refresh_tenant_jwks_cache(&tenant_id, true, false)
+                .await
+                .ok(),
+            Some(JwksCacheRefreshResult::Refreshed)
+        );
+        verifier.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn superseded_refresh_does_not_overwrite_newer_refresh() {
+        let clock = Arc::new(ManualClock {
+            now: std::sync::Mutex::new(Instant::now()),
+        });
+        let fetcher = Arc::new(GatedJwksFetcher {
+            calls: AtomicUsize::new(0),
+            gate: tokio::sync::Semaphore::new(0),
+        });
+        let verifier = build_verifier_with(vec![], clock.clone(), {
+            let fetcher = fetcher.clone();
+            |builder| {
+                builder
+                    .jwks_fetcher(fetcher)
+                    .refresh_wait_timeout(Duration::from_millis(10))
+            }
+        })
+        .await
+        .ok()
+        .unwrap();
+        let tenant_id = TenantId("11111111-1111-1111-1111-111111111111".to_string());
+        let spawn_refresh = || {
+            let verifier = Arc::clone(&verifier);
+            let tenant_id = tenant_id.clone();
+            tokio::spawn(async move {
+                verifier
+                    .maybe_refresh_tenant_jwks_cache(&tenant_id, true, false)
+                    .await
+            })
+        };
+        let wait_for_calls = |calls: usize| {
+            let fetcher = fetcher.clone();
+            async move {
+                while fetcher.calls.load(Ordering::SeqCst) < calls {
+                    tokio::task::yield_now().await;
+                }
+            }
+        };
+
+        // 再試行中のリフレッシュが制限時間を超過して回復された後に、新しいリフレッシュを開始
+        let superseded = spawn_refresh();
+        wait_for_calls(2).await;
+        clock.advance(Duration::from_secs(1));
+        verifier.recover_stuck_refresh(&tenant_id).await;
+        let newer = spawn_refresh();
+        wait_for_calls(3).await;
+
+        // 回復されたリフレッシュが遅れて完了しても、新しいリフレッシュのリフレッシュ状態を解除しない
+        fetcher.gate.add_permits(1);
+        assert!(matches!(
+            superseded.await.unwrap(),
+            Err(EntraIdError::JwksRefreshTimedOut(..))
+        ));
+        assert!(verifier.cache.refresh_states.lock().await[&tenant_id].refreshing);
+        assert_eq!(verifier.cache_stats().await.refreshes, 0);
+
+        fetcher.gate.add_permits(1);
+        assert_eq!(
+            newer.await.unwrap().ok(),
+            Some(JwksCacheRefreshResult::Refreshed)
+        );
+        assert!(!verifier.cache.refresh_states.lock().await[&tenant_id].refreshing);
+        verifier.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn refresh_wait_timeout_must_exceed_retry_max_total_duration() {
+        let retry_config = || {
+            RetryConfig::new(
+                3,
+                Duration::from_millis(1),
+                2.0,
+                0.5,
+                1.5,
+                Duration::from_millis(1),
+            )?
+            .max_total_duration(Duration::from_secs(30))
+        };
+
+        let err = build_verifier_with(vec![Ok(vec!["kid-1"])], Arc::new(SystemClock), |builder| {
+            builder
+                .retry_config(retry_config()?)
+                .refresh_wait_timeout(Duration::from_secs(30))
+        })
+        .await
+        .err()
+        .unwrap();
+        assert!(matches!(err, EntraIdError::Initialize(_)));
+
+        let verifier =
+            build_verifier_with(vec![Ok(vec!["kid-1"])], Arc::new(SystemClock), |builder| {
+                builder
+                    .retry_config(retry_config()?)
+                    .refresh_wait_timeout(Duration::from_secs(31))
+            })
+            .await
+            .ok()
+            .unwrap();
+        verifier.shutdown().await;
+    }
+
+    /// ジッターを除いた再試行の待機時間（ミリ秒）
+    fn expected_delay_millis(initial_wait: Duration, multiplier: f64, attempts: u32) -> f64 {
+        if initial_wait.is_zero() {
+            return 0.0;
+        }
+        initial_wait.as_millis() as f64 * multiplier.powf(attempts.saturating_sub(1) as f64)
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn retry_delay_is_capped_and_within_jitter_bounds(
+            initial_wait_ms in 0u64..60_000,
+            multiplier in 1.0f64..10.0,
+            jitter_min in 0.0f64..2.0,
+            jitter_range in 0.0f64..2.0,
+            max_wait_ms in 1u64..600_000,
+            attempts in proptest::prelude::any::<u32>(),
+        ) {
+            let jitter_max = jitter_min + jitter_range;
+            let initial_wait = Duration::from_millis(initial_wait_ms);
+            let max_wait = Duration::from_millis(max_wait_ms);
+            let config =
+                RetryConfig::new(3, initial_wait, multiplier, jitter_min, jitter_max, max_wait)
+                    .unwrap();
+
+            let delay = config.calculate_delay(attempts);
+
+            let expected = expected_delay_millis(initial_wait, multiplier, attempts);
+            let bound = |jitter: f64| {
+                if jitter == 0.0 {
+                    0.0
+                } else {
+                    (expected * jitter).min(max_wait_ms as f64)
+                }
+            };
+            let lower = bound(jitter_min).floor() - 1.0;
+            let upper = bound(jitter_max) + 1.0;
+            proptest::prop_assert!(delay <= max_wait);
+            proptest::prop_assert!(lower <= delay.as_millis() as f64);
+            proptest::prop_assert!(delay.as_millis() as f64 <= upper);
+        }
+
+        #[test]
+        fn retry_delay_without_jitter_does_not_decrease(
+            initial_wait_ms in 0u64..60_000,
+            multiplier in 1.0f64..10.0,
+            max_wait_ms in 1u64..600_000,
+            attempts in 0u32..u32::MAX,
+        ) {
+            let config = RetryConfig::new(
+                3,
+                Duration::from_millis(initial_wait_ms),
+                multiplier,
+                1.0,
+                1.0,
+                Duration::from_millis(max_wait_ms),
+            )
+            .unwrap();
+
+            proptest::prop_assert!(
+                config.calculate_delay(attempts) <= config.calculate_delay(attempts + 1)
+            );
+        }
+
+        #[test]
+        fn retry_delay_reaches_max_wait_at_high_attempts(
+            initial_wait_ms in 1u64..60_000,
+            multiplier in 1.5f64..10.0,
+            max_wait_ms in 1u64..600_000,
+            attempts in 10_000u32..,
+        ) {
+            let max_wait = Duration::from_millis(max_wait_ms);
+            let config = RetryConfig::new(
+                3,
+                Duration::from_millis(initial_wait_ms),
+                multiplier,
+                0.5,
+                1.5,
+                max_wait,
+            )
+            .unwrap();
+
+            proptest::prop_assert_eq!(config.calculate_delay(attempts), max_wait);
+        }
+    }
+
+    #[test]
+    fn left_half_hash_matches_oidc_at_hash() {
+        // OpenID Connect Core 1.0 Appendix A.3の例
+        let access_token = "jHkWEdUXMU1BwAsC4vtUsZwnNvTIxEl0z9K3vx5KF0Y";
+
+        assert_eq!(
+            left_half_hash(Algorithm::RS256, access_token),
+            "77QmUPtjPfzWtF2AnpK9RQ"
+        );
     }
 }
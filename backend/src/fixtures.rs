@@ -0,0 +1,146 @@
+// Entra IDとGraph APIから取得したレスポンスを記録したフィクスチャ
+// 実際のレスポンスから、テナントID、オブジェクトID、トークン、証明書などを置き換えて記録している。
+// Microsoftはレスポンスにフィールドを追加することがあるため、記録したレスポンスを構造体にデシリアライズできることをテストして、
+// 厳格すぎる構造体の定義による互換性の問題を検出する。
+// レスポンスの形式が変わった場合は、実際のレスポンスを取得し、同様に置き換えてから更新すること。
+
+/// JWKsエンドポイントのレスポンス
+pub const JWKS: &str = include_str!("../fixtures/recorded/jwks.json");
+
+/// トークンエンドポイントの成功レスポンス
+#[cfg(feature = "obo")]
+pub const TOKEN_RESPONSE: &str = include_str!("../fixtures/recorded/token_response.json");
+
+/// 期限切れのアサーションでOBOフローを実行したときのトークンエンドポイントのエラーレスポンス
+#[cfg(feature = "obo")]
+pub const TOKEN_ERROR_INVALID_GRANT: &str =
+    include_str!("../fixtures/recorded/token_error_invalid_grant.json");
+
+/// 多要素認証が必要なときのトークンエンドポイントのエラーレスポンス
+#[cfg(feature = "obo")]
+pub const TOKEN_ERROR_INTERACTION_REQUIRED: &str =
+    include_str!("../fixtures/recorded/token_error_interaction_required.json");
+
+/// 一時的な障害が発生したときのトークンエンドポイントのエラーレスポンス
+#[cfg(feature = "obo")]
+pub const TOKEN_ERROR_TEMPORARILY_UNAVAILABLE: &str =
+    include_str!("../fixtures/recorded/token_error_temporarily_unavailable.json");
+
+/// Graph APIの`/me`のレスポンス
+#[cfg(feature = "graph")]
+pub const GRAPH_ME: &str = include_str!("../fixtures/recorded/graph_me.json");
+
+/// Graph APIの`/me/manager`のレスポンス
+#[cfg(feature = "graph")]
+pub const GRAPH_MANAGER: &str = include_str!("../fixtures/recorded/graph_manager.json");
+
+/// Graph APIの`/me/checkMemberGroups`のレスポンス
+#[cfg(feature = "graph")]
+pub const GRAPH_CHECK_MEMBER_GROUPS: &str =
+    include_str!("../fixtures/recorded/graph_check_member_groups.json");
+
+#[cfg(any(feature = "tower", feature = "actix"))]
+pub use verifier::{build_verifier, sign_token};
+
+#[cfg(any(feature = "tower", feature = "actix"))]
+mod verifier {
+    use std::sync::{Arc, LazyLock};
+    use std::time::Duration;
+
+    use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+    use jsonwebtoken::{Algorithm, EncodingKey, Header};
+    use rsa::pkcs1::EncodeRsaPrivateKey as _;
+    use rsa::traits::PublicKeyParts as _;
+    use rsa::{RsaPrivateKey, rand_core::OsRng};
+    use tokio_util::sync::CancellationToken;
+    use url::Url;
+
+    use crate::entra_id::{
+        EntraIdTokenVerifier, EntraIdTokenVerifierBuilder, JwksFetcher, JwksFuture, JwksResponse,
+        RetryConfig, Tenant,
+    };
+
+    /// テストの実行時に生成した署名鍵
+    ///
+    /// 秘密鍵をリポジトリに含めないように、テストプロセスごとに生成して、すべてのテストで共有する。
+    static SIGNING_KEY: LazyLock<RsaPrivateKey> =
+        LazyLock::new(|| RsaPrivateKey::new(&mut OsRng, 2048).unwrap());
+
+    /// 署名鍵に対応するJWK公開鍵のkid
+    const SIGNING_KEY_ID: &str = "sanitized-kid-1";
+
+    const TENANT_ID: &str = "00000000-0000-0000-0000-000000000000";
+
+    /// 記録したJWK公開鍵セットの、署名鍵のkidを持つJWK公開鍵を、署名鍵の公開鍵に置き換えて返すフェッチャー
+    struct RecordedJwksFetcher;
+
+    impl JwksFetcher for RecordedJwksFetcher {
+        fn fetch<'a>(&'a self, _jwks_uri: &'a Url) -> JwksFuture<'a> {
+            Box::pin(async {
+                let mut jwks = serde_json::from_str::<JwksResponse>(super::JWKS).unwrap();
+                for key in jwks.keys.iter_mut().filter(|key| key.kid == SIGNING_KEY_ID) {
+                    key.n = URL_SAFE_NO_PAD.encode(SIGNING_KEY.n().to_bytes_be());
+                    key.e = URL_SAFE_NO_PAD.encode(SIGNING_KEY.e().to_bytes_be());
+                }
+                Ok(jwks)
+            })
+        }
+    }
+
+    /// 記録したJWK公開鍵セットで、トークンを検証するEntra IDトークン検証者を作成する。
+    pub async fn build_verifier() -> Arc<EntraIdTokenVerifier> {
+        let tenant: Tenant = serde_json::from_value(serde_json::json!({
+            "id": TENANT_ID,
+            "uri": format!("https://login.microsoftonline.com/{TENANT_ID}/discovery/v2.0/keys"),
+            "issuer": format!("https://login.microsoftonline.com/{TENANT_ID}/v2.0"),
+            "audience": "api://backend",
+        }))
+        .unwrap();
+        EntraIdTokenVerifierBuilder::default()
+            .tenants(vec![tenant])
+            .unwrap()
+            .jwk_cache_ttl(Duration::from_hours(1))
+            .unwrap()
+            .background_refresh(false)
+            .refresh_tenant_jwks_interval(Duration::from_mins(5))
+            .unwrap()
+            .retry_config(
+                RetryConfig::new(
+                    1,
+                    Duration::from_millis(1),
+                    2.0,
+                    1.0,
+                    1.0,
+                    Duration::from_millis(1),
+                )
+                .unwrap(),
+            )
+            .jwks_fetcher(Arc::new(RecordedJwksFetcher))
+            .shutdown(CancellationToken::new())
+            .build()
+            .await
+            .ok()
+            .unwrap()
+    }
+
+    /// `build_verifier`で作成したトークン検証者で検証できる、`oid`が`user-oid`のトークンを署名する。
+    pub fn sign_token() -> String {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(SIGNING_KEY_ID.into());
+        let exp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            + 3600;
+        let claims = serde_json::json!({
+            "aud": "api://backend",
+            "iss": format!("https://login.microsoftonline.com/{TENANT_ID}/v2.0"),
+            "tid": TENANT_ID,
+            "exp": exp,
+            "oid": "user-oid",
+            "sub": "user-sub",
+        });
+        let key = EncodingKey::from_rsa_der(SIGNING_KEY.to_pkcs1_der().unwrap().as_bytes());
+        jsonwebtoken::encode(&header, &claims, &key).unwrap()
+    }
+}
@@ -0,0 +1,77 @@
+use axum::http::{HeaderMap, HeaderValue, header};
+use secrecy::SecretString;
+
+use crate::bearer_auth::bearer_token;
+use crate::entra_id::{self, BearerToken};
+
+/// `extract_payload`のファジングターゲット
+///
+/// # Arguments
+///
+/// * `data` - ファザーが生成したJWT
+pub fn extract_payload(data: &[u8]) {
+    if let Ok(token) = std::str::from_utf8(data) {
+        let _ = entra_id::extract_payload(&BearerToken(SecretString::from(token)));
+    }
+}
+
+/// `extract_issuer_from_iss`のファジングターゲット
+///
+/// # Arguments
+///
+/// * `data` - ファザーが生成した`iss`クレームの値
+pub fn extract_issuer_from_iss(data: &[u8]) {
+    if let Ok(iss) = std::str::from_utf8(data) {
+        let _ = entra_id::extract_issuer_from_iss(iss);
+    }
+}
+
+/// `Authorization`ヘッダからBearerトークンを取得する処理のファジングターゲット
+///
+/// # Arguments
+///
+/// * `data` - ファザーが生成した`Authorization`ヘッダの値
+pub fn bearer_header(data: &[u8]) {
+    if let Ok(value) = HeaderValue::from_bytes(data) {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, value);
+        let _ = bearer_token(&headers);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    /// ファジングのコーパスを格納したディレクトリ
+    const CORPUS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fuzz/corpus");
+
+    /// ファジングターゲットのコーパスのすべての入力で、ターゲットを実行する。
+    fn replay_corpus(target: &str, fuzz: fn(&[u8])) {
+        let dir = Path::new(CORPUS_DIR).join(target);
+        let mut replayed = 0;
+        for entry in std::fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            fuzz(&std::fs::read(&path).unwrap());
+            replayed += 1;
+        }
+        assert!(0 < replayed, "No corpus found in {}", dir.display());
+    }
+
+    #[test]
+    fn extract_payload_corpus_does_not_panic() {
+        replay_corpus("extract_payload", extract_payload);
+    }
+
+    #[test]
+    fn extract_issuer_from_iss_corpus_does_not_panic() {
+        replay_corpus("extract_issuer_from_iss", extract_issuer_from_iss);
+    }
+
+    #[test]
+    fn bearer_header_corpus_does_not_panic() {
+        replay_corpus("bearer_header", bearer_header);
+    }
+}
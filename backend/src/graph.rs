@@ -0,0 +1,631 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use http::{StatusCode, header};
+use secrecy::SecretString;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tokio::sync::Mutex;
+use url::Url;
+
+use crate::{
+    circuit_breaker::CircuitBreaker,
+    common::RequestError,
+    entra_id::{Claims, is_retryable_status},
+    secret_buffer::bearer_authorization,
+    trace_context::TraceContext,
+};
+
+/// Graph APIのベースURI
+pub const GRAPH_API_BASE_URI: &str = "https://graph.microsoft.com/v1.0";
+
+/// 下流リソースのレジストリに登録するGraph APIの名前
+pub const GRAPH_RESOURCE: &str = "graph";
+
+/// `/me`で`$select`に指定できるプロパティ
+///
+/// `MeResponse`のフィールドに対応するプロパティのみを許可する。
+pub const ME_SELECTABLE_FIELDS: &[&str] = &[
+    "id",
+    "userPrincipalName",
+    "surname",
+    "givenName",
+    "displayName",
+    "mail",
+    "jobTitle",
+    "department",
+    "officeLocation",
+    "businessPhones",
+    "mobilePhone",
+    "preferredLanguage",
+];
+
+/// Graph API関連のエラー
+#[derive(Debug, thiserror::Error)]
+pub enum GraphError {
+    /// Graph APIへのリクエストの送信に失敗
+    #[error("Failed to call Graph API: {0}")]
+    Request(reqwest::Error),
+
+    /// Graph APIがエラーステータスを返した
+    #[error("Graph API returned error status: {0}")]
+    ErrorStatus(reqwest::StatusCode),
+
+    /// Graph APIのレスポンスのパースに失敗
+    #[error("Failed to parse Graph API response: {0}")]
+    ResponseParse(reqwest::Error),
+
+    /// サーキットブレーカーがGraph APIの呼び出しを遮断している
+    #[error("Graph API is temporarily unavailable")]
+    CircuitOpen,
+}
+
+impl GraphError {
+    /// Graph APIの障害を示すエラーかどうかを判定する。
+    ///
+    /// 送信の失敗（タイムアウトや接続エラーなど）、サーバーエラー、レートリミットエラーは障害とみなし、
+    /// サーキットブレーカーの失敗として記録する。
+    fn is_outage(&self) -> bool {
+        match self {
+            Self::Request(_) => true,
+            Self::ErrorStatus(status) => is_retryable_status(*status),
+            Self::ResponseParse(_) | Self::CircuitOpen => false,
+        }
+    }
+}
+
+impl From<GraphError> for RequestError {
+    fn from(err: GraphError) -> Self {
+        let code = match err {
+            GraphError::CircuitOpen => StatusCode::SERVICE_UNAVAILABLE,
+            _ => StatusCode::BAD_GATEWAY,
+        };
+        RequestError {
+            code,
+            message: err.to_string(),
+        }
+    }
+}
+
+/// サインインしているユーザーのプロファイル
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MeResponse {
+    pub id: String,
+    pub user_principal_name: Option<String>,
+    pub surname: Option<String>,
+    pub given_name: Option<String>,
+    pub display_name: Option<String>,
+    pub mail: Option<String>,
+    pub job_title: Option<String>,
+    pub department: Option<String>,
+    pub office_location: Option<String>,
+    pub business_phones: Option<Vec<String>>,
+    pub mobile_phone: Option<String>,
+    pub preferred_language: Option<String>,
+    /// Graph APIを呼び出せないため、トークンのクレームから作成したプロファイルであるか
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub degraded: bool,
+}
+
+impl MeResponse {
+    /// Graph APIを呼び出せない場合に返す、トークンのクレームから作成したプロファイルを返す。
+    ///
+    /// # Arguments
+    ///
+    /// * `claims` - 検証済みのクレーム
+    /// * `select` - 取得するプロパティ（`None`の場合はクレームから取得できるすべてのプロパティ）
+    ///
+    /// # Returns
+    ///
+    /// * クレームから作成した、`degraded`が`true`のプロファイル
+    ///
+    /// # Notes
+    ///
+    /// クレームに含まれないプロパティは`None`とする。
+    pub fn from_claims(claims: &Claims, select: Option<&[String]>) -> Self {
+        let claim = |field: &str, names: &[&str]| {
+            if select.is_some_and(|fields| !fields.iter().any(|f| f == field)) {
+                return None;
+            }
+            names
+                .iter()
+                .find_map(|name| claims.extra.get(*name).and_then(|v| v.as_str()))
+                .map(str::to_string)
+        };
+        Self {
+            id: claims.oid.clone(),
+            user_principal_name: claim("userPrincipalName", &["upn", "preferred_username"]),
+            surname: claim("surname", &["family_name"]),
+            given_name: claim("givenName", &["given_name"]),
+            display_name: claim("displayName", &["name"]),
+            mail: claim("mail", &["email"]),
+            job_title: None,
+            department: None,
+            office_location: None,
+            business_phones: None,
+            mobile_phone: None,
+            preferred_language: None,
+            degraded: true,
+        }
+    }
+}
+
+/// サインインしているユーザーの上司のプロファイル
+///
+/// Graph APIの`/me/manager`は`directoryObject`を返すため、ユーザー以外のオブジェクトである可能性を考慮して
+/// `id`以外のプロパティを省略可能とする。
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManagerResponse {
+    pub id: String,
+    pub user_principal_name: Option<String>,
+    pub display_name: Option<String>,
+    pub mail: Option<String>,
+    pub job_title: Option<String>,
+    pub department: Option<String>,
+    pub office_location: Option<String>,
+}
+
+/// キャッシュしたプロファイル
+struct CachedProfile {
+    /// サインインしているユーザーのプロファイル
+    profile: MeResponse,
+    /// キャッシュの有効期限
+    expires_at: Instant,
+}
+
+/// Graph APIで取得したサインインしているユーザーのプロファイルのキャッシュ
+///
+/// プロファイルはほとんど変更されないため、`/me`のリクエストごとにOBOとGraph APIを呼び出さないように、
+/// ユーザーと取得するプロパティの組み合わせごとにプロファイルをキャッシュする。
+pub struct MeProfileCache {
+    /// キャッシュの有効期間（ゼロの場合はキャッシュしない）
+    ttl: Duration,
+    /// ユーザーのオブジェクトIDと取得するプロパティをキー、プロファイルを値としたハッシュマップ
+    entries: Mutex<HashMap<(String, Option<String>), CachedProfile>>,
+}
+
+impl MeProfileCache {
+    /// コンストラクタ
+    ///
+    /// # Arguments
+    ///
+    /// * `ttl` - キャッシュの有効期間（ゼロの場合はキャッシュしない）
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// キャッシュが有効かを返す。
+    pub fn is_enabled(&self) -> bool {
+        !self.ttl.is_zero()
+    }
+
+    /// キャッシュの有効期間を返す。
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// キャッシュからプロファイルを取得する。
+    ///
+    /// # Arguments
+    ///
+    /// * `oid` - ユーザーのオブジェクトID
+    /// * `select` - 取得するプロパティ（`None`の場合はGraph APIの既定のプロパティ）
+    ///
+    /// # Returns
+    ///
+    /// * キャッシュしたプロファイルと有効期限までの残り時間、キャッシュされていない場合は`None`
+    pub async fn get(
+        &self,
+        oid: &str,
+        select: Option<&[String]>,
+    ) -> Option<(MeResponse, Duration)> {
+        if !self.is_enabled() {
+            return None;
+        }
+        let now = Instant::now();
+        let entries = self.entries.lock().await;
+        entries
+            .get(&(oid.to_string(), select_key(select)))
+            .filter(|cached| now < cached.expires_at)
+            .map(|cached| (cached.profile.clone(), cached.expires_at - now))
+    }
+
+    /// Graph APIで取得したプロファイルをキャッシュする。
+    ///
+    /// # Arguments
+    ///
+    /// * `oid` - ユーザーのオブジェクトID
+    /// * `select` - 取得したプロパティ（`None`の場合はGraph APIの既定のプロパティ）
+    /// * `profile` - サインインしているユーザーのプロファイル
+    pub async fn store(&self, oid: &str, select: Option<&[String]>, profile: &MeResponse) {
+        if !self.is_enabled() {
+            return;
+        }
+        let now = Instant::now();
+        let mut entries = self.entries.lock().await;
+        // 有効期限が切れたエントリを削除して、キャッシュが際限なく大きくならないようにする
+        entries.retain(|_, cached| now < cached.expires_at);
+        entries.insert(
+            (oid.to_string(), select_key(select)),
+            CachedProfile {
+                profile: profile.clone(),
+                expires_at: now + self.ttl,
+            },
+        );
+    }
+}
+
+/// 取得するプロパティから、プロファイルのキャッシュのキーを作成する。
+///
+/// プロパティの指定順序や重複にかかわらず同じキャッシュを使用できるように、並べ替えて重複を除く。
+fn select_key(select: Option<&[String]>) -> Option<String> {
+    select.map(|fields| {
+        let mut fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+        fields.sort_unstable();
+        fields.dedup();
+        fields.join(",")
+    })
+}
+
+/// `checkMemberGroups`で一度に確認できるグループの最大数
+const CHECK_MEMBER_GROUPS_MAX_IDS: usize = 20;
+
+/// `checkMemberGroups`のリクエスト
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CheckMemberGroupsRequest<'a> {
+    group_ids: &'a [String],
+}
+
+/// `checkMemberGroups`のレスポンス
+#[derive(Deserialize)]
+struct CheckMemberGroupsResponse {
+    /// 指定したグループのうち、ユーザーが所属するグループのオブジェクトID
+    value: Vec<String>,
+}
+
+/// Graph APIクライアント
+#[derive(Clone)]
+pub struct GraphClient {
+    /// HTTPクライアント
+    client: reqwest::Client,
+    /// Graph APIのベースURI（末尾の`/`を除く）
+    base_url: String,
+    /// Graph APIからの応答を待つタイムアウト
+    timeout: Duration,
+    /// Graph APIの障害時に呼び出しを遮断するサーキットブレーカー
+    circuit_breaker: Option<Arc<CircuitBreaker>>,
+}
+
+impl GraphClient {
+    /// コンストラクタ
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - HTTPクライアント
+    /// * `base_url` - Graph APIのベースURI（例: `https://graph.microsoft.com/v1.0`）
+    /// * `timeout` - Graph APIからの応答を待つタイムアウト
+    pub fn new(client: reqwest::Client, base_url: &Url, timeout: Duration) -> Self {
+        Self {
+            client,
+            base_url: base_url.as_str().trim_end_matches('/').to_string(),
+            timeout,
+            circuit_breaker: None,
+        }
+    }
+
+    /// Graph APIの障害時に呼び出しを遮断するサーキットブレーカーを設定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `circuit_breaker` - サーキットブレーカー
+    pub fn with_circuit_breaker(mut self, circuit_breaker: CircuitBreaker) -> Self {
+        self.circuit_breaker = Some(Arc::new(circuit_breaker));
+        self
+    }
+
+    /// サーキットブレーカーがGraph APIの呼び出しを遮断しているかを返す。
+    pub fn is_circuit_open(&self) -> bool {
+        self.circuit_breaker
+            .as_ref()
+            .is_some_and(|breaker| breaker.is_open())
+    }
+
+    /// サインインしているユーザーのプロファイルを取得する。
+    ///
+    /// # Arguments
+    ///
+    /// * `access_token` - Graph API用のアクセストークン
+    /// * `select` - 取得するプロパティ（`None`の場合はGraph APIの既定のプロパティ）
+    /// * `trace` - 伝搬するトレースコンテキスト
+    ///
+    /// # Returns
+    ///
+    /// * サインインしているユーザーのプロファイル、またはエラー
+    pub async fn get_me(
+        &self,
+        access_token: &SecretString,
+        select: Option<&[String]>,
+        trace: &TraceContext,
+    ) -> Result<MeResponse, GraphError> {
+        let query = select.map(|fields| {
+            // `MeResponse`は`id`を必須とするため、常に`id`を取得する
+            let mut fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+            if !fields.contains(&"id") {
+                fields.insert(0, "id");
+            }
+            vec![("$select", fields.join(","))]
+        });
+        self.get_json(
+            "me",
+            "/me",
+            access_token,
+            query.as_deref().unwrap_or_default(),
+            trace,
+        )
+        .await
+    }
+
+    /// サインインしているユーザーの上司のプロファイルを取得する。
+    ///
+    /// # Arguments
+    ///
+    /// * `access_token` - Graph API用のアクセストークン
+    /// * `trace` - 伝搬するトレースコンテキスト
+    ///
+    /// # Returns
+    ///
+    /// * 上司のプロファイル、上司が設定されていない場合は`None`、またはエラー
+    pub async fn get_manager(
+        &self,
+        access_token: &SecretString,
+        trace: &TraceContext,
+    ) -> Result<Option<ManagerResponse>, GraphError> {
+        match self
+            .get_json("me_manager", "/me/manager", access_token, &[], trace)
+            .await
+        {
+            Ok(manager) => Ok(Some(manager)),
+            Err(GraphError::ErrorStatus(status)) if status == reqwest::StatusCode::NOT_FOUND => {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// サインインしているユーザーが、指定したグループに所属しているかを確認する。
+    ///
+    /// 推移的なメンバーシップ（グループに含まれるグループへの所属）も考慮される。
+    ///
+    /// # Arguments
+    ///
+    /// * `access_token` - Graph API用のアクセストークン
+    /// * `group_ids` - 確認するグループのオブジェクトID
+    /// * `trace` - 伝搬するトレースコンテキスト
+    ///
+    /// # Returns
+    ///
+    /// * 指定したグループのうち、ユーザーが所属するグループのオブジェクトID、またはエラー
+    ///
+    /// # Notes
+    ///
+    /// Graph APIの制限により、20個ずつに分割して確認する。
+    pub async fn check_member_groups(
+        &self,
+        access_token: &SecretString,
+        group_ids: &[String],
+        trace: &TraceContext,
+    ) -> Result<Vec<String>, GraphError> {
+        let mut member_of = Vec::new();
+        for chunk in group_ids.chunks(CHECK_MEMBER_GROUPS_MAX_IDS) {
+            let body = serde_json::to_value(CheckMemberGroupsRequest { group_ids: chunk })
+                .expect("checkMemberGroups request must be serializable");
+            let response: CheckMemberGroupsResponse = self
+                .request_json(
+                    "me_check_member_groups",
+                    reqwest::Method::POST,
+                    "/me/checkMemberGroups",
+                    access_token,
+                    &[],
+                    Some(&body),
+                    trace,
+                )
+                .await?;
+            member_of.extend(response.value);
+        }
+        Ok(member_of)
+    }
+
+    /// Graph APIにGETリクエストを送信して、JSONレスポンスをパースする。
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - メトリクスのラベルに使用するエンドポイント名
+    /// * `path` - Graph APIのベースURIからのパス
+    /// * `access_token` - Graph API用のアクセストークン
+    /// * `query` - クエリパラメーター
+    /// * `trace` - 伝搬するトレースコンテキスト
+    ///
+    /// # Returns
+    ///
+    /// * パースしたレスポンス、またはエラー
+    async fn get_json<T: DeserializeOwned>(
+        &self,
+        endpoint: &'static str,
+        path: &str,
+        access_token: &SecretString,
+        query: &[(&str, String)],
+        trace: &TraceContext,
+    ) -> Result<T, GraphError> {
+        self.request_json(
+            endpoint,
+            reqwest::Method::GET,
+            path,
+            access_token,
+            query,
+            None,
+            trace,
+        )
+        .await
+    }
+
+    /// Graph APIにリクエストを送信して、JSONレスポンスをパースする。
+    ///
+    /// # Arguments
+    ///
+    /// * `endpoint` - メトリクスのラベルに使用するエンドポイント名
+    /// * `method` - HTTPメソッド
+    /// * `path` - Graph APIのベースURIからのパス
+    /// * `access_token` - Graph API用のアクセストークン
+    /// * `query` - クエリパラメーター
+    /// * `body` - JSONボディ
+    /// * `trace` - 伝搬するトレースコンテキスト
+    ///
+    /// # Returns
+    ///
+    /// * パースしたレスポンス、またはエラー
+    #[allow(clippy::too_many_arguments)]
+    async fn request_json<T: DeserializeOwned>(
+        &self,
+        endpoint: &'static str,
+        method: reqwest::Method,
+        path: &str,
+        access_token: &SecretString,
+        query: &[(&str, String)],
+        body: Option<&serde_json::Value>,
+        trace: &TraceContext,
+    ) -> Result<T, GraphError> {
+        if let Some(breaker) = &self.circuit_breaker
+            && !breaker.try_acquire()
+        {
+            tracing::debug!(
+                endpoint = endpoint,
+                "Graph API request rejected by circuit breaker"
+            );
+            return Err(GraphError::CircuitOpen);
+        }
+        let started_at = Instant::now();
+        let result = self
+            .send(method, path, access_token, query, body, trace)
+            .await;
+        if let Some(breaker) = &self.circuit_breaker {
+            match &result {
+                Err(e) if e.is_outage() => breaker.record_failure(),
+                // Graph APIが応答した場合は、クライアントエラーであっても障害とみなさない
+                _ => breaker.record_success(),
+            }
+        }
+        metrics::histogram!(
+            crate::metrics::GRAPH_REQUEST_DURATION_SECONDS,
+            "endpoint" => endpoint,
+            "outcome" => crate::metrics::outcome_label(&result),
+        )
+        .record(started_at.elapsed().as_secs_f64());
+        if let Err(e) = &result {
+            tracing::error!(endpoint = endpoint, error = %e, "Graph API request failed");
+        }
+        result
+    }
+
+    /// Graph APIにリクエストを送信する。
+    ///
+    /// Graph APIが返す`request-id`と`client-request-id`ヘッダをスパンに記録して、
+    /// Microsoftのサポートに問い合わせる際に、Graph API側のログと突き合わせられるようにする。
+    #[tracing::instrument(
+        name = "graph_request",
+        skip_all,
+        fields(method = %method, path = %path, graph_request_id, graph_client_request_id)
+    )]
+    async fn send<T: DeserializeOwned>(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        access_token: &SecretString,
+        query: &[(&str, String)],
+        body: Option<&serde_json::Value>,
+        trace: &TraceContext,
+    ) -> Result<T, GraphError> {
+        let uri = Url::parse_with_params(&format!("{}{}", self.base_url, path), query)
+            .expect("Graph API URI must be valid");
+        let mut builder = trace
+            .inject(self.client.request(method, uri))
+            .timeout(self.timeout)
+            .header(header::AUTHORIZATION, bearer_authorization(access_token));
+        if let Some(body) = body {
+            builder = builder.json(body);
+        }
+        let response = builder.send().await.map_err(GraphError::Request)?;
+        let span = tracing::Span::current();
+        for (header, field) in [
+            ("request-id", "graph_request_id"),
+            ("client-request-id", "graph_client_request_id"),
+        ] {
+            if let Some(value) = response.headers().get(header).and_then(|v| v.to_str().ok()) {
+                span.record(field, value);
+            }
+        }
+        let status = response.status();
+        if status.is_client_error() || status.is_server_error() {
+            return Err(GraphError::ErrorStatus(status));
+        }
+        response
+            .json::<T>()
+            .await
+            .map_err(GraphError::ResponseParse)
+    }
+}
+
+/// カンマ区切りのプロパティを、許可されたプロパティであるか確認しながら分割する。
+///
+/// # Arguments
+///
+/// * `fields` - カンマ区切りのプロパティ（例: `displayName,mail`）
+/// * `allowed` - 許可するプロパティ
+///
+/// # Returns
+///
+/// * 分割したプロパティ、または許可されていないプロパティ
+pub fn parse_select_fields(fields: &str, allowed: &[&str]) -> Result<Vec<String>, Vec<String>> {
+    let mut selected = Vec::new();
+    let mut disallowed = Vec::new();
+    for field in fields.split(',').map(str::trim).filter(|f| !f.is_empty()) {
+        if !allowed.contains(&field) {
+            disallowed.push(field.to_string());
+        } else if !selected.iter().any(|s| s == field) {
+            selected.push(field.to_string());
+        }
+    }
+    if disallowed.is_empty() {
+        Ok(selected)
+    } else {
+        Err(disallowed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recorded_graph_responses_can_be_parsed() {
+        let me: MeResponse = serde_json::from_str(crate::fixtures::GRAPH_ME).unwrap();
+        assert_eq!(me.id, "00000000-0000-0000-0000-000000000001");
+        assert_eq!(me.business_phones.as_ref().map(Vec::len), Some(1));
+        assert!(me.mobile_phone.is_none());
+        assert!(!me.degraded);
+
+        let manager: ManagerResponse =
+            serde_json::from_str(crate::fixtures::GRAPH_MANAGER).unwrap();
+        assert_eq!(manager.id, "00000000-0000-0000-0000-000000000002");
+        assert!(manager.office_location.is_none());
+
+        let groups: CheckMemberGroupsResponse =
+            serde_json::from_str(crate::fixtures::GRAPH_CHECK_MEMBER_GROUPS).unwrap();
+        assert_eq!(groups.value, ["00000000-0000-0000-0000-000000000010"]);
+    }
+}
@@ -0,0 +1,106 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde::Serialize;
+
+use crate::{
+    authorization::{JwksRead, JwksRefresh},
+    common::{AppResult, RequestError},
+    entra_id::{EntraIdError, JwksCacheRefreshResult, TenantId},
+    handlers::extractors::RequirePermission,
+    state::AppState,
+};
+
+/// JWK公開鍵の強制リフレッシュのレスポンス
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RefreshJwksResponse {
+    tenant_id: String,
+    result: &'static str,
+}
+
+/// 指定したテナントのJWK公開鍵を、最小リフレッシュ間隔を無視して強制的にリフレッシュする。
+///
+/// 鍵のローテーションによってトークンの検証に失敗している場合など、障害対応で使用する。
+#[tracing::instrument(skip(app_state, caller))]
+pub async fn refresh_tenant_jwks(
+    State(app_state): State<AppState>,
+    RequirePermission(caller, _): RequirePermission<JwksRefresh>,
+    Path(tenant_id): Path<String>,
+) -> AppResult<impl IntoResponse> {
+    let tenant_id = TenantId::try_from(tenant_id).map_err(|e| RequestError {
+        code: StatusCode::BAD_REQUEST,
+        message: e,
+    })?;
+    let result = app_state
+        .token_verifier
+        .force_refresh_tenant_jwks(&tenant_id)
+        .await
+        .map_err(|e| match e {
+            EntraIdError::TenantNotFound(_) => RequestError {
+                code: StatusCode::NOT_FOUND,
+                message: format!("Tenant not found: {}", tenant_id),
+            },
+            EntraIdError::TenantDisabled(_) => RequestError {
+                code: StatusCode::CONFLICT,
+                message: format!("Tenant is disabled: {}", tenant_id),
+            },
+            e => {
+                tracing::error!(tenant = %app_state.token_verifier.tenant_label(&tenant_id), error = %e, "Failed to refresh tenant JWKs");
+                RequestError {
+                    code: StatusCode::BAD_GATEWAY,
+                    message: format!("Failed to refresh JWKs for tenant {}: {e}", tenant_id),
+                }
+            }
+        })?;
+    tracing::info!(tenant = %app_state.token_verifier.tenant_label(&tenant_id), caller = %caller, result = ?result, "Tenant JWKs refreshed by admin");
+
+    let result = match result {
+        JwksCacheRefreshResult::Refreshed => "refreshed",
+        JwksCacheRefreshResult::WaitedForRefresh => "waitedForRefresh",
+        JwksCacheRefreshResult::RecentlyRefreshed => "recentlyRefreshed",
+        JwksCacheRefreshResult::GrantedRefreshPermission => "grantedRefreshPermission",
+        JwksCacheRefreshResult::Throttled => "throttled",
+    };
+    Ok((
+        StatusCode::OK,
+        axum::Json(RefreshJwksResponse {
+            tenant_id: tenant_id.to_string(),
+            result,
+        }),
+    ))
+}
+
+/// テナントごとのJWK公開鍵キャッシュの状態を返す。
+#[tracing::instrument(skip(app_state, _permission))]
+pub async fn jwks_cache(
+    State(app_state): State<AppState>,
+    _permission: RequirePermission<JwksRead>,
+) -> AppResult<impl IntoResponse> {
+    let snapshots = app_state.token_verifier.cache_snapshot().await;
+    Ok((StatusCode::OK, axum::Json(snapshots)))
+}
+
+/// JWK公開鍵キャッシュの統計情報を返す。
+#[tracing::instrument(skip(app_state, _permission))]
+pub async fn jwks_cache_stats(
+    State(app_state): State<AppState>,
+    _permission: RequirePermission<JwksRead>,
+) -> AppResult<impl IntoResponse> {
+    let stats = app_state.token_verifier.cache_stats().await;
+    Ok((StatusCode::OK, axum::Json(stats)))
+}
+
+/// テナントごとのJWK公開鍵のリフレッシュの健全性を返す。
+///
+/// 構成したテナントIDや障害の状況を含むため、認証なしで公開する`/readyz`ではなく管理者APIで返す。
+#[tracing::instrument(skip(app_state, _permission))]
+pub async fn tenant_health(
+    State(app_state): State<AppState>,
+    _permission: RequirePermission<JwksRead>,
+) -> AppResult<impl IntoResponse> {
+    let tenants = app_state.token_verifier.tenant_health().await;
+    Ok((StatusCode::OK, axum::Json(tenants)))
+}
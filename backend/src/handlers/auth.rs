@@ -0,0 +1,75 @@
+use axum::{
+    extract::{FromRequestParts as _, Request, State},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::{common::RequestError, state::AppState, user_context::UserContext};
+
+/// 保護されたルートで、ハンドラーを呼び出す前にトークンを検証するミドルウェア
+///
+/// `AuthClaims`と同じ手順でトークンの検証と認可ポリシーの評価を行い、結果と`UserContext`をリクエストのエクステンションに
+/// 挿入する。
+/// ハンドラーの`AuthClaims`、`UserContext`や`RequirePermission`は、エクステンションの結果を再利用するため、
+/// トークンを再検証しない。
+/// 検証に成功した場合は、後続のログに呼び出し元が含まれるように、現在の`http_request`スパンに`tid`、`oid`および
+/// クライアントアプリケーションのIDを記録する。
+///
+/// # Arguments
+///
+/// * `app_state` - アプリケーションの状態
+/// * `request` - リクエスト
+/// * `next` - 後続のミドルウェアまたはハンドラー
+///
+/// # Returns
+///
+/// * 後続のレスポンス、または認証もしくは認可に失敗した場合はエラー
+pub async fn auth_middleware(
+    State(app_state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, RequestError> {
+    let (mut parts, body) = request.into_parts();
+    let user = UserContext::from_request_parts(&mut parts, &app_state).await?;
+    let span = tracing::Span::current();
+    if let Some(tenant_id) = &user.tenant_id {
+        span.record("tid", tenant_id.as_str());
+    }
+    span.record("oid", user.oid.as_str());
+    if let Some(client_app_id) = &user.client_app_id {
+        span.record("client_app_id", client_app_id.as_str());
+    }
+    Ok(next.run(Request::from_parts(parts, body)).await)
+}
+
+/// APIキー認証を受け付けるルートグループで、ハンドラーを呼び出す前に呼び出し元を認証するミドルウェア
+///
+/// `X-API-Key`ヘッダが存在する場合はAPIキーで認証し、呼び出し元をリクエストのエクステンションに挿入する。
+/// `X-API-Key`ヘッダが存在しない場合は、`auth_middleware`と同様にトークンを検証する。
+///
+/// # Arguments
+///
+/// * `app_state` - アプリケーションの状態
+/// * `request` - リクエスト
+/// * `next` - 後続のミドルウェアまたはハンドラー
+///
+/// # Returns
+///
+/// * 後続のレスポンス、または認証もしくは認可に失敗した場合はエラー
+///
+/// # Notes
+///
+/// APIキーで認証した呼び出し元はEntra IDのクレームを持たないため、認可ポリシーを評価しない。
+/// APIキーに付与した権限は、`RequirePermission`で確認する。
+pub async fn api_key_or_auth_middleware(
+    State(app_state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, RequestError> {
+    let Some(principal) = app_state.api_keys.authenticate(request.headers()).await? else {
+        return auth_middleware(State(app_state), request, next).await;
+    };
+    tracing::debug!(caller = %principal, "Authenticated by API key");
+    request.extensions_mut().insert(principal);
+    Ok(next.run(request).await)
+}
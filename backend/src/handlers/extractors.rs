@@ -1,21 +1,32 @@
+use std::fmt;
+use std::marker::PhantomData;
+
 use axum::{
-    RequestPartsExt as _,
     extract::FromRequestParts,
     http::{StatusCode, request::Parts},
 };
-use axum_extra::{
-    TypedHeader,
-    headers::authorization::{Authorization, Bearer},
-};
-use secrecy::SecretString;
 
 use crate::{
+    api_key::ApiKeyPrincipal,
+    authorization::Permission,
+    authorization_policy::{PolicyDecision, PolicyInput},
+    bearer_auth::bearer_token,
     common::RequestError,
-    entra_id::{BearerToken, Claims},
+    deadline::RequestDeadline,
+    entra_id::{BearerToken, Claims, VerifiedToken},
+    graph::GRAPH_RESOURCE,
     state::AppState,
+    token_sources::TokenSource,
+    trace_context::TraceContext,
+    user_context::UserContext,
 };
 
 /// 認証済みクレームをリクエストから抽出するエクストラクタ
+///
+/// トークンの検証と認可ポリシーの評価はリクエストごとに1回だけ実行する。
+/// 保護されたルートでは`auth_middleware`が抽出済みのため、エクステンションの`AuthClaims`をそのまま返す。
+/// エクステンションに`VerifiedToken`が存在する場合はトークンを再検証せず、
+/// 抽出に成功した`AuthClaims`もエクステンションに挿入して、同じリクエストの他のエクストラクタで再利用する。
 #[derive(Clone)]
 pub struct AuthClaims {
     pub claims: Claims,
@@ -29,31 +40,523 @@ impl FromRequestParts<AppState> for AuthClaims {
         parts: &mut Parts,
         app_state: &AppState,
     ) -> Result<Self, Self::Rejection> {
-        let TypedHeader(Authorization(bearer)) = parts
-            .extract::<TypedHeader<Authorization<Bearer>>>()
-            .await
-            .map_err(|_| RequestError {
-                code: StatusCode::UNAUTHORIZED,
-                message: "Authorization header with Bearer token is required".into(),
-            })?;
-        let token = BearerToken(SecretString::new(bearer.token().into()));
+        // 同じリクエストで抽出済みの場合は、トークンの検証と認可ポリシーの評価を省略
+        if let Some(auth) = parts.extensions.get::<AuthClaims>() {
+            return Ok(auth.clone());
+        }
+
+        // ミドルウェアなどで検証済みの場合は、トークンを再検証せずに再利用
+        let verified = match parts.extensions.get::<VerifiedToken>() {
+            Some(verified) => verified.clone(),
+            None => {
+                // `Authorization`ヘッダを優先し、ルートに設定されている場合はクエリパラメーターやクッキーから取得
+                let token = bearer_token(&parts.headers)
+                    .or_else(|| {
+                        parts
+                            .extensions
+                            .get::<TokenSource>()
+                            .and_then(|source| source.token(parts))
+                    })
+                    .ok_or_else(|| {
+                        RequestError::unauthorized(
+                            "Authorization header with Bearer token is required",
+                        )
+                    })?;
+                // バックエンド用アクセストークンを検証して、クレームを認証コンテキストに変換
+                let context = app_state
+                    .token_verifier
+                    .authenticate(&token)
+                    .await
+                    .map_err(|e| {
+                        tracing::error!(error = %e, "Token verification failed");
+                        RequestError::from(e)
+                    })?;
+                app_state.role_scopes.warn_unknown_roles(&context.claims);
+                let verified = VerifiedToken {
+                    context,
+                    access_token: token,
+                };
+                parts.extensions.insert(verified.clone());
+                verified
+            }
+        };
+        let VerifiedToken {
+            context,
+            access_token,
+        } = verified;
 
-        // バックエンド用アクセストークンを検証
-        let claims = app_state
-            .token_verifier
-            .verify_token(&token)
+        // 認可ポリシーを評価
+        let Ok(trace) = TraceContext::from_request_parts(parts, app_state).await;
+        let input = PolicyInput {
+            method: parts.method.as_str(),
+            path: parts.uri.path(),
+            claims: &context.claims,
+            attributes: &context.attributes,
+        };
+        let decision = app_state
+            .authorization_policy
+            .evaluate(&input, &trace)
             .await
             .map_err(|e| {
-                tracing::error!(error = %e, "Token verification failed");
-                RequestError {
-                    code: StatusCode::UNAUTHORIZED,
-                    message: "Invalid access token".into(),
-                }
+                tracing::error!(error = %e, "Failed to evaluate authorization policy");
+                RequestError::from(e)
             })?;
+        if let PolicyDecision::Deny(reason) = decision {
+            tracing::warn!(oid = %context.claims.oid, reason = %reason, "Denied by authorization policy");
+            return Err(RequestError {
+                code: StatusCode::FORBIDDEN,
+                message: reason,
+            });
+        }
+
+        // ハンドラーが`Extension<AuthContext>`や`UserContext`で認証済みの情報を参照できるように、エクステンションに挿入
+        let auth = AuthClaims {
+            claims: context.claims.clone(),
+            access_token,
+        };
+        parts.extensions.insert(UserContext::from(&context.claims));
+        parts.extensions.insert(context);
+        parts.extensions.insert(auth.clone());
+
+        Ok(auth)
+    }
+}
+
+/// 認証済みのユーザーの情報をリクエストから抽出するエクストラクタ
+///
+/// ```ignore
+/// async fn handler(user: UserContext) { ... }
+/// ```
+///
+/// 保護されたルートでは`auth_middleware`が作成済みのため、エクステンションの`UserContext`をそのまま返す。
+/// 作成されていない場合は、`AuthClaims`と同様にトークンを検証して作成する。
+impl FromRequestParts<AppState> for UserContext {
+    type Rejection = RequestError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        app_state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        if let Some(user) = parts.extensions.get::<UserContext>() {
+            return Ok(user.clone());
+        }
+        let auth = AuthClaims::from_request_parts(parts, app_state).await?;
+        Ok(UserContext::from(&auth.claims))
+    }
+}
+
+/// 匿名のリクエストと認証済みのリクエストの両方を受け付けるルートで、認証済みクレームを抽出するエクストラクタ
+///
+/// ```ignore
+/// async fn handler(OptionalAuthClaims(claims): OptionalAuthClaims) { ... }
+/// ```
+///
+/// Bearerトークンが存在しない場合は`None`を返す。
+/// Bearerトークンが存在する場合は`AuthClaims`と同様に検証し、トークンが無効な場合や認可ポリシーで
+/// 拒否された場合は、匿名のリクエストとして扱わずにエラーを返す。
+pub struct OptionalAuthClaims(pub Option<Claims>);
+
+impl FromRequestParts<AppState> for OptionalAuthClaims {
+    type Rejection = RequestError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        app_state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let is_anonymous = !TokenSource::is_present(parts, parts.extensions.get())
+            && parts.extensions.get::<AuthClaims>().is_none()
+            && parts.extensions.get::<VerifiedToken>().is_none();
+        if is_anonymous {
+            return Ok(Self(None));
+        }
+        let auth = AuthClaims::from_request_parts(parts, app_state).await?;
+        Ok(Self(Some(auth.claims)))
+    }
+}
+
+/// 権限を確認した呼び出し元
+pub enum Caller {
+    /// Bearerトークンで認証したユーザー
+    User(Box<AuthClaims>),
+    /// APIキーで認証したサービス
+    ApiKey(ApiKeyPrincipal),
+}
+
+impl fmt::Display for Caller {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::User(auth) => write!(f, "oid:{}", auth.claims.oid),
+            Self::ApiKey(principal) => principal.fmt(f),
+        }
+    }
+}
+
+/// 呼び出し元を認証し、指定した権限が付与されているかを確認するエクストラクタ
+///
+/// ```ignore
+/// async fn handler(RequirePermission(caller, _): RequirePermission<JwksRead>) { ... }
+/// ```
+///
+/// APIキーで認証した呼び出し元の場合は、APIキーに付与した権限を確認する。
+/// 権限が付与されていない場合は、不足している権限を含めた403エラーを返す。
+pub struct RequirePermission<P: Permission>(pub Caller, pub PhantomData<P>);
+
+impl<P: Permission> FromRequestParts<AppState> for RequirePermission<P> {
+    type Rejection = RequestError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        app_state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        if let Some(principal) = parts.extensions.get::<ApiKeyPrincipal>() {
+            if principal.permissions.contains(P::NAME) {
+                return Ok(Self(Caller::ApiKey(principal.clone()), PhantomData));
+            }
+            tracing::warn!(caller = %principal, permission = P::NAME, "Permission is required");
+            return Err(RequestError::forbidden(&[P::NAME]));
+        }
+        let auth = AuthClaims::from_request_parts(parts, app_state).await?;
+        if app_state
+            .permissions
+            .is_granted_by_role(&auth.claims, P::NAME)
+        {
+            return Ok(Self(Caller::User(Box::new(auth)), PhantomData));
+        }
+        let groups = app_state.permissions.groups_granting(P::NAME);
+        if !groups.is_empty() && is_member_of_any(app_state, parts, &auth, &groups).await? {
+            return Ok(Self(Caller::User(Box::new(auth)), PhantomData));
+        }
+        tracing::warn!(oid = %auth.claims.oid, permission = P::NAME, "Permission is required");
+        Err(RequestError::forbidden(&[P::NAME]))
+    }
+}
+
+/// ユーザーが指定したグループのいずれかに所属しているかを判定する。
+///
+/// # Arguments
+///
+/// * `app_state` - アプリケーションの状態
+/// * `parts` - リクエストの構成要素
+/// * `auth` - 認証済みクレーム
+/// * `group_ids` - グループのオブジェクトID
+///
+/// # Returns
+///
+/// * いずれかのグループに所属している場合は`true`、またはエラー
+///
+/// # Notes
+///
+/// トークンに`groups`クレームが含まれている場合は、`groups`クレームで判定する。
+/// グループの超過によって`groups`クレームが含まれていない場合は、OBOで取得したトークンでGraph APIの
+/// `checkMemberGroups`を呼び出して判定し、その結果をキャッシュする。
+async fn is_member_of_any(
+    app_state: &AppState,
+    parts: &mut Parts,
+    auth: &AuthClaims,
+    group_ids: &[String],
+) -> Result<bool, RequestError> {
+    let claims = &auth.claims;
+    if let Some(groups) = &claims.groups {
+        return Ok(group_ids.iter().any(|id| groups.contains(id)));
+    }
+    if !claims.has_groups_overage() {
+        return Ok(false);
+    }
+    let missing = match app_state
+        .group_membership_cache
+        .lookup(&claims.oid, group_ids)
+        .await
+    {
+        Ok(is_member) => return Ok(is_member),
+        Err(missing) => missing,
+    };
+    let Ok(trace) = TraceContext::from_request_parts(parts, app_state).await;
+    let Ok(deadline) = RequestDeadline::from_request_parts(parts, app_state).await;
+    let graph_access_token = deadline
+        .run(app_state.acquire_obo_token(GRAPH_RESOURCE, claims, &auth.access_token, &trace))
+        .await??;
+    let member_of = deadline
+        .run(
+            app_state
+                .graph_client
+                .check_member_groups(&graph_access_token, &missing, &trace),
+        )
+        .await??;
+    app_state
+        .group_membership_cache
+        .store(&claims.oid, &missing, &member_of)
+        .await;
+    Ok(!member_of.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use axum::{
+        Router,
+        http::{Request, header},
+        routing::post,
+    };
+    use metrics_exporter_prometheus::PrometheusBuilder;
+    use secrecy::SecretString;
+    use tokio::net::TcpListener;
+    use url::Url;
+
+    use super::*;
+    use crate::{
+        api_key::ApiKeyAuth,
+        authorization::{GroupMembershipCache, PermissionMap, RoleScopeRegistry},
+        authorization_policy::AllowAllPolicy,
+        confidential_client::ConfidentialClient,
+        config::{ApiKeysConfig, ClientCredentialsRegistry, GraphConfig, ResourceRegistry},
+        entra_id::RetryConfig,
+        fixtures,
+        graph::{GraphClient, MeProfileCache},
+    };
+
+    /// 記録したJWK公開鍵セットでトークンを検証する、アプリケーションの状態を作成する。
+    async fn app_state() -> AppState {
+        let _ = crate::crypto::install_default_provider();
+        let http_client = reqwest::Client::new();
+        let graph: GraphConfig = serde_json::from_value(serde_json::json!({})).unwrap();
+        let client_credentials = ClientCredentialsRegistry::new(
+            serde_json::from_value(serde_json::json!({
+                "client_id": "11111111-1111-1111-1111-111111111111",
+                "client_secret": "secret",
+            }))
+            .unwrap(),
+            &[],
+        );
+        let role_scopes = Arc::new(RoleScopeRegistry::new(&[], &[]));
+        let permissions = PermissionMap::new(
+            &role_scopes,
+            HashMap::new(),
+            HashMap::new(),
+            &HashMap::new(),
+            "Admin",
+        )
+        .unwrap();
+        AppState {
+            token_verifier: fixtures::build_verifier().await,
+            client_credentials: client_credentials.clone(),
+            permissions,
+            role_scopes,
+            group_membership_cache: Arc::new(GroupMembershipCache::new(Duration::from_mins(5))),
+            authorization_policy: Arc::new(AllowAllPolicy),
+            api_keys: Arc::new(ApiKeyAuth::from_config(&ApiKeysConfig::default())),
+            public_rate_limiter: None,
+            resources: ResourceRegistry::new(HashMap::new(), &graph),
+            metrics_handle: PrometheusBuilder::new().build_recorder().handle(),
+            graph_client: GraphClient::new(
+                http_client.clone(),
+                &Url::parse("https://graph.microsoft.com/v1.0/").unwrap(),
+                Duration::from_secs(graph.graph_timeout),
+            ),
+            me_profile_cache: Arc::new(MeProfileCache::new(Duration::from_mins(5))),
+            token_endpoint_retry: RetryConfig::new(
+                1,
+                Duration::from_millis(1),
+                2.0,
+                1.0,
+                1.0,
+                Duration::from_millis(1),
+            )
+            .unwrap(),
+            confidential_client: Arc::new(ConfidentialClient::new(
+                http_client.clone(),
+                client_credentials,
+                graph.authority_host.clone(),
+                Duration::from_secs(graph.token_endpoint_timeout),
+            )),
+            http_client,
+            graph,
+        }
+    }
+
+    /// `Authorization`ヘッダを指定して、リクエストの構成要素を作成する。
+    fn parts(authorization: Option<&str>) -> Parts {
+        let mut request = Request::builder().uri("/");
+        if let Some(authorization) = authorization {
+            request = request.header(header::AUTHORIZATION, authorization);
+        }
+        request.body(()).unwrap().into_parts().0
+    }
+
+    #[tokio::test]
+    async fn optional_auth_claims_is_none_only_without_token() {
+        let app_state = app_state().await;
+        let extract = |authorization: Option<String>| {
+            let app_state = app_state.clone();
+            async move {
+                OptionalAuthClaims::from_request_parts(
+                    &mut parts(authorization.as_deref()),
+                    &app_state,
+                )
+                .await
+            }
+        };
+
+        // トークンが存在しない場合は、匿名のリクエストとして扱う
+        let OptionalAuthClaims(claims) = extract(None).await.ok().unwrap();
+        assert!(claims.is_none());
+
+        // トークンが無効な場合は、匿名のリクエストとして扱わずに拒否する
+        let err = extract(Some("Bearer invalid".into())).await.err().unwrap();
+        assert_eq!(err.code, StatusCode::UNAUTHORIZED);
+
+        // トークンが有効な場合は、検証したクレームを返す
+        let token = format!("Bearer {}", fixtures::sign_token());
+        let OptionalAuthClaims(claims) = extract(Some(token)).await.ok().unwrap();
+        assert_eq!(claims.unwrap().oid, "user-oid");
+    }
+
+    /// `checkMemberGroups`のレスポンスでユーザーが所属しているグループ
+    const MEMBER_GROUP_ID: &str = "00000000-0000-0000-0000-000000000010";
+
+    /// ユーザーが所属していないグループ
+    const OTHER_GROUP_ID: &str = "00000000-0000-0000-0000-000000000020";
+
+    /// トークンエンドポイントとGraph APIのモックを起動する。
+    ///
+    /// # Arguments
+    ///
+    /// * `check_member_groups_status` - `checkMemberGroups`が返すステータス
+    ///
+    /// # Returns
+    ///
+    /// * モックのベースURLと、`checkMemberGroups`の呼び出し回数
+    async fn serve_graph(check_member_groups_status: StatusCode) -> (Url, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let app = Router::new()
+            .route(
+                "/{tenant}/oauth2/v2.0/token",
+                post(|| async {
+                    (
+                        [(header::CONTENT_TYPE, "application/json")],
+                        fixtures::TOKEN_RESPONSE,
+                    )
+                }),
+            )
+            .route(
+                "/v1.0/me/checkMemberGroups",
+                post(move || {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    async move {
+                        (
+                            check_member_groups_status,
+                            [(header::CONTENT_TYPE, "application/json")],
+                            fixtures::GRAPH_CHECK_MEMBER_GROUPS,
+                        )
+                    }
+                }),
+            );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (Url::parse(&format!("http://{address}/")).unwrap(), calls)
+    }
+
+    /// トークンエンドポイントとGraph APIにモックを使用する、アプリケーションの状態を作成する。
+    async fn app_state_with_graph(base_url: &Url) -> AppState {
+        let mut app_state = app_state().await;
+        app_state.graph.authority_host = base_url.clone();
+        app_state.graph_client = GraphClient::new(
+            app_state.http_client.clone(),
+            &base_url.join("v1.0/").unwrap(),
+            Duration::from_secs(5),
+        );
+        app_state
+    }
+
+    /// 指定したクレームを追加した認証済みクレームを作成する。
+    fn auth_claims(extra: serde_json::Value) -> AuthClaims {
+        let mut claims = serde_json::json!({
+            "aud": "api://backend",
+            "iss": "https://login.microsoftonline.com/00000000-0000-0000-0000-000000000000/v2.0",
+            "tid": "00000000-0000-0000-0000-000000000000",
+            "exp": 0,
+            "oid": "user-oid",
+            "sub": "user-sub",
+        });
+        claims
+            .as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        AuthClaims {
+            claims: serde_json::from_value(claims).unwrap(),
+            access_token: BearerToken(SecretString::new("user-access-token".into())),
+        }
+    }
+
+    /// グループの超過を示すクレーム
+    fn groups_overage() -> serde_json::Value {
+        serde_json::json!({ "_claim_names": { "groups": "src1" } })
+    }
+
+    #[tokio::test]
+    async fn groups_claim_is_used_without_calling_graph() {
+        let (base_url, calls) = serve_graph(StatusCode::OK).await;
+        let app_state = app_state_with_graph(&base_url).await;
+        let auth = auth_claims(serde_json::json!({ "groups": [MEMBER_GROUP_ID] }));
+
+        for (group_id, expected) in [(MEMBER_GROUP_ID, true), (OTHER_GROUP_ID, false)] {
+            let is_member =
+                is_member_of_any(&app_state, &mut parts(None), &auth, &[group_id.into()])
+                    .await
+                    .ok()
+                    .unwrap();
+            assert_eq!(is_member, expected);
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn groups_overage_falls_back_to_graph_and_caches_the_result() {
+        let (base_url, calls) = serve_graph(StatusCode::OK).await;
+        let app_state = app_state_with_graph(&base_url).await;
+        let auth = auth_claims(groups_overage());
+        let group_ids = [MEMBER_GROUP_ID.to_string(), OTHER_GROUP_ID.to_string()];
+
+        for _ in 0..2 {
+            let is_member = is_member_of_any(&app_state, &mut parts(None), &auth, &group_ids)
+                .await
+                .ok()
+                .unwrap();
+            assert!(is_member);
+        }
+        // 2回目はキャッシュから判定して、Graph APIを呼び出さない
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn graph_failure_denies() {
+        let (base_url, calls) = serve_graph(StatusCode::INTERNAL_SERVER_ERROR).await;
+        let app_state = app_state_with_graph(&base_url).await;
+        let auth = auth_claims(groups_overage());
 
-        Ok(AuthClaims {
-            claims,
-            access_token: token,
-        })
+        let err = is_member_of_any(
+            &app_state,
+            &mut parts(None),
+            &auth,
+            &[MEMBER_GROUP_ID.into()],
+        )
+        .await
+        .err()
+        .unwrap();
+        assert!(err.code.is_server_error(), "{}", err.code);
+        assert!(0 < calls.load(Ordering::SeqCst));
+        // 失敗した結果はキャッシュしない
+        assert!(
+            app_state
+                .group_membership_cache
+                .lookup("user-oid", &[MEMBER_GROUP_ID.into()])
+                .await
+                .is_err()
+        );
     }
 }
@@ -1,4 +1,54 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use serde::Serialize;
+
+use crate::state::AppState;
+
 #[tracing::instrument]
 pub async fn health_check() -> &'static str {
     "OK"
 }
+
+/// レディネスの状態
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+enum ReadinessStatus {
+    /// すべての有効なテナントが健全
+    Ready,
+    /// 一部の有効なテナントが劣化しているが、リクエストを受け付けられる
+    Degraded,
+    /// すべての有効なテナントが劣化している
+    Unready,
+}
+
+/// レディネスのレスポンス
+///
+/// 認証なしで公開するため、構成したテナントIDなどのテナントごとの状態は含めない。
+/// テナントごとの状態は、管理者APIの`/api/admin/tenants/health`で参照する。
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ReadinessResponse {
+    status: ReadinessStatus,
+}
+
+/// レディネスを返す。
+///
+/// 一部のテナントのJWK公開鍵のリフレッシュに失敗していても、他のテナントのトークンは検証できるため、
+/// すべての有効なテナントが劣化している場合にのみ503を返す。
+#[tracing::instrument(skip(app_state))]
+pub async fn readiness(State(app_state): State<AppState>) -> impl IntoResponse {
+    let tenants = app_state.token_verifier.tenant_health().await;
+    let enabled = tenants.iter().filter(|tenant| tenant.enabled);
+    let degraded = enabled.clone().filter(|tenant| tenant.degraded).count();
+    let status = if degraded == 0 {
+        ReadinessStatus::Ready
+    } else if degraded < enabled.count() {
+        ReadinessStatus::Degraded
+    } else {
+        ReadinessStatus::Unready
+    };
+    let code = match status {
+        ReadinessStatus::Unready => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::OK,
+    };
+    (code, axum::Json(ReadinessResponse { status }))
+}
@@ -1,136 +1,163 @@
+use std::time::Duration;
+
 use crate::{
     common::{AppResult, RequestError},
-    entra_id::extract_issuer_from_iss,
+    deadline::RequestDeadline,
+    graph::{GRAPH_RESOURCE, GraphError, ME_SELECTABLE_FIELDS, MeResponse, parse_select_fields},
     handlers::extractors::AuthClaims,
     state::AppState,
+    trace_context::TraceContext,
+};
+use axum::{
+    extract::{Query, State},
+    http::{HeaderValue, StatusCode, header},
+    response::IntoResponse,
 };
-use axum::{extract::State, http::StatusCode, response::IntoResponse};
-use secrecy::ExposeSecret as _;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 
-/// Entra IDのOBOで返されるGraph API用アクセストークンレスポンスの例
-/// ```json
-/// {
-///     "token_type": "Bearer",
-///     "scope": "https://graph.microsoft.com/user.read",
-///     "expires_in": 3269,
-///     "ext_expires_in": 0,
-///     "access_token": "eyJhbGciO...",
-///     "refresh_token": "OAQABAAAA...",
-/// }
-/// ```
-#[derive(Deserialize)]
-struct TokenResponse {
-    access_token: String,
-    // 他のフィールドは省略
+/// `/me`のクエリパラメーター
+#[derive(Debug, Deserialize)]
+pub struct MeQuery {
+    /// 取得するプロパティのカンマ区切りリスト（例: `displayName,mail`）
+    ///
+    /// 許可されたプロパティのみ指定でき、Graph APIの`$select`として転送する。
+    fields: Option<String>,
 }
 
-#[tracing::instrument(skip(app_state, claims, access_token))]
+#[tracing::instrument(skip(app_state, claims, access_token, trace, deadline))]
 pub async fn me(
     State(app_state): State<AppState>,
     AuthClaims {
         claims,
         access_token,
     }: AuthClaims,
+    trace: TraceContext,
+    deadline: RequestDeadline,
+    Query(query): Query<MeQuery>,
 ) -> AppResult<impl IntoResponse> {
-    // テナントIDを取得
-    let tenant_id = extract_issuer_from_iss(&claims.iss).map_err(|e| {
-        tracing::error!(error = %e, "Failed to extract tenant ID from iss");
-        RequestError {
-            code: StatusCode::UNAUTHORIZED,
-            message: format!("Failed to extract tenant ID from iss: {e}"),
-        }
-    })?;
+    // 取得するプロパティを検証
+    let select = query
+        .fields
+        .as_deref()
+        .map(|fields| parse_select_fields(fields, ME_SELECTABLE_FIELDS))
+        .transpose()
+        .map_err(|disallowed| RequestError {
+            code: StatusCode::BAD_REQUEST,
+            message: format!(
+                "Unsupported fields: {}. Allowed fields: {}",
+                disallowed.join(","),
+                ME_SELECTABLE_FIELDS.join(",")
+            ),
+        })?
+        .filter(|fields| !fields.is_empty());
 
-    // OBOでGraph APIを呼び出すためのアクセストークンを取得
-    // The user or administrator has not consented to use the application with ID ...
-    // のようなエラーが出た場合、管理者がバックエンドアプリケーションに対して
-    // Graph APIのアクセス許可を付与していない可能性がある。
-    //
-    // また、バックエンドアプリケーションに対して、Graph APIのUser.Readなどのアクセス許可を追加しても、管理者の同意が必要になる。
-    // Entra ID画面でUser.Readの行に緑のチェックマークが付いていることを確認すること。
-    let uri = format!(
-        "https://login.microsoftonline.com/{}/oauth2/v2.0/token",
-        tenant_id.0
-    );
-    let params = [
-        ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
-        ("client_id", &app_state.client_credentials.client_id.0),
+    // キャッシュしたプロファイルがある場合は、OBOとGraph APIを呼び出さずに返す
+    let cache = &app_state.me_profile_cache;
+    if let Some((response, remaining)) = cache.get(&claims.oid, select.as_deref()).await {
+        return Ok((
+            StatusCode::OK,
+            [(header::CACHE_CONTROL, cache_control(remaining))],
+            axum::Json(response),
+        )
+            .into_response());
+    }
+
+    // サーキットブレーカーがGraph APIの呼び出しを遮断している場合は、設定に応じてクレームから作成したプロファイルを返す
+    let fallback_to_claims = app_state
+        .graph
+        .circuit_breaker
+        .as_ref()
+        .is_some_and(|circuit_breaker| circuit_breaker.fallback_to_claims);
+    let degraded_response = || {
         (
-            "client_secret",
-            app_state.client_credentials.client_secret.expose_secret(),
-        ),
-        ("assertion", access_token.0.expose_secret()),
-        ("scope", "https://graph.microsoft.com/User.Read"),
-        ("requested_token_use", "on_behalf_of"),
-    ];
-    let client = reqwest::Client::new();
-    let response = client.post(&uri).form(&params).send().await.map_err(|e| {
-        tracing::error!(error = %e, "Failed to request Graph API access token");
-        RequestError {
-            code: StatusCode::BAD_GATEWAY,
-            message: format!("Failed to request Graph API access token: {e}"),
-        }
-    })?;
-    if response.status().is_client_error() || response.status().is_server_error() {
-        tracing::error!(status = %response.status(), "Graph API access token request returned error status");
-        let message = response.text().await.map_err(|e| {
-            tracing::error!(error = %e, "Failed to read Graph API access token error body");
-            RequestError {
-                code: StatusCode::BAD_GATEWAY,
-                message: format!("Failed to read Graph API access token error body: {e}"),
-            }
-        })?;
-        tracing::error!(body = %message, "Graph API access token request error body");
-        return Err(RequestError {
-            code: StatusCode::BAD_GATEWAY,
-            message,
-        });
+            StatusCode::OK,
+            [(header::CACHE_CONTROL, cache_control(Duration::ZERO))],
+            axum::Json(MeResponse::from_claims(&claims, select.as_deref())),
+        )
+            .into_response()
     };
-    let token_response = response.json::<TokenResponse>().await.map_err(|e| {
-        tracing::error!(error = %e, "Failed to parse Graph API access token response");
-        RequestError {
-            code: StatusCode::BAD_GATEWAY,
-            message: format!("Failed to parse Graph API access token response: {e}"),
-        }
-    })?;
+    if fallback_to_claims && app_state.graph_client.is_circuit_open() {
+        return Ok(degraded_response());
+    }
+
+    // OBOでGraph APIを呼び出すためのアクセストークンを取得
+    let graph_access_token = deadline
+        .run(app_state.acquire_obo_token(GRAPH_RESOURCE, &claims, &access_token, &trace))
+        .await??;
 
     // Graph APIの呼び出し
-    let response = client
-        .get("https://graph.microsoft.com/v1.0/me")
-        .bearer_auth(token_response.access_token)
-        .send()
-        .await
-        .map_err(|e| {
-            tracing::error!(error = %e, "Failed to call Graph API");
-            RequestError {
-                code: StatusCode::BAD_GATEWAY,
-                message: format!("Failed to call Graph API: {e}"),
-            }
-        })?
-        .json::<MeResponse>()
-        .await
-        .map_err(|e| RequestError {
-            code: StatusCode::BAD_GATEWAY,
-            message: format!("Failed to parse Graph API response: {e}"),
-        })?;
+    let response = match deadline
+        .run(
+            app_state
+                .graph_client
+                .get_me(&graph_access_token, select.as_deref(), &trace),
+        )
+        .await?
+    {
+        Ok(response) => response,
+        Err(GraphError::CircuitOpen) if fallback_to_claims => return Ok(degraded_response()),
+        Err(e) => return Err(e.into()),
+    };
+    cache.store(&claims.oid, select.as_deref(), &response).await;
 
-    Ok((StatusCode::OK, axum::Json(response)).into_response())
+    Ok((
+        StatusCode::OK,
+        [(header::CACHE_CONTROL, cache_control(cache.ttl()))],
+        axum::Json(response),
+    )
+        .into_response())
+}
+
+/// `Cache-Control`ヘッダの値を返す。
+///
+/// # Arguments
+///
+/// * `max_age` - クライアントがレスポンスを再利用できる期間（ゼロの場合はキャッシュさせない）
+///
+/// # Returns
+///
+/// * `Cache-Control`ヘッダの値
+///
+/// # Notes
+///
+/// ユーザーごとのプロファイルを共有キャッシュに保存させないように、`private`を指定する。
+fn cache_control(max_age: Duration) -> HeaderValue {
+    if max_age.is_zero() {
+        HeaderValue::from_static("private, no-store")
+    } else {
+        HeaderValue::from_str(&format!("private, max-age={}", max_age.as_secs()))
+            .expect("Cache-Control header value is valid")
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct MeResponse {
-    id: String,
-    user_principal_name: Option<String>,
-    surname: Option<String>,
-    given_name: Option<String>,
-    display_name: Option<String>,
-    mail: Option<String>,
-    job_title: Option<String>,
-    department: Option<String>,
-    office_location: Option<String>,
-    business_phones: Option<Vec<String>>,
-    mobile_phone: Option<String>,
-    preferred_language: Option<String>,
+/// サインインしているユーザーの上司のプロファイルを返す。
+#[tracing::instrument(skip(app_state, claims, access_token, trace, deadline))]
+pub async fn manager(
+    State(app_state): State<AppState>,
+    AuthClaims {
+        claims,
+        access_token,
+    }: AuthClaims,
+    trace: TraceContext,
+    deadline: RequestDeadline,
+) -> AppResult<impl IntoResponse> {
+    // OBOでGraph APIを呼び出すためのアクセストークンを取得
+    let graph_access_token = deadline
+        .run(app_state.acquire_obo_token(GRAPH_RESOURCE, &claims, &access_token, &trace))
+        .await??;
+
+    // Graph APIの呼び出し
+    let response = deadline
+        .run(
+            app_state
+                .graph_client
+                .get_manager(&graph_access_token, &trace),
+        )
+        .await??
+        .ok_or_else(|| RequestError {
+            code: StatusCode::NOT_FOUND,
+            message: "The signed-in user has no manager".into(),
+        })?;
+
+    Ok((StatusCode::OK, axum::Json(response)).into_response())
 }
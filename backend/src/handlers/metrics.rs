@@ -0,0 +1,12 @@
+use axum::{extract::State, http::header, response::IntoResponse};
+
+use crate::state::AppState;
+
+/// Prometheus形式でメトリクスを返す。
+pub async fn metrics(State(app_state): State<AppState>) -> impl IntoResponse {
+    app_state.metrics_handle.run_upkeep();
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        app_state.metrics_handle.render(),
+    )
+}
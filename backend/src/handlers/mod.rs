@@ -1,64 +1,276 @@
-mod extractors;
+mod admin;
+mod auth;
+pub mod extractors;
 mod health_check;
 mod me;
+mod metrics;
+mod version;
 
-use axum::{Router, routing};
+use std::sync::Arc;
 
-use self::health_check::health_check;
-use self::me::me;
+use axum::{
+    Router, middleware,
+    routing::{self, MethodRouter},
+};
 
+use self::admin::{jwks_cache, jwks_cache_stats, refresh_tenant_jwks, tenant_health};
+use self::auth::{api_key_or_auth_middleware, auth_middleware};
+use self::health_check::{health_check, readiness};
+use self::me::{manager, me};
+use self::metrics::metrics;
+use self::version::version;
+
+use crate::config::ApiKeyRouteGroup;
+use crate::rate_limit::limit_public_requests;
+use crate::route_timeouts::RouteTimeouts;
 use crate::state::AppState;
+use crate::token_sources::RouteTokenSources;
+
+/// APIのルートをネストするパス
+const API_PREFIX: &str = "/api";
+
+/// 管理者APIのルートをネストするパス
+const ADMIN_API_PREFIX: &str = "/api/admin";
 
 /// ルートを作成する。
 ///
 /// # Arguments
 ///
+/// * `timeouts` - ルートごとのタイムアウト
+/// * `token_sources` - ルートごとのBearerトークンの取得方法
 /// * `app_state` - アプリケーションの状態
 ///
 /// # Returns
 ///
 /// 作成したルーター
-pub fn create_routes() -> Router<AppState> {
-    Router::new().nest("/api", create_api_routes())
+pub fn create_routes(
+    timeouts: &RouteTimeouts,
+    token_sources: &RouteTokenSources,
+    app_state: &AppState,
+) -> Router<AppState> {
+    create_health_routes(timeouts, app_state)
+        .route(
+            "/metrics",
+            timeouts.apply("/metrics", routing::get(metrics)),
+        )
+        .nest(
+            API_PREFIX,
+            create_protected_api_routes(timeouts, token_sources, app_state).nest(
+                "/admin",
+                create_admin_api_routes(timeouts, token_sources, app_state),
+            ),
+        )
 }
 
-/// 公開ルートと保護されたルートをまとめて返す。
+/// 運用リスナーを使用する場合に、APIのリスナーで公開するルートを作成する。
+///
+/// メトリクスと管理者APIは含めない。
 ///
 /// # Arguments
 ///
+/// * `timeouts` - ルートごとのタイムアウト
+/// * `token_sources` - ルートごとのBearerトークンの取得方法
 /// * `app_state` - アプリケーションの状態
 ///
 /// # Returns
 ///
 /// 作成したルーター
-fn create_api_routes() -> Router<AppState> {
-    Router::new()
-        .merge(create_public_api_routes())
-        .merge(create_protected_api_routes())
+pub fn create_public_routes(
+    timeouts: &RouteTimeouts,
+    token_sources: &RouteTokenSources,
+    app_state: &AppState,
+) -> Router<AppState> {
+    create_health_routes(timeouts, app_state).nest(
+        API_PREFIX,
+        create_protected_api_routes(timeouts, token_sources, app_state),
+    )
 }
 
-/// 公開ルートを作成する。
+/// 運用リスナーで公開するルートを作成する。
+///
+/// メトリクス、ヘルスチェック、管理者APIのみを含める。
 ///
 /// # Arguments
 ///
+/// * `timeouts` - ルートごとのタイムアウト
+/// * `token_sources` - ルートごとのBearerトークンの取得方法
 /// * `app_state` - アプリケーションの状態
 ///
 /// # Returns
 ///
 /// 作成したルーター
-fn create_public_api_routes() -> Router<AppState> {
-    Router::new().route("/health-check", routing::get(health_check))
+pub fn create_operational_routes(
+    timeouts: &RouteTimeouts,
+    token_sources: &RouteTokenSources,
+    app_state: &AppState,
+) -> Router<AppState> {
+    create_health_routes(timeouts, app_state)
+        .route(
+            "/metrics",
+            timeouts.apply("/metrics", routing::get(metrics)),
+        )
+        .nest(
+            ADMIN_API_PREFIX,
+            create_admin_api_routes(timeouts, token_sources, app_state),
+        )
+}
+
+/// ヘルスチェックとビルド情報のルートを作成する。
+///
+/// 公開ルートのレート制限を設定した場合は、接続元のIPアドレスごとにリクエストを制限する。
+///
+/// # Arguments
+///
+/// * `timeouts` - ルートごとのタイムアウト
+/// * `app_state` - アプリケーションの状態
+///
+/// # Returns
+///
+/// 作成したルーター
+fn create_health_routes(timeouts: &RouteTimeouts, app_state: &AppState) -> Router<AppState> {
+    let router = Router::new()
+        .route(
+            "/readyz",
+            timeouts.apply("/readyz", routing::get(readiness)),
+        )
+        .route(
+            "/api/health-check",
+            timeouts.apply("/api/health-check", routing::get(health_check)),
+        )
+        .route(
+            "/api/version",
+            timeouts.apply("/api/version", routing::get(version)),
+        );
+    match &app_state.public_rate_limiter {
+        Some(limiter) => router.route_layer(middleware::from_fn_with_state(
+            Arc::clone(limiter),
+            limit_public_requests,
+        )),
+        None => router,
+    }
 }
 
 /// 保護されたルートを作成する。
 ///
+/// ハンドラーを呼び出す前に、ミドルウェアでトークンを検証する。
+///
+/// # Arguments
+///
+/// * `timeouts` - ルートごとのタイムアウト
+/// * `token_sources` - ルートごとのBearerトークンの取得方法
+/// * `app_state` - アプリケーションの状態
+///
+/// # Returns
+///
+/// 作成したルーター
+fn create_protected_api_routes(
+    timeouts: &RouteTimeouts,
+    token_sources: &RouteTokenSources,
+    app_state: &AppState,
+) -> Router<AppState> {
+    let route = |path: &str, method_router| {
+        let pattern = format!("{API_PREFIX}{path}");
+        let method_router = protect(
+            &pattern,
+            ApiKeyRouteGroup::Api,
+            method_router,
+            token_sources,
+            app_state,
+        );
+        timeouts.apply(&pattern, method_router)
+    };
+    Router::new()
+        .route("/me", route("/me", routing::get(me)))
+        .route("/me/manager", route("/me/manager", routing::get(manager)))
+}
+
+/// 管理者ルートを作成する。
+///
+/// ハンドラーを呼び出す前に、ミドルウェアでトークンを検証する。
+/// 必要な権限は、各ハンドラーの`RequirePermission`で確認する。
+///
 /// # Arguments
 ///
+/// * `timeouts` - ルートごとのタイムアウト
+/// * `token_sources` - ルートごとのBearerトークンの取得方法
 /// * `app_state` - アプリケーションの状態
 ///
 /// # Returns
 ///
 /// 作成したルーター
-fn create_protected_api_routes() -> Router<AppState> {
-    Router::new().route("/me", routing::get(me))
+fn create_admin_api_routes(
+    timeouts: &RouteTimeouts,
+    token_sources: &RouteTokenSources,
+    app_state: &AppState,
+) -> Router<AppState> {
+    let route = |path: &str, method_router| {
+        let pattern = format!("{ADMIN_API_PREFIX}{path}");
+        let method_router = protect(
+            &pattern,
+            ApiKeyRouteGroup::Admin,
+            method_router,
+            token_sources,
+            app_state,
+        );
+        timeouts.apply(&pattern, method_router)
+    };
+    Router::new()
+        .route(
+            "/jwks-cache",
+            route("/jwks-cache", routing::get(jwks_cache)),
+        )
+        .route(
+            "/jwks-cache/stats",
+            route("/jwks-cache/stats", routing::get(jwks_cache_stats)),
+        )
+        .route(
+            "/tenants/health",
+            route("/tenants/health", routing::get(tenant_health)),
+        )
+        .route(
+            "/tenants/{tenant_id}/refresh-jwks",
+            route(
+                "/tenants/{tenant_id}/refresh-jwks",
+                routing::post(refresh_tenant_jwks),
+            ),
+        )
+}
+
+/// 保護されたルートに、トークンを検証するミドルウェアと、ルートのBearerトークンの取得方法を適用する。
+///
+/// # Arguments
+///
+/// * `pattern` - ネストしたルーターのパスを含む、ルートのパターン
+/// * `route_group` - ルートが属するルートグループ
+/// * `method_router` - ルートのメソッドルーター
+/// * `token_sources` - ルートごとのBearerトークンの取得方法
+/// * `app_state` - アプリケーションの状態
+///
+/// # Returns
+///
+/// * ミドルウェアとBearerトークンの取得方法を適用したメソッドルーター
+///
+/// # Notes
+///
+/// ミドルウェアがBearerトークンの取得方法を参照できるように、取得方法をミドルウェアより外側に適用する。
+/// ルートグループでAPIキー認証を受け付ける場合は、APIキーとトークンのどちらでも認証できるミドルウェアを適用する。
+fn protect(
+    pattern: &str,
+    route_group: ApiKeyRouteGroup,
+    method_router: MethodRouter<AppState>,
+    token_sources: &RouteTokenSources,
+    app_state: &AppState,
+) -> MethodRouter<AppState> {
+    let method_router = if app_state.api_keys.accepts(route_group) {
+        method_router.layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            api_key_or_auth_middleware,
+        ))
+    } else {
+        method_router.layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            auth_middleware,
+        ))
+    };
+    token_sources.apply(pattern, method_router)
 }
@@ -0,0 +1,9 @@
+use axum::Json;
+
+use crate::build_info::{BUILD_INFO, BuildInfo};
+
+/// デプロイされているビルドの情報を返す。
+#[tracing::instrument]
+pub async fn version() -> Json<BuildInfo> {
+    Json(BUILD_INFO)
+}
@@ -0,0 +1,137 @@
+use std::sync::Arc;
+
+use axum::{
+    body::{Body, Bytes, HttpBody as _},
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse as _, Response},
+};
+
+use crate::{
+    config::HttpDebugLogConfig,
+    redaction::{redact_body, redact_headers, truncate},
+};
+
+/// デバッグログのためにバッファリングするボディの最大バイト数
+///
+/// ログに出力するボディは`max_body_bytes`に切り詰めるが、ボディをハンドラーに渡すために全体をバッファリングする必要がある。
+const MAX_BUFFERED_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// 失敗したリクエストのヘッダとボディをログに出力するミドルウェア
+///
+/// エラーステータス（4xxまたは5xx）を返したリクエストについて、設定で指定したリクエストヘッダ、
+/// 切り詰めたリクエストボディとレスポンスボディを`debug`レベルで出力する。
+///
+/// # Notes
+///
+/// 機密情報を含むヘッダやフィールド、JWTと思われる文字列は、設定にかかわらず常に伏せ字にする。
+/// 長さが不明なレスポンスボディや、バッファリングする最大バイト数を超えるレスポンスボディはバッファリングせず、
+/// レスポンスボディを省略してログに出力し、元のレスポンスをそのまま返す。
+pub async fn log_failed_request(
+    State(config): State<Arc<HttpDebugLogConfig>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (parts, body) = request.into_parts();
+    let request_body = match axum::body::to_bytes(body, MAX_BUFFERED_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::warn!(error = %e, "Failed to buffer request body for debug logging");
+            return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+        }
+    };
+    let method = parts.method.clone();
+    let uri = parts.uri.path().to_string();
+    let request_headers = redact_headers(&parts.headers, &config.headers);
+    let response = next
+        .run(Request::from_parts(parts, Body::from(request_body.clone())))
+        .await;
+
+    let status = response.status();
+    if !(status.is_client_error() || status.is_server_error()) {
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let is_bufferable = body
+        .size_hint()
+        .upper()
+        .is_some_and(|upper| upper <= MAX_BUFFERED_BODY_BYTES as u64);
+    let log_details = |response_body: &str| {
+        tracing::debug!(
+            %method,
+            uri = %uri,
+            %status,
+            request_headers = ?request_headers,
+            request_body = %format_body(&request_body, config.max_body_bytes),
+            response_body = %response_body,
+            "Failed request details"
+        );
+    };
+    if !is_bufferable {
+        log_details("<not buffered>");
+        return Response::from_parts(parts, body);
+    }
+    let response_body = match axum::body::to_bytes(body, MAX_BUFFERED_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            // 読み取りに失敗したボディは復元できないため、空のボディを元のステータスで返さずに500エラーとする
+            tracing::error!(error = %e, "Failed to read response body for debug logging");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+    log_details(&format_body(&response_body, config.max_body_bytes));
+    Response::from_parts(parts, Body::from(response_body))
+}
+
+/// ボディの機密情報を伏せ字にして、ログに出力する長さに切り詰める。
+fn format_body(body: &Bytes, max_bytes: usize) -> String {
+    truncate(&redact_body(&String::from_utf8_lossy(body)), max_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{Router, middleware, routing::get};
+    use tower::ServiceExt as _;
+
+    use super::*;
+
+    /// 指定したステータスとボディを返すルートに、ミドルウェアを適用する。
+    async fn respond(status: StatusCode, body: Vec<u8>) -> Response {
+        let app = Router::new()
+            .route("/", get(move || async move { (status, body) }))
+            .layer(middleware::from_fn_with_state(
+                Arc::new(HttpDebugLogConfig::default()),
+                log_failed_request,
+            ));
+        app.oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn failed_response_body_is_preserved() {
+        let response = respond(StatusCode::BAD_REQUEST, b"invalid".to_vec()).await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"invalid");
+    }
+
+    #[tokio::test]
+    async fn oversized_response_body_is_passed_through() {
+        let response = respond(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            vec![b'x'; MAX_BUFFERED_BODY_BYTES + 1],
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body.len(), MAX_BUFFERED_BODY_BYTES + 1);
+    }
+}
@@ -0,0 +1,68 @@
+#[cfg(feature = "actix")]
+pub mod actix;
+#[cfg(feature = "server")]
+pub mod api_key;
+#[cfg(feature = "server")]
+pub mod authorization;
+#[cfg(feature = "server")]
+pub mod authorization_policy;
+#[cfg(feature = "tower")]
+pub mod bearer_auth;
+#[cfg(feature = "server")]
+pub mod build_info;
+#[cfg(feature = "graph")]
+pub mod circuit_breaker;
+#[cfg(feature = "server")]
+pub mod cli;
+#[cfg(feature = "verify")]
+pub mod common;
+#[cfg(feature = "server")]
+pub mod confidential_client;
+#[cfg(feature = "server")]
+pub mod config;
+#[cfg(feature = "server")]
+pub mod config_summary;
+#[cfg(feature = "verify")]
+pub mod crypto;
+#[cfg(feature = "server")]
+pub mod deadline;
+#[cfg(feature = "verify")]
+pub mod entra_id;
+#[cfg(test)]
+mod fixtures;
+#[cfg(all(any(fuzzing, test), feature = "server"))]
+pub mod fuzzing;
+#[cfg(feature = "graph")]
+pub mod graph;
+#[cfg(feature = "server")]
+pub mod handlers;
+#[cfg(feature = "server")]
+pub mod http_debug_log;
+#[cfg(feature = "server")]
+pub mod log_file;
+#[cfg(feature = "verify")]
+pub mod metrics;
+#[cfg(feature = "server")]
+pub mod rate_limit;
+#[cfg(feature = "verify")]
+pub mod redaction;
+#[cfg(feature = "server")]
+pub mod request_id;
+#[cfg(feature = "server")]
+pub mod route_timeouts;
+#[cfg(feature = "obo")]
+pub mod secret_buffer;
+#[cfg(feature = "server")]
+pub mod state;
+#[cfg(feature = "verify")]
+pub mod tls_pinning;
+#[cfg(feature = "obo")]
+pub mod token_endpoint;
+#[cfg(feature = "server")]
+pub mod token_sources;
+#[cfg(feature = "obo")]
+pub mod trace_context;
+#[cfg(feature = "server")]
+pub mod trace_sampling;
+#[cfg(feature = "server")]
+pub mod user_context;
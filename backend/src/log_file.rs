@@ -0,0 +1,180 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// サイズでローテーションするログファイルのライター
+///
+/// 出力中のログファイルは`{接頭辞}`で、書き込みによって`max_size_bytes`を超えるときに、
+/// `{接頭辞}.1`へ名前を変更して新しいログファイルを作成する。
+/// ローテーション済みのログファイルは、番号が大きいほど古い。
+pub struct SizeRollingFileWriter {
+    /// ログファイルを出力するディレクトリ
+    directory: PathBuf,
+    /// ログファイル名の接頭辞
+    file_name_prefix: String,
+    /// ログファイルの最大サイズ（バイト）
+    max_size_bytes: u64,
+    /// 出力中のログファイルを含めて保持するログファイルの最大数
+    max_files: Option<usize>,
+    /// 出力中のログファイル
+    file: File,
+    /// 出力中のログファイルのサイズ（バイト）
+    size: u64,
+}
+
+impl SizeRollingFileWriter {
+    /// サイズでローテーションするログファイルのライターを作成する。
+    ///
+    /// # Arguments
+    ///
+    /// * `directory` - ログファイルを出力するディレクトリ
+    /// * `file_name_prefix` - ログファイル名の接頭辞
+    /// * `max_size_bytes` - ログファイルの最大サイズ（バイト）
+    /// * `max_files` - 出力中のログファイルを含めて保持するログファイルの最大数
+    ///
+    /// # Returns
+    ///
+    /// ライター
+    ///
+    /// # Notes
+    ///
+    /// 出力中のログファイルがすでに存在する場合は、その末尾に追記する。
+    pub fn new(
+        directory: &Path,
+        file_name_prefix: &str,
+        max_size_bytes: u64,
+        max_files: Option<usize>,
+    ) -> io::Result<Self> {
+        fs::create_dir_all(directory)?;
+        let path = directory.join(file_name_prefix);
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            directory: directory.to_path_buf(),
+            file_name_prefix: file_name_prefix.to_string(),
+            max_size_bytes,
+            max_files,
+            file,
+            size,
+        })
+    }
+
+    /// ログファイルのパスを返す。
+    ///
+    /// `index`が`0`の場合は出力中のログファイル、それ以外はローテーション済みのログファイルのパスを返す。
+    fn path(&self, index: usize) -> PathBuf {
+        match index {
+            0 => self.directory.join(&self.file_name_prefix),
+            _ => self
+                .directory
+                .join(format!("{}.{}", self.file_name_prefix, index)),
+        }
+    }
+
+    /// ログファイルをローテーションする。
+    ///
+    /// ローテーション済みのログファイルの番号を1つずつ繰り下げて、保持する最大数を超えたログファイルを削除する。
+    fn roll(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let archives = self.max_files.map(|max_files| max_files.saturating_sub(1));
+        let mut last = 0;
+        while self.path(last + 1).exists() {
+            last += 1;
+        }
+        for index in (0..=last).rev() {
+            if archives.is_some_and(|archives| index >= archives) {
+                fs::remove_file(self.path(index))?;
+            } else {
+                fs::rename(self.path(index), self.path(index + 1))?;
+            }
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(self.path(0))?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for SizeRollingFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // 空のログファイルには、最大サイズを超える場合でも書き込んで、ローテーションを繰り返さない。
+        if 0 < self.size && self.max_size_bytes < self.size + buf.len() as u64 {
+            self.roll()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// テストごとに空のディレクトリを作成する。
+    fn test_directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("backend-log-file-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    fn read(directory: &Path, name: &str) -> String {
+        fs::read_to_string(directory.join(name)).unwrap()
+    }
+
+    #[test]
+    fn rolls_when_max_size_is_exceeded() {
+        let directory = test_directory("roll");
+        let mut writer = SizeRollingFileWriter::new(&directory, "app.log", 10, None).unwrap();
+
+        writer.write_all(b"first\n").unwrap();
+        writer.write_all(b"second\n").unwrap();
+        writer.write_all(b"third\n").unwrap();
+        writer.flush().unwrap();
+
+        assert_eq!(read(&directory, "app.log"), "third\n");
+        assert_eq!(read(&directory, "app.log.1"), "second\n");
+        assert_eq!(read(&directory, "app.log.2"), "first\n");
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn removes_log_files_beyond_max_files() {
+        let directory = test_directory("retain");
+        let mut writer = SizeRollingFileWriter::new(&directory, "app.log", 4, Some(2)).unwrap();
+
+        for line in [b"one\n", b"two\n", b"six\n"] {
+            writer.write_all(line).unwrap();
+        }
+        writer.flush().unwrap();
+
+        assert_eq!(read(&directory, "app.log"), "six\n");
+        assert_eq!(read(&directory, "app.log.1"), "two\n");
+        assert!(!directory.join("app.log.2").exists());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn appends_to_existing_log_file() {
+        let directory = test_directory("append");
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("app.log"), "old\n").unwrap();
+        let mut writer = SizeRollingFileWriter::new(&directory, "app.log", 8, None).unwrap();
+
+        writer.write_all(b"new\n").unwrap();
+        writer.write_all(b"next\n").unwrap();
+        writer.flush().unwrap();
+
+        assert_eq!(read(&directory, "app.log"), "next\n");
+        assert_eq!(read(&directory, "app.log.1"), "old\nnew\n");
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
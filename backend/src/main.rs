@@ -1,34 +1,146 @@
-use std::{sync::Arc, time::Duration};
+use std::{future::IntoFuture as _, net::SocketAddr, path::Path, sync::Arc, time::Duration};
 
 use axum::http::{HeaderName, Response};
-use axum::{body::Body, http::Request};
+use axum::{Router, body::Body, http::Request};
+use clap::Parser as _;
+use opentelemetry::trace::{TraceContextExt as _, TracerProvider as _};
+use opentelemetry_sdk::trace::SdkTracerProvider;
 use tokio::net::TcpListener;
+use tokio::task::JoinSet;
 use tokio_util::sync::CancellationToken;
-use tower_http::request_id::{MakeRequestUuid, RequestId};
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, RequestId};
 use tower_http::{request_id::SetRequestIdLayer, trace::TraceLayer};
 use tracing::Span;
 use tracing::subscriber::set_global_default;
+use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_log::LogTracer;
-use tracing_subscriber::{EnvFilter, Registry, layer::SubscriberExt};
-
-mod common;
-mod config;
-mod entra_id;
-mod handlers;
-mod state;
-
-use crate::config::AppConfig;
-use crate::entra_id::{EntraIdTokenVerifier, EntraIdTokenVerifierBuilder, RetryConfig};
-use crate::handlers::create_routes;
-use crate::state::AppState;
-
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    // アプリケーション設定の読み込み
-    let app_config = AppConfig::load()?;
-    let web_server_port = app_config.web.port;
-    let client_credentials = app_config.client_credentials.clone();
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+use tracing_subscriber::{EnvFilter, Layer, Registry, layer::SubscriberExt};
+use url::Url;
+
+use backend::api_key::ApiKeyAuth;
+use backend::authorization::{GroupMembershipCache, PermissionMap, RoleScopeRegistry};
+use backend::authorization_policy::{AllowAllPolicy, AuthorizationPolicy, OpaHttpPolicy};
+use backend::circuit_breaker::CircuitBreaker;
+use backend::cli::{Cli, Command};
+use backend::confidential_client::ConfidentialClient;
+use backend::config::{
+    AppConfig, ClientCredentialsRegistry, HttpDebugLogConfig, LogFileConfig, LogFormat,
+    LogRotation, OperationalListenAddresses, PolicyConfig, RequestIdConfig, ResourceRegistry,
+    WebConfig,
+};
+use backend::deadline::attach_request_deadline;
+use backend::entra_id::{
+    ConnectionPoolConfig, EntraIdTokenVerifier, EntraIdTokenVerifierBuilder, RetryConfig,
+    UnknownKidRefreshLimits,
+};
+use backend::graph::{GRAPH_API_BASE_URI, GRAPH_RESOURCE, GraphClient, MeProfileCache};
+use backend::handlers::{create_operational_routes, create_public_routes, create_routes};
+use backend::http_debug_log::log_failed_request;
+use backend::log_file::SizeRollingFileWriter;
+use backend::rate_limit::RateLimiter;
+use backend::request_id::sanitize_incoming_request_id;
+use backend::route_timeouts::RouteTimeouts;
+use backend::state::AppState;
+use backend::token_sources::RouteTokenSources;
+use backend::trace_context::{TraceContext, X_REQUEST_ID, attach_trace_context};
+use backend::trace_sampling::TraceSampler;
+use backend::{build_info, cli, config_summary, crypto, metrics};
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    match cli.command {
+        None | Some(Command::Serve) => {
+            // アプリケーション設定の読み込み
+            //
+            // ランタイムの設定を使用するため、ランタイムを構築する前に読み込む。
+            let app_config = AppConfig::load(&cli.config, &cli.config_overrides())?;
+            let runtime = build_runtime(&app_config.web)?;
+            runtime.block_on(run(app_config))
+        }
+        Some(Command::CheckConfig) => {
+            AppConfig::load(&cli.config, &cli.config_overrides())?;
+            println!("Configuration is valid: {}", cli.config.display());
+            Ok(())
+        }
+        Some(Command::GenerateConfig { output, force }) => {
+            cli::generate_config(output.as_deref(), force)
+        }
+        Some(Command::InspectToken { token }) => cli::inspect_token(token),
+    }
+}
+
+/// 設定に従ってtokioのマルチスレッドランタイムを構築する。
+///
+/// # Arguments
+///
+/// * `config` - Webサーバー設定
+///
+/// # Returns
+///
+/// 構築したランタイム
+///
+/// # Notes
+///
+/// コンテナに割り当てられたCPU数がホストのCPU数より少ない場合に備えて、ワーカースレッド数と
+/// ブロッキングスレッドの最大数を設定できるようにする。設定しなかった項目はtokioの既定値を使用する。
+fn build_runtime(config: &WebConfig) -> anyhow::Result<tokio::runtime::Runtime> {
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    builder.enable_all();
+    // tokioは0を指定するとパニックするため、事前に検証する
+    if config.worker_threads == Some(0) || config.max_blocking_threads == Some(0) {
+        anyhow::bail!("worker_threads and max_blocking_threads must be greater than zero");
+    }
+    if let Some(worker_threads) = config.worker_threads {
+        builder.worker_threads(worker_threads);
+    }
+    if let Some(max_blocking_threads) = config.max_blocking_threads {
+        builder.max_blocking_threads(max_blocking_threads);
+    }
+    Ok(builder.build()?)
+}
+
+/// アプリケーションを実行する。
+///
+/// # Arguments
+///
+/// * `app_config` - アプリケーション設定
+async fn run(app_config: AppConfig) -> anyhow::Result<()> {
+    let bind_addresses = app_config.web.bind_addresses()?;
+    let operational_addresses = app_config
+        .web
+        .operational
+        .as_ref()
+        .map(|operational| operational.listen_addresses())
+        .transpose()?;
+    let client_credentials = ClientCredentialsRegistry::new(
+        app_config.client_credentials.clone(),
+        &app_config.entra_id.tenants,
+    );
+    let role_scopes = Arc::new(RoleScopeRegistry::new(
+        &app_config.authorization.known_roles,
+        &app_config.authorization.known_scopes,
+    ));
+    let permissions = PermissionMap::new(
+        &role_scopes,
+        app_config.authorization.role_permissions.clone(),
+        app_config.authorization.group_permissions.clone(),
+        &app_config.authorization.role_hierarchy,
+        &app_config.admin.role,
+    )
+    .map_err(|e| anyhow::anyhow!("Failed to build the permission map: {e}"))?;
+    let group_membership_cache = Arc::new(GroupMembershipCache::new(Duration::from_secs(
+        app_config.authorization.group_membership_cache_ttl,
+    )));
+    let graph = app_config.graph.clone();
+    let resources = ResourceRegistry::new(app_config.resources.clone(), &app_config.graph);
+    let trace_sampling = app_config.trace_sampling;
+    let http_debug_log = app_config.http_debug_log.clone();
+    let request_id = Arc::new(app_config.request_id.clone());
+    let route_timeouts = RouteTimeouts::new(&app_config.web.route_timeouts);
+    let token_sources = RouteTokenSources::new(&app_config.web.token_sources);
     let retry_config = RetryConfig::new(
         app_config.entra_id.jwks_request_max_attempts,
         Duration::from_millis(app_config.entra_id.jwks_request_retry_initial_wait),
@@ -37,52 +149,287 @@ async fn main() -> anyhow::Result<()> {
         app_config.entra_id.jwks_request_retry_wait_jitter_max,
         Duration::from_secs(app_config.entra_id.jwks_request_retry_max_wait),
     )?;
+    let retry_config = match app_config.entra_id.jwks_request_retry_dns_initial_wait {
+        Some(wait) => retry_config.dns_initial_wait(Duration::from_millis(wait)),
+        None => retry_config,
+    };
+    let retry_config = match app_config.entra_id.jwks_request_retry_max_total_duration {
+        Some(duration) => retry_config.max_total_duration(Duration::from_secs(duration))?,
+        None => retry_config,
+    };
+    let token_endpoint_retry = &app_config.graph.token_endpoint_retry;
+    let token_endpoint_retry = RetryConfig::new(
+        token_endpoint_retry.max_attempts,
+        Duration::from_millis(token_endpoint_retry.initial_wait),
+        token_endpoint_retry.backoff_multiplier,
+        token_endpoint_retry.wait_jitter_min,
+        token_endpoint_retry.wait_jitter_max,
+        Duration::from_millis(token_endpoint_retry.max_wait),
+    )?;
 
     // ログの設定
     LogTracer::init().map_err(|e| {
         tracing::error!(error = %e, "Failed to initialize LogTracer");
         e
     })?;
-    let subscriber = create_subscriber("entra-id-backend", &app_config.log_level);
+    // ファイルへのログ出力は、非同期に書き込むワーカーのガードを保持している間だけ有効なため、
+    // `main`関数の終了までガードを保持する
+    let (log_file_writer, _log_file_guard) = match app_config.log_file.as_ref() {
+        Some(log_file) => {
+            let (writer, guard) = create_log_file_writer(log_file)?;
+            (Some(writer), Some(guard))
+        }
+        None => (None, None),
+    };
+    let subscriber = create_subscriber(
+        "entra-id-backend",
+        &app_config.log_level,
+        app_config.log_format,
+        log_file_writer,
+    );
     set_global_default(subscriber).map_err(|e| {
         tracing::error!(error = %e, "Failed to set global default subscriber");
         e
     })?;
-    tracing::info!("Starting the application...");
+    tracing::info!(
+        version = build_info::BUILD_INFO.version,
+        git_sha = build_info::BUILD_INFO.git_sha,
+        git_dirty = build_info::BUILD_INFO.git_dirty,
+        built_at = build_info::BUILD_INFO.built_at,
+        profile = build_info::BUILD_INFO.profile,
+        features = ?build_info::BUILD_INFO.features,
+        "Starting the application..."
+    );
+    tracing::info!(
+        config = %config_summary::config_summary(&app_config),
+        "Loaded configuration"
+    );
+
+    // 暗号プロバイダの登録
+    //
+    // HTTPクライアントが使用するため、HTTPクライアントを構築する前に登録する。
+    crypto::install_default_provider().map_err(|e| {
+        tracing::error!(error = %e, "Failed to install crypto provider");
+        e
+    })?;
+    tracing::info!(
+        provider = crypto::PROVIDER_NAME,
+        "Installed crypto provider"
+    );
+
+    // メトリクスレコーダーの登録
+    let metrics_handle = metrics::install_recorder().map_err(|e| {
+        tracing::error!(error = %e, "Failed to install metrics recorder");
+        e
+    })?;
+
+    // Entra IDのトークンエンドポイントやGraph APIを呼び出すHTTPクライアントの構築
+    //
+    // コネクションプールを再利用するため、すべての外部呼び出しで同じHTTPクライアントを共有する。
+    // トークンエンドポイントとGraph APIの呼び出しでは、リクエストごとにそれぞれのタイムアウトを設定する。
+    let http_client = build_http_client(
+        Duration::from_secs(app_config.entra_id.connection_timeout),
+        Duration::from_secs(app_config.entra_id.timeout),
+        &(&app_config.graph.pool).into(),
+    )?;
+    let graph_base_url = match resources
+        .get(GRAPH_RESOURCE)
+        .and_then(|r| r.base_url.clone())
+    {
+        Some(base_url) => base_url,
+        None => Url::parse(GRAPH_API_BASE_URI)?,
+    };
+    let mut graph_client = GraphClient::new(
+        http_client.clone(),
+        &graph_base_url,
+        Duration::from_secs(app_config.graph.graph_timeout),
+    );
+    if let Some(circuit_breaker) = app_config.graph.circuit_breaker.as_ref() {
+        graph_client = graph_client.with_circuit_breaker(CircuitBreaker::new(
+            "graph",
+            circuit_breaker.failure_threshold,
+            Duration::from_secs(circuit_breaker.open_duration),
+        ));
+    }
+    let me_profile_cache = Arc::new(MeProfileCache::new(Duration::from_secs(
+        app_config.graph.me_cache_ttl,
+    )));
+
+    // 認可ポリシーの構築
+    let authorization_policy: Arc<dyn AuthorizationPolicy> =
+        match app_config.authorization.policy.clone() {
+            PolicyConfig::AllowAll => Arc::new(AllowAllPolicy),
+            PolicyConfig::OpaHttp { url } => {
+                tracing::info!(url = %url, "Using OPA authorization policy");
+                Arc::new(OpaHttpPolicy::new(http_client.clone(), url))
+            }
+        };
+
+    // APIキー認証の構築
+    let api_keys = Arc::new(ApiKeyAuth::from_config(&app_config.api_keys));
+    if !app_config.api_keys.route_groups.is_empty() {
+        tracing::info!(
+            route_groups = ?app_config.api_keys.route_groups,
+            keys = app_config.api_keys.keys.len(),
+            "API key authentication is enabled"
+        );
+    }
+
+    // 公開ルートのレートリミッターの構築
+    let public_rate_limiter = app_config
+        .web
+        .public_rate_limit
+        .as_ref()
+        .map(|rate_limit| Arc::new(RateLimiter::new(rate_limit.rps, rate_limit.burst)));
 
     // Entra IDトークン検証者の構築
     let shutdown_token = CancellationToken::new();
     let token_verifier =
         build_token_verifier(app_config, retry_config, shutdown_token.clone()).await?;
+    // 公開ルートのレートリミッターの、トークンが満たされたバケットを定期的に削除
+    if let Some(limiter) = &public_rate_limiter {
+        limiter.spawn_pruning(shutdown_token.clone());
+    }
+
+    // アプリケーション専用トークンを取得する機密クライアントの構築
+    let confidential_client = Arc::new(ConfidentialClient::new(
+        http_client.clone(),
+        client_credentials.clone(),
+        graph.authority_host.clone(),
+        Duration::from_secs(graph.token_endpoint_timeout),
+    ));
+    // 有効期限が近いアプリケーション専用トークンを、呼び出し元が要求する前に定期的に更新
+    confidential_client.spawn_renewal(shutdown_token.clone());
 
     // ルーターの作成
     let app_state = AppState {
         token_verifier,
         client_credentials,
+        permissions,
+        role_scopes,
+        group_membership_cache,
+        authorization_policy,
+        api_keys,
+        public_rate_limiter,
+        graph,
+        resources,
+        metrics_handle,
+        http_client,
+        graph_client,
+        me_profile_cache,
+        token_endpoint_retry,
+        confidential_client,
     };
-    let x_request_id = HeaderName::from_static("x-request-id");
-    let router = create_routes()
-        .with_state(app_state.clone())
-        .layer(
-            TraceLayer::new_for_http()
-                .make_span_with(make_span)
-                .on_response(on_response),
+    let trace_sampler = Arc::new(TraceSampler::new(trace_sampling));
+    let http_debug_log = if http_debug_log.enabled {
+        tracing::warn!("Debug logging of failed requests is enabled");
+        Some(Arc::new(http_debug_log))
+    } else {
+        None
+    };
+    // 運用リスナーを使用する場合、メトリクスと管理者APIは運用リスナーでのみ公開する
+    let (router, operational_router) = match operational_addresses {
+        Some(addresses) => (
+            create_public_routes(&route_timeouts, &token_sources, &app_state),
+            Some((
+                create_operational_routes(&route_timeouts, &token_sources, &app_state),
+                addresses,
+            )),
+        ),
+        None => (
+            create_routes(&route_timeouts, &token_sources, &app_state),
+            None,
+        ),
+    };
+    let unmatched = route_timeouts.unmatched();
+    if !unmatched.is_empty() {
+        tracing::error!(routes = ?unmatched, "Route timeouts are configured for unknown routes");
+        anyhow::bail!(
+            "web.route_timeouts: unknown routes: {}",
+            unmatched.join(", ")
+        );
+    }
+    let unmatched = token_sources.unmatched();
+    if !unmatched.is_empty() {
+        tracing::error!(routes = ?unmatched, "Token sources are configured for unknown or unprotected routes");
+        anyhow::bail!(
+            "web.token_sources: unknown or unprotected routes: {}",
+            unmatched.join(", ")
+        );
+    }
+    let with_layers = |router: Router<AppState>| {
+        apply_layers(
+            router.with_state(app_state.clone()),
+            http_debug_log.clone(),
+            Arc::clone(&trace_sampler),
+            Arc::clone(&request_id),
         )
-        .layer(SetRequestIdLayer::new(x_request_id, MakeRequestUuid));
+    };
+    let router = with_layers(router);
 
     // Webサーバーの起動
-    tracing::info!("Starting the web server on port {}", web_server_port);
-    let listener = TcpListener::bind(format!("0.0.0.0:{}", web_server_port)).await?;
-    axum::serve(listener, router)
-        // `shutdown_signal`関数は、非同期関数であり`impl Future<Output = ()>`を返す。
-        // したがって、axumは、`with_graceful_shutdown`で渡された`Future`が完了したとき、
-        // axumサーバーをシャットダウンする。
-        .with_graceful_shutdown(shutdown_signal(shutdown_token.clone()))
-        .await
-        .map_err(|e| {
-            tracing::error!(error = %e, "Failed to start the web server");
-            e
-        })?;
+    //
+    // 複数のアドレスで待ち受ける場合は、アドレスごとにサーバーを起動する。
+    // いずれかのサーバーが失敗した場合は、他のサーバーも停止する。
+    let mut listeners = Vec::new();
+    for address in bind_addresses {
+        listeners.push((bind_tcp_listener(address).await?, router.clone()));
+    }
+    let mut unix_listener = None;
+    if let Some((operational_router, addresses)) = operational_router {
+        let operational_router = with_layers(operational_router);
+        match addresses {
+            OperationalListenAddresses::Tcp(addresses) => {
+                for address in addresses {
+                    listeners.push((
+                        bind_tcp_listener(address).await?,
+                        operational_router.clone(),
+                    ));
+                }
+            }
+            OperationalListenAddresses::Unix(path) => {
+                unix_listener = Some((bind_unix_listener(&path)?, operational_router));
+            }
+        }
+    }
+    // `shutdown_signal`関数は、シャットダウンシグナルを受け取ったときにキャンセレーショントークンをキャンセルする。
+    // 各サーバーは、キャンセレーショントークンの子トークンがキャンセルされたとき、優雅にシャットダウンする。
+    tokio::spawn(shutdown_signal(shutdown_token.clone()));
+    let server_token = shutdown_token.child_token();
+    let mut servers = JoinSet::new();
+    for (listener, router) in listeners {
+        // 公開ルートのレート制限で接続元のIPアドレスを参照できるように、接続情報を付与する
+        servers.spawn(
+            axum::serve(
+                listener,
+                router.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(server_token.clone().cancelled_owned())
+            .into_future(),
+        );
+    }
+    #[cfg(unix)]
+    if let Some((listener, router)) = unix_listener {
+        servers.spawn(
+            axum::serve(listener, router)
+                .with_graceful_shutdown(server_token.clone().cancelled_owned())
+                .into_future(),
+        );
+    }
+    let mut server_error = None;
+    while let Some(result) = servers.join_next().await {
+        let e = match result {
+            Ok(Ok(())) => continue,
+            Ok(Err(e)) => anyhow::Error::from(e),
+            Err(e) => anyhow::Error::from(e),
+        };
+        tracing::error!(error = %e, "Failed to start the web server");
+        server_token.cancel();
+        server_error.get_or_insert(e);
+    }
+    if let Some(e) = server_error {
+        return Err(e);
+    }
 
     // Webサーバーが優雅にシャットダウンされたかをログに出力
     if shutdown_token.is_cancelled() {
@@ -91,26 +438,293 @@ async fn main() -> anyhow::Result<()> {
         tracing::warn!("Application has been shut down unexpectedly");
     }
 
+    // バックグラウンドタスクを停止して、終了するまで待機
+    app_state.token_verifier.shutdown().await;
+
     Ok(())
 }
 
+/// ルーターにミドルウェアを適用する。
+///
+/// # Arguments
+///
+/// * `router` - ルーター
+/// * `http_debug_log` - 失敗したリクエストのデバッグログの設定（出力しない場合は`None`）
+/// * `trace_sampler` - トレースのサンプラー
+/// * `request_id` - リクエストIDの設定
+///
+/// # Returns
+///
+/// ミドルウェアを適用したルーター
+///
+/// # Notes
+///
+/// リクエストIDは、受け入れられない`x-request-id`ヘッダを削除した後、ヘッダがない場合にのみ生成し、
+/// レスポンスの`x-request-id`ヘッダで返す。
+fn apply_layers(
+    mut router: Router,
+    http_debug_log: Option<Arc<HttpDebugLogConfig>>,
+    trace_sampler: Arc<TraceSampler>,
+    request_id: Arc<RequestIdConfig>,
+) -> Router {
+    if let Some(http_debug_log) = http_debug_log {
+        router = router.layer(axum::middleware::from_fn_with_state(
+            http_debug_log,
+            log_failed_request,
+        ));
+    }
+    router
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(move |request: &Request<Body>| make_span(request, &trace_sampler))
+                .on_response(on_response),
+        )
+        .layer(axum::middleware::map_request(attach_trace_context))
+        .layer(PropagateRequestIdLayer::new(HeaderName::from_static(
+            X_REQUEST_ID,
+        )))
+        .layer(SetRequestIdLayer::new(
+            HeaderName::from_static(X_REQUEST_ID),
+            MakeRequestUuid,
+        ))
+        .layer(axum::middleware::map_request_with_state(
+            request_id,
+            sanitize_incoming_request_id,
+        ))
+        .layer(axum::middleware::map_request(attach_request_deadline))
+}
+
+/// TCPのリスナーをバインドする。
+///
+/// # Arguments
+///
+/// * `address` - 待ち受けるソケットアドレス
+///
+/// # Returns
+///
+/// バインドしたリスナー
+async fn bind_tcp_listener(address: SocketAddr) -> anyhow::Result<TcpListener> {
+    let listener = TcpListener::bind(address).await.map_err(|e| {
+        tracing::error!(error = %e, address = %address, "Failed to bind the web server");
+        anyhow::anyhow!("Failed to bind the web server to {address}: {e}")
+    })?;
+    tracing::info!("Starting the web server on {}", address);
+    Ok(listener)
+}
+
+/// Unixドメインソケットのリスナーをバインドする。
+///
+/// # Arguments
+///
+/// * `path` - Unixドメインソケットのパス
+///
+/// # Returns
+///
+/// バインドしたリスナー
+///
+/// # Notes
+///
+/// 前回の起動時に作成したソケットファイルが残っている場合は、削除してからバインドする。
+/// パスの誤りで通常のファイルなどを削除しないように、ソケット以外のファイルが存在する場合はエラーにする。
+#[cfg(unix)]
+fn bind_unix_listener(path: &Path) -> anyhow::Result<tokio::net::UnixListener> {
+    use std::os::unix::fs::FileTypeExt as _;
+
+    // シンボリックリンクをたどらずに、パスに存在するファイルの種類を確認
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            tracing::warn!(path = %path.display(), "Removing stale Unix domain socket");
+            std::fs::remove_file(path)?;
+        }
+        Ok(_) => {
+            tracing::error!(path = %path.display(), "Operational listener path exists and is not a socket");
+            anyhow::bail!(
+                "Failed to bind the operational listener to {}: path exists and is not a socket",
+                path.display()
+            );
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => {
+            anyhow::bail!(
+                "Failed to inspect the operational listener path {}: {e}",
+                path.display()
+            );
+        }
+    }
+    let listener = tokio::net::UnixListener::bind(path).map_err(|e| {
+        tracing::error!(error = %e, path = %path.display(), "Failed to bind the operational listener");
+        anyhow::anyhow!("Failed to bind the operational listener to {}: {e}", path.display())
+    })?;
+    tracing::info!("Starting the operational listener on {}", path.display());
+    Ok(listener)
+}
+
+/// Unixドメインソケットをサポートしないプラットフォームでは、設定の検証でエラーにするため呼び出されない。
+#[cfg(not(unix))]
+fn bind_unix_listener(path: &Path) -> anyhow::Result<std::convert::Infallible> {
+    anyhow::bail!("Unix domain sockets are not supported: {}", path.display())
+}
+
 /// ログ購読者を作成する。
 ///
 /// # Arguments
 ///
 /// * `name` - アプリケーション名
 /// * `level` - ログレベル
+/// * `format` - ログの出力形式
+/// * `file_writer` - ログファイルへの書き込み（ファイルに出力しない場合は`None`）
 ///
 /// # Returns
 ///
 /// 作成したログ購読者
-fn create_subscriber(name: &str, level: &str) -> impl tracing::Subscriber + Send + Sync {
+///
+/// # Notes
+///
+/// `tokio-console`フィーチャーを有効にした場合、tokio-consoleにタスクの情報を送信するレイヤーを追加する。
+/// このレイヤーはtokioのランタイムが出力する`tokio`と`runtime`ターゲットのトレースを、レイヤー固有のフィルターで有効にする。
+/// これらのトレースがログに出力されないように、ログレベルのフィルターはログを出力するレイヤーにのみ適用する。
+fn create_subscriber(
+    name: &str,
+    level: &str,
+    format: LogFormat,
+    file_writer: Option<NonBlocking>,
+) -> impl tracing::Subscriber + Send + Sync {
     let env_filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(level));
-    let formatting_layer = BunyanFormattingLayer::new(name.into(), std::io::stdout);
+    #[cfg(feature = "tokio-console")]
+    let console_layer = Some(console_subscriber::spawn());
+    #[cfg(not(feature = "tokio-console"))]
+    let console_layer: Option<tracing_subscriber::layer::Identity> = None;
+    // 出力形式に対応するレイヤーのみを有効にする
+    let json_layer = (format == LogFormat::Json).then_some(JsonStorageLayer);
+    let bunyan_layer = (format == LogFormat::Json)
+        .then(|| BunyanFormattingLayer::new(name.into(), std::io::stdout));
+    let pretty_layer =
+        (format == LogFormat::Pretty).then(|| tracing_subscriber::fmt::layer().pretty());
+    let compact_layer =
+        (format == LogFormat::Compact).then(|| tracing_subscriber::fmt::layer().compact());
+    // ログファイルにも、標準出力と同じ形式で出力する
+    let file_bunyan_layer = file_writer
+        .clone()
+        .filter(|_| format == LogFormat::Json)
+        .map(|writer| BunyanFormattingLayer::new(name.into(), writer));
+    let file_pretty_layer = file_writer
+        .clone()
+        .filter(|_| format == LogFormat::Pretty)
+        .map(|writer| {
+            tracing_subscriber::fmt::layer()
+                .pretty()
+                .with_ansi(false)
+                .with_writer(writer)
+        });
+    let file_compact_layer = file_writer
+        .filter(|_| format == LogFormat::Compact)
+        .map(|writer| {
+            tracing_subscriber::fmt::layer()
+                .compact()
+                .with_ansi(false)
+                .with_writer(writer)
+        });
+    // `Option`の`and_then`と区別するため、`Layer`の`and_then`を明示して呼び出す
+    let log_layers = Layer::and_then(json_layer, bunyan_layer)
+        .and_then(file_bunyan_layer)
+        .and_then(pretty_layer)
+        .and_then(file_pretty_layer)
+        .and_then(compact_layer)
+        .and_then(file_compact_layer)
+        .with_filter(env_filter);
+    // 受信した`traceparent`を親としてスパンを呼び出し元のトレースに連結し、外部呼び出しに伝搬するスパンIDを採番する
+    //
+    // スパンはエクスポートしないため、トレーサープロバイダーにはエクスポーターを設定しない。
+    let tracer = SdkTracerProvider::builder()
+        .build()
+        .tracer(name.to_string());
+    let otel_layer = tracing_opentelemetry::layer().with_tracer(tracer);
     Registry::default()
-        .with(env_filter)
-        .with(JsonStorageLayer)
-        .with(formatting_layer)
+        .with(console_layer)
+        .with(otel_layer)
+        .with(log_layers)
+}
+
+/// ローテーションするログファイルへ非同期に書き込むライターを作成する。
+///
+/// # Arguments
+///
+/// * `config` - ファイルへのログ出力設定
+///
+/// # Returns
+///
+/// ログファイルへのライターと、ライターが書き込みを終えるまで保持するガード
+fn create_log_file_writer(config: &LogFileConfig) -> anyhow::Result<(NonBlocking, WorkerGuard)> {
+    let rotation = match config.rotation {
+        LogRotation::Minutely => Rotation::MINUTELY,
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+        LogRotation::Weekly => Rotation::WEEKLY,
+        LogRotation::Never => Rotation::NEVER,
+        LogRotation::Size => {
+            // 設定の検証で、サイズでローテーションする場合は最大サイズの指定を必須にしている。
+            let max_size_bytes = config
+                .max_size_bytes
+                .expect("max_size_bytes must be validated when rotation is size");
+            let writer = SizeRollingFileWriter::new(
+                &config.directory,
+                &config.file_name_prefix,
+                max_size_bytes,
+                config.max_files,
+            )
+            .map_err(|e| {
+                anyhow::anyhow!(
+                    "Failed to create log file writer in {}: {}",
+                    config.directory.display(),
+                    e
+                )
+            })?;
+            return Ok(tracing_appender::non_blocking(writer));
+        }
+    };
+    let mut builder = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(config.file_name_prefix.clone());
+    if let Some(max_files) = config.max_files {
+        builder = builder.max_log_files(max_files);
+    }
+    let appender = builder.build(&config.directory).map_err(|e| {
+        anyhow::anyhow!(
+            "Failed to create log file appender in {}: {}",
+            config.directory.display(),
+            e
+        )
+    })?;
+    Ok(tracing_appender::non_blocking(appender))
+}
+
+/// 外部呼び出しで共有するHTTPクライアントを構築する。
+///
+/// # Arguments
+///
+/// * `connection_timeout` - 接続タイムアウト
+/// * `timeout` - 応答待機タイムアウト
+/// * `pool_config` - コネクションプール設定
+///
+/// # Returns
+///
+/// 構築したHTTPクライアント
+fn build_http_client(
+    connection_timeout: Duration,
+    timeout: Duration,
+    pool_config: &ConnectionPoolConfig,
+) -> anyhow::Result<reqwest::Client> {
+    pool_config
+        .apply(
+            reqwest::Client::builder()
+                .connect_timeout(connection_timeout)
+                .timeout(timeout),
+        )
+        .build()
+        .map_err(|e| {
+            tracing::error!(error = %e, "Failed to build HTTP client");
+            anyhow::anyhow!(e)
+        })
 }
 
 async fn build_token_verifier(
@@ -118,18 +732,56 @@ async fn build_token_verifier(
     retry_config: RetryConfig,
     shutdown_token: CancellationToken,
 ) -> anyhow::Result<Arc<EntraIdTokenVerifier>> {
-    EntraIdTokenVerifierBuilder::default()
-        .tenants(std::mem::take(&mut app_config.entra_id.tenants))?
+    let mut builder = EntraIdTokenVerifierBuilder::default();
+    if let Some(template) = app_config.entra_id.issuer_template.take() {
+        builder = builder.issuer_template(template)?;
+    }
+    if let Some(deadline) = app_config.entra_id.startup_fetch_deadline {
+        builder = builder.startup_fetch_deadline(
+            Duration::from_secs(deadline),
+            app_config.entra_id.startup_deadline_policy,
+        )?;
+    }
+    if let Some(interval) = app_config.entra_id.min_refresh_jwks_interval {
+        builder = builder.min_refresh_jwks_interval(Duration::from_secs(interval));
+    }
+    if let Some(pins) = app_config.entra_id.jwks_tls_spki_pins.take() {
+        builder = builder.jwks_tls_spki_pins(pins)?;
+    }
+    if let Some(timeout) = app_config.entra_id.refresh_wait_timeout {
+        builder = builder.refresh_wait_timeout(Duration::from_secs(timeout))?;
+    }
+    if let Some(timeout) = app_config.entra_id.unknown_kid_refresh_timeout {
+        builder = builder.unknown_kid_refresh_timeout(Duration::from_millis(timeout))?;
+    }
+    builder
+        .tenants(
+            std::mem::take(&mut app_config.entra_id.tenants)
+                .into_iter()
+                .map(|tenant| tenant.tenant)
+                .collect(),
+        )?
         .jwk_cache_ttl(Duration::from_secs(app_config.entra_id.jwk_cache_ttl))?
+        .jwk_cache_stale_grace(Duration::from_secs(
+            app_config.entra_id.jwk_cache_stale_grace,
+        ))
         .refresh_jwks_interval(Duration::from_secs(
             app_config.entra_id.refresh_jwks_interval,
         ))?
         .refresh_tenant_jwks_interval(Duration::from_secs(
             app_config.entra_id.refresh_tenant_jwks_interval,
         ))?
+        .unknown_kid_refresh_limits(UnknownKidRefreshLimits {
+            max_waiters: app_config.entra_id.unknown_kid_refresh_max_waiters,
+            max_refreshes_per_minute: app_config.entra_id.unknown_kid_refresh_max_per_minute,
+        })?
+        .success_audit_sample_rate(app_config.entra_id.success_audit_sample_rate)?
+        .accept_organizations_issuer(app_config.entra_id.accept_organizations_issuer)
         .entra_id_connection_timeout(Duration::from_secs(app_config.entra_id.connection_timeout))?
         .entra_id_timeout(Duration::from_secs(app_config.entra_id.timeout))?
         .retry_config(retry_config)
+        .pool_config((&app_config.entra_id.jwks_pool).into())
+        .validation_options(app_config.entra_id.validation.clone())
         .shutdown(shutdown_token)
         .build()
         .await
@@ -180,18 +832,65 @@ async fn shutdown_signal(token: CancellationToken) {
     token.cancel();
 }
 
-fn make_span(request: &Request<Body>) -> Span {
+/// リクエストごとの`http_request`スパンを作成する。
+///
+/// # Arguments
+///
+/// * `request` - リクエスト
+/// * `sampler` - スパンを作成するかを判定するサンプラー
+///
+/// # Returns
+///
+/// 作成したスパン、サンプリングされなかった場合は無効なスパン
+///
+/// # Notes
+///
+/// 呼び出し元から`traceparent`を受け取った場合は、呼び出し元のスパンをOpenTelemetryのリモートの親に設定し、
+/// スパンを呼び出し元のトレースに連結する。
+/// スパンのトレースIDとスパンIDをフィールドとして記録し、呼び出し元や外部呼び出し先のログと関連付けられるようにする。
+/// `tid`、`oid`および`client_app_id`は、トークンの検証に成功した後に`auth_middleware`が記録する。
+fn make_span(request: &Request<Body>, sampler: &TraceSampler) -> Span {
+    if !sampler.should_sample() {
+        return Span::none();
+    }
     let request_id = request
         .extensions()
         .get::<RequestId>()
         .and_then(|id| id.header_value().to_str().ok())
         .unwrap_or("unknown");
-    tracing::info_span!(
+    let span = tracing::info_span!(
         "http_request",
         request_id = %request_id,
         method = %request.method(),
         uri = %request.uri().path(),
-    )
+        trace_id = tracing::field::Empty,
+        span_id = tracing::field::Empty,
+        parent_span_id = tracing::field::Empty,
+        tid = tracing::field::Empty,
+        oid = tracing::field::Empty,
+        client_app_id = tracing::field::Empty,
+    );
+    if let Some(trace) = request.extensions().get::<TraceContext>() {
+        if let Some(parent) = trace.remote_parent()
+            && let Err(e) = span.set_parent(parent)
+        {
+            tracing::warn!(error = ?e, "Failed to set the remote parent of the request span");
+        }
+        if let Some(parent_span_id) = trace.parent_span_id() {
+            span.record("parent_span_id", parent_span_id);
+        }
+        // 外部呼び出しに伝搬するスパンIDと一致させるため、OpenTelemetryのスパンコンテキストのIDを記録する
+        let context = span.context();
+        let span_context = context.span().span_context().clone();
+        if span_context.is_valid() {
+            span.record("trace_id", span_context.trace_id().to_string());
+            span.record("span_id", span_context.span_id().to_string());
+        } else {
+            span.record("trace_id", trace.trace_id());
+            span.record("span_id", trace.span_id());
+        }
+    }
+    span
 }
 
 fn on_response(response: &Response<Body>, latency: Duration, _span: &Span) {
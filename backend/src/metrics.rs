@@ -0,0 +1,92 @@
+#[cfg(feature = "server")]
+use metrics_exporter_prometheus::{BuildError, Matcher, PrometheusBuilder, PrometheusHandle};
+
+/// トークン検証に要した時間（秒）のヒストグラム
+///
+/// ラベル: `tenant`、`outcome`
+pub const VERIFY_TOKEN_DURATION_SECONDS: &str = "entra_id_verify_token_duration_seconds";
+
+/// OBOによるアクセストークンの取得に要した時間（秒）のヒストグラム
+///
+/// ラベル: `tenant`、`resource`、`outcome`
+pub const OBO_TOKEN_REQUEST_DURATION_SECONDS: &str = "obo_token_request_duration_seconds";
+
+/// Graph APIの呼び出しに要した時間（秒）のヒストグラム
+///
+/// ラベル: `endpoint`、`outcome`
+pub const GRAPH_REQUEST_DURATION_SECONDS: &str = "graph_request_duration_seconds";
+
+/// テナントのJWK公開鍵キャッシュのリフレッシュで発生した事象の回数のカウンター
+///
+/// ラベル: `tenant`、`event`（`refresh`、`failure`、`cooldown_skip`、`wait`、`wait_timeout`、`throttle`）
+pub const JWKS_REFRESH_EVENTS_TOTAL: &str = "entra_id_jwks_refresh_events_total";
+
+/// JWKsエンドポイントのホスト名の名前解決に失敗した回数のカウンター
+///
+/// ラベル: `host`
+pub const JWKS_DNS_FAILURES_TOTAL: &str = "entra_id_jwks_dns_failures_total";
+
+/// 共通エンドポイント（`common`または`organizations`）が発行したため拒否したトークンの数のカウンター
+///
+/// ラベル: `issuer`（`common`、`organizations`）
+pub const DISALLOWED_ISSUER_TENANT_TOTAL: &str = "entra_id_disallowed_issuer_tenant_total";
+
+/// バックグラウンドでのJWK公開鍵のリフレッシュに失敗した回数のカウンター
+///
+/// ラベル: `tenant`
+pub const BACKGROUND_JWKS_REFRESH_FAILURES_TOTAL: &str =
+    "entra_id_background_jwks_refresh_failures_total";
+
+/// バックグラウンドでのJWK公開鍵のリフレッシュに連続して失敗している回数のゲージ
+///
+/// ラベル: `tenant`
+pub const BACKGROUND_JWKS_REFRESH_CONSECUTIVE_FAILURES: &str =
+    "entra_id_background_jwks_refresh_consecutive_failures";
+
+/// バックグラウンドでJWK公開鍵をリフレッシュするタスクがパニックした回数のカウンター
+pub const BACKGROUND_JWKS_REFRESH_TASK_PANICS_TOTAL: &str =
+    "entra_id_background_jwks_refresh_task_panics_total";
+
+/// サーキットブレーカーが呼び出しを遮断しているか（遮断している場合は`1`）のゲージ
+///
+/// ラベル: `name`
+pub const CIRCUIT_BREAKER_OPEN: &str = "circuit_breaker_open";
+
+/// テナントを特定できなかった場合に使用するラベル値
+pub const UNKNOWN_TENANT_LABEL: &str = "unknown";
+
+/// 所要時間のヒストグラムのバケット（秒）
+///
+/// JWK公開鍵キャッシュに存在しないkidによるリフレッシュなど、p99の悪化を捉えられるように上限を広めに取る。
+#[cfg(feature = "server")]
+const DURATION_BUCKETS: &[f64] = &[
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+/// Prometheus形式でメトリクスを出力するレコーダーをグローバルに登録する。
+///
+/// # Returns
+///
+/// * メトリクスを出力するためのハンドル、またはエラー
+#[cfg(feature = "server")]
+pub fn install_recorder() -> Result<PrometheusHandle, BuildError> {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Suffix("_duration_seconds".into()),
+            DURATION_BUCKETS,
+        )?
+        .install_recorder()
+}
+
+/// 処理結果からメトリクスの`outcome`ラベル値を返す。
+///
+/// # Arguments
+///
+/// * `result` - 処理結果
+///
+/// # Returns
+///
+/// * 成功した場合は`success`、失敗した場合は`failure`
+pub fn outcome_label<T, E>(result: &Result<T, E>) -> &'static str {
+    if result.is_ok() { "success" } else { "failure" }
+}
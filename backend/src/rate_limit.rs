@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse as _, Response},
+};
+
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::common::RequestError;
+
+/// 保持するバケットの最大数
+///
+/// 接続元のIPアドレスを変えながらリクエストされても、メモリを際限なく消費しないように制限する。
+const MAX_BUCKETS: usize = 100_000;
+
+/// トークンが満たされたバケットを削除する間隔
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// IPv6アドレスのクライアントを識別するプレフィックス長
+///
+/// 1つのクライアントに/64のプレフィックスが割り当てられることが一般的で、その範囲でアドレスを自由に変えられるため、
+/// /64単位で制限する。
+const IPV6_CLIENT_PREFIX_LEN: u32 = 64;
+
+/// IPアドレスごとのトークンバケット
+struct Bucket {
+    /// 残りのトークン数
+    tokens: f64,
+    /// トークン数を更新した日時
+    updated_at: Instant,
+}
+
+/// 認証を必要としない公開ルートの、IPアドレスごとのレートリミッター
+///
+/// トークンバケット方式で、IPアドレスごとに1秒あたり`rps`個のトークンを補充し、最大`burst`個まで蓄える。
+///
+/// # Notes
+///
+/// 接続元のIPアドレスで制限するため、リバースプロキシの背後で使用する場合は、プロキシ単位で制限される。
+/// IPv6アドレスは/64のプレフィックス単位で制限する。
+pub struct RateLimiter {
+    /// 1秒あたりに補充するトークン数
+    rps: f64,
+    /// 蓄えられるトークンの最大数
+    burst: f64,
+    /// クライアントのIPアドレス（IPv6アドレスは/64のプレフィックス）をキー、トークンバケットを値としたハッシュマップ
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl RateLimiter {
+    /// コンストラクタ
+    ///
+    /// # Arguments
+    ///
+    /// * `rps` - 1秒あたりに許可するリクエスト数
+    /// * `burst` - 連続して許可するリクエストの最大数
+    pub fn new(rps: u32, burst: u32) -> Self {
+        Self {
+            rps: f64::from(rps),
+            burst: f64::from(burst),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// IPアドレスからのリクエストを許可するかを判定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `ip` - 接続元のIPアドレス
+    /// * `now` - 現在の日時
+    ///
+    /// # Returns
+    ///
+    /// * 許可する場合は`Ok(())`、許可しない場合は次のトークンが補充されるまでの時間
+    ///
+    /// # Notes
+    ///
+    /// バケットの数が上限に達している場合は、任意のバケットを1つ削除してから新しいバケットを追加する。
+    /// 削除されたクライアントは、次のリクエストでトークンが満たされたバケットから再開するため、制限が緩むだけで、
+    /// 正当なリクエストを拒否することはない。
+    fn check_at(&self, ip: IpAddr, now: Instant) -> Result<(), Duration> {
+        let key = client_key(ip);
+        let mut buckets = self
+            .buckets
+            .lock()
+            .expect("Rate limiter lock must not be poisoned");
+        if MAX_BUCKETS <= buckets.len()
+            && !buckets.contains_key(&key)
+            && let Some(evicted) = buckets.keys().next().copied()
+        {
+            buckets.remove(&evicted);
+        }
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: self.burst,
+            updated_at: now,
+        });
+        bucket.tokens = self.refill(bucket, now);
+        bucket.updated_at = now;
+        if 1.0 <= bucket.tokens {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / self.rps))
+        }
+    }
+
+    /// 経過時間に応じて補充した後のトークン数を返す。
+    fn refill(&self, bucket: &Bucket, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated_at);
+        (bucket.tokens + elapsed.as_secs_f64() * self.rps).min(self.burst)
+    }
+
+    /// トークンが満たされたバケットを削除する。
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - 現在の日時
+    ///
+    /// # Notes
+    ///
+    /// トークンが満たされたバケットは、新しいバケットと同じ状態のため、削除しても制限に影響しない。
+    fn prune_at(&self, now: Instant) {
+        let mut buckets = self
+            .buckets
+            .lock()
+            .expect("Rate limiter lock must not be poisoned");
+        buckets.retain(|_, bucket| self.refill(bucket, now) < self.burst);
+    }
+
+    /// 定期的にトークンが満たされたバケットを削除するタスクを起動する。
+    ///
+    /// # Arguments
+    ///
+    /// * `shutdown` - タスクを停止するためのキャンセルトークン
+    ///
+    /// # Returns
+    ///
+    /// * 起動したタスクのハンドル
+    pub fn spawn_pruning(self: &Arc<Self>, shutdown: CancellationToken) -> JoinHandle<()> {
+        let limiter = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tokio::select! {
+                    _ = shutdown.cancelled() => return,
+                    _ = interval.tick() => {}
+                }
+                let Some(limiter) = limiter.upgrade() else {
+                    return;
+                };
+                limiter.prune_at(Instant::now());
+            }
+        })
+    }
+}
+
+/// 接続元のIPアドレスから、レートリミットのバケットのキーを作成する。
+///
+/// # Arguments
+///
+/// * `ip` - 接続元のIPアドレス
+///
+/// # Returns
+///
+/// * IPv4アドレス（IPv4射影アドレスを含む）はそのまま、IPv6アドレスは/64のプレフィックス
+fn client_key(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(_) => ip,
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => IpAddr::V4(v4),
+            None => {
+                let mask = u128::MAX << (128 - IPV6_CLIENT_PREFIX_LEN);
+                IpAddr::V6(Ipv6Addr::from(u128::from(v6) & mask))
+            }
+        },
+    }
+}
+
+/// 公開ルートへのリクエストを、接続元のIPアドレスごとに制限するミドルウェア
+///
+/// 制限を超えた場合は、`Retry-After`ヘッダを含む429 Too Many Requestsを返す。
+///
+/// # Arguments
+///
+/// * `limiter` - レートリミッター
+/// * `request` - リクエスト
+/// * `next` - 後続のミドルウェアまたはハンドラー
+///
+/// # Returns
+///
+/// * 後続のレスポンス、または制限を超えた場合は429のレスポンス
+///
+/// # Notes
+///
+/// Unixドメインソケットで受け付けたリクエストなど、接続元のIPアドレスが不明な場合は制限しない。
+pub async fn limit_public_requests(
+    State(limiter): State<Arc<RateLimiter>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(ConnectInfo(address)) = request.extensions().get::<ConnectInfo<SocketAddr>>() else {
+        return next.run(request).await;
+    };
+    let ip = address.ip();
+    match limiter.check_at(ip, Instant::now()) {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => {
+            tracing::warn!(ip = %ip, path = %request.uri().path(), "Public route rate limit exceeded");
+            let mut response = RequestError {
+                code: StatusCode::TOO_MANY_REQUESTS,
+                message: "Too many requests".into(),
+            }
+            .into_response();
+            response.headers_mut().insert(
+                header::RETRY_AFTER,
+                HeaderValue::from(retry_after.as_secs_f64().ceil() as u64),
+            );
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_beyond_burst_are_rejected_until_tokens_are_refilled() {
+        let limiter = RateLimiter::new(2, 3);
+        let client = IpAddr::from([192, 0, 2, 1]);
+        let other = IpAddr::from([192, 0, 2, 2]);
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter.check_at(client, now).is_ok());
+        }
+        assert_eq!(
+            limiter.check_at(client, now),
+            Err(Duration::from_millis(500))
+        );
+        assert!(limiter.check_at(other, now).is_ok());
+        assert!(
+            limiter
+                .check_at(client, now + Duration::from_millis(500))
+                .is_ok()
+        );
+        assert!(
+            limiter
+                .check_at(client, now + Duration::from_millis(500))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn ipv6_clients_share_a_bucket_per_64_prefix() {
+        let limiter = RateLimiter::new(1, 1);
+        let now = Instant::now();
+        let client: IpAddr = "2001:db8:1:2::1".parse().unwrap();
+        let same_prefix: IpAddr = "2001:db8:1:2:ffff::2".parse().unwrap();
+        let other_prefix: IpAddr = "2001:db8:1:3::1".parse().unwrap();
+
+        assert!(limiter.check_at(client, now).is_ok());
+        assert!(limiter.check_at(same_prefix, now).is_err());
+        assert!(limiter.check_at(other_prefix, now).is_ok());
+        assert_eq!(
+            client_key("::ffff:192.0.2.1".parse().unwrap()),
+            IpAddr::from([192, 0, 2, 1])
+        );
+    }
+
+    #[test]
+    fn buckets_are_capped_and_full_buckets_are_pruned() {
+        let limiter = RateLimiter::new(1, 1);
+        let now = Instant::now();
+
+        for i in 0..=MAX_BUCKETS as u32 {
+            let _ = limiter.check_at(IpAddr::from(i.to_be_bytes()), now);
+        }
+        assert_eq!(limiter.buckets.lock().unwrap().len(), MAX_BUCKETS);
+
+        limiter.prune_at(now + Duration::from_secs(1));
+        assert!(limiter.buckets.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,69 @@
+use std::sync::Arc;
+
+use axum::extract::{Request, State};
+
+use crate::{config::RequestIdConfig, trace_context::X_REQUEST_ID};
+
+/// 受け入れられない`x-request-id`ヘッダをリクエストから削除するミドルウェア
+///
+/// 削除した場合は、後続の`SetRequestIdLayer`がリクエストIDを生成する。
+///
+/// # Notes
+///
+/// ログの改ざんやログ基盤の負荷を避けるため、信頼する設定の場合でも、最大長を超える値や
+/// 英数字と`-`、`_`、`.`、`:`以外の文字を含む値は受け入れない。
+pub async fn sanitize_incoming_request_id(
+    State(config): State<Arc<RequestIdConfig>>,
+    mut request: Request,
+) -> Request {
+    let accepted = config.trust_incoming
+        && request
+            .headers()
+            .get(X_REQUEST_ID)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| is_valid_request_id(value, config.max_length));
+    if !accepted && request.headers_mut().remove(X_REQUEST_ID).is_some() {
+        tracing::debug!("Discarded incoming x-request-id header");
+    }
+    request
+}
+
+/// リクエストIDとして受け入れられる値かを判定する。
+///
+/// # Arguments
+///
+/// * `value` - `x-request-id`ヘッダの値
+/// * `max_length` - 最大長
+///
+/// # Returns
+///
+/// * 受け入れられる場合は`true`
+fn is_valid_request_id(value: &str, max_length: usize) -> bool {
+    !value.is_empty()
+        && value.len() <= max_length
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_gateway_assigned_ids() {
+        assert!(is_valid_request_id(
+            "3f2b8c1e-9a4d-4e7b-8f6a-2c1d0e9b7a65",
+            128
+        ));
+        assert!(is_valid_request_id("gw:req_20261016.0001", 128));
+    }
+
+    #[test]
+    fn rejects_empty_too_long_or_unsafe_ids() {
+        assert!(!is_valid_request_id("", 128));
+        assert!(!is_valid_request_id(&"a".repeat(129), 128));
+        assert!(!is_valid_request_id("id with spaces", 128));
+        assert!(!is_valid_request_id("id\"injected", 128));
+    }
+}
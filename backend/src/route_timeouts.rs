@@ -0,0 +1,83 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use axum::routing::MethodRouter;
+use tower_http::timeout::TimeoutLayer;
+
+use crate::state::AppState;
+
+/// ルートごとのタイムアウト
+///
+/// ルートのパターン（例: `/api/me`）をキーとして、ハンドラーの処理に許可する時間を設定する。
+/// タイムアウトした場合は、504 Gateway Timeoutを返す。
+pub struct RouteTimeouts {
+    /// ルートのパターンをキー、タイムアウトを値としたハッシュマップ
+    timeouts: HashMap<String, Duration>,
+    /// タイムアウトを適用したルートのパターン
+    applied: Mutex<HashSet<String>>,
+}
+
+impl RouteTimeouts {
+    /// コンストラクタ
+    ///
+    /// # Arguments
+    ///
+    /// * `timeouts` - ルートのパターンをキー、タイムアウト（ミリ秒）を値としたハッシュマップ
+    pub fn new(timeouts: &HashMap<String, u64>) -> Self {
+        Self {
+            timeouts: timeouts
+                .iter()
+                .map(|(pattern, timeout)| (pattern.clone(), Duration::from_millis(*timeout)))
+                .collect(),
+            applied: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// ルートにタイムアウトが設定されている場合は、タイムアウトを適用する。
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - ネストしたルーターのパスを含む、ルートのパターン
+    /// * `method_router` - ルートのメソッドルーター
+    ///
+    /// # Returns
+    ///
+    /// * タイムアウトを適用したメソッドルーター
+    pub fn apply(
+        &self,
+        pattern: &str,
+        method_router: MethodRouter<AppState>,
+    ) -> MethodRouter<AppState> {
+        let Some(timeout) = self.timeouts.get(pattern) else {
+            return method_router;
+        };
+        self.applied
+            .lock()
+            .expect("Route timeouts lock must not be poisoned")
+            .insert(pattern.to_string());
+        method_router.layer(TimeoutLayer::with_status_code(
+            StatusCode::GATEWAY_TIMEOUT,
+            *timeout,
+        ))
+    }
+
+    /// タイムアウトを設定したが、どのルートにも一致しなかったパターンを返す。
+    ///
+    /// ルートを作成した後に呼び出して、設定の誤りを検出する。
+    pub fn unmatched(&self) -> Vec<String> {
+        let applied = self
+            .applied
+            .lock()
+            .expect("Route timeouts lock must not be poisoned");
+        let mut unmatched: Vec<_> = self
+            .timeouts
+            .keys()
+            .filter(|pattern| !applied.contains(*pattern))
+            .cloned()
+            .collect();
+        unmatched.sort();
+        unmatched
+    }
+}
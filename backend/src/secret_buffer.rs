@@ -0,0 +1,109 @@
+use bytes::Bytes;
+use http::HeaderValue;
+use secrecy::{ExposeSecret as _, SecretString};
+use zeroize::{Zeroize as _, Zeroizing};
+
+/// `Authorization`ヘッダのBearerスキームの接頭辞
+const BEARER_PREFIX: &str = "Bearer ";
+
+/// 破棄時に内容を消去するバッファーを作成する。
+///
+/// # Arguments
+///
+/// * `buffer` - 秘密情報を含むバッファー
+///
+/// # Returns
+///
+/// * バッファーを所有するバイト列
+///
+/// # Notes
+///
+/// reqwestやhyperは、リクエストボディやヘッダの値を`Bytes`で保持して、送信後に消去せずに解放する。
+/// バッファーの所有者を`Zeroizing`にすることで、最後の参照が破棄されたときに内容を消去する。
+fn zeroizing_bytes(buffer: Vec<u8>) -> Bytes {
+    Bytes::from_owner(Zeroizing::new(buffer))
+}
+
+/// `application/x-www-form-urlencoded`形式のリクエストボディを作成する。
+///
+/// # Arguments
+///
+/// * `params` - フォームのパラメーター
+///
+/// # Returns
+///
+/// * 破棄時に内容を消去するリクエストボディ
+///
+/// # Notes
+///
+/// クライアントシークレットやOBOの`assertion`を含むため、エンコードの途中で再確保したバッファーが
+/// 消去されずに解放されないように、エンコード後の最大長を事前に確保する。
+pub fn form_body(params: &[(&str, &str)]) -> Bytes {
+    // パーセントエンコードで1バイトが最大3バイトになり、区切り文字の`=`と`&`が加わる
+    let capacity = params
+        .iter()
+        .map(|(name, value)| (name.len() + value.len()) * 3 + 2)
+        .sum();
+    let encoded = url::form_urlencoded::Serializer::new(String::with_capacity(capacity))
+        .extend_pairs(params)
+        .finish();
+    zeroizing_bytes(encoded.into_bytes())
+}
+
+/// Bearerスキームの`Authorization`ヘッダの値を作成する。
+///
+/// # Arguments
+///
+/// * `access_token` - アクセストークン
+///
+/// # Returns
+///
+/// * 破棄時に内容を消去する、機密としてマークしたヘッダの値
+///
+/// # Panics
+///
+/// アクセストークンにヘッダの値として使用できない文字が含まれている場合はパニックする。
+/// アクセストークンはEntra IDが発行したJWTであるため、発生しない。
+pub fn bearer_authorization(access_token: &SecretString) -> HeaderValue {
+    let token = access_token.expose_secret();
+    let mut buffer = Vec::with_capacity(BEARER_PREFIX.len() + token.len());
+    buffer.extend_from_slice(BEARER_PREFIX.as_bytes());
+    buffer.extend_from_slice(token.as_bytes());
+    let mut value = HeaderValue::from_maybe_shared(zeroizing_bytes(buffer))
+        .expect("Access token must be a valid header value");
+    value.set_sensitive(true);
+    value
+}
+
+/// 他から参照されていない場合に、バイト列の内容を消去する。
+///
+/// # Arguments
+///
+/// * `bytes` - 秘密情報を含むバイト列
+///
+/// # Notes
+///
+/// レスポンスボディがコネクションの受信バッファーと共有されている場合は消去できない。
+pub fn zeroize_if_unique(bytes: Bytes) {
+    if let Ok(mut bytes) = bytes.try_into_mut() {
+        bytes[..].zeroize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn form_body_encodes_params() {
+        let body = form_body(&[
+            ("grant_type", "client_credentials"),
+            ("client_secret", "a b&c"),
+        ]);
+
+        assert_eq!(
+            &body[..],
+            b"grant_type=client_credentials&client_secret=a+b%26c"
+        );
+    }
+}
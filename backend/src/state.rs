@@ -1,9 +1,132 @@
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::{config::ClientCredentials, entra_id::EntraIdTokenVerifier};
+use axum::http::StatusCode;
+use metrics_exporter_prometheus::PrometheusHandle;
+use secrecy::{ExposeSecret as _, SecretString};
+
+use crate::{
+    api_key::ApiKeyAuth,
+    authorization::{GroupMembershipCache, PermissionMap, RoleScopeRegistry},
+    authorization_policy::AuthorizationPolicy,
+    common::{AppResult, RequestError},
+    confidential_client::ConfidentialClient,
+    config::{ClientCredentialsRegistry, GraphConfig, ResourceRegistry},
+    entra_id::{
+        BearerToken, Claims, EntraIdTokenVerifier, RetryConfig, TenantId, extract_issuer_from_iss,
+    },
+    graph::{GraphClient, MeProfileCache},
+    rate_limit::RateLimiter,
+    token_endpoint::{request_token_with_retry, token_endpoint_uri},
+    trace_context::TraceContext,
+};
 
 #[derive(Clone)]
 pub struct AppState {
     pub token_verifier: Arc<EntraIdTokenVerifier>,
-    pub client_credentials: ClientCredentials,
+    pub client_credentials: ClientCredentialsRegistry,
+    pub permissions: PermissionMap,
+    pub role_scopes: Arc<RoleScopeRegistry>,
+    pub group_membership_cache: Arc<GroupMembershipCache>,
+    pub authorization_policy: Arc<dyn AuthorizationPolicy>,
+    pub api_keys: Arc<ApiKeyAuth>,
+    pub public_rate_limiter: Option<Arc<RateLimiter>>,
+    pub graph: GraphConfig,
+    pub resources: ResourceRegistry,
+    pub metrics_handle: PrometheusHandle,
+    pub http_client: reqwest::Client,
+    pub graph_client: GraphClient,
+    pub me_profile_cache: Arc<MeProfileCache>,
+    pub token_endpoint_retry: RetryConfig,
+    pub confidential_client: Arc<ConfidentialClient>,
+}
+
+impl AppState {
+    /// OBOで、指定した下流リソースを呼び出すためのアクセストークンを取得する。
+    ///
+    /// # Arguments
+    ///
+    /// * `resource` - 設定ファイルの`resources`に登録した下流リソースの名前（例: `graph`）
+    /// * `claims` - 検証済みのクレーム
+    /// * `access_token` - バックエンド用のアクセストークン
+    /// * `trace` - 伝搬するトレースコンテキスト
+    ///
+    /// # Returns
+    ///
+    /// * 下流リソース用のアクセストークン、またはエラー
+    pub async fn acquire_obo_token(
+        &self,
+        resource: &str,
+        claims: &Claims,
+        access_token: &BearerToken,
+        trace: &TraceContext,
+    ) -> AppResult<SecretString> {
+        let scope = self
+            .resources
+            .get(resource)
+            .ok_or_else(|| {
+                tracing::error!(resource = resource, "Downstream resource is not configured");
+                RequestError {
+                    code: StatusCode::INTERNAL_SERVER_ERROR,
+                    message: format!("Downstream resource is not configured: {resource}"),
+                }
+            })?
+            .scope();
+
+        // テナントIDを取得
+        //
+        // 検証済みのクレームにtidが含まれている場合はtidを、含まれていない場合はissから抽出したテナントIDを使用する。
+        let tenant_id = match &claims.tid {
+            Some(tid) => TenantId::try_from(tid.clone()).map_err(|e| {
+                tracing::error!(error = %e, "Invalid tid claim");
+                RequestError::unauthorized(format!("Invalid tid claim: {e}"))
+            })?,
+            None => extract_issuer_from_iss(&claims.iss).map_err(|e| {
+                tracing::error!(error = %e, "Failed to extract tenant ID from iss");
+                RequestError::unauthorized(format!("Failed to extract tenant ID from iss: {e}"))
+            })?,
+        };
+        let credentials = self.client_credentials.for_tenant(&tenant_id);
+
+        // The user or administrator has not consented to use the application with ID ...
+        // のようなエラーが出た場合、管理者がバックエンドアプリケーションに対して
+        // 下流リソース（Graph APIなど）のアクセス許可を付与していない可能性がある。
+        //
+        // また、バックエンドアプリケーションに対して、Graph APIのUser.Readなどのアクセス許可を追加しても、管理者の同意が必要になる。
+        // Entra ID画面でUser.Readの行に緑のチェックマークが付いていることを確認すること。
+        //
+        // 要求するスコープは設定ファイルの`resources`（Graph APIは`graph.scopes`でも可）で指定する。
+        let uri = token_endpoint_uri(&self.graph.authority_host, &tenant_id);
+        let params = [
+            ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+            ("client_id", &credentials.client_id.0),
+            ("client_secret", credentials.client_secret.expose_secret()),
+            ("assertion", access_token.0.expose_secret()),
+            ("scope", &scope),
+            ("requested_token_use", "on_behalf_of"),
+        ];
+        let started_at = Instant::now();
+        let token_response = request_token_with_retry(
+            &self.http_client,
+            &uri,
+            &params,
+            Duration::from_secs(self.graph.token_endpoint_timeout),
+            &self.token_endpoint_retry,
+            trace,
+        )
+        .await;
+        metrics::histogram!(
+            crate::metrics::OBO_TOKEN_REQUEST_DURATION_SECONDS,
+            "tenant" => self.token_verifier.tenant_label(&tenant_id),
+            "resource" => resource.to_string(),
+            "outcome" => crate::metrics::outcome_label(&token_response),
+        )
+        .record(started_at.elapsed().as_secs_f64());
+        let token_response = token_response.map_err(|e| {
+            tracing::error!(resource = resource, error = %e, "Failed to acquire downstream access token");
+            RequestError::from(e)
+        })?;
+
+        Ok(token_response.access_token)
+    }
 }
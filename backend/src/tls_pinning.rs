@@ -0,0 +1,167 @@
+use std::sync::Arc;
+
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, SignatureScheme};
+use serde::Deserialize;
+use sha2::{Digest as _, Sha256};
+
+/// ピンの文字列に付ける、ハッシュアルゴリズムを示す接頭辞
+const SPKI_PIN_PREFIX: &str = "sha256/";
+
+/// 証明書の公開鍵（SubjectPublicKeyInfo）のSHA-256ハッシュによるピン
+///
+/// 設定ファイルでは、`sha256/`に続けてBase64エンコードしたハッシュを指定する（`sha256/`は省略可）。
+/// 次のコマンドで、サーバー証明書のピンを計算できる。
+///
+/// ```text
+/// openssl s_client -connect login.microsoftonline.com:443 </dev/null 2>/dev/null \
+///   | openssl x509 -pubkey -noout \
+///   | openssl pkey -pubin -outform der \
+///   | openssl dgst -sha256 -binary | base64
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct SpkiPin([u8; 32]);
+
+impl TryFrom<String> for SpkiPin {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let encoded = value.strip_prefix(SPKI_PIN_PREFIX).unwrap_or(&value);
+        let hash = STANDARD
+            .decode(encoded)
+            .map_err(|e| format!("Invalid SPKI pin '{value}': {e}"))?;
+        let hash = hash
+            .try_into()
+            .map_err(|_| format!("Invalid SPKI pin '{value}': must be a SHA-256 hash"))?;
+        Ok(Self(hash))
+    }
+}
+
+/// 証明書チェーンの検証に加えて、公開鍵のピンを検証するサーバー証明書の検証者
+///
+/// プロキシやDNSの乗っ取りによって、信頼されたCAが発行した別の証明書を提示された場合でも接続を拒否する。
+#[derive(Debug)]
+struct SpkiPinningVerifier {
+    /// 証明書チェーンを検証する検証者
+    inner: Arc<dyn ServerCertVerifier>,
+    /// 許可する公開鍵のピン
+    pins: Vec<SpkiPin>,
+}
+
+impl SpkiPinningVerifier {
+    /// 証明書の公開鍵がピンのいずれかと一致するかを判定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `cert` - DERエンコードされた証明書
+    ///
+    /// # Returns
+    ///
+    /// * 一致する場合は`true`、一致しない場合や証明書をパースできない場合は`false`
+    fn matches(&self, cert: &CertificateDer<'_>) -> bool {
+        let Ok(cert) = webpki::EndEntityCert::try_from(cert) else {
+            return false;
+        };
+        let hash: [u8; 32] = Sha256::digest(cert.subject_public_key_info().as_ref()).into();
+        self.pins.iter().any(|pin| pin.0 == hash)
+    }
+}
+
+impl ServerCertVerifier for SpkiPinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        // 証明書チェーンを検証した後、サーバー証明書または中間証明書の公開鍵がピンと一致するかを検証
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        )?;
+        if std::iter::once(end_entity)
+            .chain(intermediates)
+            .any(|cert| self.matches(cert))
+        {
+            return Ok(verified);
+        }
+        tracing::error!(server_name = ?server_name, "Server certificate does not match any SPKI pin");
+        Err(rustls::Error::General(
+            "Server certificate does not match any SPKI pin".into(),
+        ))
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// 公開鍵のピンを検証するTLSクライアント設定を作成する。
+///
+/// # Arguments
+///
+/// * `pins` - 許可する公開鍵のピン
+///
+/// # Returns
+///
+/// * TLSクライアント設定、またはエラー
+///
+/// # Notes
+///
+/// 証明書チェーンは、reqwestの既定と同じくOSの証明書ストアで検証する。
+/// 暗号プロバイダには、フィーチャーで選択した暗号プロバイダを使用する。
+/// サーバー証明書は定期的に更新されるため、中間証明書のピンや更新後の証明書のピンなど、予備のピンも指定すること。
+pub fn pinned_tls_config(pins: Vec<SpkiPin>) -> Result<ClientConfig, rustls::Error> {
+    let provider = Arc::new(crate::crypto::tls_provider());
+    let verifier = SpkiPinningVerifier {
+        inner: Arc::new(rustls_platform_verifier::Verifier::new(provider.clone())?),
+        pins,
+    };
+    Ok(ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spki_pin_accepts_optional_prefix_and_rejects_non_sha256_length() {
+        let encoded = STANDARD.encode([7u8; 32]);
+
+        assert_eq!(
+            SpkiPin::try_from(format!("sha256/{encoded}")).unwrap(),
+            SpkiPin::try_from(encoded).unwrap()
+        );
+        assert!(SpkiPin::try_from(STANDARD.encode([7u8; 20])).is_err());
+    }
+}
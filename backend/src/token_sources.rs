@@ -0,0 +1,207 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use axum::Extension;
+use axum::http::{header, request::Parts};
+use axum::routing::MethodRouter;
+use axum_extra::headers::{Cookie, HeaderMapExt as _};
+use secrecy::SecretString;
+
+use crate::config::TokenSourceConfig;
+use crate::entra_id::BearerToken;
+use crate::state::AppState;
+
+/// `Authorization`ヘッダ以外からBearerトークンを取得する方法
+///
+/// ルートに設定した場合、リクエストのエクステンションに挿入され、`AuthClaims`が参照する。
+#[derive(Clone)]
+pub struct TokenSource {
+    /// Bearerトークンを取得するクエリパラメーターの名前
+    query: Option<String>,
+    /// Bearerトークンを取得するクッキーの名前
+    cookie: Option<String>,
+}
+
+impl From<&TokenSourceConfig> for TokenSource {
+    fn from(config: &TokenSourceConfig) -> Self {
+        Self {
+            query: config.query.clone(),
+            cookie: config.cookie.clone(),
+        }
+    }
+}
+
+impl TokenSource {
+    /// クエリパラメーター、クッキーの順にBearerトークンを取得する。
+    ///
+    /// # Arguments
+    ///
+    /// * `parts` - リクエストの構成要素
+    ///
+    /// # Returns
+    ///
+    /// * Bearerトークン、またはどちらにも存在しない場合は`None`
+    pub fn token(&self, parts: &Parts) -> Option<BearerToken> {
+        let from_query = self.query.as_deref().and_then(|name| {
+            url::form_urlencoded::parse(parts.uri.query()?.as_bytes())
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+        });
+        let token = from_query.or_else(|| {
+            let name = self.cookie.as_deref()?;
+            let cookie = parts.headers.typed_get::<Cookie>()?;
+            cookie.get(name).map(str::to_string)
+        })?;
+        let token = token.trim();
+        (!token.is_empty()).then(|| BearerToken(SecretString::new(token.into())))
+    }
+
+    /// リクエストにBearerトークンが含まれているかを判定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `parts` - リクエストの構成要素
+    /// * `source` - ルートに設定された、`Authorization`ヘッダ以外からBearerトークンを取得する方法
+    ///
+    /// # Returns
+    ///
+    /// * `Authorization`ヘッダ、またはルートに設定されたクエリパラメーターやクッキーが存在する場合は`true`
+    pub fn is_present(parts: &Parts, source: Option<&Self>) -> bool {
+        parts.headers.contains_key(header::AUTHORIZATION)
+            || source.is_some_and(|source| source.token(parts).is_some())
+    }
+}
+
+/// ルートごとのBearerトークンの取得方法
+///
+/// ルートのパターン（例: `/api/me`）をキーとして、`Authorization`ヘッダ以外からBearerトークンを取得する方法を設定する。
+/// 設定しなかったルートでは、`Authorization`ヘッダからのみBearerトークンを取得する。
+pub struct RouteTokenSources {
+    /// ルートのパターンをキー、Bearerトークンの取得方法を値としたハッシュマップ
+    sources: HashMap<String, TokenSource>,
+    /// Bearerトークンの取得方法を適用したルートのパターン
+    applied: Mutex<HashSet<String>>,
+}
+
+impl RouteTokenSources {
+    /// コンストラクタ
+    ///
+    /// # Arguments
+    ///
+    /// * `sources` - ルートのパターンをキー、Bearerトークンの取得方法を値としたハッシュマップ
+    pub fn new(sources: &HashMap<String, TokenSourceConfig>) -> Self {
+        Self {
+            sources: sources
+                .iter()
+                .map(|(pattern, source)| (pattern.clone(), source.into()))
+                .collect(),
+            applied: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// ルートにBearerトークンの取得方法が設定されている場合は、取得方法をリクエストのエクステンションに挿入する。
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - ネストしたルーターのパスを含む、ルートのパターン
+    /// * `method_router` - ルートのメソッドルーター
+    ///
+    /// # Returns
+    ///
+    /// * Bearerトークンの取得方法を適用したメソッドルーター
+    ///
+    /// # Notes
+    ///
+    /// トークンを検証するミドルウェアより外側に適用する必要があるため、ミドルウェアを適用したメソッドルーターを渡す。
+    pub fn apply(
+        &self,
+        pattern: &str,
+        method_router: MethodRouter<AppState>,
+    ) -> MethodRouter<AppState> {
+        let Some(source) = self.sources.get(pattern) else {
+            return method_router;
+        };
+        if let Some(query) = &source.query {
+            tracing::warn!(
+                route = pattern,
+                query = %query,
+                "Bearer token is accepted from a query parameter; it may be recorded in access logs and browser history"
+            );
+        }
+        if let Some(cookie) = &source.cookie {
+            tracing::warn!(
+                route = pattern,
+                cookie = %cookie,
+                "Bearer token is accepted from a cookie; the route must be protected against CSRF"
+            );
+        }
+        self.applied
+            .lock()
+            .expect("Route token sources lock must not be poisoned")
+            .insert(pattern.to_string());
+        method_router.layer(Extension(source.clone()))
+    }
+
+    /// Bearerトークンの取得方法を設定したが、どの保護されたルートにも一致しなかったパターンを返す。
+    ///
+    /// ルートを作成した後に呼び出して、設定の誤りを検出する。
+    pub fn unmatched(&self) -> Vec<String> {
+        let applied = self
+            .applied
+            .lock()
+            .expect("Route token sources lock must not be poisoned");
+        let mut unmatched: Vec<_> = self
+            .sources
+            .keys()
+            .filter(|pattern| !applied.contains(*pattern))
+            .cloned()
+            .collect();
+        unmatched.sort();
+        unmatched
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Request;
+    use secrecy::ExposeSecret as _;
+
+    use super::*;
+
+    fn parts(uri: &str, cookie: Option<&str>) -> Parts {
+        let mut request = Request::builder().uri(uri);
+        if let Some(cookie) = cookie {
+            request = request.header(header::COOKIE, cookie);
+        }
+        request.body(()).unwrap().into_parts().0
+    }
+
+    fn token(source: &TokenSource, parts: &Parts) -> Option<String> {
+        source
+            .token(parts)
+            .map(|token| token.0.expose_secret().to_string())
+    }
+
+    #[test]
+    fn token_is_read_only_from_configured_query_parameter_or_cookie() {
+        let source = TokenSource {
+            query: Some("access_token".into()),
+            cookie: Some("session".into()),
+        };
+
+        let query = parts("/api/me?access_token=a%2Eb&x=1", Some("session=c.d"));
+        assert_eq!(token(&source, &query).as_deref(), Some("a.b"));
+        let cookie = parts("/api/me?x=1", Some("other=1; session=c.d"));
+        assert_eq!(token(&source, &cookie).as_deref(), Some("c.d"));
+        let empty = parts("/api/me?access_token=", None);
+        assert_eq!(token(&source, &empty), None);
+
+        let header_only = TokenSource {
+            query: None,
+            cookie: None,
+        };
+        assert_eq!(token(&header_only, &query), None);
+        assert!(!TokenSource::is_present(&query, Some(&header_only)));
+        assert!(TokenSource::is_present(&query, Some(&source)));
+    }
+}
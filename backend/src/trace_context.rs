@@ -0,0 +1,431 @@
+#[cfg(feature = "server")]
+use std::convert::Infallible;
+
+#[cfg(feature = "server")]
+use axum::extract::{FromRequestParts, Request};
+use http::HeaderMap;
+#[cfg(feature = "server")]
+use http::request::Parts;
+use rand::RngCore as _;
+#[cfg(feature = "server")]
+use tower_http::request_id::RequestId;
+
+/// W3C Trace Contextの`traceparent`ヘッダ名
+pub const TRACEPARENT: &str = "traceparent";
+
+/// W3C Trace Contextの`tracestate`ヘッダ名
+pub const TRACESTATE: &str = "tracestate";
+
+/// リクエストIDのヘッダ名
+pub const X_REQUEST_ID: &str = "x-request-id";
+
+/// `traceparent`のバージョン
+const TRACEPARENT_VERSION: &str = "00";
+
+/// 無効な`traceparent`のバージョン
+const INVALID_TRACEPARENT_VERSION: &str = "ff";
+
+/// サンプリングされたことを示す`trace-flags`
+const SAMPLED_FLAGS: &str = "01";
+
+/// W3C Trace Context
+///
+/// 受信したリクエストの`traceparent`を引き継ぎ、外部呼び出しに伝搬する。
+/// 受信したリクエストに有効な`traceparent`が含まれていない場合は、新しいトレースを開始する。
+///
+/// 呼び出し元のスパンは、OpenTelemetryのリモートの親スパンコンテキストとして`http_request`スパンに設定し、
+/// このサービスのスパンを呼び出し元のトレースに連結する（[`TraceContext::remote_parent`]を参照）。
+#[derive(Debug, Clone)]
+pub struct TraceContext {
+    /// トレースID（32桁の16進数）
+    trace_id: String,
+    /// このサービスでリクエストを処理するスパンのID（16桁の16進数）
+    span_id: String,
+    /// 呼び出し元のスパンID（16桁の16進数）
+    ///
+    /// 新しいトレースを開始した場合は`None`
+    parent_span_id: Option<String>,
+    /// トレースフラグ（2桁の16進数）
+    flags: String,
+    /// ベンダー固有のトレース情報
+    tracestate: Option<String>,
+    /// リクエストID
+    request_id: Option<String>,
+}
+
+impl TraceContext {
+    /// 新しいトレースを開始する。
+    ///
+    /// バックグラウンドジョブなど、受信したリクエストを伴わない外部呼び出しで使用する。
+    pub fn new_root() -> Self {
+        Self {
+            trace_id: random_hex::<16>(),
+            span_id: random_hex::<8>(),
+            parent_span_id: None,
+            flags: SAMPLED_FLAGS.into(),
+            tracestate: None,
+            request_id: None,
+        }
+    }
+
+    /// 受信したリクエストのヘッダからトレースコンテキストを作成する。
+    ///
+    /// # Arguments
+    ///
+    /// * `headers` - 受信したリクエストのヘッダ
+    /// * `request_id` - リクエストID
+    ///
+    /// # Returns
+    ///
+    /// * `traceparent`が有効な場合はそのトレースを引き継いだトレースコンテキスト、そうでない場合は新しいトレースコンテキスト
+    pub fn from_headers(headers: &HeaderMap, request_id: Option<String>) -> Self {
+        let parsed = headers
+            .get(TRACEPARENT)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_traceparent);
+        match parsed {
+            Some((trace_id, parent_span_id, flags)) => Self {
+                trace_id,
+                span_id: random_hex::<8>(),
+                parent_span_id: Some(parent_span_id),
+                flags,
+                tracestate: headers
+                    .get(TRACESTATE)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_string),
+                request_id,
+            },
+            None => Self {
+                request_id,
+                ..Self::new_root()
+            },
+        }
+    }
+
+    /// トレースIDを返す。
+    pub fn trace_id(&self) -> &str {
+        &self.trace_id
+    }
+
+    /// このサービスでリクエストを処理するスパンのIDを返す。
+    pub fn span_id(&self) -> &str {
+        &self.span_id
+    }
+
+    /// 呼び出し元のスパンIDを返す。
+    pub fn parent_span_id(&self) -> Option<&str> {
+        self.parent_span_id.as_deref()
+    }
+
+    /// 呼び出し元のスパンを、OpenTelemetryのリモートの親スパンコンテキストとして返す。
+    ///
+    /// # Returns
+    ///
+    /// * 呼び出し元のスパンを親とするコンテキスト、新しいトレースを開始した場合は`None`
+    #[cfg(feature = "server")]
+    pub fn remote_parent(&self) -> Option<opentelemetry::Context> {
+        use opentelemetry::trace::{
+            SpanContext, SpanId, TraceContextExt as _, TraceFlags, TraceId,
+        };
+
+        let parent_span_id = self.parent_span_id.as_deref()?;
+        let span_context = SpanContext::new(
+            TraceId::from_hex(&self.trace_id).ok()?,
+            SpanId::from_hex(parent_span_id).ok()?,
+            TraceFlags::new(u8::from_str_radix(&self.flags, 16).ok()?),
+            true,
+            self.tracestate
+                .as_deref()
+                .and_then(|tracestate| tracestate.parse().ok())
+                .unwrap_or_default(),
+        );
+        Some(opentelemetry::Context::new().with_remote_span_context(span_context))
+    }
+
+    /// 外部呼び出しの`traceparent`に設定する、トレースID、親スパンIDおよびトレースフラグを返す。
+    ///
+    /// 現在のスパンがOpenTelemetryのスパンコンテキストを持つ場合は、ログに記録したそのスパンを親とする。
+    /// 持たない場合は、このトレースコンテキストのスパンを親とする。
+    fn outbound_parent(&self) -> (String, String, String) {
+        #[cfg(feature = "server")]
+        {
+            use opentelemetry::trace::TraceContextExt as _;
+            use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
+            let context = tracing::Span::current().context();
+            let span_context = context.span().span_context().clone();
+            if span_context.is_valid() {
+                return (
+                    span_context.trace_id().to_string(),
+                    span_context.span_id().to_string(),
+                    format!("{:02x}", span_context.trace_flags().to_u8()),
+                );
+            }
+        }
+        (
+            self.trace_id.clone(),
+            self.span_id.clone(),
+            self.flags.clone(),
+        )
+    }
+
+    /// 外部呼び出しのリクエストにトレースコンテキストを付与する。
+    ///
+    /// 現在のスパンを親として`traceparent`に設定し、`tracestate`とリクエストIDを引き継ぐ。
+    /// 外部呼び出し先が記録する親スパンIDは、このサービスがログに記録したスパンIDと一致する。
+    ///
+    /// # Arguments
+    ///
+    /// * `builder` - 外部呼び出しのリクエストビルダー
+    ///
+    /// # Returns
+    ///
+    /// * トレースコンテキストを付与したリクエストビルダー
+    pub fn inject(&self, builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        let (trace_id, parent_id, flags) = self.outbound_parent();
+        let traceparent = format!("{TRACEPARENT_VERSION}-{trace_id}-{parent_id}-{flags}");
+        let mut builder = builder.header(TRACEPARENT, traceparent);
+        if let Some(tracestate) = &self.tracestate {
+            builder = builder.header(TRACESTATE, tracestate);
+        }
+        if let Some(request_id) = &self.request_id {
+            builder = builder.header(X_REQUEST_ID, request_id);
+        }
+        builder
+    }
+}
+
+#[cfg(feature = "server")]
+impl<S: Send + Sync> FromRequestParts<S> for TraceContext {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        // `attach_trace_context`ミドルウェアが付与したトレースコンテキストを優先する
+        if let Some(trace) = parts.extensions.get::<TraceContext>() {
+            return Ok(trace.clone());
+        }
+        let request_id = parts
+            .extensions
+            .get::<RequestId>()
+            .and_then(|id| id.header_value().to_str().ok())
+            .map(str::to_string);
+        Ok(Self::from_headers(&parts.headers, request_id))
+    }
+}
+
+/// 受信したリクエストのトレースコンテキストを、リクエストの拡張に付与するミドルウェア
+///
+/// `http_request`スパンと外部呼び出しで同じトレースコンテキストを使用できるように、`TraceLayer`より前に適用する。
+/// また、リクエストIDを引き継ぐため、`SetRequestIdLayer`より後に適用する。
+#[cfg(feature = "server")]
+pub async fn attach_trace_context(mut request: Request) -> Request {
+    let request_id = request
+        .extensions()
+        .get::<RequestId>()
+        .and_then(|id| id.header_value().to_str().ok())
+        .map(str::to_string);
+    let trace = TraceContext::from_headers(request.headers(), request_id);
+    request.extensions_mut().insert(trace);
+    request
+}
+
+/// `traceparent`ヘッダをパースする。
+///
+/// # Arguments
+///
+/// * `value` - `traceparent`ヘッダの値（例: `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`）
+///
+/// # Returns
+///
+/// * トレースID、親スパンIDおよびトレースフラグ、無効な場合は`None`
+///
+/// # Notes
+///
+/// W3C Trace Contextに従い、バージョン`ff`は無効とする。
+/// バージョン00より新しいバージョンは、後続の部分が追加されている可能性があるため、先頭の4つの部分をバージョン00として
+/// パースする。
+fn parse_traceparent(value: &str) -> Option<(String, String, String)> {
+    let is_hex = |s: &str, len: usize| {
+        s.len() == len && s.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'))
+    };
+    let is_all_zero = |s: &str| s.chars().all(|c| c == '0');
+    let mut parts = value.trim().split('-');
+    let version = parts.next()?;
+    let trace_id = parts.next()?;
+    let parent_id = parts.next()?;
+    let flags = parts.next()?;
+    if !is_hex(version, 2) || version == INVALID_TRACEPARENT_VERSION {
+        return None;
+    }
+    // バージョン00では4つの部分で構成される
+    if version == TRACEPARENT_VERSION && parts.next().is_some() {
+        return None;
+    }
+    if !is_hex(trace_id, 32) || is_all_zero(trace_id) {
+        return None;
+    }
+    if !is_hex(parent_id, 16) || is_all_zero(parent_id) {
+        return None;
+    }
+    if !is_hex(flags, 2) {
+        return None;
+    }
+    Some((trace_id.into(), parent_id.into(), flags.into()))
+}
+
+/// `N`バイトのランダムな値を16進数の文字列で返す。
+fn random_hex<const N: usize>() -> String {
+    let mut bytes = [0u8; N];
+    loop {
+        rand::rng().fill_bytes(&mut bytes);
+        // W3C Trace Contextでは、すべて0のIDは無効
+        if bytes.iter().any(|b| *b != 0) {
+            break;
+        }
+    }
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use http::HeaderValue;
+
+    use super::*;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const PARENT_ID: &str = "00f067aa0ba902b7";
+
+    #[test]
+    fn valid_traceparent_is_parsed() {
+        assert_eq!(
+            parse_traceparent(&format!("00-{TRACE_ID}-{PARENT_ID}-01")),
+            Some((TRACE_ID.into(), PARENT_ID.into(), "01".into()))
+        );
+        // 新しいバージョンでは、追加された後続の部分を無視する
+        assert_eq!(
+            parse_traceparent(&format!("01-{TRACE_ID}-{PARENT_ID}-00-extra")),
+            Some((TRACE_ID.into(), PARENT_ID.into(), "00".into()))
+        );
+    }
+
+    #[test]
+    fn malformed_traceparent_is_rejected() {
+        let zero_trace_id = "0".repeat(32);
+        let zero_parent_id = "0".repeat(16);
+        let invalid = [
+            String::new(),
+            format!("00-{TRACE_ID}-{PARENT_ID}"),
+            format!("00-{TRACE_ID}-{PARENT_ID}-01-extra"),
+            format!("ff-{TRACE_ID}-{PARENT_ID}-01"),
+            format!("0-{TRACE_ID}-{PARENT_ID}-01"),
+            format!("zz-{TRACE_ID}-{PARENT_ID}-01"),
+            format!("00-{zero_trace_id}-{PARENT_ID}-01"),
+            format!("00-{TRACE_ID}-{zero_parent_id}-01"),
+            format!("00-{}-{PARENT_ID}-01", TRACE_ID.to_uppercase()),
+            format!("00-{}-{PARENT_ID}-01", &TRACE_ID[1..]),
+            format!("00-{TRACE_ID}-{}-01", &PARENT_ID[1..]),
+            format!("00-{TRACE_ID}-{PARENT_ID}-1"),
+            format!("00-{TRACE_ID}-{PARENT_ID}-0g"),
+        ];
+        for value in invalid {
+            assert_eq!(parse_traceparent(&value), None, "{value}");
+        }
+    }
+
+    #[test]
+    fn from_headers_continues_valid_trace() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            TRACEPARENT,
+            HeaderValue::from_str(&format!("00-{TRACE_ID}-{PARENT_ID}-01")).unwrap(),
+        );
+        headers.insert(TRACESTATE, HeaderValue::from_static("vendor=value"));
+
+        let trace = TraceContext::from_headers(&headers, Some("request-id".into()));
+
+        assert_eq!(trace.trace_id(), TRACE_ID);
+        assert_eq!(trace.parent_span_id(), Some(PARENT_ID));
+        assert_ne!(trace.span_id(), PARENT_ID);
+        assert_eq!(trace.tracestate.as_deref(), Some("vendor=value"));
+        assert_eq!(trace.request_id.as_deref(), Some("request-id"));
+    }
+
+    #[test]
+    fn from_headers_starts_new_trace_for_invalid_traceparent() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            TRACEPARENT,
+            HeaderValue::from_str(&format!("ff-{TRACE_ID}-{PARENT_ID}-01")).unwrap(),
+        );
+        headers.insert(TRACESTATE, HeaderValue::from_static("vendor=value"));
+
+        let trace = TraceContext::from_headers(&headers, None);
+
+        assert_ne!(trace.trace_id(), TRACE_ID);
+        assert_eq!(trace.trace_id().len(), 32);
+        assert_eq!(trace.parent_span_id(), None);
+        // 引き継がないトレースの`tracestate`は破棄する
+        assert_eq!(trace.tracestate, None);
+    }
+
+    #[cfg(feature = "server")]
+    #[test]
+    fn request_span_joins_caller_trace_and_is_propagated() {
+        use opentelemetry::trace::{TraceContextExt as _, TracerProvider as _};
+        use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+        use tracing_subscriber::layer::SubscriberExt as _;
+
+        let tracer = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+            .build()
+            .tracer("test");
+        let subscriber = tracing_subscriber::Registry::default()
+            .with(tracing_opentelemetry::layer().with_tracer(tracer));
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            TRACEPARENT,
+            HeaderValue::from_str(&format!("00-{TRACE_ID}-{PARENT_ID}-01")).unwrap(),
+        );
+        headers.insert(TRACESTATE, HeaderValue::from_static("vendor=value"));
+        let trace = TraceContext::from_headers(&headers, None);
+        let _ = crate::crypto::install_default_provider();
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("http_request");
+            span.set_parent(trace.remote_parent().unwrap()).unwrap();
+            let span_context = span.context().span().span_context().clone();
+            assert_eq!(span_context.trace_id().to_string(), TRACE_ID);
+            assert_ne!(span_context.span_id().to_string(), PARENT_ID);
+
+            // 外部呼び出しには、呼び出し元のトレースIDとこのサービスのスパンIDを伝搬する
+            let request = span.in_scope(|| {
+                trace
+                    .inject(reqwest::Client::new().get("http://localhost/"))
+                    .build()
+                    .unwrap()
+            });
+            assert_eq!(
+                request.headers()[TRACEPARENT],
+                format!("00-{TRACE_ID}-{}-01", span_context.span_id())
+            );
+            assert_eq!(request.headers()[TRACESTATE], "vendor=value");
+        });
+    }
+
+    #[test]
+    fn new_trace_has_no_remote_parent() {
+        let trace = TraceContext::from_headers(&HeaderMap::new(), None);
+        let _ = crate::crypto::install_default_provider();
+
+        #[cfg(feature = "server")]
+        assert!(trace.remote_parent().is_none());
+        // スパンがない場合は、このトレースコンテキストのスパンIDを伝搬する
+        let request = trace
+            .inject(reqwest::Client::new().get("http://localhost/"))
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.headers()[TRACEPARENT],
+            format!("00-{}-{}-01", trace.trace_id(), trace.span_id())
+        );
+    }
+}
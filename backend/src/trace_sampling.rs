@@ -0,0 +1,67 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use rand::Rng as _;
+
+use crate::config::TraceSampling;
+
+/// `http_request`スパンを作成するかを判定するサンプラー
+pub struct TraceSampler {
+    /// サンプリング設定
+    sampling: TraceSampling,
+    /// 秒単位の時間枠を計算する基準時刻
+    started_at: Instant,
+    /// 現在の時間枠（`started_at`からの経過秒数）
+    current_window: AtomicU64,
+    /// 現在の時間枠でサンプリングしたスパンの数
+    sampled_in_window: AtomicU64,
+}
+
+impl TraceSampler {
+    /// コンストラクタ
+    ///
+    /// # Arguments
+    ///
+    /// * `sampling` - サンプリング設定
+    pub fn new(sampling: TraceSampling) -> Self {
+        Self {
+            sampling,
+            started_at: Instant::now(),
+            current_window: AtomicU64::new(0),
+            sampled_in_window: AtomicU64::new(0),
+        }
+    }
+
+    /// スパンを作成するかを判定する。
+    ///
+    /// # Returns
+    ///
+    /// * スパンを作成する場合は`true`
+    pub fn should_sample(&self) -> bool {
+        match self.sampling {
+            TraceSampling::Always => true,
+            TraceSampling::Ratio(ratio) => rand::rng().random_bool(ratio),
+            TraceSampling::RatePerSecond(rate) => self.sample_by_rate(rate),
+        }
+    }
+
+    /// 1秒あたりのスパン数が上限を超えないようにサンプリングする。
+    ///
+    /// # Notes
+    ///
+    /// 時間枠が切り替わったときにカウンタをリセットする。
+    /// リセットとカウントが競合した場合、上限をわずかに超える可能性があるが、サンプリングの目的では許容する。
+    fn sample_by_rate(&self, rate: u32) -> bool {
+        let window = self.started_at.elapsed().as_secs();
+        let current = self.current_window.load(Ordering::Relaxed);
+        if window != current
+            && self
+                .current_window
+                .compare_exchange(current, window, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            self.sampled_in_window.store(0, Ordering::Relaxed);
+        }
+        self.sampled_in_window.fetch_add(1, Ordering::Relaxed) < rate as u64
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::HashSet;
+
+use crate::authorization::{Role, Scope};
+use crate::entra_id::{Claims, Scopes, TenantId};
+
+/// 認証済みのユーザーの情報
+///
+/// `auth_middleware`が検証済みのクレームから作成して、リクエストのエクステンションに挿入する。
+/// ハンドラーは、ロールやスコープをクレームから都度取り出さずに、この型で参照する。
+#[derive(Debug, Clone)]
+pub struct UserContext {
+    /// ユーザーのオブジェクトID（`oid`）
+    pub oid: String,
+    /// トークンを発行したテナントのID（`tid`）
+    ///
+    /// トークンに`tid`が含まれていないか、テナントIDの形式でない場合は`None`
+    pub tenant_id: Option<TenantId>,
+    /// トークンを要求したクライアントアプリケーションのID（`azp`または`appid`）
+    pub client_app_id: Option<String>,
+    /// ユーザーの表示名（`name`）
+    ///
+    /// トークンに`name`が含まれていない場合は`None`
+    pub display_name: Option<String>,
+    /// ユーザーに割り当てられたアプリケーションロール（`roles`）
+    pub roles: HashSet<String>,
+    /// 委任されたスコープ（`scp`）
+    pub scopes: Scopes,
+}
+
+impl UserContext {
+    /// ユーザーにアプリケーションロールが割り当てられているかを判定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `role` - `RoleScopeRegistry::role`で取得したロール
+    ///
+    /// # Notes
+    ///
+    /// ロールの階層は考慮しない。上位のロールを含めて判定する場合は、`PermissionMap::has_role`を使用する。
+    pub fn has_role(&self, role: &Role) -> bool {
+        self.roles.contains(role.name())
+    }
+
+    /// ユーザーにスコープが委任されているかを、大文字と小文字を区別せずに判定する。
+    ///
+    /// # Arguments
+    ///
+    /// * `scope` - `RoleScopeRegistry::scope`で取得したスコープ
+    pub fn has_scope(&self, scope: &Scope) -> bool {
+        self.scopes.contains(scope.name())
+    }
+}
+
+impl From<&Claims> for UserContext {
+    fn from(claims: &Claims) -> Self {
+        Self {
+            oid: claims.oid.clone(),
+            tenant_id: claims
+                .tid
+                .clone()
+                .and_then(|tid| TenantId::try_from(tid).ok()),
+            client_app_id: claims.client_app_id().map(str::to_string),
+            display_name: claims
+                .extra
+                .get("name")
+                .and_then(|name| name.as_str())
+                .map(str::to_string),
+            roles: claims.roles.iter().flatten().cloned().collect(),
+            scopes: claims.scopes().clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::authorization::RoleScopeRegistry;
+
+    #[test]
+    fn user_context_is_derived_from_claims() {
+        let claims: Claims = serde_json::from_value(serde_json::json!({
+            "aud": "api://backend",
+            "iss": "https://login.microsoftonline.com/00000000-0000-0000-0000-000000000001/v2.0",
+            "exp": 0,
+            "oid": "user-1",
+            "sub": "sub-1",
+            "tid": "00000000-0000-0000-0000-000000000001",
+            "roles": ["Reader"],
+            "scp": "User.Read Files.Read",
+            "azp": "client-1",
+            "name": "Alice",
+        }))
+        .unwrap();
+
+        let user = UserContext::from(&claims);
+
+        assert_eq!(user.oid, "user-1");
+        assert_eq!(
+            user.tenant_id,
+            Some(TenantId::try_from("00000000-0000-0000-0000-000000000001".to_string()).unwrap())
+        );
+        assert_eq!(user.client_app_id.as_deref(), Some("client-1"));
+        assert_eq!(user.display_name.as_deref(), Some("Alice"));
+        let registry = RoleScopeRegistry::new(
+            &["Reader".to_string(), "Writer".to_string()],
+            &["User.Read".to_string(), "Files.ReadWrite".to_string()],
+        );
+        assert!(user.has_role(&registry.role("Reader").unwrap()));
+        assert!(!user.has_role(&registry.role("Writer").unwrap()));
+        assert!(user.has_scope(&registry.scope("user.read").unwrap()));
+        assert!(!user.has_scope(&registry.scope("Files.ReadWrite").unwrap()));
+    }
+}